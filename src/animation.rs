@@ -0,0 +1,391 @@
+use gtk::{gdk, gdk_pixbuf, glib};
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use crate::window::{AlphaMask, ChibiWindow};
+
+// --- ANIMATION ---
+// Decoded images ready to be shown on a chibi window, and the timing for cycling
+// through a sequence of them (walk cycles). Frames carry their alpha mask so
+// switching between them doesn't re-read any pixels, and they're shared: every chibi
+// showing the same file at about the same size gets the same texture (see FrameCache).
+// Images bigger than the chibi are scaled down as they're decoded (see ImageQuality).
+
+#[derive(Clone)]
+pub struct Frame {
+    pub texture: gdk::Texture,
+    pub mask: Rc<AlphaMask>,
+    pub path: Rc<PathBuf>,
+    // Longest side it was scaled down to; None is the image's own size
+    pub scaled_to: Option<i32>,
+}
+
+impl Frame {
+    // Decoded once per file version and size, however many windows show it; `px` is how
+    // big it's drawn, in device pixels
+    pub fn load(path: &Path, px: i32) -> Result<Frame, glib::Error> {
+        let quality = quality();
+        // Images that already fit are kept whole: there's nothing to save
+        let scaled_to = Some(decode_size(px))
+            .filter(|_| quality != ImageQuality::Full)
+            .filter(|size| gdk_pixbuf::Pixbuf::file_info(path).is_some_and(|(_, w, h)| w.max(h) > *size));
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if let Some(frame) = FRAMES.with(|c| c.borrow().get(path, scaled_to, modified)) { return Ok(frame); }
+        let texture = match scaled_to {
+            Some(size) => gdk::Texture::for_pixbuf(&decode_scaled(path, size, quality)?),
+            None => gdk::Texture::from_filename(path)?,
+        };
+        let frame = Frame { mask: Rc::new(AlphaMask::from_texture(&texture)), texture, path: Rc::new(path.to_path_buf()), scaled_to };
+        FRAMES.with(|c| {
+            let mut cache = c.borrow_mut();
+            // Nothing but the cache holds on to these any more
+            cache.retain(|f| Rc::strong_count(&f.mask) > 1 || f.texture.ref_count() > 1);
+            cache.insert(path, scaled_to, modified, frame.clone());
+        });
+        Ok(frame)
+    }
+
+    // Roughly what the texture takes in video memory, at 4 bytes a pixel
+    pub fn bytes(&self) -> usize {
+        self.texture.width() as usize * self.texture.height() as usize * 4
+    }
+}
+
+// --- IMAGE QUALITY ---
+// Oversized art (say a 6000px PNG shown at 200px) is decoded straight to about the size
+// it's drawn at rather than kept whole in memory. Decode sizes go up in powers of two, so
+// chibis of similar sizes still share a texture and only a resize past one re-decodes
+// (see ChibiWindow::set_size).
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageQuality {
+    // Scaled down smoothly
+    #[default]
+    Smooth,
+    // Scaled down without blending neighbouring pixels, for pixel art
+    Crisp,
+    // Never scaled down: the whole image, whatever the chibi's size
+    Full,
+}
+
+impl ImageQuality {
+    pub const ALL: [ImageQuality; 3] = [ImageQuality::Smooth, ImageQuality::Crisp, ImageQuality::Full];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ImageQuality::Smooth => "Scaled to fit (smooth)",
+            ImageQuality::Crisp => "Scaled to fit (pixel art)",
+            ImageQuality::Full => "Full size",
+        }
+    }
+}
+
+thread_local! {
+    static QUALITY: Cell<ImageQuality> = const { Cell::new(ImageQuality::Smooth) };
+}
+
+// Applies to images loaded from now on
+pub fn set_quality(quality: ImageQuality) {
+    if QUALITY.with(|q| q.replace(quality)) != quality {
+        FRAMES.with(|c| c.borrow_mut().retain(|_| false));
+    }
+}
+
+pub fn quality() -> ImageQuality {
+    QUALITY.with(|q| q.get())
+}
+
+const MIN_DECODE_SIZE: i32 = 64;
+
+// Longest side to decode at for an image drawn `px` pixels big
+pub fn decode_size(px: i32) -> i32 {
+    (px.max(1) as u32).next_power_of_two().max(MIN_DECODE_SIZE as u32) as i32
+}
+
+// (width, height) shrunk to fit in size × size, keeping the aspect ratio
+fn fit(width: i32, height: i32, size: i32) -> (i32, i32) {
+    let scale = size as f64 / width.max(height).max(1) as f64;
+    (((width as f64 * scale).round() as i32).max(1), ((height as f64 * scale).round() as i32).max(1))
+}
+
+fn decode_scaled(path: &Path, size: i32, quality: ImageQuality) -> Result<gdk_pixbuf::Pixbuf, glib::Error> {
+    if quality != ImageQuality::Crisp {
+        // The loader scales as it reads, so the full image is never held at once
+        return gdk_pixbuf::Pixbuf::from_file_at_scale(path, size, size, true);
+    }
+    let full = gdk_pixbuf::Pixbuf::from_file(path)?;
+    let (width, height) = fit(full.width(), full.height(), size);
+    full.scale_simple(width, height, gdk_pixbuf::InterpType::Nearest)
+        .ok_or_else(|| glib::Error::new(glib::FileError::Nomem, "not enough memory to scale the image"))
+}
+
+// --- FRAME CACHE ---
+// Loaded frames by path and decode size, along with the file's modification time when it
+// was read: an image edited on disk is a miss, and loads again. Entries no window uses any
+// more are dropped the next time something new loads.
+
+type CacheKey = (PathBuf, Option<i32>);
+
+pub struct FrameCache<V> {
+    entries: HashMap<CacheKey, (Option<SystemTime>, V)>,
+}
+
+impl<V: Clone> FrameCache<V> {
+    pub fn new() -> Self {
+        FrameCache { entries: HashMap::new() }
+    }
+
+    pub fn get(&self, path: &Path, size: Option<i32>, modified: Option<SystemTime>) -> Option<V> {
+        // Without an mtime there's no telling whether the file changed
+        let (at, value) = self.entries.get(&(path.to_path_buf(), size)).filter(|_| modified.is_some())?;
+        (*at == modified).then(|| value.clone())
+    }
+
+    pub fn insert(&mut self, path: &Path, size: Option<i32>, modified: Option<SystemTime>, value: V) {
+        self.entries.insert((path.to_path_buf(), size), (modified, value));
+    }
+
+    pub fn retain(&mut self, keep: impl Fn(&V) -> bool) {
+        self.entries.retain(|_, (_, value)| keep(value));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|(_, value)| value)
+    }
+}
+
+impl<V: Clone> Default for FrameCache<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    static FRAMES: RefCell<FrameCache<Frame>> = RefCell::new(FrameCache::new());
+}
+
+// Frames in the cache, and the bytes their textures take
+pub fn cache_stats() -> (usize, usize) {
+    FRAMES.with(|c| {
+        let cache = c.borrow();
+        (cache.values().count(), cache.values().map(Frame::bytes).sum())
+    })
+}
+
+// Which of `count` frames to show, moving on every `interval` seconds
+pub struct FrameClock {
+    count: usize,
+    interval: f64,
+    elapsed: f64,
+    index: usize,
+}
+
+impl FrameClock {
+    pub fn new(count: usize, interval: f64) -> Self {
+        FrameClock { count, interval: interval.max(0.01), elapsed: 0.0, index: 0 }
+    }
+
+    // Back to the first frame, shown from now
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+        self.index = 0;
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    // Advances by `dt` seconds; the new index when it changed
+    pub fn advance(&mut self, dt: f64) -> Option<usize> {
+        if self.count < 2 { return None; }
+        self.elapsed += dt;
+        let steps = (self.elapsed / self.interval) as usize;
+        if steps == 0 { return None; }
+        self.elapsed -= steps as f64 * self.interval;
+        self.index = (self.index + steps) % self.count;
+        Some(self.index)
+    }
+}
+
+// --- PACING ---
+// Frame clock callbacks go through add_paced_tick, which holds them to the FPS cap in
+// Preferences and hands them the seconds since they last ran. Plain timers for chibi
+// animations go through every_while_shown, which takes them off the main loop while the
+// chibi is hidden (smart hide, Hide All, another workspace), so hidden chibis cost nothing.
+
+thread_local! {
+    // 0 follows the display
+    static MAX_FPS: Cell<u32> = const { Cell::new(0) };
+}
+
+pub fn set_max_fps(fps: u32) {
+    MAX_FPS.with(|f| f.set(fps));
+}
+
+pub fn max_fps() -> u32 {
+    MAX_FPS.with(|f| f.get())
+}
+
+// Longest step a tick is given: coming back after a while off screen shouldn't jump
+const MAX_STEP: f64 = 0.1;
+// Frames arrive a little early or late; without some give a 30 FPS cap on a 60 Hz
+// display would drop to 20
+const SLACK: f64 = 0.002;
+
+#[derive(Default)]
+pub struct Pacer {
+    last: Option<i64>,
+}
+
+impl Pacer {
+    // Seconds since the last tick that went through, or None while the cap says wait;
+    // `now` is the frame time in µs
+    pub fn step(&mut self, now: i64, max_fps: u32) -> Option<f64> {
+        let Some(last) = self.last.replace(now) else { return Some(0.0) };
+        let dt = (now - last) as f64 / 1_000_000.0;
+        if max_fps > 0 && dt < 1.0 / max_fps as f64 - SLACK {
+            self.last = Some(last);
+            return None;
+        }
+        Some(dt.clamp(0.0, MAX_STEP))
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+// add_tick_callback under the FPS cap; `tick` gets the seconds since it last ran
+pub fn add_paced_tick<W: IsA<gtk::Widget>>(widget: &W, tick: impl FnMut(&W, f64) -> glib::ControlFlow + 'static) -> gtk::TickCallbackId {
+    let (pacer, tick) = (RefCell::new(Pacer::default()), RefCell::new(tick));
+    widget.add_tick_callback(move |w, clock| {
+        let mut pacer = pacer.borrow_mut();
+        if !w.is_mapped() {
+            pacer.reset();
+            return glib::ControlFlow::Continue;
+        }
+        match pacer.step(clock.frame_time(), max_fps()) {
+            Some(dt) => (tick.borrow_mut())(w, dt),
+            None => glib::ControlFlow::Continue,
+        }
+    })
+}
+
+type ShownTick = Rc<dyn Fn(&ChibiWindow) -> glib::ControlFlow>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Timer {
+    Paused,
+    Running,
+    Done,
+}
+
+fn resume(win: &ChibiWindow, interval: Duration, tick: ShownTick, timer: Rc<Cell<Timer>>) {
+    if timer.get() != Timer::Paused || !win.is_visible() { return; }
+    timer.set(Timer::Running);
+    let win_weak = win.downgrade();
+    glib::timeout_add_local(interval, move || {
+        let Some(w) = win_weak.upgrade() else { return glib::ControlFlow::Break };
+        if !w.is_visible() {
+            timer.set(Timer::Paused);
+            return glib::ControlFlow::Break;
+        }
+        let flow = tick(&w);
+        if flow == glib::ControlFlow::Break { timer.set(Timer::Done); }
+        flow
+    });
+}
+
+// Calls `tick` every `interval` while the chibi is on screen, until the window goes away
+// or `tick` breaks; the timer is gone entirely while it's hidden
+pub fn every_while_shown(win: &ChibiWindow, interval: Duration, tick: impl Fn(&ChibiWindow) -> glib::ControlFlow + 'static) {
+    // Once `tick` breaks nothing is left behind, so callers may start another later
+    let handler: Rc<RefCell<Option<glib::SignalHandlerId>>> = Rc::new(RefCell::new(None));
+    let handler_tick = handler.clone();
+    let tick: ShownTick = Rc::new(move |w| {
+        let flow = tick(w);
+        if flow == glib::ControlFlow::Break {
+            if let Some(id) = handler_tick.borrow_mut().take() { w.disconnect(id); }
+        }
+        flow
+    });
+    let timer = Rc::new(Cell::new(Timer::Paused));
+    resume(win, interval, tick.clone(), timer.clone());
+    *handler.borrow_mut() = Some(win.connect_visible_notify(move |w| resume(w, interval, tick.clone(), timer.clone())));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_advance_on_the_interval() {
+        let mut clock = FrameClock::new(3, 0.25);
+        assert_eq!(clock.advance(0.125), None);
+        assert_eq!(clock.advance(0.125), Some(1));
+        assert_eq!(clock.advance(0.25), Some(2));
+        assert_eq!(clock.advance(0.25), Some(0));
+        // A long gap skips ahead instead of playing catch-up
+        assert_eq!(clock.advance(0.5), Some(2));
+        clock.restart();
+        assert_eq!(clock.index(), 0);
+    }
+
+    #[test]
+    fn cache_follows_the_mtime() {
+        let (path, then) = (Path::new("/chibi.png"), SystemTime::UNIX_EPOCH);
+        let later = then + std::time::Duration::from_secs(1);
+        let mut cache = FrameCache::new();
+        assert_eq!(cache.get(path, None, Some(then)), None);
+        let shared = Rc::new(1);
+        cache.insert(path, None, Some(then), shared.clone());
+        assert!(Rc::ptr_eq(&cache.get(path, None, Some(then)).unwrap(), &shared));
+        assert_eq!(cache.get(path, Some(256), Some(then)), None);
+        assert_eq!(cache.get(path, None, Some(later)), None);
+        assert_eq!(cache.get(path, None, None), None);
+        drop(shared);
+        cache.retain(|v| Rc::strong_count(v) > 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn decode_sizes() {
+        assert_eq!(decode_size(200), 256);
+        assert_eq!(decode_size(256), 256);
+        assert_eq!(decode_size(257), 512);
+        assert_eq!(decode_size(10), MIN_DECODE_SIZE);
+        assert_eq!(fit(6000, 3000, 256), (256, 128));
+        assert_eq!(fit(100, 4000, 512), (13, 512));
+    }
+
+    #[test]
+    fn pacer_holds_to_the_cap() {
+        let mut pacer = Pacer::default();
+        assert_eq!(pacer.step(0, 30), Some(0.0));
+        // 60 Hz frames, every other one goes through
+        assert_eq!(pacer.step(16_667, 30), None);
+        assert_eq!(pacer.step(33_333, 30), Some(0.033333));
+        assert_eq!(pacer.step(50_000, 0), Some(0.016667));
+        // A long gap is one short step
+        assert_eq!(pacer.step(5_000_000, 30), Some(MAX_STEP));
+        pacer.reset();
+        assert_eq!(pacer.step(6_000_000, 30), Some(0.0));
+    }
+
+    #[test]
+    fn single_frames_never_change() {
+        let mut clock = FrameClock::new(1, 0.1);
+        assert_eq!(clock.advance(10.0), None);
+        assert_eq!(FrameClock::new(0, 0.1).advance(1.0), None);
+    }
+}
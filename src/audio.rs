@@ -0,0 +1,270 @@
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::preset::ChibiPreset;
+
+// --- AUDIO ---
+// Sound effects for chibis, played through GTK's own media support (GStreamer), so there's
+// no separate audio stack to carry. Streams go through a small mixer that keeps decoded
+// files around for replaying and caps how many play at once; one global mute silences all.
+//
+// A sound pack is a directory with a pack.json mapping events to files in it:
+//
+//   { "name": "Kitty", "volume": 0.8,
+//     "sounds": { "spawn": "meow.ogg", "click": ["purr1.ogg", "purr2.ogg"], "hourly": "chime.ogg" } }
+//
+// Lists play one of their files at random. A preset's own click/spawn sound wins over its pack.
+// The master volume and mute apply to speech (speech.rs) as well.
+
+thread_local! {
+    static MUTED: Cell<bool> = const { Cell::new(false) };
+    static VOLUME: Cell<f64> = const { Cell::new(1.0) };
+    static MIXER: RefCell<Mixer> = RefCell::new(Mixer::default());
+    // Loaded packs by directory; None remembers a pack that failed to load
+    static PACKS: RefCell<HashMap<PathBuf, Option<Rc<SoundPack>>>> = RefCell::new(HashMap::new());
+    static APP_SOUNDS: RefCell<HashMap<AppSound, PathBuf>> = RefCell::new(HashMap::new());
+}
+
+pub fn set_muted(muted: bool) {
+    MUTED.with(|m| m.set(muted));
+}
+
+pub fn muted() -> bool {
+    MUTED.with(|m| m.get())
+}
+
+// Master volume, 0..100 percent
+pub fn set_volume(percent: u32) {
+    VOLUME.with(|v| v.set(percent.min(100) as f64 / 100.0));
+}
+
+pub fn volume() -> f64 {
+    VOLUME.with(|v| v.get())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundEvent {
+    Spawn,
+    Click,
+    Pet,
+    Hide,
+    Show,
+    // Top of every hour
+    Hourly,
+}
+
+pub const MANIFEST: &str = "pack.json";
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default = "default_volume")]
+    volume: f64,
+    sounds: HashMap<SoundEvent, OneOrMany>,
+}
+
+fn default_volume() -> f64 { 1.0 }
+
+pub struct SoundPack {
+    pub name: String,
+    volume: f64,
+    sounds: HashMap<SoundEvent, Vec<PathBuf>>,
+}
+
+impl SoundPack {
+    // Files in the manifest are relative to `dir`
+    pub fn parse(text: &str, dir: &Path) -> Result<SoundPack, String> {
+        let manifest: Manifest = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let sounds = manifest.sounds.into_iter()
+            .map(|(event, files)| {
+                let files = match files { OneOrMany::One(f) => vec![f], OneOrMany::Many(fs) => fs };
+                (event, files.into_iter().map(|f| dir.join(f)).collect())
+            })
+            .collect();
+        let name = manifest.name
+            .unwrap_or_else(|| dir.file_name().unwrap_or_default().to_string_lossy().to_string());
+        Ok(SoundPack { name, volume: manifest.volume.clamp(0.0, 1.0), sounds })
+    }
+
+    pub fn load(dir: &Path) -> Result<SoundPack, String> {
+        let path = dir.join(MANIFEST);
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        SoundPack::parse(&text, dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // File for `event`; `pick(n)` chooses among n candidates
+    pub fn sound_for(&self, event: SoundEvent, pick: &mut dyn FnMut(usize) -> usize) -> Option<&Path> {
+        let files = self.sounds.get(&event).filter(|f| !f.is_empty())?;
+        Some(&files[pick(files.len()).min(files.len() - 1)])
+    }
+
+    fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.sounds.values().flatten()
+    }
+}
+
+// Streams playing at once; starting another stops the oldest
+const MAX_VOICES: usize = 4;
+
+#[derive(Default)]
+struct Mixer {
+    // One stream per file, kept for replaying
+    loaded: HashMap<PathBuf, gtk::MediaFile>,
+    playing: Vec<gtk::MediaFile>,
+}
+
+fn open(path: &Path) -> gtk::MediaFile {
+    let media = gtk::MediaFile::for_filename(path);
+    media.connect_ended_notify(finished);
+    let name = path.display().to_string();
+    media.connect_error_notify(move |m| {
+        if let Some(e) = m.error() { tracing::warn!("Can't play {}: {}", name, e); }
+        finished(m);
+    });
+    media
+}
+
+fn finished(media: &gtk::MediaFile) {
+    MIXER.with(|m| m.borrow_mut().playing.retain(|p| p != media));
+}
+
+// Opens `path` ahead of time so the first play doesn't wait for it
+fn preload(path: &Path) {
+    if MIXER.with(|m| m.borrow().loaded.contains_key(path)) { return; }
+    let media = open(path);
+    MIXER.with(|m| m.borrow_mut().loaded.insert(path.to_path_buf(), media));
+}
+
+// `gain` is on top of the master volume
+fn play_at(path: &Path, gain: f64) {
+    if muted() || volume() <= 0.0 { return; }
+    preload(path);
+    let loaded = MIXER.with(|m| m.borrow().loaded[path].clone());
+    // The same sound again while it still plays gets a stream of its own
+    let media = if loaded.is_playing() { open(path) } else { loaded };
+    media.seek(0);
+    media.set_volume(gain * volume());
+    media.play();
+    let oldest = MIXER.with(|m| {
+        let mut m = m.borrow_mut();
+        m.playing.retain(|p| p != &media);
+        m.playing.push(media);
+        let excess = m.playing.len().saturating_sub(MAX_VOICES);
+        m.playing.drain(..excess).collect::<Vec<_>>()
+    });
+    for media in oldest {
+        media.pause();
+    }
+}
+
+// Plays `path` once, unless muted
+pub fn play(path: &Path) {
+    play_at(path, 1.0);
+}
+
+// Loads (once) and preloads the pack in `dir`
+pub fn load_pack(dir: &Path) -> Result<Rc<SoundPack>, String> {
+    if let Some(cached) = PACKS.with(|p| p.borrow().get(dir).cloned()) {
+        return cached.ok_or_else(|| format!("{} couldn't be loaded", dir.display()));
+    }
+    let pack = SoundPack::load(dir).map(Rc::new);
+    PACKS.with(|p| p.borrow_mut().insert(dir.to_path_buf(), pack.as_ref().ok().cloned()));
+    let pack = pack?;
+    for file in pack.files() {
+        preload(file);
+    }
+    Ok(pack)
+}
+
+// Plays whatever the preset has for `event`: its own sound, else one from its pack,
+// else (on spawn) the app's
+pub fn play_for(preset: &ChibiPreset, event: SoundEvent) {
+    let own = match event {
+        SoundEvent::Spawn => preset.spawn_sound.as_deref(),
+        SoundEvent::Click => preset.click_sound.as_deref(),
+        _ => None,
+    };
+    if let Some(path) = own { return play(path); }
+    let pack = preset.sound_pack.as_deref().and_then(|dir| load_pack(dir).ok());
+    let random = &mut |n: usize| gtk::glib::random_int_range(0, n as i32) as usize;
+    match pack.as_ref().and_then(|p| Some((p.sound_for(event, random)?, p.volume))) {
+        Some((path, volume)) => play_at(path, volume),
+        None if event == SoundEvent::Spawn => play_app(AppSound::Spawn),
+        None => {}
+    }
+}
+
+// --- APP SOUNDS ---
+// Feedback from the app itself rather than from a chibi's preset, picked in Preferences.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AppSound {
+    // Any chibi appearing without a spawn sound of its own
+    Spawn,
+    HideAll,
+    Error,
+    // A chibi's speech reminder coming up
+    Reminder,
+}
+
+impl AppSound {
+    pub const ALL: [AppSound; 4] = [AppSound::Spawn, AppSound::HideAll, AppSound::Error, AppSound::Reminder];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AppSound::Spawn => "Chibi appears",
+            AppSound::HideAll => "Hide All",
+            AppSound::Error => "Error",
+            AppSound::Reminder => "Reminder",
+        }
+    }
+}
+
+pub fn set_app_sounds(sounds: &HashMap<AppSound, PathBuf>) {
+    APP_SOUNDS.with(|s| *s.borrow_mut() = sounds.clone());
+}
+
+// Only plays on the main thread, where the sounds are set
+pub fn play_app(sound: AppSound) {
+    if let Some(path) = APP_SOUNDS.with(|s| s.borrow().get(&sound).cloned()) {
+        play(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_files_are_relative_to_the_pack() {
+        let pack = SoundPack::parse(r#"{ "sounds": { "spawn": "meow.ogg", "click": ["a.ogg", "b.ogg"] } }"#, Path::new("/packs/kitty")).unwrap();
+        assert_eq!(pack.name, "kitty");
+        assert_eq!(pack.volume, 1.0);
+        assert_eq!(pack.sound_for(SoundEvent::Spawn, &mut |_| 0), Some(Path::new("/packs/kitty/meow.ogg")));
+        assert_eq!(pack.sound_for(SoundEvent::Click, &mut |n| n - 1), Some(Path::new("/packs/kitty/b.ogg")));
+        assert_eq!(pack.sound_for(SoundEvent::Hourly, &mut |_| 0), None);
+    }
+
+    #[test]
+    fn volume_is_clamped_and_events_checked() {
+        let pack = SoundPack::parse(r#"{ "name": "Loud", "volume": 3, "sounds": {} }"#, Path::new("/p")).unwrap();
+        assert_eq!(pack.name, "Loud");
+        assert_eq!(pack.volume, 1.0);
+        assert!(SoundPack::parse(r#"{ "sounds": { "sneeze": "a.ogg" } }"#, Path::new("/p")).is_err());
+    }
+}
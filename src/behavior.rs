@@ -0,0 +1,267 @@
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::EventControllerMotion;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::animation::{self, Frame, FrameClock};
+use crate::interaction;
+use crate::movement::default_frame_ms;
+use crate::particles::ParticleKind;
+use crate::window::ChibiWindow;
+
+// --- BEHAVIOR FILES ---
+// A preset can point at a JSON file describing its chibi as a small state machine:
+//
+//   { "initial": "idle",
+//     "states": {
+//       "idle":  { "image": "idle.png", "on": { "enter": "alert", "click": "happy" } },
+//       "alert": { "frames": ["look1.png", "look2.png"], "frame_ms": 200, "on": { "leave": "idle" } },
+//       "happy": { "image": "happy.png", "particles": "heart", "after_ms": 1500, "next": "idle" } } }
+//
+// Each state shows an image or cycles through frames (paths relative to the file); triggers
+// from the window move it along, and `after_ms`/`next` leave a state on a timer. `particles`
+// (heart, sparkle, z) plays a burst on the way in.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    // The pointer came onto / left the chibi
+    Enter,
+    Leave,
+    Click,
+    DoubleClick,
+    MiddleClick,
+    Pet,
+    // Smart hide tucked it away / brought it back
+    Hide,
+    Show,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct State {
+    #[serde(default)]
+    pub image: Option<PathBuf>,
+    #[serde(default)]
+    pub frames: Vec<PathBuf>,
+    #[serde(default = "default_frame_ms")]
+    pub frame_ms: u32,
+    #[serde(default)]
+    pub on: HashMap<Trigger, String>,
+    #[serde(default)]
+    pub after_ms: Option<u32>,
+    #[serde(default)]
+    pub next: Option<String>,
+    // Burst of particles on entering the state
+    #[serde(default)]
+    pub particles: Option<ParticleKind>,
+}
+
+impl State {
+    // What to show, in order; empty keeps whatever the chibi showed before
+    fn images(&self) -> Vec<&PathBuf> {
+        if self.frames.is_empty() { self.image.iter().collect() } else { self.frames.iter().collect() }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Behavior {
+    pub initial: String,
+    pub states: HashMap<String, State>,
+}
+
+impl Behavior {
+    // Rejects files whose transitions point at states that don't exist
+    pub fn parse(text: &str) -> Result<Behavior, String> {
+        let behavior: Behavior = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let check = |name: &str, from: &str| {
+            if behavior.states.contains_key(name) { Ok(()) } else { Err(format!("{}: no state named \"{}\"", from, name)) }
+        };
+        check(&behavior.initial, "initial")?;
+        for (name, state) in &behavior.states {
+            for target in state.on.values().chain(&state.next) {
+                check(target, name)?;
+            }
+        }
+        Ok(behavior)
+    }
+
+    pub fn load(path: &Path) -> Result<Behavior, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Behavior::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+pub struct Machine {
+    behavior: Behavior,
+    state: String,
+    // Seconds since the current state was entered
+    elapsed: f64,
+}
+
+impl Machine {
+    pub fn new(behavior: Behavior) -> Self {
+        let state = behavior.initial.clone();
+        Machine { behavior, state, elapsed: 0.0 }
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    fn current(&self) -> &State {
+        &self.behavior.states[&self.state]
+    }
+
+    fn enter(&mut self, state: String) -> Option<String> {
+        self.state = state.clone();
+        self.elapsed = 0.0;
+        Some(state)
+    }
+
+    // The state entered, if the current one reacts to `trigger`
+    pub fn fire(&mut self, trigger: Trigger) -> Option<String> {
+        let next = self.current().on.get(&trigger)?.clone();
+        self.enter(next)
+    }
+
+    // Advances by `dt` seconds; the state entered, if a timer ran out
+    pub fn advance(&mut self, dt: f64) -> Option<String> {
+        self.elapsed += dt;
+        let state = self.current();
+        let (Some(ms), Some(next)) = (state.after_ms, &state.next) else { return None };
+        if self.elapsed * 1000.0 < ms as f64 { return None; }
+        let next = next.clone();
+        self.enter(next)
+    }
+}
+
+// The running side: decoded frames per state and what is on screen now
+struct Engine {
+    machine: RefCell<Machine>,
+    frames: HashMap<String, Vec<Frame>>,
+    clock: RefCell<FrameClock>,
+}
+
+impl Engine {
+    fn show(&self, win: &ChibiWindow, state: &str) {
+        let frames = &self.frames[state];
+        let interval = self.machine.borrow().behavior.states[state].frame_ms as f64 / 1000.0;
+        *self.clock.borrow_mut() = FrameClock::new(frames.len(), interval);
+        if let Some(first) = frames.first() { win.show_frame(first); }
+        if let Some(kind) = self.machine.borrow().behavior.states[state].particles { win.emit_particles(kind, 6); }
+    }
+
+    fn fire(&self, win: &ChibiWindow, trigger: Trigger) {
+        let entered = self.machine.borrow_mut().fire(trigger);
+        if let Some(state) = entered { self.show(win, &state); }
+    }
+}
+
+// Runs the behavior file at `path` on the chibi until the window goes away
+pub fn attach(win: &ChibiWindow, path: &Path) -> Result<(), String> {
+    let behavior = Behavior::load(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    // Decoded up front, so a bad file is reported once instead of on every transition
+    let mut frames = HashMap::new();
+    for (name, state) in &behavior.states {
+        let loaded = state.images().into_iter()
+            .map(|p| win.load_frame(&dir.join(p)).map_err(|e| format!("{}: {}", dir.join(p).display(), e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        frames.insert(name.clone(), loaded);
+    }
+    let initial = behavior.initial.clone();
+    let engine = Rc::new(Engine { machine: RefCell::new(Machine::new(behavior)), frames, clock: RefCell::new(FrameClock::new(0, 1.0)) });
+    engine.show(win, &initial);
+
+    let e = engine.clone();
+    win.connect_clicked(move |w| e.fire(w, Trigger::Click));
+    let e = engine.clone();
+    win.connect_double_clicked(move |w| e.fire(w, Trigger::DoubleClick));
+    let e = engine.clone();
+    win.connect_middle_clicked(move |w| e.fire(w, Trigger::MiddleClick));
+    let e = engine.clone();
+    win.connect_hidden(move |w, hidden| e.fire(w, if hidden { Trigger::Hide } else { Trigger::Show }));
+    let e = engine.clone();
+    interaction::setup_petting(win, move |w| e.fire(w, Trigger::Pet));
+
+    let hover = EventControllerMotion::new();
+    let win_weak = win.downgrade();
+    let e = engine.clone();
+    hover.connect_enter(move |_, _, _| {
+        if let Some(w) = win_weak.upgrade() { e.fire(&w, Trigger::Enter); }
+    });
+    let win_weak = win.downgrade();
+    let e = engine.clone();
+    hover.connect_leave(move |_| {
+        if let Some(w) = win_weak.upgrade() { e.fire(&w, Trigger::Leave); }
+    });
+    win.add_controller(hover);
+
+    animation::add_paced_tick(win, move |w, dt| {
+        let entered = engine.machine.borrow_mut().advance(dt);
+        if let Some(state) = entered {
+            engine.show(w, &state);
+        } else if let Some(i) = engine.clock.borrow_mut().advance(dt) {
+            let state = engine.machine.borrow().state().to_string();
+            w.show_frame(&engine.frames[&state][i]);
+        }
+        glib::ControlFlow::Continue
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAT: &str = r#"{
+        "initial": "idle",
+        "states": {
+            "idle":  { "image": "idle.png", "on": { "enter": "alert", "click": "happy" } },
+            "alert": { "frames": ["a1.png", "a2.png"], "on": { "leave": "idle", "click": "happy" } },
+            "happy": { "image": "happy.png", "particles": "heart", "after_ms": 1500, "next": "idle" }
+        }
+    }"#;
+
+    #[test]
+    fn triggers_move_between_states() {
+        let mut m = Machine::new(Behavior::parse(CAT).unwrap());
+        assert_eq!(m.state(), "idle");
+        assert_eq!(m.fire(Trigger::Leave), None);
+        assert_eq!(m.fire(Trigger::Enter).as_deref(), Some("alert"));
+        assert_eq!(m.fire(Trigger::Click).as_deref(), Some("happy"));
+        // Timed states ignore triggers they don't list
+        assert_eq!(m.fire(Trigger::Click), None);
+    }
+
+    #[test]
+    fn timers_leave_the_state() {
+        let mut m = Machine::new(Behavior::parse(CAT).unwrap());
+        assert_eq!(m.advance(10.0), None);
+        m.fire(Trigger::Click);
+        assert_eq!(m.advance(1.0), None);
+        assert_eq!(m.advance(0.5).as_deref(), Some("idle"));
+    }
+
+    #[test]
+    fn frames_take_precedence_over_the_image() {
+        let b = Behavior::parse(CAT).unwrap();
+        assert_eq!(b.states["alert"].images().len(), 2);
+        assert_eq!(b.states["alert"].frame_ms, default_frame_ms());
+        assert_eq!(b.states["idle"].images(), vec![&PathBuf::from("idle.png")]);
+        assert_eq!(b.states["happy"].particles, Some(ParticleKind::Heart));
+    }
+
+    #[test]
+    fn unknown_states_are_rejected() {
+        let err = Behavior::parse(r#"{ "initial": "idle", "states": { "idle": { "on": { "pet": "purr" } } } }"#).unwrap_err();
+        assert!(err.contains("purr"));
+        assert!(Behavior::parse(r#"{ "initial": "nope", "states": {} }"#).is_err());
+        assert!(Behavior::parse(r#"{ "initial": "idle", "states": { "idle": { "on": { "wave": "idle" } } } }"#).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use gtk::gdk_pixbuf::{Colorspace, Pixbuf};
+use gtk::prelude::*;
+use gtk::{gdk, gio, glib, Application};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::animation;
+use crate::config;
+use crate::debug::size_text;
+use crate::preset::ChibiPreset;
+use crate::settings::RENDERERS;
+use crate::window::{self, ChibiWindow};
+
+// --- BENCHMARK ---
+// `bench --count N` is a stress run of its own, away from the manager and the saved chibis.
+// N chibis with a generated image are spawned one at a time, each timed until its first frame
+// is drawn; then all of them are moved as fast as the main loop allows, as a drag would; then
+// they circle for a while as their frame clocks are watched. The report goes to stdout, so
+// runs under different compositors and renderers can be put side by side.
+
+const DEFAULT_COUNT: usize = 20;
+const MAX_COUNT: usize = 500;
+const IMAGE_SIZE: i32 = 256;
+const CHIBI_SIZE: i32 = 128;
+// A chibi that hasn't drawn by then is counted as stuck, and the next one spawned
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(5);
+const MOVE_TIME: Duration = Duration::from_secs(2);
+const WANDER_TIME: Duration = Duration::from_secs(5);
+// How far chibis stray from their spot, in pixels
+const ORBIT: f64 = 40.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub count: usize,
+    // GSK renderer for the run; None goes by GSK_RENDERER or the saved setting, like a launch
+    pub renderer: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { count: DEFAULT_COUNT, renderer: None }
+    }
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--count" | "-n" => {
+                    let n = args.next().ok_or("--count needs a number")?;
+                    options.count = n.parse().ok().filter(|n| (1..=MAX_COUNT).contains(n))
+                        .ok_or_else(|| format!("--count takes 1 to {}, not '{}'", MAX_COUNT, n))?;
+                }
+                "--renderer" | "-r" => {
+                    let name = args.next().ok_or("--renderer needs a name")?;
+                    if !RENDERERS.contains(&name.as_str()) {
+                        return Err(format!("unknown renderer '{}', expected one of: {}", name, RENDERERS.join(", ")));
+                    }
+                    options.renderer = Some(name.clone());
+                }
+                _ => return Err(format!("unknown bench option '{}'", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Spread of a set of timings, in milliseconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spread {
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    pub fn of(samples: &[f64]) -> Option<Spread> {
+        if samples.is_empty() { return None; }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let at = |q: f64| sorted[((sorted.len() - 1) as f64 * q).round() as usize];
+        Some(Spread { median: at(0.5), p95: at(0.95), max: sorted[sorted.len() - 1] })
+    }
+
+    fn text(&self) -> String {
+        format!("median {:.1} ms · p95 {:.1} ms · max {:.1} ms", self.median, self.p95, self.max)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub count: usize,
+    pub desktop: String,
+    pub placement: String,
+    pub renderer: String,
+    // Milliseconds from creating each chibi to its first frame
+    pub spawns: Vec<f64>,
+    // Chibis that hadn't drawn by SPAWN_TIMEOUT
+    pub stuck: usize,
+    // Moves applied while dragging, over how many seconds
+    pub moves: usize,
+    pub move_secs: f64,
+    // Milliseconds between the frames of every circling chibi
+    pub frames: Vec<f64>,
+    pub cached_frames: usize,
+    pub cached_bytes: usize,
+}
+
+impl Report {
+    pub fn text(&self) -> String {
+        let mut out = format!("{} chibis on {} ({}), {} renderer\n", self.count, self.desktop, self.placement, self.renderer);
+        let mut spawn = Spread::of(&self.spawns).map_or("no chibi drew".into(), |s| s.text());
+        if self.stuck > 0 { spawn.push_str(&format!(" · {} never drew", self.stuck)); }
+        out.push_str(&format!("spawn to first frame  {}\n", spawn));
+        if self.moves > 0 && self.move_secs > 0.0 {
+            let per_sec = self.moves as f64 / self.move_secs;
+            out.push_str(&format!("drag moves            {:.0}/s · {:.1} µs each\n", per_sec, 1e6 / per_sec));
+        }
+        match Spread::of(&self.frames) {
+            Some(s) => {
+                // Frames taking twice the usual time mean one was dropped
+                let long = self.frames.iter().filter(|&&f| f > s.median * 2.0).count();
+                out.push_str(&format!("frame times           {} · {:.0} fps · {} long\n", s.text(), 1000.0 / s.median, long));
+            }
+            None => out.push_str("frame times           no frames drawn\n"),
+        }
+        out.push_str(&format!("frame cache           {} images · {}", self.cached_frames, size_text(self.cached_bytes)));
+        out
+    }
+}
+
+// A round stand-in with see-through corners, like the art real chibis use
+fn test_image(path: &Path) -> Result<(), glib::Error> {
+    let pixbuf = Pixbuf::new(Colorspace::Rgb, true, 8, IMAGE_SIZE, IMAGE_SIZE)
+        .ok_or_else(|| glib::Error::new(glib::FileError::Nomem, "no memory for the test image"))?;
+    pixbuf.fill(0);
+    let r = IMAGE_SIZE as f64 / 2.0;
+    for y in 0..IMAGE_SIZE {
+        for x in 0..IMAGE_SIZE {
+            let (dx, dy) = (x as f64 + 0.5 - r, y as f64 + 0.5 - r);
+            if dx * dx + dy * dy <= r * r { pixbuf.put_pixel(x as u32, y as u32, 0xf4, 0x8f, 0xb1, 0xff); }
+        }
+    }
+    pixbuf.savev(path, "png", &[])
+}
+
+// Where spawn number `i` goes: a grid over the screen, starting over once it's full
+fn spot(i: usize, (width, height): (i32, i32)) -> (f64, f64) {
+    let step = CHIBI_SIZE + ORBIT as i32 * 2;
+    let cols = (width / step).max(1) as usize;
+    let rows = (height / step).max(1) as usize;
+    let cell = i % (cols * rows);
+    ((cell % cols) as f64 * step as f64 + ORBIT, (cell / cols) as f64 * step as f64 + ORBIT)
+}
+
+fn orbit((x, y): (f64, f64), secs: f64, i: usize) -> (f64, f64) {
+    let angle = secs * std::f64::consts::TAU + i as f64;
+    ((x + ORBIT * angle.cos()).round(), (y + ORBIT * angle.sin()).round())
+}
+
+struct Bench {
+    app: Application,
+    count: usize,
+    image: PathBuf,
+    screen: (i32, i32),
+    // Every chibi with its spot
+    chibis: RefCell<Vec<(ChibiWindow, (f64, f64))>>,
+    report: Rc<RefCell<Report>>,
+    // Keeps the app running between the phases
+    hold: RefCell<Option<gio::ApplicationHoldGuard>>,
+}
+
+fn spawn_next(bench: Rc<Bench>) {
+    let i = bench.chibis.borrow().len();
+    if i == bench.count { return drag(bench); }
+    let (x, y) = spot(i, bench.screen);
+    let preset = ChibiPreset {
+        name: format!("Bench {}", i + 1),
+        path: bench.image.clone(),
+        width: CHIBI_SIZE,
+        x: x as i32,
+        y: y as i32,
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let win = ChibiWindow::new(&bench.app, &preset);
+    win.present();
+    bench.chibis.borrow_mut().push((win.clone(), (x, y)));
+    if i == 0 {
+        let renderer = win.renderer().map(|r| r.type_().name().trim_start_matches("Gsk").trim_end_matches("Renderer").to_string());
+        bench.report.borrow_mut().renderer = renderer.unwrap_or_else(|| "no".into());
+    }
+
+    // Whichever comes first, the first frame or the timeout, moves on to the next chibi
+    let done = Rc::new(Cell::new(false));
+    if let Some(clock) = win.frame_clock() {
+        let (bench, done) = (bench.clone(), done.clone());
+        let handler = Rc::new(Cell::new(None));
+        let this = handler.clone();
+        handler.set(Some(clock.connect_after_paint(move |clock| {
+            if let Some(id) = this.take() { clock.disconnect(id); }
+            if done.replace(true) { return; }
+            bench.report.borrow_mut().spawns.push(started.elapsed().as_secs_f64() * 1000.0);
+            let bench = bench.clone();
+            glib::idle_add_local_once(move || spawn_next(bench));
+        })));
+    }
+    glib::timeout_add_local_once(SPAWN_TIMEOUT, move || {
+        if done.replace(true) { return; }
+        bench.report.borrow_mut().stuck += 1;
+        spawn_next(bench);
+    });
+}
+
+// Moves every chibi once per main loop turn, the way a pointer drives a drag
+fn drag(bench: Rc<Bench>) {
+    let started = Instant::now();
+    let mut moves = 0;
+    glib::idle_add_local(move || {
+        let secs = started.elapsed().as_secs_f64();
+        if secs >= MOVE_TIME.as_secs_f64() {
+            let mut report = bench.report.borrow_mut();
+            (report.moves, report.move_secs) = (moves, secs);
+            drop(report);
+            wander(bench.clone());
+            return glib::ControlFlow::Break;
+        }
+        for (i, (win, home)) in bench.chibis.borrow().iter().enumerate() {
+            let (x, y) = orbit(*home, secs, i);
+            win.move_to(x, y);
+            moves += 1;
+        }
+        glib::ControlFlow::Continue
+    });
+}
+
+// Every chibi circles on its frame clock, which records the time between its frames
+fn wander(bench: Rc<Bench>) {
+    let running = Rc::new(Cell::new(true));
+    for (i, (win, home)) in bench.chibis.borrow().iter().enumerate() {
+        let (home, report, running) = (*home, bench.report.clone(), running.clone());
+        let started = Instant::now();
+        let last = Cell::new(None);
+        win.add_tick_callback(move |w, clock| {
+            if !running.get() { return glib::ControlFlow::Break; }
+            let now = clock.frame_time();
+            if let Some(last) = last.replace(Some(now)) { report.borrow_mut().frames.push((now - last) as f64 / 1000.0); }
+            let (x, y) = orbit(home, started.elapsed().as_secs_f64() / 4.0, i);
+            w.move_to(x, y);
+            glib::ControlFlow::Continue
+        });
+    }
+    glib::timeout_add_local_once(WANDER_TIME, move || {
+        running.set(false);
+        let (cached_frames, cached_bytes) = animation::cache_stats();
+        let mut report = bench.report.borrow_mut();
+        (report.cached_frames, report.cached_bytes) = (cached_frames, cached_bytes);
+        drop(report);
+        for (win, _) in bench.chibis.take() { win.close(); }
+        bench.hold.take();
+        bench.app.quit();
+    });
+}
+
+// Runs the benchmark and prints its report; the exit code for the command line
+pub fn run(options: &Options) -> i32 {
+    let settings = config::load_settings();
+    let renderer = options.renderer.clone().unwrap_or_else(|| settings.renderer.clone());
+    if renderer != "auto" && (options.renderer.is_some() || std::env::var_os("GSK_RENDERER").is_none()) {
+        std::env::set_var("GSK_RENDERER", &renderer);
+    }
+    animation::set_quality(settings.image_quality);
+    animation::set_max_fps(settings.max_fps);
+
+    let image = std::env::temp_dir().join(format!("chibi-bench-{}.png", std::process::id()));
+    if let Err(e) = test_image(&image) {
+        eprintln!("bench: couldn't make the test image: {}", e);
+        return 1;
+    }
+    let report = Rc::new(RefCell::new(Report {
+        count: options.count,
+        desktop: std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| "an unknown desktop".into()),
+        placement: format!("{:?}", window::placement()),
+        ..Default::default()
+    }));
+
+    let app = Application::builder()
+    .application_id("com.example.chibimanager.bench")
+    .flags(gio::ApplicationFlags::NON_UNIQUE)
+    .build();
+    let (count, image_path, report_run) = (options.count, image.clone(), report.clone());
+    app.connect_startup(|_| {
+        let Some(display) = gdk::Display::default() else { return };
+        let provider = gtk::CssProvider::new();
+        provider.load_from_data(window::CSS);
+        gtk::style_context_add_provider_for_display(&display, &provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    });
+    app.connect_activate(move |app| {
+        let screen = gdk::Display::default()
+            .and_then(|d| d.monitors().item(0))
+            .and_downcast::<gdk::Monitor>()
+            .map_or((1280, 720), |m| (m.geometry().width(), m.geometry().height()));
+        spawn_next(Rc::new(Bench {
+            app: app.clone(),
+            count,
+            image: image_path.clone(),
+            screen,
+            chibis: RefCell::new(Vec::new()),
+            report: report_run.clone(),
+            hold: RefCell::new(Some(app.hold())),
+        }));
+    });
+    let status = app.run_with_args::<&str>(&[]);
+    let _ = std::fs::remove_file(&image);
+    if status != glib::ExitCode::SUCCESS { return status.value(); }
+    println!("{}", report.borrow().text());
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn bench_options() {
+        assert_eq!(Options::parse(&[]), Ok(Options::default()));
+        assert_eq!(Options::parse(&args("--count 50 -r cairo")), Ok(Options { count: 50, renderer: Some("cairo".into()) }));
+        assert!(Options::parse(&args("--count 0")).is_err());
+        assert!(Options::parse(&args("--count")).is_err());
+        assert!(Options::parse(&args("--renderer metal")).is_err());
+        assert!(Options::parse(&args("--fast")).is_err());
+    }
+
+    #[test]
+    fn timing_spread() {
+        assert_eq!(Spread::of(&[]), None);
+        let frames: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(Spread::of(&frames), Some(Spread { median: 51.0, p95: 95.0, max: 100.0 }));
+        let report = Report { count: 2, spawns: vec![12.0, 20.0], stuck: 1, moves: 4000, move_secs: 2.0, frames: vec![16.7, 16.7, 40.0], ..Default::default() };
+        let text = report.text();
+        assert!(text.contains("median 20.0 ms") && text.contains("1 never drew"));
+        assert!(text.contains("2000/s · 500.0 µs each"));
+        assert!(text.contains("60 fps · 1 long"));
+    }
+}
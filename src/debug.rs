@@ -0,0 +1,137 @@
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use crate::animation;
+use crate::window::ChibiWindow;
+
+// --- DEBUG OVERLAY ---
+// Numbers for performance reports, in the corner of every chibi: how fast its window is
+// drawing, what its current image takes as a texture, what the shared frame cache holds and
+// how many chibis are out. On from Preferences, or for one run with --debug-overlay.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    // Frames the window drew per second lately; 0 while nothing changes
+    pub fps: f64,
+    pub image: Option<(i32, i32)>,
+    pub image_bytes: usize,
+    pub cached_frames: usize,
+    pub cached_bytes: usize,
+    pub chibis: usize,
+    pub hidden: usize,
+}
+
+// e.g. "512 B", "1.5 MiB"
+pub fn size_text(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+impl Stats {
+    pub fn text(&self) -> String {
+        let image = match self.image {
+            Some((w, h)) => format!("{}×{} · {}", w, h, size_text(self.image_bytes)),
+            None => "no texture".into(),
+        };
+        format!(
+            "{:.0} fps\n{}\ncache {} · {}\nchibis {} ({} hidden)",
+            self.fps, image, self.cached_frames, size_text(self.cached_bytes), self.chibis, self.hidden,
+        )
+    }
+}
+
+type Counter = Box<dyn Fn() -> (usize, usize)>;
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    // Bumped every time it's turned on, so overlays left from before stop by themselves
+    static GENERATION: Cell<u32> = const { Cell::new(0) };
+    // Chibis out and how many of them are hidden, from whoever keeps the registry
+    static COUNTER: RefCell<Option<Counter>> = const { RefCell::new(None) };
+}
+
+pub fn set_counter(counter: impl Fn() -> (usize, usize) + 'static) {
+    COUNTER.with(|c| c.replace(Some(Box::new(counter))));
+}
+
+// True when this just turned it on, and the chibis already out need attaching
+pub fn set_enabled(enabled: bool) -> bool {
+    let was = ENABLED.with(|e| e.replace(enabled));
+    if enabled && !was { GENERATION.with(|g| g.set(g.get() + 1)); }
+    enabled && !was
+}
+
+pub fn enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+const REFRESH: Duration = Duration::from_millis(500);
+
+fn stats(win: &ChibiWindow) -> Stats {
+    let frame = win.frame();
+    let (cached_frames, cached_bytes) = animation::cache_stats();
+    let (chibis, hidden) = COUNTER.with(|c| c.borrow().as_ref().map_or((0, 0), |count| count()));
+    Stats {
+        fps: win.frame_clock().map_or(0.0, |clock| clock.fps()),
+        image: frame.as_ref().map(|f| (f.texture.width(), f.texture.height())),
+        image_bytes: frame.as_ref().map_or(0, |f| f.bytes()),
+        cached_frames,
+        cached_bytes,
+        chibis,
+        hidden,
+    }
+}
+
+// Keeps the overlay up to date on `win` until it's turned off
+pub fn attach(win: &ChibiWindow) {
+    if !enabled() { return; }
+    let generation = GENERATION.with(|g| g.get());
+    win.set_debug_text(Some(&stats(win).text()));
+    animation::every_while_shown(win, REFRESH, move |w| {
+        if !enabled() || GENERATION.with(|g| g.get()) != generation {
+            if !enabled() { w.set_debug_text(None); }
+            return glib::ControlFlow::Break;
+        }
+        w.set_debug_text(Some(&stats(w).text()));
+        glib::ControlFlow::Continue
+    });
+}
+
+pub const CSS: &str = "\
+.chibi-debug { font-family: monospace; font-size: x-small; color: white; background-color: rgba(0,0,0,0.6); \
+padding: 2px 4px; border-radius: 4px; }";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!(size_text(512), "512 B");
+        assert_eq!(size_text(256 * 256 * 4), "256.0 KiB");
+        assert_eq!(size_text(3 * 1024 * 1024 / 2), "1.5 MiB");
+    }
+
+    #[test]
+    fn overlay_text() {
+        let stats = Stats {
+            fps: 59.7,
+            image: Some((256, 256)),
+            image_bytes: 262_144,
+            cached_frames: 3,
+            cached_bytes: 786_432,
+            chibis: 4,
+            hidden: 1,
+        };
+        assert_eq!(stats.text(), "60 fps\n256×256 · 256.0 KiB\ncache 3 · 768.0 KiB\nchibis 4 (1 hidden)");
+        assert!(Stats::default().text().contains("no texture"));
+    }
+}
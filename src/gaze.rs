@@ -0,0 +1,99 @@
+use gtk::glib;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::animation;
+use crate::window::ChibiWindow;
+#[cfg(feature = "x11")]
+use crate::x11;
+
+// --- CURSOR FOLLOWING ---
+// A preset can add a second image (eyes, a head) drawn over the base one and nudged a few
+// pixels towards the pointer, so the chibi seems to be watching. The pointer is polled:
+// X11 reports it anywhere on screen, Wayland only while it is over one of our chibis, and
+// in between the layer eases back to the middle.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FollowLayer {
+    pub image: PathBuf,
+    // How far (px) the layer may move off center
+    #[serde(default = "default_max_offset")]
+    pub max_offset: u32,
+}
+
+pub fn default_max_offset() -> u32 { 6 }
+
+const POLL: Duration = Duration::from_millis(33);
+// Pointers further away than this (px) get the full offset
+const FULL_RANGE: f64 = 300.0;
+// Share of the remaining distance covered per poll
+const EASING: f64 = 0.3;
+
+// Offset towards `cursor` for a layer centered on `center`, at most `max` px long
+pub fn gaze_offset(center: (f64, f64), cursor: (f64, f64), max: f64) -> (f64, f64) {
+    let (dx, dy) = (cursor.0 - center.0, cursor.1 - center.1);
+    let dist = dx.hypot(dy);
+    if dist < 1.0 { return (0.0, 0.0); }
+    let len = max * (dist / FULL_RANGE).min(1.0);
+    (dx / dist * len, dy / dist * len)
+}
+
+// Pointer position relative to the top-left corner of `win`, when it can be known
+fn pointer_position(win: &ChibiWindow) -> Option<(f64, f64)> {
+    #[cfg(feature = "x11")]
+    if let Some((x, y)) = x11::pointer() {
+        return Some((x as f64 - win.x(), y as f64 - win.placed_y() as f64));
+    }
+    let device = WidgetExt::display(win).default_seat()?.pointer()?;
+    let (surface, sx, sy) = device.surface_at_position();
+    let under = gtk::Native::for_surface(&surface?)?.downcast::<ChibiWindow>().ok()?;
+    // Chibis share one coordinate space, so another chibi's position translates to ours
+    Some((under.x() + sx - win.x(), (under.placed_y() - win.placed_y()) as f64 + sy))
+}
+
+// Shows `layer` over the chibi and keeps it looking at the pointer until the window goes away
+pub fn start_following(win: &ChibiWindow, layer: &FollowLayer) {
+    win.set_top_layer(Some(&layer.image));
+    let max = layer.max_offset as f64;
+    let current = Cell::new((0.0, 0.0));
+    animation::every_while_shown(win, POLL, move |w| {
+        let half = w.size() as f64 / 2.0;
+        let target = pointer_position(w).map_or((0.0, 0.0), |p| gaze_offset((half, half), p, max));
+        let (cx, cy) = current.get();
+        let next = (cx + (target.0 - cx) * EASING, cy + (target.1 - cy) * EASING);
+        if (next.0 - cx).abs() + (next.1 - cy).abs() > 0.05 {
+            current.set(next);
+            w.set_layer_offset(next.0.round() as i32, next.1.round() as i32);
+        }
+        glib::ControlFlow::Continue
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_points_at_the_cursor() {
+        let (dx, dy) = gaze_offset((100.0, 100.0), (1000.0, 100.0), 6.0);
+        assert_eq!((dx, dy), (6.0, 0.0));
+        let (dx, dy) = gaze_offset((100.0, 100.0), (100.0, -500.0), 6.0);
+        assert_eq!((dx, dy), (0.0, -6.0));
+    }
+
+    #[test]
+    fn nearby_cursors_move_it_less() {
+        let (dx, _) = gaze_offset((0.0, 0.0), (FULL_RANGE / 2.0, 0.0), 6.0);
+        assert_eq!(dx, 3.0);
+        assert_eq!(gaze_offset((5.0, 5.0), (5.0, 5.0), 6.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn max_offset_defaults_when_missing() {
+        let layer: FollowLayer = serde_json::from_str(r#"{"image": "/tmp/eyes.png"}"#).unwrap();
+        assert_eq!(layer.max_offset, default_max_offset());
+    }
+}
@@ -0,0 +1,159 @@
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::process::Command;
+
+// --- EXEC HOOKS ---
+// Shell commands run on chibi lifecycle events, per preset and/or globally (preset first).
+// They run through `sh -c` without blocking the UI, with the chibi described in CHIBI_* variables.
+// Files dropped onto a chibi are passed to its drop hook as "$@" (and in CHIBI_FILES, one per line).
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Hooks {
+    pub on_spawn: String,
+    pub on_close: String,
+    pub on_hide: String,
+    pub on_show: String,
+    pub on_click: String,
+    pub on_drop: String,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    Spawn,
+    Close,
+    Hide,
+    Show,
+    Click,
+    Drop,
+}
+
+const EVENTS: [Event; 6] = [Event::Spawn, Event::Close, Event::Hide, Event::Show, Event::Click, Event::Drop];
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Spawn => "spawn",
+            Event::Close => "close",
+            Event::Hide => "hide",
+            Event::Show => "show",
+            Event::Click => "click",
+            Event::Drop => "drop",
+        }
+    }
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        *self == Hooks::default()
+    }
+
+    fn command(&self, event: Event) -> &String {
+        match event {
+            Event::Spawn => &self.on_spawn,
+            Event::Close => &self.on_close,
+            Event::Hide => &self.on_hide,
+            Event::Show => &self.on_show,
+            Event::Click => &self.on_click,
+            Event::Drop => &self.on_drop,
+        }
+    }
+
+    fn command_mut(&mut self, event: Event) -> &mut String {
+        match event {
+            Event::Spawn => &mut self.on_spawn,
+            Event::Close => &mut self.on_close,
+            Event::Hide => &mut self.on_hide,
+            Event::Show => &mut self.on_show,
+            Event::Click => &mut self.on_click,
+            Event::Drop => &mut self.on_drop,
+        }
+    }
+}
+
+pub struct ChibiInfo {
+    pub id: u64,
+    pub preset_id: String,
+    pub name: String,
+    pub image: PathBuf,
+    pub x: i32,
+    pub y: i32,
+    pub size: i32,
+}
+
+thread_local! {
+    static GLOBAL: RefCell<Hooks> = RefCell::new(Hooks::default());
+}
+
+pub fn set_global(hooks: &Hooks) {
+    GLOBAL.with(|g| *g.borrow_mut() = hooks.clone());
+}
+
+pub fn fire(event: Event, preset_hooks: &Hooks, info: &ChibiInfo) {
+    fire_with_files(event, preset_hooks, info, &[]);
+}
+
+pub fn fire_with_files(event: Event, preset_hooks: &Hooks, info: &ChibiInfo, files: &[PathBuf]) {
+    let global = GLOBAL.with(|g| g.borrow().command(event).clone());
+    for cmd in [preset_hooks.command(event), &global] {
+        if cmd.trim().is_empty() { continue; }
+        run_shell(cmd, event.name(), info, files);
+    }
+}
+
+// `sh -c cmd` in the background, with CHIBI_EVENT set to `event` and `files` as "$@"
+pub fn run_shell(cmd: &str, event: &str, info: &ChibiInfo, files: &[PathBuf]) {
+    let file_list: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .arg("sh")
+        .args(files)
+        .env("CHIBI_EVENT", event)
+        .env("CHIBI_FILES", file_list.join("\n"))
+        .env("CHIBI_ID", info.id.to_string())
+        .env("CHIBI_PRESET_ID", &info.preset_id)
+        .env("CHIBI_NAME", &info.name)
+        .env("CHIBI_IMAGE", &info.image)
+        .env("CHIBI_X", info.x.to_string())
+        .env("CHIBI_Y", info.y.to_string())
+        .env("CHIBI_SIZE", info.size.to_string())
+        .spawn();
+    match child {
+        // Reap in the background so finished hooks don't linger as zombies
+        Ok(mut child) => { std::thread::spawn(move || child.wait()); }
+        Err(e) => tracing::warn!("Failed to run {} hook: {}", event, e),
+    }
+}
+
+// Collapsible block of command entries for the preset editor and Preferences;
+// the returned closure reads the current values back
+pub fn editor(hooks: &Hooks) -> (gtk::Expander, impl Fn() -> Hooks) {
+    let grid = gtk::Grid::builder().row_spacing(6).column_spacing(10).margin_top(6).build();
+    let entries: Vec<(Event, gtk::Entry)> = EVENTS.iter().enumerate().map(|(i, event)| {
+        let label = gtk::Label::new(Some(&format!("On {}", event.name())));
+        label.set_xalign(0.0);
+        let entry = gtk::Entry::new();
+        entry.set_hexpand(true);
+        entry.set_placeholder_text(Some("shell command, e.g. notify-send \"$CHIBI_NAME\""));
+        entry.set_text(hooks.command(*event));
+        grid.attach(&label, 0, i as i32, 1, 1);
+        grid.attach(&entry, 1, i as i32, 1, 1);
+        (*event, entry)
+    }).collect();
+
+    let expander = gtk::Expander::new(Some("Hooks"));
+    expander.set_child(Some(&grid));
+    expander.set_expanded(!hooks.is_empty());
+
+    let read = move || {
+        let mut hooks = Hooks::default();
+        for (event, entry) in &entries {
+            *hooks.command_mut(*event) = entry.text().trim().to_string();
+        }
+        hooks
+    };
+    (expander, read)
+}
@@ -0,0 +1,128 @@
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+use crate::control::{Command, Reply};
+
+// --- HTTP API ---
+// Optional REST endpoints on 127.0.0.1 for Home Assistant, Stream Deck plugins and the like.
+// Off unless enabled in Preferences. Requests go through the control socket's command path.
+//
+//   GET    /chibis                 active chibis
+//   POST   /chibis                 {"preset": "...", "x"?, "y"?, "size"?}
+//   DELETE /chibis/<id>
+//   POST   /chibis/<id>/move       {"x": .., "y": ..}
+//   POST   /chibis/<id>/resize     {"size": ..}
+//   POST   /hide, /show
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_BODY: usize = 64 * 1024;
+
+pub fn spawn_server<F>(port: u16, on_command: F) -> io::Result<()>
+where
+    F: Fn(Command, Sender<Reply>) + Send + Clone + 'static,
+{
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let on_command = on_command.clone();
+            std::thread::spawn(move || {
+                let _ = handle_client(stream, &on_command);
+            });
+        }
+    });
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Value,
+    // Browsers always send Origin on cross-site requests; local tools don't
+    from_browser: bool,
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut length = 0;
+    let mut from_browser = false;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() { break; }
+        let Some((name, value)) = header.split_once(':') else { continue };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().unwrap_or(0),
+            "origin" => from_browser = true,
+            _ => {}
+        }
+    }
+
+    let mut body = vec![0; length.min(MAX_BODY)];
+    reader.read_exact(&mut body)?;
+    let body = if body.is_empty() { Value::Null } else { serde_json::from_slice(&body).unwrap_or(Value::Null) };
+    Ok(Request { method, path, body, from_browser })
+}
+
+fn route(req: &Request) -> Result<Command, (u16, String)> {
+    let segments: Vec<&str> = req.path.trim_matches('/').split('/').collect();
+    let int = |key: &str| req.body[key].as_i64().map(|v| v as i32);
+    let need = |key: &str| int(key).ok_or((400, format!("missing integer '{}'", key)));
+    let chibi_id = |s: &str| s.parse::<u64>().map_err(|_| (404, format!("no chibi '{}'", s)));
+
+    match (req.method.as_str(), segments.as_slice()) {
+        ("GET", ["chibis"]) => Ok(Command::List),
+        ("POST", ["chibis"]) => {
+            let preset = req.body["preset"].as_str().ok_or((400, "missing string 'preset'".to_string()))?;
+            Ok(Command::Spawn { preset: preset.to_string(), x: int("x"), y: int("y"), size: int("size") })
+        }
+        ("DELETE", ["chibis", id]) => Ok(Command::Close(chibi_id(id)?)),
+        ("POST", ["chibis", id, "move"]) => Ok(Command::Move { id: chibi_id(id)?, x: need("x")?, y: need("y")? }),
+        ("POST", ["chibis", id, "resize"]) => Ok(Command::Resize { id: chibi_id(id)?, size: need("size")? }),
+        ("POST", ["hide"]) => Ok(Command::HideAll),
+        ("POST", ["show"]) => Ok(Command::ShowAll),
+        _ => Err((404, format!("no route for {} {}", req.method, req.path))),
+    }
+}
+
+fn handle_client<F: Fn(Command, Sender<Reply>)>(mut stream: TcpStream, on_command: &F) -> io::Result<()> {
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    let req = read_request(&stream)?;
+
+    let (status, body) = if req.from_browser {
+        (403, json!({ "error": "cross-origin requests are not allowed" }))
+    } else {
+        match route(&req) {
+            Ok(cmd) => {
+                let spawning = matches!(cmd, Command::Spawn { .. });
+                let (tx, rx) = channel();
+                on_command(cmd, tx);
+                let reply = rx.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(|_| Reply::Error("no reply from the manager".into()));
+                match reply.to_json() {
+                    Ok(result) => (if spawning { 201 } else { 200 }, result),
+                    Err(e) => (422, json!({ "error": e })),
+                }
+            }
+            Err((code, e)) => (code, json!({ "error": e })),
+        }
+    };
+
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        _ => "Unprocessable Entity",
+    };
+    let body = body.to_string();
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           status, reason, body.len(), body)
+}
@@ -0,0 +1,67 @@
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+
+// --- IDLE MONITOR ---
+// Follows logind's IdleHint for the current session. Idle daemons such as swayidle
+// (`swayidle idlehint <secs>`), GNOME and KDE keep this property up to date.
+
+#[derive(Debug, Clone, Copy)]
+pub enum IdleEvent {
+    // Realtime timestamp (µs) of when the user went idle
+    Idle(i64),
+    Active,
+}
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const SESSION_IFACE: &str = "org.freedesktop.login1.Session";
+
+// Subscribes on the main context; `on_event` is called on the main thread.
+pub fn watch<F: Fn(IdleEvent) + 'static>(on_event: F) {
+    gio::bus_get(gio::BusType::System, None::<&gio::Cancellable>, move |res| {
+        let Ok(conn) = res else { return };
+        let conn_c = conn.clone();
+        conn.call(
+            Some(LOGIN1),
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            "GetSession",
+            Some(&("auto",).to_variant()),
+            Some(glib::VariantTy::new("(o)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+            move |reply| {
+                let Ok(reply) = reply else { return };
+                let Some(path) = reply.child_value(0).str().map(str::to_string) else { return };
+                subscribe(&conn_c, &path, on_event);
+            },
+        );
+    });
+}
+
+fn subscribe<F: Fn(IdleEvent) + 'static>(conn: &gio::DBusConnection, path: &str, on_event: F) {
+    conn.signal_subscribe(
+        Some(LOGIN1),
+        Some("org.freedesktop.DBus.Properties"),
+        Some("PropertiesChanged"),
+        Some(path),
+        Some(SESSION_IFACE),
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            // (s interface, a{sv} changed, as invalidated)
+            let changed = params.child_value(1);
+            let dict = glib::VariantDict::new(Some(&changed));
+            let Some(idle) = dict.lookup::<bool>("IdleHint").ok().flatten() else { return };
+            if idle {
+                let since = dict.lookup::<u64>("IdleSinceHint").ok().flatten()
+                    .filter(|t| *t > 0)
+                    .map(|t| t as i64)
+                    .unwrap_or_else(glib::real_time);
+                on_event(IdleEvent::Idle(since));
+            } else {
+                on_event(IdleEvent::Active);
+            }
+        },
+    );
+}
@@ -0,0 +1,273 @@
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::EventControllerMotion;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::particles::ParticleKind;
+use crate::window::ChibiWindow;
+
+// --- PETTING ---
+// Petting is a burst of short back-and-forth strokes over the chibi. A second motion
+// controller on the window feeds PetDetector, which counts direction changes on either
+// axis; enough of them in quick succession (and not too far apart) count as one pet.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PetReaction {
+    #[default]
+    Nothing,
+    // Swaps to the preset's `happy_image` for a moment
+    Happy,
+    Wiggle,
+    Hearts,
+}
+
+impl PetReaction {
+    pub const ALL: [PetReaction; 4] = [PetReaction::Nothing, PetReaction::Happy, PetReaction::Wiggle, PetReaction::Hearts];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PetReaction::Nothing => "Do nothing",
+            PetReaction::Happy => "Happy image",
+            PetReaction::Wiggle => "Wiggle",
+            PetReaction::Hearts => "Hearts",
+        }
+    }
+
+    pub fn is_nothing(&self) -> bool {
+        *self == PetReaction::Nothing
+    }
+}
+
+// Movements shorter than this are jitter and don't change the stroke direction
+const JITTER_PX: f64 = 3.0;
+// A stroke longer than this is the pointer passing by, not petting
+const MAX_STROKE_PX: f64 = 120.0;
+const REVERSALS_NEEDED: usize = 4;
+const WINDOW_MS: u32 = 1200;
+// Quiet time after a pet, long enough for the reaction to play out
+const COOLDOWN_MS: u32 = 2500;
+const REACTION_TIME: Duration = Duration::from_millis(2000);
+
+#[derive(Default)]
+struct Axis {
+    // Where the current stroke started and which way it goes (+1/-1, 0 before the first move)
+    start: f64,
+    last: f64,
+    dir: i8,
+}
+
+impl Axis {
+    // True when the pointer turned around at the end of a short stroke
+    fn feed(&mut self, pos: f64) -> bool {
+        let delta = pos - self.last;
+        if delta.abs() < JITTER_PX { return false; }
+        self.last = pos;
+        let dir = if delta > 0.0 { 1 } else { -1 };
+        if self.dir == dir { return false; }
+        let turned = self.dir != 0 && (pos - delta - self.start).abs() <= MAX_STROKE_PX;
+        self.start = pos - delta;
+        self.dir = dir;
+        turned
+    }
+}
+
+#[derive(Default)]
+pub struct PetDetector {
+    x: Axis,
+    y: Axis,
+    // Event times (ms) of recent direction changes
+    reversals: Vec<u32>,
+    quiet_until: Option<u32>,
+}
+
+impl PetDetector {
+    // Feeds one pointer position; true when it completes a pet
+    pub fn motion(&mut self, x: f64, y: f64, time: u32) -> bool {
+        if self.quiet_until.is_some_and(|t| time < t) { return false; }
+        let turned_x = self.x.feed(x);
+        let turned_y = self.y.feed(y);
+        if !(turned_x || turned_y) { return false; }
+        self.reversals.retain(|t| time.saturating_sub(*t) <= WINDOW_MS);
+        self.reversals.push(time);
+        if self.reversals.len() < REVERSALS_NEEDED { return false; }
+        self.reversals.clear();
+        self.quiet_until = Some(time.saturating_add(COOLDOWN_MS));
+        true
+    }
+
+    // The pointer left: a new visit starts from scratch
+    pub fn reset(&mut self) {
+        self.x = Axis::default();
+        self.y = Axis::default();
+        self.reversals.clear();
+    }
+}
+
+// Calls `on_pet` whenever the chibi is petted outside move mode
+pub fn setup_petting(win: &ChibiWindow, on_pet: impl Fn(&ChibiWindow) + 'static) {
+    let detector = Rc::new(RefCell::new(PetDetector::default()));
+    let motion = EventControllerMotion::new();
+
+    let detector_enter = detector.clone();
+    motion.connect_enter(move |_, x, y| {
+        let mut d = detector_enter.borrow_mut();
+        d.reset();
+        d.x.last = x;
+        d.y.last = y;
+    });
+    let detector_leave = detector.clone();
+    motion.connect_leave(move |_| detector_leave.borrow_mut().reset());
+
+    let win_weak = win.downgrade();
+    motion.connect_motion(move |ctrl, x, y| {
+        let Some(w) = win_weak.upgrade() else { return };
+        if w.move_mode() { return; }
+        let petted = detector.borrow_mut().motion(x, y, ctrl.current_event_time());
+        if petted { on_pet(&w); }
+    });
+    win.add_controller(motion);
+}
+
+// Shows `temp` for `lifetime`, then goes back to `image`
+fn swap_image(win: &ChibiWindow, image: &Path, temp: &Path, lifetime: Duration) {
+    win.set_image(temp);
+    let win_weak = win.downgrade();
+    let image: PathBuf = image.to_path_buf();
+    glib::timeout_add_local_once(lifetime, move || {
+        if let Some(w) = win_weak.upgrade() { w.set_image(&image); }
+    });
+}
+
+// Plays `reaction` on the chibi; Happy shows `happy` and then goes back to `image`
+pub fn react(win: &ChibiWindow, reaction: PetReaction, image: &Path, happy: Option<&Path>) {
+    match reaction {
+        PetReaction::Nothing => {}
+        PetReaction::Happy => {
+            if let Some(happy) = happy { swap_image(win, image, happy, REACTION_TIME); }
+        }
+        PetReaction::Wiggle => win.flash_picture_class("chibi-wiggle", REACTION_TIME / 2),
+        PetReaction::Hearts => win.emit_particles(ParticleKind::Heart, 5),
+    }
+}
+
+// Two chibis bumped into each other (see movement::Crowd)
+pub fn greet(win: &ChibiWindow) {
+    win.flash_picture_class("chibi-wiggle", REACTION_TIME / 4);
+    win.emit_particles(ParticleKind::Heart, 2);
+}
+
+// --- CLICK REACTIONS ---
+// A short bit of feedback on a plain click (not a drag, not in move mode).
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickReaction {
+    #[default]
+    Nothing,
+    // Swaps to the preset's `click_image` for `click_ms`
+    Swap,
+    Squash,
+    Jump,
+}
+
+impl ClickReaction {
+    pub const ALL: [ClickReaction; 4] = [ClickReaction::Nothing, ClickReaction::Swap, ClickReaction::Squash, ClickReaction::Jump];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ClickReaction::Nothing => "Do nothing",
+            ClickReaction::Swap => "Alternate image",
+            ClickReaction::Squash => "Squash and stretch",
+            ClickReaction::Jump => "Jump",
+        }
+    }
+
+    pub fn is_nothing(&self) -> bool {
+        *self == ClickReaction::Nothing
+    }
+}
+
+pub fn default_click_ms() -> u32 { 600 }
+
+// Matches the animations in CSS
+const SQUASH_TIME: Duration = Duration::from_millis(400);
+const JUMP_TIME: Duration = Duration::from_millis(450);
+
+// Plays `reaction` on the chibi; Swap shows `alternate` for `ms` and then goes back to `image`
+pub fn react_to_click(win: &ChibiWindow, reaction: ClickReaction, image: &Path, alternate: Option<&Path>, ms: u32) {
+    match reaction {
+        ClickReaction::Nothing => {}
+        ClickReaction::Swap => {
+            if let Some(alternate) = alternate { swap_image(win, image, alternate, Duration::from_millis(ms as u64)); }
+        }
+        ClickReaction::Squash => win.flash_picture_class("chibi-squash", SQUASH_TIME),
+        ClickReaction::Jump => win.flash_picture_class("chibi-jump", JUMP_TIME),
+    }
+}
+
+// Built-in styling for the reactions; user style.css can override it
+pub const CSS: &str = "\
+@keyframes chibi-wiggle { 0% { transform: rotate(0deg); } 25% { transform: rotate(-6deg); } \
+75% { transform: rotate(6deg); } 100% { transform: rotate(0deg); } }\n\
+.chibi-wiggle { animation: chibi-wiggle 250ms ease-in-out 4; }\n\
+@keyframes chibi-squash { 0% { transform: scale(1, 1); } 30% { transform: scale(1.2, 0.8); } \
+60% { transform: scale(0.9, 1.1); } 100% { transform: scale(1, 1); } }\n\
+.chibi-squash { animation: chibi-squash 400ms ease-out 1; }\n\
+@keyframes chibi-jump { 0% { transform: translateY(0); } 40% { transform: translateY(-24px); } \
+70% { transform: translateY(0); } 85% { transform: scale(1.1, 0.9); } 100% { transform: scale(1, 1); } }\n\
+.chibi-jump { animation: chibi-jump 450ms ease-out 1; }";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Strokes of `len` px left and right along x, 50ms each
+    fn stroke(d: &mut PetDetector, from: u32, count: u32, len: f64) -> Option<u32> {
+        (0..count).map(|i| from + i * 50).find(|&t| {
+            let x = if (t / 50) % 2 == 0 { 0.0 } else { len };
+            d.motion(100.0 + x, 100.0, t)
+        })
+    }
+
+    #[test]
+    fn quick_short_strokes_are_petting() {
+        let mut d = PetDetector::default();
+        assert!(stroke(&mut d, 0, 10, 30.0).is_some());
+    }
+
+    #[test]
+    fn long_sweeps_and_jitter_are_not() {
+        let mut d = PetDetector::default();
+        assert_eq!(stroke(&mut d, 0, 20, 300.0), None);
+        let mut d = PetDetector::default();
+        assert_eq!(stroke(&mut d, 0, 20, 2.0), None);
+    }
+
+    #[test]
+    fn slow_strokes_are_not() {
+        let mut d = PetDetector::default();
+        let petted = (0..20).any(|i| d.motion(100.0 + (i % 2) as f64 * 30.0, 100.0, i * 1000));
+        assert!(!petted);
+    }
+
+    #[test]
+    fn cooldown_after_a_pet() {
+        let mut d = PetDetector::default();
+        let first = stroke(&mut d, 0, 10, 30.0).unwrap();
+        assert_eq!(stroke(&mut d, first + 50, 10, 30.0), None);
+        assert!(stroke(&mut d, first + COOLDOWN_MS, 10, 30.0).is_some());
+    }
+
+    #[test]
+    fn reaction_serializes_snake_case() {
+        assert_eq!(serde_json::to_string(&PetReaction::Hearts).unwrap(), "\"hearts\"");
+        assert!(PetReaction::default().is_nothing());
+        assert_eq!(serde_json::to_string(&ClickReaction::Squash).unwrap(), "\"squash\"");
+        assert!(ClickReaction::default().is_nothing());
+    }
+}
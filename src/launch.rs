@@ -0,0 +1,104 @@
+use gtk::gio;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::hooks::{self, ChibiInfo};
+use crate::report;
+
+// --- CLICK ACTIONS ---
+// What double-clicking a chibi does: run a shell command (with the same CHIBI_* variables
+// as the hooks), or hand a URL or file to the desktop's default application for it.
+// A preset's plain `link` is opened on single clicks the same way.
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "kind", content = "target", rename_all = "snake_case")]
+pub enum ClickAction {
+    #[default]
+    Nothing,
+    Command(String),
+    Url(String),
+    File(PathBuf),
+}
+
+// Order of the editor's drop-down, matching `kind` and `from_kind`
+pub const KINDS: [&str; 4] = ["Do nothing", "Run command", "Open URL", "Open file"];
+
+impl ClickAction {
+    pub fn is_nothing(&self) -> bool {
+        *self == ClickAction::Nothing
+    }
+
+    pub fn kind(&self) -> usize {
+        match self {
+            ClickAction::Nothing => 0,
+            ClickAction::Command(_) => 1,
+            ClickAction::Url(_) => 2,
+            ClickAction::File(_) => 3,
+        }
+    }
+
+    pub fn target(&self) -> String {
+        match self {
+            ClickAction::Nothing => String::new(),
+            ClickAction::Command(cmd) => cmd.clone(),
+            ClickAction::Url(url) => url.clone(),
+            ClickAction::File(path) => path.display().to_string(),
+        }
+    }
+
+    // An empty target means there is nothing to do
+    pub fn from_kind(kind: usize, target: &str) -> ClickAction {
+        let target = target.trim();
+        if target.is_empty() { return ClickAction::Nothing; }
+        match kind {
+            1 => ClickAction::Command(target.to_string()),
+            2 => ClickAction::Url(target.to_string()),
+            3 => ClickAction::File(PathBuf::from(target)),
+            _ => ClickAction::Nothing,
+        }
+    }
+
+    pub fn run(&self, info: &ChibiInfo) {
+        let uri = match self {
+            ClickAction::Nothing => return,
+            ClickAction::Command(cmd) => return hooks::run_shell(cmd, "double_click", info, &[]),
+            ClickAction::Url(url) => url.clone(),
+            ClickAction::File(path) => gio::File::for_path(path).uri().to_string(),
+        };
+        open_uri(&uri);
+    }
+}
+
+// Opens `uri` in the default application, reporting failures
+pub fn open_uri(uri: &str) {
+    let context = gtk::gdk::Display::default().map(|d| d.app_launch_context());
+    if let Err(e) = gio::AppInfo::launch_default_for_uri(uri, context.as_ref()) {
+        report::error(&format!("Could not open {}", uri), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editor_fields_round_trip() {
+        let actions = [
+            ClickAction::Command("mpc toggle".into()),
+            ClickAction::Url("https://example.org".into()),
+            ClickAction::File(PathBuf::from("/home/me/notes.txt")),
+        ];
+        for action in actions {
+            assert_eq!(ClickAction::from_kind(action.kind(), &action.target()), action);
+        }
+        assert_eq!(ClickAction::from_kind(1, "   "), ClickAction::Nothing);
+        assert_eq!(ClickAction::from_kind(0, "ignored"), ClickAction::Nothing);
+    }
+
+    #[test]
+    fn serialized_with_kind_and_target() {
+        let json = serde_json::to_string(&ClickAction::Url("https://example.org".into())).unwrap();
+        assert_eq!(json, r#"{"kind":"url","target":"https://example.org"}"#);
+    }
+}
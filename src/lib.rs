@@ -0,0 +1,161 @@
+// Core of Chibi Manager: presets, config files, the active chibi registry, the tray and
+// the chibi windows themselves. The binary in main.rs builds the manager UI on top of it.
+// Optional subsystems (the [features] in Cargo.toml) use the stand-ins in stubs/ when left out.
+
+use gtk::glib;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, SendError, Sender, TryRecvError};
+use std::sync::Arc;
+
+pub mod animation;
+pub mod audio;
+pub mod behavior;
+pub mod bench;
+pub mod bundle;
+#[cfg_attr(not(feature = "ipc"), path = "stubs/bus.rs")]
+pub mod bus;
+pub mod capture;
+pub mod compositor;
+pub mod config;
+pub mod control;
+pub mod debug;
+pub mod gaze;
+pub mod hooks;
+#[cfg_attr(not(feature = "ipc"), path = "stubs/http_api.rs")]
+pub mod http_api;
+pub mod idle;
+pub mod interaction;
+pub mod launch;
+pub mod movement;
+pub mod mpris;
+pub mod notifications;
+pub mod particles;
+pub mod pomodoro;
+pub mod preset;
+pub mod quotes;
+pub mod registry;
+pub mod reminders;
+pub mod report;
+pub mod schedule;
+pub mod screencast;
+pub mod settings;
+pub mod shortcuts;
+#[cfg_attr(not(feature = "ipc"), path = "stubs/socket.rs")]
+pub mod socket;
+pub mod speech;
+pub mod state;
+pub mod sysinfo;
+pub mod theme;
+pub mod thumbnail;
+pub mod toml_store;
+pub mod tray;
+pub mod typing;
+pub mod weather;
+pub mod window;
+#[cfg(feature = "x11")]
+pub mod x11;
+
+// Everything that reaches the main loop from the tray, background watchers and control interfaces
+pub enum AppMsg {
+    Quit,
+    ToggleManager,
+    SetHideAll(bool),
+    RefreshPresets,
+    SetHideOnFullscreen(bool),
+    FullscreenChanged(bool),
+    SetHideWhileScreencasting(bool),
+    SetMuted(bool),
+    ScreencastChanged(bool),
+    WorkspaceChanged(String),
+    RefreshVisibility,
+    IdleChanged(Option<i64>),
+    IdleTick,
+    ScheduleTick,
+    SpawnPreset(String),
+    // Random saved preset, optionally only among those with the given tag
+    SpawnRandom(Option<String>),
+    ToggleChibi(u64),
+    ToggleClickThrough(u64),
+    // Asks for a reminder the chibi will give
+    AskReminder(u64),
+    // Start/pause, skip or stop the pomodoro timer of an active chibi
+    Pomodoro(u64, pomodoro::Action),
+    RefreshTrayIcon,
+    // A chibi came, went or changed in a way the tray's list of them shows
+    ChibisChanged,
+    RefreshScenes,
+    RefreshTrayActions,
+    ActivateScene(String),
+    TrayAvailable(bool),
+    // A config file was changed by something other than this app (an editor, git pull)
+    ConfigChanged(config::ConfigFile),
+    // New reading from the weather provider
+    WeatherChanged(weather::Weather),
+    // Some app sent a desktop notification
+    Notification(notifications::Notification),
+    // Request from the control socket or D-Bus; the answer goes back on the sender
+    Control(control::Command, Sender<control::Reply>),
+}
+
+// --- MESSAGE QUEUE ---
+// AppMsgs are sent from all sorts of threads. Each send makes sure a wakeup for the main loop
+// is on its way, which then hands over everything queued, so nothing has to poll the queue
+// while the app sits idle.
+#[derive(Clone)]
+pub struct AppSender {
+    tx: Sender<AppMsg>,
+    // A delivery is already scheduled
+    woken: Arc<AtomicBool>,
+}
+
+pub struct AppReceiver {
+    rx: Receiver<AppMsg>,
+    woken: Arc<AtomicBool>,
+}
+
+pub fn app_channel() -> (AppSender, AppReceiver) {
+    let (tx, rx) = channel();
+    let woken = Arc::new(AtomicBool::new(false));
+    (AppSender { tx, woken: woken.clone() }, AppReceiver { rx, woken })
+}
+
+impl AppSender {
+    pub fn send(&self, msg: AppMsg) -> Result<(), SendError<AppMsg>> {
+        self.tx.send(msg)?;
+        if !self.woken.swap(true, Ordering::AcqRel) { glib::idle_add_once(deliver); }
+        Ok(())
+    }
+}
+
+type Delivery = (AppReceiver, Box<dyn Fn(Vec<AppMsg>)>);
+
+thread_local! {
+    static DELIVERY: RefCell<Option<Delivery>> = const { RefCell::new(None) };
+}
+
+// Runs on the thread that owns the default main context, like every idle callback
+fn deliver() {
+    DELIVERY.with(|d| {
+        let d = d.borrow();
+        let Some((receiver, handle)) = d.as_ref() else { return };
+        // Anything sent from here on needs a delivery of its own
+        receiver.woken.store(false, Ordering::Release);
+        let msgs: Vec<AppMsg> = receiver.rx.try_iter().collect();
+        if !msgs.is_empty() { handle(msgs); }
+    });
+}
+
+impl AppReceiver {
+    // From now on `handle` gets every batch of messages, on the calling (main) thread,
+    // starting with whatever was sent before
+    pub fn attach(self, handle: impl Fn(Vec<AppMsg>) + 'static) {
+        self.woken.store(true, Ordering::Release);
+        DELIVERY.with(|d| d.replace(Some((self, Box::new(handle)))));
+        glib::idle_add_once(deliver);
+    }
+
+    pub fn try_recv(&self) -> Result<AppMsg, TryRecvError> {
+        self.rx.try_recv()
+    }
+}
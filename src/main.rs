@@ -4,119 +4,764 @@ use gtk::{
     Application, ApplicationWindow, Button, CheckButton, ToggleButton, CssProvider,
     FileDialog, Label, ListBox, ListBoxRow, Orientation, Picture,
     ScrolledWindow, SpinButton, STYLE_PROVIDER_PRIORITY_APPLICATION,
-    EventControllerMotion, GestureClick, Entry,
+    Entry, DropDown,
     Box as GtkBox
 };
-use gtk4_layer_shell::{Edge, Layer, LayerShell};
-use ksni::{Tray, MenuItem, menu::{StandardItem, CheckmarkItem}, ToolTip};
 use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
-use std::sync::mpsc::{channel, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use std::path::PathBuf;
-use serde::{Serialize, Deserialize};
-use std::fs;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
-// --- DATA STRUCTURES ---
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct ChibiPreset {
-    id: String,
-    name: String,
-    path: PathBuf,
-    width: i32,
-    x: i32,
-    y: i32,
-    smart_hide: bool,
-    always_on_top: bool,
+use chibi_core::animation::{self, ImageQuality};
+use chibi_core::audio::{self, AppSound, SoundEvent};
+use chibi_core::behavior;
+use chibi_core::capture::{default_strength, default_threshold, Dance, DanceMove, MicReaction};
+use chibi_core::config::{self, get_config_dir, load_presets, load_session, load_settings, save_presets, save_settings, ConfigFile, Session};
+use chibi_core::gaze::{self, default_max_offset, FollowLayer};
+use chibi_core::interaction::{ClickReaction, PetReaction};
+use chibi_core::launch::ClickAction;
+use chibi_core::movement::{self, default_speed, Crowd, CrowdMode, WalkCycle, WanderArea};
+use chibi_core::mpris::{self, MediaReaction};
+use chibi_core::notifications::{self, NotificationSettings};
+use chibi_core::particles::ParticleKind;
+use chibi_core::pomodoro::{self, Pomodoro};
+use chibi_core::preset::{default_hide_delay, default_idle_minutes, move_onto, ChibiPreset, IdleAction};
+use chibi_core::quotes::{self, Quotes};
+use chibi_core::reminders::{self, Reminder};
+use chibi_core::registry::{close_active_rows, close_preset_windows, hook_info, step_crowd, update_idle_states, ActiveWindowRef};
+use chibi_core::schedule::{parse_crons, Chime, Schedule};
+use chibi_core::settings::{AppSettings, ChibiClick, RestoreMode, Scene, TrayAction, WhenFull, RENDERERS};
+use chibi_core::speech::SpeechEvent;
+use chibi_core::state::AppState;
+use chibi_core::sysinfo::{self, BatteryReaction, SystemMonitor};
+use chibi_core::tray::{ChibiTray, TrayChibi};
+use chibi_core::typing::{self, TypingReaction};
+use chibi_core::weather::{self, WeatherReaction, WeatherSettings};
+use chibi_core::window::{self, ChibiWindow, Placement};
+use chibi_core::{bench, bundle, bus, capture, compositor, control, debug, hooks, http_api, idle, interaction, launch, report, screencast, shortcuts, socket, speech, theme, thumbnail, tray};
+use chibi_core::{app_channel, AppMsg, AppSender};
+
+type Spawner = Rc<dyn Fn(ChibiPreset, bool) -> Option<u64>>;
+
+// Handles the preset list rows need; cloned into each row's callbacks
+#[derive(Clone)]
+struct PresetListCtx {
+    state: Rc<AppState>,
+    active_list: ListBox,
+    spawner: Spawner,
+    sender: AppSender,
+    window: ApplicationWindow,
 }
 
-struct ActiveWindowRef {
-    preset_id: Option<String>,
-    window: glib::WeakRef<gtk::Window>,
-    list_row: glib::WeakRef<ListBoxRow>,
+// --- CONTROLLER ---
+// Owns the long-lived widgets and services around the shared AppState; every AppMsg from the
+// tray, the watchers, the control interfaces and the UI lands in `handle`.
+struct Controller {
+    state: Rc<AppState>,
+    app: Application,
+    window: ApplicationWindow,
+    tray: tray::TrayHandle,
+    tray_warning: Label,
+    sender: AppSender,
+    spawner: Spawner,
+    active_list: ListBox,
+    preset_list: ListBox,
+    search: gtk::SearchEntry,
+    preset_ctx: PresetListCtx,
+    // Preset list rows still waiting to be built
+    preset_rows: Rc<RefCell<VecDeque<PresetListItem>>>,
+    screencast_enabled: screencast::Switch,
+    idle_tick: RefCell<Option<glib::SourceId>>,
+    // Runs while moving chibis might run into each other
+    crowd_tick: RefCell<Option<glib::SourceId>>,
+    crowd: Rc<RefCell<Crowd>>,
+    // Last seen in/out state of every scheduled preset
+    schedule_state: RefCell<HashMap<String, bool>>,
+    // What the tray's Active Chibis submenu currently shows
+    tray_chibis: RefCell<Vec<TrayChibi>>,
+    // Hour of the last schedule tick, for the hourly sounds
+    last_hour: Cell<Option<i32>>,
+    // Last minute (since the epoch) checked for chimes
+    last_minute: Cell<Option<i64>>,
+    // Runs while a schedule, chime or hourly sound/line has something to wait for
+    schedule_tick: RefCell<Option<glib::SourceId>>,
+    // Runs while chibis are out, the only time the session can change
+    autosave_tick: RefCell<Option<glib::SourceId>>,
+    // JSON of the session as last written
+    autosaved: Rc<RefCell<String>>,
 }
 
-enum AppMsg {
-    Quit,
-    ToggleManager,
-    ToggleHideAll,
-    RefreshPresets,
-}
+impl Controller {
+    fn handle(&self, msg: AppMsg) {
+        let state = &self.state;
+        let needs_visibility = matches!(msg,
+            AppMsg::SetHideAll(_) | AppMsg::SetHideOnFullscreen(_) | AppMsg::ScreencastChanged(_)
+            | AppMsg::FullscreenChanged(_) | AppMsg::WorkspaceChanged(_)
+            | AppMsg::RefreshVisibility | AppMsg::IdleChanged(_) | AppMsg::IdleTick
+            | AppMsg::ToggleChibi(_) | AppMsg::ConfigChanged(_));
+        match msg {
+            AppMsg::ToggleManager => {
+                self.window.set_visible(true);
+                self.window.present();
+            }
+            AppMsg::SetHideAll(hidden) => {
+                if hidden && !state.hide_all.get() { audio::play_app(AppSound::HideAll); }
+                state.set_hide_all(hidden);
+                // Echo back so the tray is right even when the change came from elsewhere
+                self.tray.update(|tray| tray.is_hidden = hidden);
+            }
+            AppMsg::SetHideOnFullscreen(enabled) => {
+                state.settings.borrow_mut().hide_on_fullscreen = enabled;
+                state.save_settings();
+            }
+            AppMsg::SetMuted(muted) => {
+                state.settings.borrow_mut().mute_sounds = muted;
+                state.save_settings();
+                audio::set_muted(muted);
+                self.tray.update(|tray| tray.is_muted = muted);
+            }
+            AppMsg::SetHideWhileScreencasting(enabled) => {
+                state.settings.borrow_mut().hide_while_screencasting = enabled;
+                state.save_settings();
+                self.screencast_enabled.set(enabled);
+            }
+            AppMsg::ScreencastChanged(active) => {
+                state.screencast.set(active);
+            }
+            AppMsg::WeatherChanged(w) => weather::set_current(w),
+            AppMsg::Notification(n) => {
+                let Some(settings) = state.settings.borrow().notifications.clone() else { return };
+                if !settings.allows(&n.app) { return; }
+                // The chosen preset's chibi, or else (with none chosen) the first one out
+                let win = state.registry.borrow().iter()
+                    .filter(|r| settings.preset.is_none() || r.preset_id == settings.preset)
+                    .find_map(|r| r.window.upgrade().filter(|w| w.is_visible()));
+                if let Some(w) = win { w.flash_bubble(&n.text(), Duration::from_secs(settings.seconds.max(1) as u64)); }
+            }
+            AppMsg::FullscreenChanged(fs) => {
+                if state.fullscreen.get() == fs { return; }
+                state.fullscreen.set(fs);
+            }
+            AppMsg::WorkspaceChanged(name) => {
+                *state.workspace.borrow_mut() = Some(name);
+            }
+            AppMsg::RefreshVisibility => {}
+            AppMsg::IdleChanged(since) => {
+                state.idle_since.set(since);
+                // Tick only while idle so per-chibi thresholds get crossed
+                let mut tick = self.idle_tick.borrow_mut();
+                if let Some(src) = tick.take() { src.remove(); }
+                if since.is_some() {
+                    let s = self.sender.clone();
+                    *tick = Some(glib::timeout_add_seconds_local(30, move || {
+                        let _ = s.send(AppMsg::IdleTick);
+                        glib::ControlFlow::Continue
+                    }));
+                }
+                update_idle_states(&mut state.registry.borrow_mut(), since);
+            }
+            AppMsg::IdleTick => {
+                update_idle_states(&mut state.registry.borrow_mut(), state.idle_since.get());
+            }
+            AppMsg::ScheduleTick => {
+                let Ok(now) = glib::DateTime::now_local() else { return };
+                // Chime (and speak) once the hour turns, not on the first tick after startup
+                if self.last_hour.replace(Some(now.hour())).is_some_and(|h| h != now.hour()) {
+                    for entry in state.registry.borrow().iter() {
+                        if entry.window.upgrade().is_some_and(|w| w.is_visible()) {
+                            // A chime takes over the pack's hourly sound
+                            if entry.data.chime.is_none() { audio::play_for(&entry.data, SoundEvent::Hourly); }
+                            speech::say_for(&entry.data.speech, &entry.data.name, SpeechEvent::Hourly);
+                        }
+                    }
+                }
+                // Every minute since the last tick (in case the timer slipped past one), again not at startup
+                let minute = now.to_unix().div_euclid(60);
+                let since = self.last_minute.replace(Some(minute)).map_or(minute + 1, |m| (m + 1).max(minute - 5));
+                for m in since..=minute {
+                    let Ok(at) = now.add_minutes((m - minute) as i32) else { continue };
+                    for entry in state.registry.borrow().iter() {
+                        let (Some(w), Some(chime)) = (entry.window.upgrade(), &entry.data.chime) else { continue };
+                        if w.is_visible() && chime.due(&at) { ring_chime(&w, &entry.data, chime); }
+                    }
+                }
+                let scheduled: Vec<ChibiPreset> = state.presets.borrow().iter()
+                    .filter(|p| p.schedule.is_some())
+                    .cloned()
+                    .collect();
+                let mut schedule_state = self.schedule_state.borrow_mut();
+                schedule_state.retain(|id, _| scheduled.iter().any(|p| &p.id == id));
+                for preset in scheduled {
+                    let active = preset.schedule.as_ref().is_some_and(|s| s.contains(&now));
+                    // Only act on boundary crossings so manual spawns/closes are respected
+                    if schedule_state.insert(preset.id.clone(), active) == Some(active) { continue; }
+                    let running = state.is_running(&preset.id);
+                    if active && !running {
+                        (self.spawner)(preset, false);
+                    } else if !active && running {
+                        close_preset_windows(&mut state.registry.borrow_mut(), &self.active_list, &preset.id);
+                    }
+                }
+            }
+            AppMsg::TrayAvailable(available) => {
+                state.tray_available.set(available);
+                self.tray_warning.set_visible(!available);
+                if !available {
+                    self.window.set_visible(true);
+                    self.window.present();
+                }
+            }
+            AppMsg::RefreshTrayActions => {
+                let st = state.settings.borrow();
+                let (primary, middle, favorite) = (st.tray_primary, st.tray_middle, st.favorite_preset.clone());
+                self.tray.update(|tray| {
+                    tray.primary_action = primary;
+                    tray.middle_action = middle;
+                    tray.favorite = favorite;
+                });
+            }
+            AppMsg::RefreshScenes => {
+                let st = state.settings.borrow();
+                let names: Vec<String> = st.scenes.iter().map(|s| s.name.clone()).collect();
+                let active = st.active_scene.clone();
+                self.tray.update(|tray| {
+                    tray.scenes = names;
+                    tray.active_scene = active;
+                });
+            }
+            AppMsg::ActivateScene(name) => {
+                let scene = state.settings.borrow().scenes.iter().find(|s| s.name == name).cloned();
+                let Some(scene) = scene else { return };
+                // Everything outside the scene goes, including unsaved spawns
+                let leaving: Vec<ListBoxRow> = state.registry.borrow().iter()
+                    .filter(|r| r.is_new.get() || !r.preset_id.as_ref().is_some_and(|id| scene.presets.contains(id)))
+                    .filter_map(|r| r.list_row.upgrade())
+                    .collect();
+                close_active_rows(&mut state.registry.borrow_mut(), &self.active_list, &leaving);
+                for id in &scene.presets {
+                    if state.is_running(id) { continue; }
+                    if let Some(preset) = state.preset(id) { (self.spawner)(preset, false); }
+                }
+                state.settings.borrow_mut().active_scene = Some(name);
+                state.save_settings();
+                let _ = self.sender.send(AppMsg::RefreshScenes);
+            }
+            // Nothing to do but the tray sync after every batch
+            AppMsg::ChibisChanged => {}
+            AppMsg::RefreshTrayIcon => {
+                let chosen = state.settings.borrow().tray_icon_preset.clone();
+                let preset = chosen.and_then(|id| state.preset(&id));
+                let icon = preset.as_ref().and_then(|p| thumbnail::tray_icon(&p.path));
+                let label = preset.filter(|_| icon.is_some()).map(|p| p.name);
+                self.tray.update(|tray| {
+                    tray.icon = icon.into_iter().collect();
+                    tray.icon_label = label;
+                });
+            }
+            AppMsg::ToggleChibi(key) => {
+                if let Some(r) = state.registry.borrow_mut().iter_mut().find(|r| r.key == key) {
+                    r.user_hidden = !r.user_hidden;
+                }
+            }
+            AppMsg::ToggleClickThrough(key) => {
+                let win = state.registry.borrow().iter().find(|r| r.key == key).and_then(|r| r.window.upgrade());
+                if let Some(w) = win { w.set_click_through(!w.click_through()); }
+            }
+            AppMsg::AskReminder(key) => {
+                let Some((win, preset)) = state.registry.borrow().iter()
+                    .find(|r| r.key == key)
+                    .and_then(|r| Some((r.window.upgrade()?, r.preset_id.clone()))) else { return };
+                prompt_text(&self.window, "Reminder", "Remind me in… (e.g. 20m to stretch)", "", move |line| {
+                    match reminders::parse(&line) {
+                        Ok((seconds, text)) => {
+                            win.flash_bubble(&format!("Okay! In {}: {}", reminders::describe_in(seconds), text), REMINDER_TIME);
+                            reminders::add(Reminder::in_seconds(seconds, &text, preset.clone()));
+                        }
+                        Err(e) => win.flash_bubble(&format!("Hmm, {}", e), REMINDER_TIME),
+                    }
+                });
+            }
+            AppMsg::Pomodoro(key, action) => {
+                let win = state.registry.borrow().iter().find(|r| r.key == key).and_then(|r| r.window.upgrade());
+                if let Some(w) = win { pomodoro::activate(&w, action); }
+            }
+            AppMsg::SpawnPreset(id) => {
+                if let Some(preset) = state.preset(&id) {
+                    (self.spawner)(preset, false);
+                }
+            }
+            AppMsg::SpawnRandom(tag) => {
+                let pool: Vec<ChibiPreset> = state.presets.borrow().iter()
+                    .filter(|p| tag.as_ref().is_none_or(|t| p.tags.contains(t)))
+                    .cloned()
+                    .collect();
+                if pool.is_empty() { return; }
+                let pick = glib::random_int_range(0, pool.len() as i32) as usize;
+                (self.spawner)(pool[pick].clone(), false);
+            }
+            AppMsg::RefreshPresets => self.refresh_presets(),
+            AppMsg::ConfigChanged(ConfigFile::Presets) => {
+                // Running chibis keep going; the list, tray and schedules follow the file
+                *state.presets.borrow_mut() = load_presets();
+                self.refresh_presets();
+            }
+            AppMsg::ConfigChanged(ConfigFile::Settings) => {
+                let settings = load_settings();
+                hooks::set_global(&settings.hooks);
+                movement::set_fling_enabled(settings.fling);
+                animation::set_quality(settings.image_quality);
+                animation::set_max_fps(settings.max_fps);
+                audio::set_muted(settings.mute_sounds);
+                audio::set_volume(settings.volume);
+                audio::set_app_sounds(&settings.sounds);
+                speech::set_muted(settings.mute_speech);
+                weather::configure(settings.weather.clone());
+                set_debug_overlay(state, settings.debug_overlay);
+                if settings.notifications.is_some() { notifications::listen(&self.sender); }
+                self.screencast_enabled.set(settings.hide_while_screencasting);
+                state.hide_all.set(settings.hide_all);
+                let (hidden, fs_hide, cast_hide) = (settings.hide_all, settings.hide_on_fullscreen, settings.hide_while_screencasting);
+                let muted = settings.mute_sounds;
+                *state.settings.borrow_mut() = settings;
+                self.tray.update(|tray| {
+                    tray.is_hidden = hidden;
+                    tray.is_muted = muted;
+                    tray.hide_on_fullscreen = fs_hide;
+                    tray.hide_while_screencasting = cast_hide;
+                });
+                for msg in [AppMsg::RefreshTrayActions, AppMsg::RefreshScenes] {
+                    let _ = self.sender.send(msg);
+                }
+                // Collapsed categories live in the settings too
+                self.refresh_presets();
+            }
+            AppMsg::Control(cmd, reply) => {
+                let _ = reply.send(self.handle_control(cmd));
+            }
+            AppMsg::Quit => {
+                socket::remove_socket();
+                state.save_session();
+                self.app.quit();
+            }
+        }
+        if needs_visibility {
+            state.apply_visibility();
+        }
+    }
 
-// --- TRAY HANDLER ---
+    fn handle_control(&self, cmd: control::Command) -> control::Reply {
+        use control::{Command, Reply};
+        let state = &self.state;
+        // Fire-and-forget commands map straight onto a loop message
+        let forward = |msg: AppMsg| {
+            let _ = self.sender.send(msg);
+            Reply::Done
+        };
+        match cmd {
+            Command::Spawn { preset: name, x, y, size } => match state.find_preset(&name) {
+                Some(preset) if preset.image_missing() => {
+                    Reply::Error(format!("the image for '{}' is missing ({})", preset.name, preset.path.display()))
+                }
+                Some(mut preset) => {
+                    let name = preset.name.clone();
+                    preset.x = x.unwrap_or(preset.x);
+                    preset.y = y.unwrap_or(preset.y);
+                    preset.width = size.unwrap_or(preset.width);
+                    match (self.spawner)(preset, false) {
+                        Some(id) => Reply::Spawned { id, name },
+                        None => Reply::Error(format!("'{}' wasn't spawned: the limit in Preferences is reached", name)),
+                    }
+                }
+                None => Reply::Error(format!("no preset named '{}'", name)),
+            },
+            Command::List => Reply::Active(state.active_chibis()),
+            Command::Status => Reply::Status {
+                active: state.active_chibis().len(),
+                hidden: state.hide_all.get(),
+                scene: state.settings.borrow().active_scene.clone(),
+            },
+            Command::HideAll => forward(AppMsg::SetHideAll(true)),
+            Command::ShowAll => forward(AppMsg::SetHideAll(false)),
+            Command::Close(key) => {
+                let row = state.registry.borrow().iter()
+                    .find(|r| r.key == key)
+                    .and_then(|r| r.list_row.upgrade());
+                match row {
+                    Some(row) => {
+                        close_active_rows(&mut state.registry.borrow_mut(), &self.active_list, &[row]);
+                        Reply::Done
+                    }
+                    None => Reply::Error(format!("no active chibi with id {}", key)),
+                }
+            }
+            Command::Move { id, x, y } => {
+                let win = state.registry.borrow().iter()
+                    .find(|r| r.key == id)
+                    .and_then(|r| r.window.upgrade());
+                match win {
+                    Some(win) => {
+                        win.move_to(x as f64, y as f64);
+                        Reply::Done
+                    }
+                    None => Reply::Error(format!("no active chibi with id {}", id)),
+                }
+            }
+            Command::Resize { id, size } => {
+                // Going through the row's spin button keeps it and the window in step
+                let spin = state.registry.borrow().iter()
+                    .find(|r| r.key == id)
+                    .and_then(|r| r.size_spin.upgrade());
+                match spin {
+                    Some(spin) => {
+                        spin.set_value(size as f64);
+                        Reply::Done
+                    }
+                    None => Reply::Error(format!("no active chibi with id {}", id)),
+                }
+            }
+            Command::SpawnRandom(tag) => forward(AppMsg::SpawnRandom(tag)),
+            Command::ToggleChibi(id) => forward(AppMsg::ToggleChibi(id)),
+            Command::ActivateScene(name) => {
+                let scene = state.settings.borrow().scenes.iter()
+                    .find(|s| s.name.eq_ignore_ascii_case(&name))
+                    .map(|s| s.name.clone());
+                match scene {
+                    Some(scene) => forward(AppMsg::ActivateScene(scene)),
+                    None => Reply::Error(format!("no scene named '{}'", name)),
+                }
+            }
+            Command::ToggleManager => forward(AppMsg::ToggleManager),
+            Command::SetHideOnFullscreen(on) => forward(AppMsg::SetHideOnFullscreen(on)),
+            Command::SetHideWhileScreencasting(on) => forward(AppMsg::SetHideWhileScreencasting(on)),
+            Command::Remind { seconds, text } => {
+                reminders::add(Reminder::in_seconds(seconds, &text, None));
+                Reply::Done
+            }
+            Command::Quit => forward(AppMsg::Quit),
+        }
+    }
 
-struct ChibiTray {
-    sender: Sender<AppMsg>,
-    is_hidden: bool,
-}
+    fn refresh_presets(&self) {
+        let list = &self.preset_list;
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+
+        let data_vec = self.state.presets.borrow();
+        let tray_presets: Vec<(String, String)> = data_vec.iter()
+            .map(|p| (p.id.clone(), p.name.clone()))
+            .collect();
+        let mut tray_tags: Vec<String> = data_vec.iter().flat_map(|p| p.tags.iter().cloned()).collect();
+        tray_tags.sort_unstable();
+        tray_tags.dedup();
+        self.tray.update(|tray| {
+            tray.presets = tray_presets;
+            tray.tags = tray_tags;
+        });
+        // The chosen preset's image may have been edited or the preset deleted
+        let _ = self.sender.send(AppMsg::RefreshTrayIcon);
+        let query = self.search.text().to_string();
+        let filtering = !query.trim().is_empty();
+
+        // Uncategorized presets first, then one collapsible section per category
+        let mut categories: Vec<&str> = data_vec.iter()
+            .filter_map(|p| p.category.as_deref())
+            .collect();
+        categories.sort_unstable();
+        categories.dedup();
+
+        let mut items: VecDeque<PresetListItem> = data_vec.iter()
+            .filter(|p| p.category.is_none() && p.matches_filter(&query))
+            .map(|p| PresetListItem::Preset(Box::new(p.clone())))
+            .collect();
+        for cat in categories {
+            let members: Vec<&ChibiPreset> = data_vec.iter()
+                .filter(|p| p.category.as_deref() == Some(cat) && p.matches_filter(&query))
+                .collect();
+            if members.is_empty() { continue; }
+            // Matches are always shown expanded while a filter is active
+            let collapsed = !filtering
+                && self.state.settings.borrow().collapsed_categories.iter().any(|c| c == cat);
+            items.push_back(PresetListItem::Header { category: cat.to_string(), count: members.len(), collapsed });
+            if collapsed { continue; }
+            items.extend(members.into_iter().map(|p| PresetListItem::Preset(Box::new(p.clone()))));
+        }
+
+        // The first rows go in now so the list never flashes empty; the rest follow from an
+        // idle source, which a refresh while it's still going simply hands the new rows to
+        let mut pending = self.preset_rows.borrow_mut();
+        let filling = !pending.is_empty();
+        *pending = items;
+        fill_preset_rows(list, &mut pending, &self.preset_ctx);
+        if filling || pending.is_empty() { return; }
+        let (list, pending, ctx) = (list.clone(), self.preset_rows.clone(), self.preset_ctx.clone());
+        glib::idle_add_local(move || {
+            let mut pending = pending.borrow_mut();
+            fill_preset_rows(&list, &mut pending, &ctx);
+            if pending.is_empty() { glib::ControlFlow::Break } else { glib::ControlFlow::Continue }
+        });
+    }
 
-impl Tray for ChibiTray {
-    fn id(&self) -> String { "chibi-manager".into() }
-    fn category(&self) -> ksni::Category { ksni::Category::ApplicationStatus }
-    fn title(&self) -> String { "Chibi Manager".into() }
-    fn status(&self) -> ksni::Status { ksni::Status::Active }
-    fn icon_name(&self) -> String { "face-smile".into() }
-
-    fn tool_tip(&self) -> ToolTip {
-        ToolTip {
-            title: "Chibi Manager".into(),
-            description: "Right-click for options".into(),
-            icon_name: "face-smile".into(),
-            icon_pixmap: Vec::new(),
-        }
-    }
-
-    fn menu(&self) -> Vec<MenuItem<Self>> {
-        vec![
-            StandardItem {
-                label: "Open Manager".into(),
-                activate: Box::new(move |this: &mut Self| {
-                    let _ = this.sender.send(AppMsg::ToggleManager);
-                }),
-                ..Default::default()
-            }.into(),
-            CheckmarkItem {
-                label: "Hide All Chibis".into(),
-                checked: self.is_hidden,
-                activate: Box::new(move |this: &mut Self| {
-                    this.is_hidden = !this.is_hidden;
-                    let _ = this.sender.send(AppMsg::ToggleHideAll);
-                }),
-                ..Default::default()
-            }.into(),
-            MenuItem::Separator,
-            StandardItem {
-                label: "Quit".into(),
-                activate: Box::new(move |this: &mut Self| {
-                    let _ = this.sender.send(AppMsg::Quit);
-                }),
-                ..Default::default()
-            }.into(),
-        ]
+    // Keeps the tray's Active Chibis submenu (and D-Bus listeners) in step with the registry
+    fn sync_tray_chibis(&self) {
+        let snapshot: Vec<TrayChibi> = self.state.registry.borrow().iter()
+            .filter_map(|r| {
+                let win = r.window.upgrade()?;
+                let label = r.name_label.upgrade().map(|l| l.text().to_string()).unwrap_or_default();
+                Some(TrayChibi { key: r.key, label, visible: !r.user_hidden, click_through: win.click_through(), pomodoro: r.data.pomodoro.is_some() })
+            })
+            .collect();
+        if *self.tray_chibis.borrow() == snapshot { return; }
+        let previous = self.tray_chibis.replace(snapshot.clone());
+        for c in &snapshot {
+            if !previous.iter().any(|p| p.key == c.key) { bus::emit_spawned(c.key, &c.label); }
+        }
+        for p in &previous {
+            if !snapshot.iter().any(|c| c.key == p.key) { bus::emit_closed(p.key); }
+        }
+        self.tray.update(|tray| tray.chibis = snapshot);
+    }
+
+    // Moving chibis steer clear of (or greet) each other. Chibis that stay put can't run into
+    // anything, so the check only ticks while there are two or more and one of them moves.
+    fn sync_crowd_tick(&self) {
+        let mode = self.state.settings.borrow().crowds;
+        let (count, moving) = self.state.registry.borrow().iter()
+            .filter(|r| r.window.upgrade().is_some())
+            .fold((0, false), |(count, moving), r| (count + 1, moving || r.data.wander.is_some() || r.data.walk.is_some()));
+        let needed = mode != CrowdMode::Ignore && count >= 2 && moving;
+        let mut tick = self.crowd_tick.borrow_mut();
+        if needed == tick.is_some() { return; }
+        match tick.take() {
+            Some(src) => src.remove(),
+            None => {
+                let (state, crowd) = (self.state.clone(), self.crowd.clone());
+                *tick = Some(glib::timeout_add_local(movement::CROWD_TICK, move || {
+                    let mode = state.settings.borrow().crowds;
+                    if mode != CrowdMode::Ignore {
+                        step_crowd(&state.registry.borrow(), &mut crowd.borrow_mut(), mode);
+                    }
+                    glib::ControlFlow::Continue
+                }));
+            }
+        }
+    }
+
+    fn sync_schedule_tick(&self) {
+        let state = &self.state;
+        let needed = state.presets.borrow().iter().any(|p| p.schedule.is_some())
+            || state.registry.borrow().iter()
+                .filter(|r| r.window.upgrade().is_some())
+                .any(|r| r.data.chime.is_some() || r.data.sound_pack.is_some() || !r.data.speech.hourly.is_empty());
+        let mut tick = self.schedule_tick.borrow_mut();
+        if needed == tick.is_some() { return; }
+        match tick.take() {
+            Some(src) => {
+                src.remove();
+                // Nothing to catch up on after a pause; the next start counts as startup
+                self.last_hour.set(None);
+                self.last_minute.set(None);
+            }
+            None => {
+                let _ = self.sender.send(AppMsg::ScheduleTick);
+                let sender = self.sender.clone();
+                *tick = Some(glib::timeout_add_seconds_local(60, move || {
+                    let _ = sender.send(AppMsg::ScheduleTick);
+                    glib::ControlFlow::Continue
+                }));
+            }
+        }
+    }
+
+    // Periodic session autosave, so a crash doesn't lose the layout; skipped while nothing
+    // moved, so an idle laptop doesn't write the disk every minute
+    fn sync_autosave(&self) {
+        let needed = self.state.registry.borrow().iter().any(|r| r.window.upgrade().is_some());
+        let mut tick = self.autosave_tick.borrow_mut();
+        if needed == tick.is_some() { return; }
+        let (state, autosaved) = (self.state.clone(), self.autosaved.clone());
+        let save = move || {
+            let session = serde_json::to_string(&state.session()).unwrap_or_default();
+            if session != *autosaved.borrow() {
+                state.save_session();
+                *autosaved.borrow_mut() = session;
+            }
+        };
+        match tick.take() {
+            Some(src) => {
+                src.remove();
+                // The last chibi just went; record that before going quiet
+                save();
+            }
+            None => {
+                *tick = Some(glib::timeout_add_seconds_local(60, move || {
+                    save();
+                    glib::ControlFlow::Continue
+                }));
+            }
+        }
     }
 }
 
 fn main() {
+    // Subcommands go to the running instance over the control socket; --flags are GTK's
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && (!args[1].starts_with('-') || args[1] == "-h" || args[1] == "--help") {
+        std::process::exit(run_command(&args));
+    }
+
+    report::init_logging();
+
     let app = Application::builder()
     .application_id("com.example.chibimanager.final_merged")
+    .flags(gtk::gio::ApplicationFlags::HANDLES_COMMAND_LINE)
     .build();
 
+    // A second launch with these flags hands them to the primary instance
+    app.add_main_option("toggle-hide", glib::Char::from(b't'), glib::OptionFlags::NONE, glib::OptionArg::None,
+                        "Toggle Hide All in the running manager", None);
+    app.add_main_option("start-minimized", glib::Char::from(b'm'), glib::OptionFlags::NONE, glib::OptionArg::None,
+                        "Start with only the tray icon, keeping the manager window hidden", None);
+    app.add_main_option("spawn", glib::Char::from(b's'), glib::OptionFlags::NONE, glib::OptionArg::String,
+                        "Spawn a saved preset by name or id", Some("PRESET"));
+    app.add_main_option("debug-overlay", glib::Char::from(b'd'), glib::OptionFlags::NONE, glib::OptionArg::None,
+                        "Show frame rate, texture memory and chibi counts on every chibi", None);
+    app.add_main_option("renderer", glib::Char::from(b'r'), glib::OptionFlags::NONE, glib::OptionArg::String,
+                        "GSK renderer for this run (auto, gl, ngl, vulkan or cairo) instead of the one in Preferences", Some("NAME"));
+    // GTK picks its renderer when it starts up, which comes right after this
+    app.connect_handle_local_options(|_, opts| {
+        let flag = opts.lookup::<String>("renderer").ok().flatten();
+        if let Some(name) = flag.as_deref().filter(|name| !RENDERERS.contains(name)) {
+            eprintln!("Unknown renderer '{}', expected one of: {}", name, RENDERERS.join(", "));
+            return 2;
+        }
+        let renderer = flag.clone().unwrap_or_else(|| load_settings().renderer);
+        // Asking on the command line beats GSK_RENDERER; the saved setting doesn't
+        if renderer != "auto" && (flag.is_some() || std::env::var_os("GSK_RENDERER").is_none()) {
+            tracing::info!("Using the {} renderer", renderer);
+            std::env::set_var("GSK_RENDERER", &renderer);
+        }
+        -1
+    });
+    app.connect_command_line(|app, cmdline| {
+        let opts = cmdline.options_dict();
+        let running = app.windows().iter().any(|w| w.title().as_deref() == Some("Chibi Manager"));
+        let toggle_hide = opts.contains("toggle-hide");
+        let spawn = opts.lookup::<String>("spawn").ok().flatten();
+        if opts.contains("start-minimized") {
+            START_MINIMIZED.store(true, Ordering::Relaxed);
+        }
+        if opts.contains("debug-overlay") {
+            DEBUG_OVERLAY.store(true, Ordering::Relaxed);
+        }
+
+        // Plain launches (first or repeated) show the manager; action flags only act
+        if !running || (!toggle_hide && spawn.is_none()) {
+            app.activate();
+        }
+        if toggle_hide {
+            app.activate_action("toggle-hide", None);
+        }
+        if let Some(name) = spawn {
+            app.activate_action("spawn", Some(&name.to_variant()));
+        }
+        0
+    });
+
     app.connect_startup(|_| {
+        let display = gtk::gdk::Display::default().expect("Could not connect to a display.");
         let provider = CssProvider::new();
-        provider.load_from_data(".ghost-window { background-color: rgba(0,0,0,0.001); }");
+        provider.load_from_data(&[
+            ".error-banner { padding: 6px 10px; background-color: alpha(@error_color, 0.15); }",
+            window::CSS,
+            interaction::CSS,
+            capture::CSS,
+            mpris::CSS,
+            debug::CSS,
+        ].join("\n"));
         gtk::style_context_add_provider_for_display(
-            &gtk::gdk::Display::default().expect("Could not connect to a display."),
+            &display,
                                                     &provider,
                                                     STYLE_PROVIDER_PRIORITY_APPLICATION,
         );
+        theme::load_user_css(&display, &get_config_dir().join("style.css"));
+        theme::follow_color_scheme();
     });
 
     app.connect_activate(build_ui);
     app.run();
 }
 
+// Set by --start-minimized before the first activation
+static START_MINIMIZED: AtomicBool = AtomicBool::new(false);
+// --debug-overlay: on for this run, whatever Preferences says
+static DEBUG_OVERLAY: AtomicBool = AtomicBool::new(false);
+
+fn run_command(args: &[String]) -> i32 {
+    let prog = Path::new(&args[0]).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    if matches!(args[1].as_str(), "-h" | "--help" | "help") {
+        println!("{}", control::usage(&prog));
+        return 0;
+    }
+    if args[1] == "completions" {
+        return match args.get(2).and_then(|shell| control::completions(shell, &prog)) {
+            Some(script) => { print!("{}", script); 0 }
+            None => { eprintln!("{}: completions needs one of bash, zsh, fish", prog); 2 }
+        };
+    }
+    // Runs here rather than in the manager, which keeps the user's chibis out of it
+    if args[1] == "bench" {
+        return match bench::Options::parse(&args[2..]) {
+            Ok(options) => bench::run(&options),
+            Err(e) => { eprintln!("{}: {}", prog, e); 2 }
+        };
+    }
+
+    let json = args.iter().any(|a| a == "--json");
+    let rest: Vec<String> = args[1..].iter().filter(|a| *a != "--json").cloned().collect();
+    let cmd = match control::Command::parse(&rest) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("{}: {}\n\n{}", prog, e, control::usage(&prog));
+            return 2;
+        }
+    };
+
+    if json {
+        return match socket::send_json(&cmd) {
+            Ok(Ok(result)) => {
+                println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+                0
+            }
+            Ok(Err(e)) => {
+                eprintln!("{}: {}", prog, e);
+                1
+            }
+            Err(e) => {
+                eprintln!("{}: could not reach the running manager ({})", prog, e);
+                1
+            }
+        };
+    }
+    match socket::send(&cmd) {
+        Ok(reply) if reply.starts_with("error: ") => {
+            eprintln!("{}: {}", prog, &reply["error: ".len()..]);
+            1
+        }
+        Ok(reply) => {
+            if !reply.is_empty() { println!("{}", reply); }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: could not reach the running manager ({})", prog, e);
+            1
+        }
+    }
+}
+
 fn build_ui(app: &Application) {
     let windows = app.windows();
     if let Some(existing_window) = windows.into_iter().find(|w| w.title().as_deref() == Some("Chibi Manager")) {
@@ -125,20 +770,113 @@ fn build_ui(app: &Application) {
     }
 
     // --- STATE MANAGEMENT ---
-    let active_registry: Rc<RefCell<Vec<ActiveWindowRef>>> = Rc::new(RefCell::new(Vec::new()));
-    let global_hide_state = Rc::new(Cell::new(false));
-    let presets: Rc<RefCell<Vec<ChibiPreset>>> = Rc::new(RefCell::new(load_presets()));
+    let state = AppState::load();
+    let settings = state.settings.borrow().clone();
+    hooks::set_global(&settings.hooks);
+    movement::set_fling_enabled(settings.fling);
+    animation::set_quality(settings.image_quality);
+    animation::set_max_fps(settings.max_fps);
+    audio::set_muted(settings.mute_sounds);
+    audio::set_volume(settings.volume);
+    audio::set_app_sounds(&settings.sounds);
+    speech::set_muted(settings.mute_speech);
+    weather::configure(settings.weather.clone());
+    let state_debug = state.clone();
+    debug::set_counter(move || {
+        let windows: Vec<ChibiWindow> = state_debug.registry.borrow().iter().filter_map(|r| r.window.upgrade()).collect();
+        (windows.len(), windows.iter().filter(|w| !w.is_visible()).count())
+    });
+    set_debug_overlay(&state, settings.debug_overlay);
+    match window::placement() {
+        #[cfg(feature = "layer-shell")]
+        Placement::LayerShell => {}
+        #[cfg(feature = "x11")]
+        Placement::X11 => tracing::info!("No layer-shell support, placing chibis through X11"),
+        Placement::Floating => tracing::warn!("No layer-shell or X11: the compositor decides where chibis go"),
+    }
 
-    let (sender, receiver) = channel();
+    let (sender, receiver) = app_channel();
     let tray_sender = sender.clone();
+    let screencast_enabled = screencast::Switch::new(settings.hide_while_screencasting);
+    let screencast_sender = sender.clone();
+    screencast::spawn_watcher(screencast_enabled.clone(), move |active| {
+        let _ = screencast_sender.send(AppMsg::ScreencastChanged(active));
+    });
+    let weather_sender = sender.clone();
+    weather::spawn_watcher(move |w| {
+        let _ = weather_sender.send(AppMsg::WeatherChanged(w));
+    });
+    if settings.notifications.is_some() { notifications::listen(&sender); }
 
-    std::thread::spawn(move || {
-        let service = ksni::TrayService::new(ChibiTray {
-            sender: tray_sender,
-            is_hidden: false,
-        });
-        let _handle = service.spawn();
-        std::thread::park();
+    let compositor_sender = sender.clone();
+    compositor::spawn_listener(move |event| match event {
+        compositor::CompositorEvent::Fullscreen(fs) => {
+            let _ = compositor_sender.send(AppMsg::FullscreenChanged(fs));
+        }
+        compositor::CompositorEvent::Workspace(name) => {
+            let _ = compositor_sender.send(AppMsg::WorkspaceChanged(name));
+        }
+    });
+
+    let idle_sender = sender.clone();
+    idle::watch(move |event| {
+        let since = match event {
+            idle::IdleEvent::Idle(t) => Some(t),
+            idle::IdleEvent::Active => None,
+        };
+        let _ = idle_sender.send(AppMsg::IdleChanged(since));
+    });
+
+    let tray_handle = tray::spawn(ChibiTray {
+        sender: tray_sender,
+        is_hidden: state.hide_all.get(),
+        hide_on_fullscreen: settings.hide_on_fullscreen,
+        hide_while_screencasting: settings.hide_while_screencasting,
+        is_muted: settings.mute_sounds,
+        presets: Vec::new(),
+        tags: Vec::new(),
+        scenes: Vec::new(),
+        active_scene: None,
+        chibis: Vec::new(),
+        primary_action: settings.tray_primary,
+        middle_action: settings.tray_middle,
+        favorite: settings.favorite_preset.clone(),
+        icon: Vec::new(),
+        icon_label: None,
+    });
+
+    let control_sender = sender.clone();
+    if let Err(e) = socket::spawn_server(move |cmd, reply| {
+        let _ = control_sender.send(AppMsg::Control(cmd, reply));
+    }) {
+        report::error("Control socket unavailable", e);
+    }
+    if settings.http_api {
+        let http_sender = sender.clone();
+        let port = settings.http_port;
+        if let Err(e) = http_api::spawn_server(port, move |cmd, reply| {
+            let _ = http_sender.send(AppMsg::Control(cmd, reply));
+        }) {
+            report::error(&format!("HTTP API unavailable on port {}", port), e);
+        }
+    }
+    let shortcut_sender = sender.clone();
+    let state_shortcut = state.clone();
+    shortcuts::register(move |id| {
+        let msg = match id {
+            "toggle-hide" => AppMsg::SetHideAll(!state_shortcut.hide_all.get()),
+            "spawn-favorite" => match state_shortcut.settings.borrow().favorite_preset.clone() {
+                Some(preset) => AppMsg::SpawnPreset(preset),
+                None => AppMsg::SpawnRandom(None),
+            },
+            "toggle-manager" => AppMsg::ToggleManager,
+            _ => return,
+        };
+        let _ = shortcut_sender.send(msg);
+    });
+    let bus_sender = sender.clone();
+    bus::export(move |cmd, reply| {
+        let _ = bus_sender.send(AppMsg::Control(cmd, reply));
     });
 
     let window = ApplicationWindow::builder()
@@ -148,11 +886,23 @@ fn build_ui(app: &Application) {
     .default_height(450)
     .build();
 
+    // Without a tray a hidden window would be unreachable, so closing only minimizes then
+    let state_close = state.clone();
     window.connect_close_request(move |win| {
-        win.set_visible(false);
+        if state_close.tray_available.get() {
+            win.set_visible(false);
+        } else {
+            win.minimize();
+        }
         glib::Propagation::Stop
     });
 
+    let tray_warning = Label::new(Some("⚠️ No system tray found. Closing this window will minimize it instead of hiding it; use Quit to exit."));
+    tray_warning.add_css_class("warning");
+    tray_warning.set_wrap(true);
+    tray_warning.set_margin_top(6);
+    tray_warning.set_visible(false);
+
     // --- UI LAYOUT ---
     let main_layout = GtkBox::new(Orientation::Horizontal, 10);
     main_layout.set_margin_top(10);
@@ -169,62 +919,146 @@ fn build_ui(app: &Application) {
     let file_btn = Button::with_label("📂 Select Image");
     let selected_path: Rc<RefCell<Option<PathBuf>>> = Rc::new(RefCell::new(None));
 
+    // Preview at the actual spawn size; larger images scroll inside the pane
+    let preview = Picture::new();
+    preview.set_content_fit(gtk::ContentFit::Contain);
+    preview.set_can_shrink(false);
+    preview.set_halign(gtk::Align::Center);
+    preview.set_valign(gtk::Align::Center);
+    let preview_scrolled = ScrolledWindow::builder().min_content_height(160).build();
+    preview_scrolled.add_css_class("frame");
+    preview_scrolled.set_child(Some(&preview));
+
     let path_c = selected_path.clone();
     let label_c = file_label.clone();
+    let preview_c = preview.clone();
+    let select_image: Rc<dyn Fn(PathBuf)> = Rc::new(move |path: PathBuf| {
+        label_c.set_text(&path.file_name().unwrap_or_default().to_string_lossy());
+        preview_c.set_filename(Some(&path));
+        *path_c.borrow_mut() = Some(path);
+    });
+
+    let select_c = select_image.clone();
     let win_c = window.clone();
     file_btn.connect_clicked(move |_| {
         let dialog = FileDialog::builder().title("Select Image").modal(true).build();
-        let p = path_c.clone();
-        let l = label_c.clone();
+        let select = select_c.clone();
         dialog.open(Some(&win_c), None::<&gtk::gio::Cancellable>, move |res| {
-            if let Ok(file) = res {
-                if let Some(path) = file.path() {
-                    *p.borrow_mut() = Some(path.clone());
-                    l.set_text(path.file_name().unwrap().to_str().unwrap());
-                }
+            if let Some(path) = report::chosen_path(res) {
+                select(path);
             }
         });
     });
 
     controls_vbox.append(&file_btn);
     controls_vbox.append(&file_label);
+    controls_vbox.append(&preview_scrolled);
     controls_vbox.append(&gtk::Separator::new(Orientation::Horizontal));
 
     controls_vbox.append(&Label::new(Some("Size (px):")));
     let spin_size = SpinButton::with_range(50.0, 1000.0, 10.0);
-    spin_size.set_value(200.0);
+    let defaults = settings.clone();
+    spin_size.set_value(defaults.default_width as f64);
+    preview.set_size_request(defaults.default_width, defaults.default_width);
+    let preview_size = preview.clone();
+    spin_size.connect_value_changed(move |sp| {
+        let size = sp.value() as i32;
+        preview_size.set_size_request(size, size);
+    });
     controls_vbox.append(&spin_size);
 
     controls_vbox.append(&Label::new(Some("Spawn X:")));
     let spin_x = SpinButton::with_range(0.0, 5000.0, 50.0);
-    spin_x.set_value(100.0);
+    spin_x.set_value(defaults.default_x as f64);
     controls_vbox.append(&spin_x);
 
     controls_vbox.append(&Label::new(Some("Spawn Y:")));
     let spin_y = SpinButton::with_range(0.0, 3000.0, 50.0);
-    spin_y.set_value(100.0);
+    spin_y.set_value(defaults.default_y as f64);
     controls_vbox.append(&spin_y);
 
     let check_hide = CheckButton::with_label("Smart Hide");
+    check_hide.set_active(defaults.default_smart_hide);
+    let hover_label = Label::new(Some("Hide after hover (ms):"));
+    let spin_hover = SpinButton::with_range(0.0, 5000.0, 50.0);
+    spin_hover.set_value(defaults.default_hover_ms as f64);
     let check_top = CheckButton::with_label("Always on Top");
+    check_top.set_active(defaults.default_on_top);
     controls_vbox.append(&check_hide);
+    controls_vbox.append(&hover_label);
+    controls_vbox.append(&spin_hover);
+    controls_vbox.append(&Label::new(Some("Stay hidden for (s):")));
+    let spin_delay = SpinButton::with_range(1.0, 60.0, 1.0);
+    spin_delay.set_value(defaults.default_hide_delay as f64);
+    controls_vbox.append(&spin_delay);
     controls_vbox.append(&check_top);
 
+    let entry_workspaces = Entry::new();
+    entry_workspaces.set_placeholder_text(Some("Workspaces (e.g. 1, 2) — blank = all"));
+    controls_vbox.append(&entry_workspaces);
+
+    controls_vbox.append(&Label::new(Some("When idle:")));
+    let idle_box = GtkBox::new(Orientation::Horizontal, 5);
+    let idle_labels: Vec<&str> = IdleAction::ALL.iter().map(|a| a.label()).collect();
+    let drop_idle = DropDown::from_strings(&idle_labels);
+    drop_idle.set_hexpand(true);
+    let spin_idle = SpinButton::with_range(1.0, 240.0, 1.0);
+    spin_idle.set_value(default_idle_minutes() as f64);
+    spin_idle.set_tooltip_text(Some("Minutes of inactivity"));
+    idle_box.append(&drop_idle);
+    idle_box.append(&spin_idle);
+    controls_vbox.append(&idle_box);
+
+    let sleep_btn = Button::with_label("💤 Sleep Image…");
+    let sleep_path: Rc<RefCell<Option<PathBuf>>> = Rc::new(RefCell::new(None));
+    let sleep_path_c = sleep_path.clone();
+    let win_sleep = window.clone();
+    sleep_btn.connect_clicked(move |btn| {
+        let dialog = FileDialog::builder().title("Select Sleep Image").modal(true).build();
+        let p = sleep_path_c.clone();
+        let b = btn.clone();
+        dialog.open(Some(&win_sleep), None::<&gtk::gio::Cancellable>, move |res| {
+            if let Some(path) = report::chosen_path(res) {
+                b.set_label(&format!("💤 {}", path.file_name().unwrap_or_default().to_string_lossy()));
+                *p.borrow_mut() = Some(path);
+            }
+        });
+    });
+    controls_vbox.append(&sleep_btn);
+
+    let entry_schedule = Entry::new();
+    entry_schedule.set_placeholder_text(Some("Schedule (e.g. Mon-Fri 09:00-18:00)"));
+    entry_schedule.connect_changed(|e| {
+        let txt = e.text();
+        if txt.trim().is_empty() || Schedule::parse(&txt).is_some() {
+            e.remove_css_class("error");
+        } else {
+            e.add_css_class("error");
+        }
+    });
+    controls_vbox.append(&entry_schedule);
+
     let spawn_btn = Button::with_label("✨ SPAWN ✨");
     spawn_btn.add_css_class("suggested-action");
     spawn_btn.set_margin_top(10);
     controls_vbox.append(&spawn_btn);
 
     controls_vbox.append(&gtk::Separator::new(Orientation::Horizontal));
+    let prefs_btn = Button::with_label("⚙️ Preferences");
+    controls_vbox.append(&prefs_btn);
     let quit_btn = Button::with_label("Quit Application");
     quit_btn.add_css_class("destructive-action");
-    let app_quit_btn = app.clone();
+    let sender_quit_btn = sender.clone();
     quit_btn.connect_clicked(move |_| {
-        app_quit_btn.quit();
+        let _ = sender_quit_btn.send(AppMsg::Quit);
     });
     controls_vbox.append(&quit_btn);
 
-    main_layout.append(&controls_vbox);
+    let controls_scrolled = ScrolledWindow::builder()
+    .hscrollbar_policy(gtk::PolicyType::Never)
+    .child(&controls_vbox)
+    .build();
+    main_layout.append(&controls_scrolled);
     main_layout.append(&gtk::Separator::new(Orientation::Vertical));
 
     // Right Column (Lists)
@@ -236,36 +1070,156 @@ fn build_ui(app: &Application) {
     let active_list = ListBox::new();
     active_list.add_css_class("frame");
     active_scrolled.set_child(Some(&active_list));
+    active_list.set_selection_mode(gtk::SelectionMode::Multiple);
     right_vbox.append(&active_scrolled);
 
-    right_vbox.append(&Label::new(Some("Saved Presets")));
+    let active_batch = GtkBox::new(Orientation::Horizontal, 5);
+    let batch_close = Button::with_label("Close Selected");
+    batch_close.set_sensitive(false);
+    active_batch.append(&batch_close);
+    let scene_btn = Button::with_label("🎬 Save Scene…");
+    scene_btn.set_tooltip_text(Some("Save the saved presets on screen as a scene for the tray"));
+    active_batch.append(&scene_btn);
+    right_vbox.append(&active_batch);
+
+    let presets_header = GtkBox::new(Orientation::Horizontal, 5);
+    let presets_title = Label::new(Some("Saved Presets"));
+    presets_title.set_hexpand(true);
+    let import_btn = Button::with_label("📥 Import…");
+    import_btn.set_tooltip_text(Some("Import a .chibi preset bundle"));
+    presets_header.append(&presets_title);
+    presets_header.append(&import_btn);
+    right_vbox.append(&presets_header);
+
+    let search_entry = gtk::SearchEntry::new();
+    search_entry.set_placeholder_text(Some("Filter by name, category or tag…"));
+    right_vbox.append(&search_entry);
     let preset_scrolled = ScrolledWindow::builder().min_content_height(150).vexpand(true).build();
     let preset_list = ListBox::new();
     preset_list.add_css_class("frame");
     preset_scrolled.set_child(Some(&preset_list));
+    preset_list.set_selection_mode(gtk::SelectionMode::Multiple);
     right_vbox.append(&preset_scrolled);
 
+    let preset_batch = GtkBox::new(Orientation::Horizontal, 5);
+    let batch_spawn = Button::with_label("Spawn Selected");
+    let batch_delay = Button::with_label("Set Delay…");
+    let batch_delete = Button::with_label("Delete Selected");
+    batch_delete.add_css_class("destructive-action");
+    for b in [&batch_spawn, &batch_delay, &batch_delete] {
+        b.set_sensitive(false);
+        preset_batch.append(b);
+    }
+    right_vbox.append(&preset_batch);
+
     main_layout.append(&right_vbox);
-    window.set_child(Some(&main_layout));
+    let window_layout = GtkBox::new(Orientation::Vertical, 0);
+    window_layout.append(&report::banner());
+    window_layout.append(&tray_warning);
+    main_layout.set_vexpand(true);
+    window_layout.append(&main_layout);
+    window.set_child(Some(&window_layout));
 
     // --- SHARED REFERENCES ---
     let app_clone = app.clone();
     let active_list_ref = active_list.clone();
-    let active_reg_ref = active_registry.clone();
-    let presets_data_ref = presets.clone();
-    let preset_list_ref = preset_list.clone();
+    let state_spawn = state.clone();
     let parent_win_ref = window.clone();
     let sender_for_spawn = sender.clone();
+    let next_key = Cell::new(0u64);
 
     // --- ACTIVE ITEM LOGIC ---
-    let add_to_active_ui = Rc::new(move |data: ChibiPreset, is_new_arg: bool| {
-        let (win, move_ctrl, cur_x, cur_y) = spawn_chibi_window(&app_clone, &data);
+    let add_to_active_ui = Rc::new(move |data: ChibiPreset, is_new_arg: bool| -> Option<u64> {
+        // The window would be empty, which on most desktops means invisible
+        if data.image_missing() {
+            report::error(&format!("Image for \"{}\" not found", data.name),
+                          format!("{} isn't there any more; use Relocate… on the preset to pick it again", data.path.display()));
+            return None;
+        }
+        // At the limit in Preferences: make room, or say why nothing appeared
+        let live: Vec<ListBoxRow> = state_spawn.registry.borrow().iter()
+            .filter(|r| r.window.upgrade().is_some())
+            .filter_map(|r| r.list_row.upgrade())
+            .collect();
+        let (over, when_full) = { let st = state_spawn.settings.borrow(); (st.over_limit(live.len()), st.when_full) };
+        if over > 0 {
+            if when_full == WhenFull::Refuse {
+                report::notice(&format!("\"{}\" wasn't spawned", data.name),
+                               format!("{} chibis are out, the most Preferences allows. Close one first, or raise the limit.", live.len()));
+                // The manager may well be in the tray, so one of them says it too
+                let newest = state_spawn.registry.borrow().iter().rev().find_map(|r| r.window.upgrade());
+                if let Some(w) = newest { w.flash_bubble("That's all of us for now!", LIMIT_BUBBLE_TIME); }
+                return None;
+            }
+            close_active_rows(&mut state_spawn.registry.borrow_mut(), &active_list_ref, &live[..over]);
+        }
+        let key = next_key.get();
+        next_key.set(key + 1);
+        let win = ChibiWindow::new(&app_clone, &data);
+        win.present();
+        debug::attach(&win);
+        if data.spawn_sparkles {
+            win.emit_particles(ParticleKind::Sparkle, 12);
+        }
+        if let Some(dir) = &data.sound_pack {
+            if let Err(e) = audio::load_pack(dir) {
+                report::error(&format!("Sound pack for \"{}\" couldn't be loaded", data.name), e);
+            }
+        }
+        audio::play_for(&data, SoundEvent::Spawn);
+        speech::say_for(&data.speech, &data.name, SpeechEvent::Spawn);
+        speech::start_reminders(&win, &data.speech, &data.name);
+        if let Some(layer) = &data.follow_layer {
+            gaze::start_following(&win, layer);
+        }
+        if let Some(mic) = &data.mic {
+            capture::start_mic(&win, mic, &data.path);
+        }
+        if let Some(dance) = data.dance {
+            capture::start_dancing(&win, dance);
+        }
+        if let Some(media) = data.media {
+            mpris::start(&win, media);
+        }
+        if let Some(quotes) = &data.quotes {
+            quotes::start(&win, quotes);
+        }
+        if let Some(monitor) = &data.monitor {
+            sysinfo::start(&win, monitor, &data.path);
+        }
+        if let Some(battery) = &data.battery {
+            sysinfo::start_battery(&win, battery, &data.path, data.sleep_image.as_deref());
+        }
+        if let Some(reaction) = &data.weather {
+            weather::start(&win, reaction, &data.path);
+        }
+        if let Some(timer) = &data.pomodoro {
+            pomodoro::start(&win, timer, &data.path);
+        }
+        if let Some(reaction) = &data.typing {
+            typing::start(&win, reaction);
+        }
+        if let Some(path) = &data.behavior {
+            if let Err(e) = behavior::attach(&win, path) {
+                report::error(&format!("Behavior file for \"{}\" couldn't be loaded", data.name), e);
+            }
+        }
+        // Floating windows can't be positioned, so they stay put
+        if window::placement() != Placement::Floating {
+            if let Some(walk) = &data.walk {
+                movement::start_walking(&win, walk);
+            } else if let Some(area) = data.wander {
+                movement::start_wandering(&win, area);
+            }
+        }
 
         let row = ListBoxRow::new();
+        row.add_css_class("chibi-row");
         let box_layout = GtkBox::new(Orientation::Horizontal, 5);
 
         // State tracking
         let is_new_state = Rc::new(Cell::new(is_new_arg));
+        let is_new_for_reg = is_new_state.clone();
         let current_id = Rc::new(RefCell::new(data.id.clone()));
         let current_name = Rc::new(RefCell::new(data.name.clone()));
 
@@ -283,14 +1237,13 @@ fn build_ui(app: &Application) {
         // Move Toggle Button
         let move_btn = ToggleButton::with_label("✋");
         move_btn.set_tooltip_text(Some("Enable Dragging"));
-        let mc = move_ctrl.clone();
         let wt = win.clone();
         move_btn.connect_toggled(move |btn| {
             let a = btn.is_active();
-            mc.set(a);
+            wt.set_move_mode(a);
             if a {
                 btn.set_label("✊");
-                wt.set_visible(true);
+                wt.reveal();
                 wt.set_can_target(true);
                 wt.set_opacity(1.0);
             } else {
@@ -299,14 +1252,19 @@ fn build_ui(app: &Application) {
             }
         });
 
+        // Click-through toggle; the tray can flip it too, so it follows the window
+        let ghost_btn = ToggleButton::with_label("👻");
+        ghost_btn.set_tooltip_text(Some("Click-through"));
+        win.bind_property("click-through", &ghost_btn, "active")
+        .bidirectional()
+        .sync_create()
+        .build();
+
         // Save/Update Button
         let save_btn = Button::with_label("💾");
-        let p_store = presets_data_ref.clone();
+        let state_save = state_spawn.clone();
         let win_parent_dialog = parent_win_ref.clone();
         let data_clone = data.clone();
-        let cx = cur_x.clone();
-        let cy = cur_y.clone();
-        let active_reg_for_save = active_reg_ref.clone();
         let name_label_upd = name_lbl.clone();
         let win_weak_for_save = win.downgrade();
         let id_for_save = current_id.clone();
@@ -314,20 +1272,24 @@ fn build_ui(app: &Application) {
         let sender_refresh = sender_for_spawn.clone();
 
         save_btn.connect_clicked(move |_| {
-            let mut final_data = data_clone.clone();
-            final_data.x = cx.get() as i32;
-            final_data.y = cy.get() as i32;
-            final_data.id = id_for_save.borrow().clone();
-
-            if !is_new_state.get() {
-                final_data.name = name_for_save.borrow().clone();
-            }
+            let Some(live) = win_weak_for_save.upgrade() else { return };
+            // Only what can change on the live chibi; everything else stays as stored, which
+            // may have been edited since this one spawned
+            let take_live = |p: &mut ChibiPreset| {
+                p.x = live.x() as i32;
+                p.y = live.y() as i32;
+                p.width = live.size();
+                p.smart_hide = live.smart_hide();
+                p.click_through = live.click_through();
+                p.always_on_top = live.always_on_top();
+            };
 
             if !is_new_state.get() {
                 // Update Existing
-                let mut vec = p_store.borrow_mut();
-                if let Some(existing) = vec.iter_mut().find(|p| p.id == final_data.id) {
-                    *existing = final_data.clone();
+                let mut vec = state_save.presets.borrow_mut();
+                let id = id_for_save.borrow();
+                if let Some(existing) = vec.iter_mut().find(|p| p.id == *id) {
+                    take_live(existing);
                 }
                 save_presets(&vec);
                 let _ = sender_refresh.send(AppMsg::RefreshPresets);
@@ -358,15 +1320,15 @@ fn build_ui(app: &Application) {
                 let d_c = dialog.clone();
                 b_cancel.connect_clicked(move |_| d_c.close());
 
-                let p_s = p_store.clone();
+                let state_new = state_save.clone();
                 let d_ok = dialog.clone();
-                let reg_upd = active_reg_for_save.clone();
                 let new_state_setter = is_new_state.clone();
                 let id_setter = id_for_save.clone();
                 let name_setter = name_for_save.clone();
                 let lbl_setter = name_label_upd.clone();
                 let w_for_lookup = win_weak_for_save.clone();
-                let data_for_save = final_data.clone();
+                let mut data_for_save = data_clone.clone();
+                take_live(&mut data_for_save);
                 let sender_ref_inner = sender_refresh.clone();
 
                 b_save.connect_clicked(move |_| {
@@ -376,15 +1338,15 @@ fn build_ui(app: &Application) {
                         new_preset.id = Uuid::new_v4().to_string();
                         new_preset.name = txt.clone();
 
-                        p_s.borrow_mut().push(new_preset.clone());
-                        save_presets(&p_s.borrow());
+                        state_new.presets.borrow_mut().push(new_preset.clone());
+                        save_presets(&state_new.presets.borrow());
 
                         new_state_setter.set(false);
                         *id_setter.borrow_mut() = new_preset.id.clone();
                         *name_setter.borrow_mut() = txt.clone();
                         lbl_setter.set_text(&txt);
 
-                        let mut reg = reg_upd.borrow_mut();
+                        let mut reg = state_new.registry.borrow_mut();
                         for entry in reg.iter_mut() {
                             if let (Some(a), Some(b)) = (entry.window.upgrade(), w_for_lookup.upgrade()) {
                                 if a == b {
@@ -406,135 +1368,321 @@ fn build_ui(app: &Application) {
         let w_close = win.clone();
         let r_close = row.downgrade();
         let l_close = active_list_ref.downgrade();
-        let reg_close = active_reg_ref.clone();
-        let id_ref_for_close = current_id.clone();
+        let state_close = state_spawn.clone();
 
         close_btn.connect_clicked(move |_| {
             w_close.close();
             if let (Some(l), Some(r)) = (l_close.upgrade(), r_close.upgrade()) {
-                l.remove(&r);
-            }
-            let current_pid = id_ref_for_close.borrow();
-            let mut reg = reg_close.borrow_mut();
-            if let Some(idx) = reg.iter().position(|x| x.preset_id.as_ref() == Some(&*current_pid)) {
-                reg.remove(idx);
+                close_active_rows(&mut state_close.registry.borrow_mut(), &l, &[r]);
             }
         });
 
+        box_layout.append(&thumbnail::thumbnail_widget(&data.path));
         box_layout.append(&name_lbl);
+        // Live size control
+        let size_spin = SpinButton::with_range(50.0, 1000.0, 10.0);
+        size_spin.set_value(data.width as f64);
+        size_spin.set_tooltip_text(Some("Size (px)"));
+        let w_size = win.downgrade();
+        size_spin.connect_value_changed(move |sp| {
+            if let Some(w) = w_size.upgrade() { w.set_size(sp.value() as i32); }
+        });
+        // Scroll-wheel resizes on the chibi itself show up here too
+        let spin_follow = size_spin.downgrade();
+        win.connect_size_notify(move |w| {
+            if let Some(sp) = spin_follow.upgrade() { sp.set_value(w.size() as f64); }
+        });
+
         box_layout.append(&move_btn);
+        box_layout.append(&ghost_btn);
+        box_layout.append(&size_spin);
         box_layout.append(&save_btn);
         box_layout.append(&close_btn);
         row.set_child(Some(&box_layout));
         active_list_ref.append(&row);
+        setup_chibi_menu(&win, key, &move_btn, &size_spin, &save_btn, &close_btn, &sender_for_spawn);
 
-        active_reg_ref.borrow_mut().push(ActiveWindowRef {
+        state_spawn.registry.borrow_mut().push(ActiveWindowRef {
             preset_id: Some(data.id.clone()),
+                                         data: data.clone(),
+                                         is_new: is_new_for_reg,
                                          window: win.downgrade(),
                                          list_row: row.downgrade(),
+                                         name_label: name_lbl.downgrade(),
+                                         size_spin: size_spin.downgrade(),
+                                         idle_triggered: false,
+                                         key,
+                                         user_hidden: false,
+        });
+        if let Some(entry) = state_spawn.registry.borrow().last() {
+            hooks::fire(hooks::Event::Spawn, &entry.data.hooks, &hook_info(entry));
+        }
+        // The tray lists active chibis; changes that don't come in as messages say so here
+        let _ = sender_for_spawn.send(AppMsg::ChibisChanged);
+        let sender_changed = sender_for_spawn.clone();
+        win.connect_click_through_notify(move |_| { let _ = sender_changed.send(AppMsg::ChibisChanged); });
+        let sender_changed = sender_for_spawn.clone();
+        win.connect_destroy(move |_| { let _ = sender_changed.send(AppMsg::ChibisChanged); });
+
+        // Plain clicks (not drags in move mode) run the click hook and open the link
+        let state_click = state_spawn.clone();
+        win.connect_clicked(move |w| {
+            if let Some(entry) = state_click.registry.borrow().iter().find(|r| r.key == key) {
+                hooks::fire(hooks::Event::Click, &entry.data.hooks, &hook_info(entry));
+                let d = &entry.data;
+                interaction::react_to_click(w, d.on_click, &d.path, d.click_image.as_deref(), d.click_ms);
+                audio::play_for(d, SoundEvent::Click);
+                if let Some(link) = &entry.data.link {
+                    launch::open_uri(link);
+                }
+            }
         });
+        let state_middle = state_spawn.clone();
+        let move_middle = move_btn.clone();
+        let sender_middle = sender_for_spawn.clone();
+        win.connect_middle_clicked(move |w| {
+            let action = state_middle.settings.borrow().chibi_middle;
+            match action {
+                ChibiClick::Nothing => {}
+                ChibiClick::ToggleMove => move_middle.set_active(!move_middle.is_active()),
+                ChibiClick::ToggleSmartHide => w.set_smart_hide(!w.smart_hide()),
+                ChibiClick::Hide => { let _ = sender_middle.send(AppMsg::ToggleChibi(key)); }
+            }
+        });
+        // Petting plays the reaction configured on the chibi's preset
+        let state_pet = state_spawn.clone();
+        interaction::setup_petting(&win, move |w| {
+            if let Some(entry) = state_pet.registry.borrow().iter().find(|r| r.key == key) {
+                interaction::react(w, entry.data.on_pet, &entry.data.path, entry.data.happy_image.as_deref());
+                audio::play_for(&entry.data, SoundEvent::Pet);
+            }
+        });
+        // Files dropped on the chibi go to its drop hook
+        let state_drop = state_spawn.clone();
+        win.connect_files_dropped(move |_, files| {
+            if files.is_empty() { return; }
+            if let Some(entry) = state_drop.registry.borrow().iter().find(|r| r.key == key) {
+                hooks::fire_with_files(hooks::Event::Drop, &entry.data.hooks, &hook_info(entry), &files);
+            }
+        });
+        let state_dbl = state_spawn.clone();
+        win.connect_double_clicked(move |_| {
+            if let Some(entry) = state_dbl.registry.borrow().iter().find(|r| r.key == key) {
+                entry.data.on_double_click.run(&hook_info(entry));
+            }
+        });
+        let _ = sender_for_spawn.send(AppMsg::RefreshVisibility);
+        Some(key)
     });
 
-    // --- MAIN MESSAGE LOOP (MERGED) ---
-    let app_quit = app.clone();
-    let win_recv = window.clone();
-    let hide_state_recv = global_hide_state.clone();
-    let registry_recv = active_registry.clone();
-    let presets_refresh = presets.clone();
-    let list_refresh = preset_list_ref.clone();
-    let spawner_for_refresh = add_to_active_ui.clone();
-    let sender_for_refresh = sender.clone();
-    let active_reg_for_delete = active_registry.clone();
-    let active_list_for_delete = active_list.clone();
+    // --- CONTROLLER ---
+    let preset_ctx = PresetListCtx {
+        state: state.clone(),
+        active_list: active_list.clone(),
+        spawner: add_to_active_ui.clone(),
+        sender: sender.clone(),
+        window: window.clone(),
+    };
+    let controller = Controller {
+        state: state.clone(),
+        app: app.clone(),
+        window: window.clone(),
+        tray: tray_handle,
+        tray_warning: tray_warning.clone(),
+        sender: sender.clone(),
+        spawner: add_to_active_ui.clone(),
+        active_list: active_list.clone(),
+        preset_list: preset_list.clone(),
+        search: search_entry.clone(),
+        preset_ctx,
+        preset_rows: Rc::new(RefCell::new(VecDeque::new())),
+        screencast_enabled,
+        idle_tick: RefCell::new(None),
+        crowd_tick: RefCell::new(None),
+        crowd: Rc::new(RefCell::new(Crowd::default())),
+        schedule_state: RefCell::new(HashMap::new()),
+        tray_chibis: RefCell::new(Vec::new()),
+        last_hour: Cell::new(None),
+        last_minute: Cell::new(None),
+        schedule_tick: RefCell::new(None),
+        autosave_tick: RefCell::new(None),
+        autosaved: Rc::new(RefCell::new(String::new())),
+    };
+
+    let config_sender = sender.clone();
+    config::watch(move |file| {
+        let _ = config_sender.send(AppMsg::ConfigChanged(file));
+    });
 
     // Trigger initial load
     let _ = sender.send(AppMsg::RefreshPresets);
+    let _ = sender.send(AppMsg::RefreshScenes);
 
-    glib::timeout_add_local(Duration::from_millis(100), move || {
-        while let Ok(msg) = receiver.try_recv() {
-            match msg {
-                AppMsg::ToggleManager => {
-                    win_recv.set_visible(true);
-                    win_recv.present();
-                }
-                AppMsg::ToggleHideAll => {
-                    let new_state = !hide_state_recv.get();
-                    hide_state_recv.set(new_state);
-                    let mut reg = registry_recv.borrow_mut();
-                    reg.retain(|r| {
-                        if let Some(w) = r.window.upgrade() {
-                            w.set_visible(!new_state);
-                            true
-                        } else {
-                            false
-                        }
-                    });
-                }
-                AppMsg::RefreshPresets => {
-                    while let Some(child) = list_refresh.first_child() {
-                        list_refresh.remove(&child);
-                    }
+    let state_remind = state.clone();
+    reminders::start(move |reminder| fire_reminder(&state_remind, reminder));
 
-                    let mut data_vec = presets_refresh.borrow_mut();
-
-                    for preset in data_vec.iter_mut() {
-                        let row = ListBoxRow::new();
-                        let box_layout = GtkBox::new(Orientation::Horizontal, 10);
-
-                        let label = Label::new(Some(&preset.name));
-                        label.set_hexpand(true);
-                        label.set_xalign(0.0);
-
-                        let play_btn = Button::with_label("Spawn");
-                        let spawner = spawner_for_refresh.clone();
-                        let p_clone = preset.clone();
-                        play_btn.connect_clicked(move |_| {
-                            spawner(p_clone.clone(), false);
-                        });
-
-                        let del_btn = Button::with_label("🗑️");
-                        let p_store = presets_refresh.clone();
-                        let pid_target = preset.id.clone();
-                        let reg_target = active_reg_for_delete.clone();
-                        let al_target = active_list_for_delete.clone();
-                        let sender_ref = sender_for_refresh.clone();
-
-                        del_btn.connect_clicked(move |_| {
-                            // Find and close associated active windows
-                            let mut reg = reg_target.borrow_mut();
-                            let mut indices = Vec::new();
-                            for (i, entry) in reg.iter().enumerate() {
-                                if entry.preset_id.as_ref() == Some(&pid_target) {
-                                    if let Some(w) = entry.window.upgrade() { w.close(); }
-                                    if let Some(r) = entry.list_row.upgrade() { al_target.remove(&r); }
-                                    indices.push(i);
-                                }
-                            }
-                            for i in indices.iter().rev() { reg.remove(*i); }
+    receiver.attach(move |msgs| {
+        for msg in msgs {
+            controller.handle(msg);
+        }
+        controller.sync_tray_chibis();
+        controller.sync_crowd_tick();
+        controller.sync_schedule_tick();
+        controller.sync_autosave();
+    });
 
-                            // Remove from storage by ID
-                            let mut vec = p_store.borrow_mut();
-                            if let Some(pos) = vec.iter().position(|p| p.id == pid_target) {
-                                vec.remove(pos);
-                                save_presets(&vec);
-                            }
-                            let _ = sender_ref.send(AppMsg::RefreshPresets);
-                        });
-
-                        box_layout.append(&label);
-                        box_layout.append(&play_btn);
-                        box_layout.append(&del_btn);
-                        row.set_child(Some(&box_layout));
-                        list_refresh.append(&row);
-                    }
-                }
-                AppMsg::Quit => app_quit.quit(),
+    let sender_search = sender.clone();
+    search_entry.connect_search_changed(move |_| {
+        let _ = sender_search.send(AppMsg::RefreshPresets);
+    });
+
+    // --- BATCH ACTIONS ---
+    let bc = batch_close.clone();
+    active_list.connect_selected_rows_changed(move |l| bc.set_sensitive(!l.selected_rows().is_empty()));
+    let preset_batch_btns = [batch_spawn.clone(), batch_delay.clone(), batch_delete.clone()];
+    preset_list.connect_selected_rows_changed(move |l| {
+        let any = !l.selected_rows().is_empty();
+        for b in &preset_batch_btns { b.set_sensitive(any); }
+    });
+
+    let state_batch = state.clone();
+    let active_list_batch = active_list.clone();
+    batch_close.connect_clicked(move |_| {
+        let rows = active_list_batch.selected_rows();
+        close_active_rows(&mut state_batch.registry.borrow_mut(), &active_list_batch, &rows);
+    });
+
+    let state_scene = state.clone();
+    let sender_scene = sender.clone();
+    let win_scene = window.clone();
+    scene_btn.connect_clicked(move |_| {
+        let ids: Vec<String> = state_scene.registry.borrow().iter()
+            .filter(|r| !r.is_new.get() && r.window.upgrade().is_some())
+            .filter_map(|r| r.preset_id.clone())
+            .collect();
+        open_scene_dialog(&win_scene, &state_scene, ids, &sender_scene);
+    });
+
+    let selected_ids = {
+        let list = preset_list.clone();
+        move || -> Vec<String> {
+            list.selected_rows().iter().map(|r| r.widget_name().to_string()).collect()
+        }
+    };
+    let selected_ids = Rc::new(selected_ids);
+
+    let ids_spawn = selected_ids.clone();
+    let state_batch_spawn = state.clone();
+    let spawner_batch = add_to_active_ui.clone();
+    batch_spawn.connect_clicked(move |_| {
+        let ids = ids_spawn();
+        let chosen: Vec<ChibiPreset> = state_batch_spawn.presets.borrow().iter()
+            .filter(|p| ids.contains(&p.id))
+            .cloned()
+            .collect();
+        for p in chosen { spawner_batch(p, false); }
+    });
+
+    let ids_delay = selected_ids.clone();
+    let state_delay = state.clone();
+    let win_delay = window.clone();
+    let sender_delay = sender.clone();
+    batch_delay.connect_clicked(move |_| {
+        let ids = ids_delay();
+        let state = state_delay.clone();
+        let s = sender_delay.clone();
+        prompt_text(&win_delay, "Set Hide Delay", "Seconds to stay hidden:", &default_hide_delay().to_string(), move |txt| {
+            let Ok(secs) = txt.parse::<u32>() else { return };
+            let mut vec = state.presets.borrow_mut();
+            for p in vec.iter_mut().filter(|p| ids.contains(&p.id)) {
+                p.hide_delay = secs.clamp(1, 60);
             }
+            save_presets(&vec);
+            let _ = s.send(AppMsg::RefreshPresets);
+        });
+    });
+
+    let ids_delete = selected_ids.clone();
+    let state_delete = state.clone();
+    let active_list_delete = active_list.clone();
+    let sender_delete = sender.clone();
+    batch_delete.connect_clicked(move |_| {
+        let ids = ids_delete();
+        for id in &ids {
+            close_preset_windows(&mut state_delete.registry.borrow_mut(), &active_list_delete, id);
         }
-        glib::ControlFlow::Continue
+        let mut vec = state_delete.presets.borrow_mut();
+        vec.retain(|p| !ids.contains(&p.id));
+        save_presets(&vec);
+        let _ = sender_delete.send(AppMsg::RefreshPresets);
+    });
+
+    // --- DRAG AND DROP ---
+    // Dropping on the window selects the image; dropping on the Active list spawns it right away
+    let window_drop = gtk::DropTarget::new(gtk::gdk::FileList::static_type(), gtk::gdk::DragAction::COPY);
+    let select_drop = select_image.clone();
+    window_drop.connect_drop(move |_, value, _, _| {
+        match dropped_image(value) {
+            Some(path) => { select_drop(path); true }
+            None => false,
+        }
+    });
+    window.add_controller(window_drop);
+
+    let active_drop = gtk::DropTarget::new(gtk::gdk::FileList::static_type(), gtk::gdk::DragAction::COPY);
+    let spawner_drop = add_to_active_ui.clone();
+    let state_drop = state.clone();
+    active_drop.connect_drop(move |_, value, _, _| {
+        match dropped_image(value) {
+            Some(path) => {
+                let data = ChibiPreset { path, ..state_drop.settings.borrow().preset_defaults() };
+                spawner_drop(data, true);
+                true
+            }
+            None => false,
+        }
+    });
+    active_scrolled.add_controller(active_drop);
+
+    // --- BUNDLE IMPORT ---
+    let state_import = state.clone();
+    let sender_import = sender.clone();
+    let win_import = window.clone();
+    import_btn.connect_clicked(move |_| {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Chibi bundles"));
+        filter.add_pattern("*.chibi");
+        let dialog = FileDialog::builder()
+        .title("Import Preset")
+        .modal(true)
+        .default_filter(&filter)
+        .build();
+        let state = state_import.clone();
+        let s = sender_import.clone();
+        let w = win_import.clone();
+        dialog.open(Some(&win_import), None::<&gtk::gio::Cancellable>, move |res| {
+            let Some(src) = report::chosen_path(res) else { return };
+            match bundle::import(&src, &get_config_dir().join("assets")) {
+                Ok(preset) => {
+                    let mut vec = state.presets.borrow_mut();
+                    vec.push(preset);
+                    save_presets(&vec);
+                    let _ = s.send(AppMsg::RefreshPresets);
+                }
+                Err(e) => show_error(&w, "Import failed", &e.to_string()),
+            }
+        });
     });
 
     // --- SPAWN NEW BUTTON ---
+    let spin_size_prefs = spin_size.clone();
+    let spin_x_prefs = spin_x.clone();
+    let spin_y_prefs = spin_y.clone();
+    let check_hide_prefs = check_hide.clone();
+    let spin_hover_prefs = spin_hover.clone();
+    let spin_delay_prefs = spin_delay.clone();
+    let check_top_prefs = check_top.clone();
+
     let spawner_new = add_to_active_ui.clone();
     spawn_btn.connect_clicked(move |_| {
         let path_borrow = selected_path.borrow();
@@ -548,145 +1696,1488 @@ fn build_ui(app: &Application) {
                               y: spin_y.value() as i32,
                               smart_hide: check_hide.is_active(),
                               always_on_top: check_top.is_active(),
+                              hover_threshold_ms: spin_hover.value() as u32,
+                              hide_delay: spin_delay.value() as u32,
+                              workspaces: parse_list(&entry_workspaces.text()),
+                              idle_action: IdleAction::ALL[drop_idle.selected() as usize],
+                              idle_minutes: spin_idle.value() as u32,
+                              sleep_image: sleep_path.borrow().clone(),
+                              schedule: Schedule::parse(&entry_schedule.text()),
+                              ..Default::default()
             };
             spawner_new(data, true);
         }
     });
 
-    window.present();
-}
+    // Tray-only launch; the window still appears if there turns out to be no tray
+    if !(settings.start_minimized || START_MINIMIZED.swap(false, Ordering::Relaxed)) {
+        window.present();
+    }
 
-// --- WINDOW SPAWNER ---
-fn spawn_chibi_window(app: &Application, data: &ChibiPreset)
--> (gtk::Window, Rc<Cell<bool>>, Rc<Cell<f64>>, Rc<Cell<f64>>)
-{
-    let window = gtk::Window::builder()
-    .application(app)
-    .default_width(data.width)
-    .default_height(data.width)
-    .decorated(false)
-    .build();
+    // --- APP ACTIONS ---
+    // Targets for forwarded command-line flags
+    let toggle_hide_action = gtk::gio::SimpleAction::new("toggle-hide", None);
+    let sender_toggle = sender.clone();
+    let state_toggle = state.clone();
+    toggle_hide_action.connect_activate(move |_, _| {
+        let _ = sender_toggle.send(AppMsg::SetHideAll(!state_toggle.hide_all.get()));
+    });
+    app.add_action(&toggle_hide_action);
+
+    let spawn_action = gtk::gio::SimpleAction::new("spawn", Some(glib::VariantTy::STRING));
+    let sender_spawn_action = sender.clone();
+    let state_action = state.clone();
+    spawn_action.connect_activate(move |_, param| {
+        let Some(name) = param.and_then(|p| p.str()) else { return };
+        match state_action.find_preset(name) {
+            Some(preset) => { let _ = sender_spawn_action.send(AppMsg::SpawnPreset(preset.id)); }
+            None => tracing::warn!("No preset named '{}'", name),
+        }
+    });
+    app.add_action(&spawn_action);
+
+    // --- PREFERENCES ---
+    let state_prefs = state.clone();
+    let sender_prefs = sender.clone();
+    let win_prefs = window.clone();
+    prefs_btn.connect_clicked(move |_| {
+        let panel = (spin_size_prefs.clone(), spin_x_prefs.clone(), spin_y_prefs.clone(),
+                     check_hide_prefs.clone(), spin_hover_prefs.clone(), spin_delay_prefs.clone(), check_top_prefs.clone());
+        let s_prefs = sender_prefs.clone();
+        open_preferences(&win_prefs, &state_prefs, move |st| {
+            let _ = s_prefs.send(AppMsg::RefreshTrayIcon);
+            let _ = s_prefs.send(AppMsg::RefreshTrayActions);
+            // Same path as the tray's toggle, so its check mark follows
+            let _ = s_prefs.send(AppMsg::SetMuted(st.mute_sounds));
+            if st.notifications.is_some() { notifications::listen(&s_prefs); }
+            // Re-seed the spawn panel with the new defaults
+            let (size, x, y, hide, hover, delay, top) = &panel;
+            size.set_value(st.default_width as f64);
+            x.set_value(st.default_x as f64);
+            y.set_value(st.default_y as f64);
+            hide.set_active(st.default_smart_hide);
+            hover.set_value(st.default_hover_ms as f64);
+            delay.set_value(st.default_hide_delay as f64);
+            top.set_active(st.default_on_top);
+        });
+    });
 
-    window.add_css_class("ghost-window");
-    window.init_layer_shell();
-    window.set_layer(if data.always_on_top { Layer::Overlay } else { Layer::Bottom });
-    window.set_anchor(Edge::Top, true);
-    window.set_anchor(Edge::Left, true);
-    window.set_margin(Edge::Left, data.x);
-    window.set_margin(Edge::Top, data.y);
-
-    let container = GtkBox::new(Orientation::Vertical, 0);
-    let picture = Picture::for_filename(&data.path);
-    picture.set_content_fit(gtk::ContentFit::Contain);
-    picture.set_vexpand(true);
-    picture.set_hexpand(true);
-    picture.set_can_target(true);
-    container.append(&picture);
-    window.set_child(Some(&container));
-
-    let click = GestureClick::new();
-    let motion = EventControllerMotion::new();
-
-    let current_x = Rc::new(Cell::new(data.x as f64));
-    let current_y = Rc::new(Cell::new(data.y as f64));
-    let anchor_x = Rc::new(Cell::new(0.0));
-    let anchor_y = Rc::new(Cell::new(0.0));
-    let is_dragging = Rc::new(Cell::new(false));
-    let move_mode = Rc::new(Cell::new(false));
-
-    let drag_c = is_dragging.clone();
-    let move_c = move_mode.clone();
-    let ax = anchor_x.clone();
-    let ay = anchor_y.clone();
-
-    click.connect_pressed(move |_, _, x, y| {
-        if move_c.get() {
-            drag_c.set(true);
-            ax.set(x);
-            ay.set(y);
-        }
-    });
-
-    let drag_rel = is_dragging.clone();
-    click.connect_released(move |_, _, _, _| drag_rel.set(false));
-
-    let drag_mot = is_dragging.clone();
-    let win_weak = window.downgrade();
-    let cx = current_x.clone();
-    let cy = current_y.clone();
-    let ax_m = anchor_x.clone();
-    let ay_m = anchor_y.clone();
-
-    motion.connect_motion(move |_, x, y| {
-        if !drag_mot.get() { return; }
-        if let Some(w) = win_weak.upgrade() {
-            let dx = x - ax_m.get();
-            let dy = y - ay_m.get();
-            let nx = cx.get() + dx;
-            let ny = cy.get() + dy;
-            w.set_margin(Edge::Left, nx as i32);
-            w.set_margin(Edge::Top, ny as i32);
-            cx.set(nx);
-            cy.set(ny);
-        }
-    });
-
-    window.add_controller(click);
-    window.add_controller(motion);
-
-    if data.smart_hide {
-        let hide_ctrl = EventControllerMotion::new();
-        let w_weak = window.downgrade();
-        let drag_chk = is_dragging.clone();
-        let move_chk = move_mode.clone();
-        hide_ctrl.connect_enter(move |_, _, _| {
-            if move_chk.get() || drag_chk.get() { return; }
-            if let Some(w) = w_weak.upgrade() {
-                w.set_visible(false);
-                let w_tmr = w.downgrade();
-                let move_tmr = move_chk.clone();
-                glib::timeout_add_seconds_local(3, move || {
-                    if let Some(ww) = w_tmr.upgrade() {
-                        if !move_tmr.get() { ww.set_visible(true); }
+    // --- AUTOSTART ---
+    let autostart: Vec<ChibiPreset> = state.presets.borrow().iter()
+        .filter(|p| settings.spawn_autostart && p.autostart)
+        .cloned()
+        .collect();
+    for preset in &autostart {
+        add_to_active_ui(preset.clone(), false);
+    }
+
+    // --- SESSION RESTORE ---
+    let mut session = load_session();
+    // Autostart presets are already on screen
+    session.chibis.retain(|c| !(c.saved && autostart.iter().any(|p| p.id == c.preset.id)));
+    let restore_mode = settings.restore_session;
+    if !session.chibis.is_empty() && restore_mode != RestoreMode::Never {
+        let spawner_restore = add_to_active_ui.clone();
+        let state_restore = state.clone();
+        let sender_restore = sender.clone();
+        let restore = move |session: Session| {
+            for chibi in session.chibis {
+                let mut data = chibi.preset;
+                // Prefer the current version of a saved preset, keeping the live placement
+                if chibi.saved {
+                    if let Some(p) = state_restore.preset(&data.id) {
+                        data = ChibiPreset { x: data.x, y: data.y, width: data.width, ..p };
                     }
-                    glib::ControlFlow::Break
-                });
+                }
+                spawner_restore(data, !chibi.saved);
             }
+            let _ = sender_restore.send(AppMsg::SetHideAll(session.hidden));
+        };
+
+        if restore_mode == RestoreMode::Always {
+            restore(session);
+            return;
+        }
+
+        let count = session.chibis.len();
+        let dialog = gtk::AlertDialog::builder()
+        .message("Restore last session?")
+        .detail(format!("{} chibi{} were active when the app last closed.", count, if count == 1 { "" } else { "s" }))
+        .buttons(["Discard", "Restore"])
+        .cancel_button(0)
+        .default_button(1)
+        .modal(true)
+        .build();
+        dialog.choose(Some(&window), None::<&gtk::gio::Cancellable>, move |res| {
+            if res == Ok(1) { restore(session); }
         });
-        window.add_controller(hide_ctrl);
     }
+}
+
+// Cuckoo: the chime's sound (or the pack's hourly one) and its reaction
+fn ring_chime(win: &ChibiWindow, preset: &ChibiPreset, chime: &Chime) {
+    match &chime.sound {
+        Some(path) => audio::play(path),
+        None => audio::play_for(preset, SoundEvent::Hourly),
+    }
+    interaction::react_to_click(win, chime.reaction, &preset.path, preset.click_image.as_deref(), preset.click_ms);
+}
+
+// Turns the debug overlay on or off; chibis already out get it too
+fn set_debug_overlay(state: &AppState, on: bool) {
+    if !debug::set_enabled(on || DEBUG_OVERLAY.load(Ordering::Relaxed)) { return; }
+    for w in state.registry.borrow().iter().filter_map(|r| r.window.upgrade()) {
+        debug::attach(&w);
+    }
+}
+
+const REMINDER_TIME: Duration = Duration::from_secs(10);
+// How long a chibi says a spawn past the limit was turned away
+const LIMIT_BUBBLE_TIME: Duration = Duration::from_secs(4);
+
+// Its own preset's chibi says it if that's on screen, else the first visible one;
+// false (try again later) with nobody out
+fn fire_reminder(state: &AppState, reminder: &Reminder) -> bool {
+    let registry = state.registry.borrow();
+    let own = registry.iter().filter(|r| reminder.preset.is_some() && r.preset_id == reminder.preset);
+    let Some((win, preset)) = own.chain(registry.iter())
+        .find_map(|r| Some((r.window.upgrade().filter(|w| w.is_visible())?, r.data.clone()))) else { return false };
+    drop(registry);
+    audio::play_app(AppSound::Reminder);
+    interaction::react_to_click(&win, ClickReaction::Jump, &preset.path, None, preset.click_ms);
+    win.flash_bubble(&format!("⏰ {}", reminder.text), REMINDER_TIME);
+    true
+}
+
+// Comma-separated list entry (workspaces, tags) into trimmed, non-empty items
+fn parse_list(text: &str) -> Vec<String> {
+    text.split(',').map(|w| w.trim().to_string()).filter(|w| !w.is_empty()).collect()
+}
+
+// First dropped file that looks like an image
+fn dropped_image(value: &glib::Value) -> Option<PathBuf> {
+    let files = value.get::<gtk::gdk::FileList>().ok()?;
+    files.files().iter().filter_map(|f| f.path()).find(|p| {
+        let (mime, _) = gtk::gio::content_type_guess(Some(p), &[]);
+        mime.starts_with("image/")
+    })
+}
 
-    window.present();
-    (window, move_mode, current_x, current_y)
+fn show_error(parent: &impl IsA<gtk::Window>, message: &str, detail: &str) {
+    gtk::AlertDialog::builder()
+    .message(message)
+    .detail(detail)
+    .modal(true)
+    .build()
+    .show(Some(parent));
 }
 
-// --- PERSISTENCE ---
-fn get_config_path() -> PathBuf {
-    if let Some(proj_dirs) = directories::ProjectDirs::from("com", "example", "chibimanager") {
-        let config_dir = proj_dirs.config_dir();
-        if !config_dir.exists() {
-            let _ = fs::create_dir_all(config_dir);
+// A line of the preset list, before its row is built
+enum PresetListItem {
+    Header { category: String, count: usize, collapsed: bool },
+    Preset(Box<ChibiPreset>),
+}
+
+// Rows built per main loop turn; big collections fill in over a few frames instead of
+// freezing the manager until the whole list is done
+const PRESET_ROWS_PER_TURN: usize = 20;
+
+fn fill_preset_rows(list: &ListBox, pending: &mut VecDeque<PresetListItem>, ctx: &PresetListCtx) {
+    let count = pending.len().min(PRESET_ROWS_PER_TURN);
+    for item in pending.drain(..count) {
+        let row = match &item {
+            PresetListItem::Header { category, count, collapsed } => build_category_header(category, *count, *collapsed, ctx),
+            PresetListItem::Preset(preset) => build_preset_row(preset, ctx),
+        };
+        list.append(&row);
+    }
+}
+
+// Builds one row of the Saved Presets list
+fn build_preset_row(preset: &ChibiPreset, ctx: &PresetListCtx) -> ListBoxRow {
+    let row = ListBoxRow::new();
+    // The widget name carries the preset id so batch actions can map selected rows back
+    row.set_widget_name(&preset.id);
+    row.add_css_class("preset-row");
+    let box_layout = GtkBox::new(Orientation::Horizontal, 10);
+
+    let label = Label::new(Some(&preset.name));
+    label.set_hexpand(true);
+    label.set_xalign(0.0);
+
+    // A preset whose image went away can't spawn anything; it asks for the image again instead
+    let missing = preset.image_missing();
+    let warning = gtk::Image::from_icon_name("dialog-warning-symbolic");
+    warning.set_tooltip_text(Some(&format!("Image not found: {}", preset.path.display())));
+    warning.set_visible(missing);
+
+    let play_btn = Button::with_label(if missing { "Relocate…" } else { "Spawn" });
+    if missing {
+        play_btn.set_tooltip_text(Some("Pick this preset's image again"));
+        let p_store = ctx.state.clone();
+        let pid = preset.id.clone();
+        let folder = preset.path.parent().filter(|dir| dir.is_dir()).map(gtk::gio::File::for_path);
+        let sender = ctx.sender.clone();
+        let win = ctx.window.clone();
+        play_btn.connect_clicked(move |_| {
+            let dialog = FileDialog::builder().title("Relocate Image").modal(true).build();
+            if let Some(folder) = &folder { dialog.set_initial_folder(Some(folder)); }
+            let (p_store, pid, s) = (p_store.clone(), pid.clone(), sender.clone());
+            dialog.open(Some(&win), None::<&gtk::gio::Cancellable>, move |res| {
+                let Some(path) = report::chosen_path(res) else { return };
+                let mut vec = p_store.presets.borrow_mut();
+                if let Some(p) = vec.iter_mut().find(|p| p.id == pid) {
+                    p.path = path;
+                    save_presets(&vec);
+                }
+                let _ = s.send(AppMsg::RefreshPresets);
+            });
+        });
+    } else {
+        let spawner = ctx.spawner.clone();
+        let p_clone = preset.clone();
+        play_btn.connect_clicked(move |_| {
+            spawner(p_clone.clone(), false);
+        });
+    }
+
+    let auto_check = CheckButton::with_label("Autostart");
+    auto_check.set_active(preset.autostart);
+    let p_auto = ctx.state.clone();
+    let pid_auto = preset.id.clone();
+    auto_check.connect_toggled(move |c| {
+        let mut vec = p_auto.presets.borrow_mut();
+        if let Some(p) = vec.iter_mut().find(|p| p.id == pid_auto) {
+            p.autostart = c.is_active();
+            save_presets(&vec);
         }
-        return config_dir.join("presets.json");
+    });
+
+    let del_btn = Button::with_label("🗑️");
+    let p_store = ctx.state.clone();
+    let pid_target = preset.id.clone();
+    let reg_target = ctx.state.clone();
+    let al_target = ctx.active_list.clone();
+    let sender_ref = ctx.sender.clone();
+
+    del_btn.connect_clicked(move |_| {
+        // Find and close associated active windows
+        close_preset_windows(&mut reg_target.registry.borrow_mut(), &al_target, &pid_target);
+
+        // Remove from storage by ID
+        let mut vec = p_store.presets.borrow_mut();
+        if let Some(pos) = vec.iter().position(|p| p.id == pid_target) {
+            vec.remove(pos);
+            save_presets(&vec);
+        }
+        let _ = sender_ref.send(AppMsg::RefreshPresets);
+    });
+
+    let export_btn = Button::with_label("📤");
+    export_btn.set_tooltip_text(Some("Export as .chibi bundle"));
+    let p_export = preset.clone();
+    let win_export = ctx.window.clone();
+    export_btn.connect_clicked(move |_| {
+        let dialog = FileDialog::builder()
+        .title("Export Preset")
+        .modal(true)
+        .initial_name(format!("{}.chibi", p_export.name))
+        .build();
+        let p = p_export.clone();
+        let w = win_export.clone();
+        dialog.save(Some(&win_export), None::<&gtk::gio::Cancellable>, move |res| {
+            let Some(dest) = report::chosen_path(res) else { return };
+            if let Err(e) = bundle::export(&p, &dest) {
+                show_error(&w, "Export failed", &e.to_string());
+            }
+        });
+    });
+
+    let cat_btn = Button::with_label("🏷️");
+    cat_btn.set_tooltip_text(Some("Set category"));
+    let p_cat = ctx.state.clone();
+    let pid_cat = preset.id.clone();
+    let current_cat = preset.category.clone().unwrap_or_default();
+    let sender_cat = ctx.sender.clone();
+    let win_cat = ctx.window.clone();
+    cat_btn.connect_clicked(move |_| {
+        let p_store = p_cat.clone();
+        let pid = pid_cat.clone();
+        let s = sender_cat.clone();
+        prompt_text(&win_cat, "Set Category", "Category (blank for none):", &current_cat, move |txt| {
+            let mut vec = p_store.presets.borrow_mut();
+            if let Some(p) = vec.iter_mut().find(|p| p.id == pid) {
+                p.category = if txt.is_empty() { None } else { Some(txt) };
+                save_presets(&vec);
+            }
+            let _ = s.send(AppMsg::RefreshPresets);
+        });
+    });
+
+    let rename_btn = Button::with_label("✏️");
+    rename_btn.set_tooltip_text(Some("Rename"));
+    let p_rename = ctx.state.clone();
+    let pid_rename = preset.id.clone();
+    let name_rename = preset.name.clone();
+    let sender_rename = ctx.sender.clone();
+    let win_rename = ctx.window.clone();
+    rename_btn.connect_clicked(move |_| {
+        let p_store = p_rename.clone();
+        let pid = pid_rename.clone();
+        let s = sender_rename.clone();
+        prompt_text(&win_rename, "Rename Preset", "Name:", &name_rename, move |txt| {
+            if txt.is_empty() { return; }
+            let mut vec = p_store.presets.borrow_mut();
+            if let Some(p) = vec.iter_mut().find(|p| p.id == pid) {
+                p.name = txt;
+                save_presets(&vec);
+            }
+            let _ = s.send(AppMsg::RefreshPresets);
+        });
+    });
+
+    let dup_btn = Button::with_label("⧉");
+    dup_btn.set_tooltip_text(Some("Duplicate"));
+    let p_dup = ctx.state.clone();
+    let pid_dup = preset.id.clone();
+    let sender_dup = ctx.sender.clone();
+    dup_btn.connect_clicked(move |_| {
+        let mut vec = p_dup.presets.borrow_mut();
+        if let Some(pos) = vec.iter().position(|p| p.id == pid_dup) {
+            let mut copy = vec[pos].clone();
+            copy.id = Uuid::new_v4().to_string();
+            copy.name = format!("{} (copy)", copy.name);
+            vec.insert(pos + 1, copy);
+            save_presets(&vec);
+        }
+        let _ = sender_dup.send(AppMsg::RefreshPresets);
+    });
+
+    // Drag rows onto each other to reorder; dropping into another category moves it there
+    let drag = gtk::DragSource::new();
+    drag.set_actions(gtk::gdk::DragAction::MOVE);
+    let pid_drag = preset.id.clone();
+    drag.connect_prepare(move |_, _, _| {
+        Some(gtk::gdk::ContentProvider::for_value(&pid_drag.to_value()))
+    });
+    let row_icon = row.downgrade();
+    drag.connect_drag_begin(move |src, _| {
+        if let Some(r) = row_icon.upgrade() {
+            src.set_icon(Some(&gtk::WidgetPaintable::new(Some(&r))), 0, 0);
+        }
+    });
+    row.add_controller(drag);
+
+    let drop = gtk::DropTarget::new(String::static_type(), gtk::gdk::DragAction::MOVE);
+    let p_drop = ctx.state.clone();
+    let pid_drop = preset.id.clone();
+    let sender_drop = ctx.sender.clone();
+    drop.connect_drop(move |_, value, _, _| {
+        let Ok(dragged) = value.get::<String>() else { return false };
+        let mut vec = p_drop.presets.borrow_mut();
+        if !move_onto(&mut vec, &dragged, &pid_drop) { return false; }
+        save_presets(&vec);
+        let _ = sender_drop.send(AppMsg::RefreshPresets);
+        true
+    });
+    row.add_controller(drop);
+
+    box_layout.append(&thumbnail::thumbnail_widget(&preset.path));
+    let edit_btn = Button::with_label("⚙️");
+    edit_btn.set_tooltip_text(Some("Edit preset"));
+    let p_edit = preset.clone();
+    let ctx_edit = ctx.clone();
+    edit_btn.connect_clicked(move |_| open_preset_editor(&p_edit, &ctx_edit));
+
+    box_layout.append(&label);
+    box_layout.append(&warning);
+    box_layout.append(&auto_check);
+    box_layout.append(&edit_btn);
+    box_layout.append(&rename_btn);
+    box_layout.append(&dup_btn);
+    box_layout.append(&cat_btn);
+    box_layout.append(&play_btn);
+    box_layout.append(&export_btn);
+    box_layout.append(&del_btn);
+    row.set_child(Some(&box_layout));
+    row
+}
+
+// File (or folder) chooser button for an optional path, with ✕ to clear it; the choice lands in the cell
+fn optional_file_row(parent: &gtk::Window, title: &str, current: Option<PathBuf>, folder: bool) -> (GtkBox, Rc<RefCell<Option<PathBuf>>>) {
+    let file_label = |p: &Option<PathBuf>| p.as_ref()
+        .map(|p| p.file_name().unwrap_or_default().to_string_lossy().to_string())
+        .unwrap_or_else(|| "Choose…".into());
+    let path = Rc::new(RefCell::new(current));
+    let row = GtkBox::new(Orientation::Horizontal, 5);
+    let choose = Button::with_label(&file_label(&path.borrow()));
+    choose.set_hexpand(true);
+    let clear = Button::with_label("✕");
+    row.append(&choose);
+    row.append(&clear);
+    let p = path.clone();
+    let parent = parent.clone();
+    let title = title.to_string();
+    choose.connect_clicked(move |btn| {
+        let fd = FileDialog::builder().title(title.as_str()).modal(true).build();
+        let p = p.clone();
+        let b = btn.clone();
+        let done = move |res| {
+            if let Some(chosen) = report::chosen_path(res) {
+                *p.borrow_mut() = Some(chosen);
+                b.set_label(&file_label(&p.borrow()));
+            }
+        };
+        if folder {
+            fd.select_folder(Some(&parent), None::<&gtk::gio::Cancellable>, done);
+        } else {
+            fd.open(Some(&parent), None::<&gtk::gio::Cancellable>, done);
+        }
+    });
+    let p = path.clone();
+    clear.connect_clicked(move |_| {
+        p.borrow_mut().take();
+        choose.set_label(&file_label(&None));
+    });
+    (row, path)
+}
+
+// Dialog editing every field of a saved preset without spawning it
+fn open_preset_editor(preset: &ChibiPreset, ctx: &PresetListCtx) {
+    let dialog = gtk::Window::builder()
+    .title(format!("Edit \"{}\"", preset.name))
+    .transient_for(&ctx.window)
+    .modal(true)
+    .default_width(360)
+    .build();
+
+    let grid = gtk::Grid::builder()
+    .row_spacing(6)
+    .column_spacing(10)
+    .margin_top(10).margin_bottom(10).margin_start(10).margin_end(10)
+    .build();
+    let mut row = 0;
+    let mut add_row = |label: &str, widget: &gtk::Widget| {
+        let l = Label::new(Some(label));
+        l.set_xalign(0.0);
+        grid.attach(&l, 0, row, 1, 1);
+        widget.set_hexpand(true);
+        grid.attach(widget, 1, row, 1, 1);
+        row += 1;
+    };
+
+    let entry_name = Entry::new();
+    entry_name.set_text(&preset.name);
+    add_row("Name", entry_name.upcast_ref());
+
+    let image_path = Rc::new(RefCell::new(preset.path.clone()));
+    let image_btn = Button::with_label(&preset.path.file_name().unwrap_or_default().to_string_lossy());
+    let ip = image_path.clone();
+    let dlg_img = dialog.clone();
+    image_btn.connect_clicked(move |btn| {
+        let fd = FileDialog::builder().title("Select Image").modal(true).build();
+        let p = ip.clone();
+        let b = btn.clone();
+        fd.open(Some(&dlg_img), None::<&gtk::gio::Cancellable>, move |res| {
+            if let Some(path) = report::chosen_path(res) {
+                b.set_label(&path.file_name().unwrap_or_default().to_string_lossy());
+                *p.borrow_mut() = path;
+            }
+        });
+    });
+    add_row("Image", image_btn.upcast_ref());
+
+    let spin = |min: f64, max: f64, step: f64, val: f64| {
+        let sp = SpinButton::with_range(min, max, step);
+        sp.set_value(val);
+        sp
+    };
+    let spin_size = spin(50.0, 1000.0, 10.0, preset.width as f64);
+    add_row("Size (px)", spin_size.upcast_ref());
+    let spin_x = spin(0.0, 5000.0, 50.0, preset.x as f64);
+    add_row("X", spin_x.upcast_ref());
+    let spin_y = spin(0.0, 3000.0, 50.0, preset.y as f64);
+    add_row("Y", spin_y.upcast_ref());
+
+    // Wander range defaults to a stretch around the current position
+    let wander = preset.wander.unwrap_or(WanderArea { left: (preset.x - 200).max(0), right: preset.x + 200, speed: default_speed() });
+    let check_wander = CheckButton::with_label("Wander back and forth");
+    check_wander.set_active(preset.wander.is_some());
+    add_row("Behavior", check_wander.upcast_ref());
+    let spin_wander_left = spin(0.0, 5000.0, 50.0, wander.left as f64);
+    add_row("Wander from X", spin_wander_left.upcast_ref());
+    let spin_wander_right = spin(0.0, 5000.0, 50.0, wander.right as f64);
+    add_row("Wander to X", spin_wander_right.upcast_ref());
+    for sp in [&spin_wander_left, &spin_wander_right] {
+        check_wander.bind_property("active", sp, "sensitive").sync_create().build();
+    }
+    let walk = preset.walk.clone().unwrap_or_default();
+    let check_walk = CheckButton::with_label("Walk along the bottom edge");
+    check_walk.set_active(preset.walk.is_some());
+    add_row("", check_walk.upcast_ref());
+    let spin_wander_speed = spin(5.0, 500.0, 5.0, preset.walk.as_ref().map_or(wander.speed, |w| w.speed) as f64);
+    add_row("Walk speed (px/s)", spin_wander_speed.upcast_ref());
+    let walk_frames = Rc::new(RefCell::new(walk.frames.clone()));
+    let frames_label = |n: usize| if n == 0 { "Choose…".to_string() } else { format!("{} frames", n) };
+    let frames_btn = Button::with_label(&frames_label(walk.frames.len()));
+    frames_btn.set_tooltip_text(Some("Images cycled while walking, in file name order"));
+    let wf = walk_frames.clone();
+    let dlg_frames = dialog.clone();
+    frames_btn.connect_clicked(move |btn| {
+        let fd = FileDialog::builder().title("Select Walk Frames").modal(true).build();
+        let wf = wf.clone();
+        let b = btn.clone();
+        fd.open_multiple(Some(&dlg_frames), None::<&gtk::gio::Cancellable>, move |res| {
+            let mut paths = report::chosen_paths(res);
+            if paths.is_empty() { return; }
+            paths.sort();
+            b.set_label(&frames_label(paths.len()));
+            *wf.borrow_mut() = paths;
+        });
+    });
+    add_row("Walk frames", frames_btn.upcast_ref());
+    let check_faces_left = CheckButton::with_label("Images face left");
+    check_faces_left.set_active(walk.faces_left);
+    add_row("", check_faces_left.upcast_ref());
+    for w in [frames_btn.upcast_ref::<gtk::Widget>(), check_faces_left.upcast_ref()] {
+        check_walk.bind_property("active", w, "sensitive").sync_create().build();
     }
-    PathBuf::from("presets.json")
+
+    let check_top = CheckButton::with_label("Always on Top");
+    check_top.set_active(preset.always_on_top);
+    add_row("Layer", check_top.upcast_ref());
+    let check_ghost = CheckButton::with_label("Click-through");
+    check_ghost.set_active(preset.click_through);
+    add_row("Input", check_ghost.upcast_ref());
+    let check_hide = CheckButton::with_label("Smart Hide");
+    check_hide.set_active(preset.smart_hide);
+    add_row("Hiding", check_hide.upcast_ref());
+    let spin_hover = spin(0.0, 5000.0, 50.0, preset.hover_threshold_ms as f64);
+    add_row("Hover before hide (ms)", spin_hover.upcast_ref());
+    let spin_delay = spin(1.0, 60.0, 1.0, preset.hide_delay as f64);
+    add_row("Stay hidden (s)", spin_delay.upcast_ref());
+
+    let entry_ws = Entry::new();
+    entry_ws.set_text(&preset.workspaces.join(", "));
+    add_row("Workspaces", entry_ws.upcast_ref());
+
+    let idle_labels: Vec<&str> = IdleAction::ALL.iter().map(|a| a.label()).collect();
+    let drop_idle = DropDown::from_strings(&idle_labels);
+    drop_idle.set_selected(IdleAction::ALL.iter().position(|a| *a == preset.idle_action).unwrap_or(0) as u32);
+    add_row("When idle", drop_idle.upcast_ref());
+    let spin_idle = spin(1.0, 240.0, 1.0, preset.idle_minutes as f64);
+    add_row("Idle after (min)", spin_idle.upcast_ref());
+
+    let pet_labels: Vec<&str> = PetReaction::ALL.iter().map(|r| r.label()).collect();
+    let drop_pet = DropDown::from_strings(&pet_labels);
+    drop_pet.set_selected(PetReaction::ALL.iter().position(|r| *r == preset.on_pet).unwrap_or(0) as u32);
+    add_row("When petted", drop_pet.upcast_ref());
+    let happy_path = Rc::new(RefCell::new(preset.happy_image.clone()));
+    let image_label = |p: &Option<PathBuf>| p.as_ref()
+        .map(|p| p.file_name().unwrap_or_default().to_string_lossy().to_string())
+        .unwrap_or_else(|| "Choose…".into());
+    let happy_btn = Button::with_label(&image_label(&preset.happy_image));
+    let hp = happy_path.clone();
+    let dlg_happy = dialog.clone();
+    happy_btn.connect_clicked(move |btn| {
+        let fd = FileDialog::builder().title("Select Happy Image").modal(true).build();
+        let p = hp.clone();
+        let b = btn.clone();
+        fd.open(Some(&dlg_happy), None::<&gtk::gio::Cancellable>, move |res| {
+            if let Some(path) = report::chosen_path(res) {
+                *p.borrow_mut() = Some(path);
+                b.set_label(&image_label(&p.borrow()));
+            }
+        });
+    });
+    add_row("Happy image", happy_btn.upcast_ref());
+
+    let click_labels: Vec<&str> = ClickReaction::ALL.iter().map(|r| r.label()).collect();
+    let drop_click = DropDown::from_strings(&click_labels);
+    drop_click.set_selected(ClickReaction::ALL.iter().position(|r| *r == preset.on_click).unwrap_or(0) as u32);
+    add_row("When clicked", drop_click.upcast_ref());
+    let click_path = Rc::new(RefCell::new(preset.click_image.clone()));
+    let click_btn = Button::with_label(&image_label(&preset.click_image));
+    let cp = click_path.clone();
+    let dlg_click = dialog.clone();
+    click_btn.connect_clicked(move |btn| {
+        let fd = FileDialog::builder().title("Select Click Image").modal(true).build();
+        let p = cp.clone();
+        let b = btn.clone();
+        fd.open(Some(&dlg_click), None::<&gtk::gio::Cancellable>, move |res| {
+            if let Some(path) = report::chosen_path(res) {
+                *p.borrow_mut() = Some(path);
+                b.set_label(&image_label(&p.borrow()));
+            }
+        });
+    });
+    add_row("Click image", click_btn.upcast_ref());
+    let spin_click = spin(100.0, 10000.0, 100.0, preset.click_ms as f64);
+    add_row("Click image for (ms)", spin_click.upcast_ref());
+    let check_sparkles = CheckButton::with_label("Sparkle when spawned");
+    check_sparkles.set_active(preset.spawn_sparkles);
+    add_row("Effects", check_sparkles.upcast_ref());
+
+    // Eyes/head layer that follows the cursor; ✕ removes it
+    let (layer_box, layer_path) = optional_file_row(&dialog, "Select Watching Layer", preset.follow_layer.as_ref().map(|l| l.image.clone()), false);
+    add_row("Watching layer", layer_box.upcast_ref());
+    let spin_gaze = spin(1.0, 50.0, 1.0, preset.follow_layer.as_ref().map_or(default_max_offset(), |l| l.max_offset) as f64);
+    add_row("Follow distance (px)", spin_gaze.upcast_ref());
+
+    // PNGTuber mode
+    let check_mic = CheckButton::with_label("React to the microphone");
+    check_mic.set_active(preset.mic.is_some());
+    add_row("Microphone", check_mic.upcast_ref());
+    let (mic_open_box, mic_open_path) = optional_file_row(&dialog, "Select Mouth Open Image", preset.mic.as_ref().and_then(|m| m.open_image.clone()), false);
+    add_row("Mouth open image", mic_open_box.upcast_ref());
+    let spin_mic = spin(0.0, 100.0, 5.0, preset.mic.as_ref().map_or(default_threshold(), |m| m.threshold) as f64);
+    add_row("Talking above (%)", spin_mic.upcast_ref());
+    let check_mic_bounce = CheckButton::with_label("Bounce while talking");
+    check_mic_bounce.set_active(preset.mic.as_ref().is_some_and(|m| m.bounce));
+    add_row("", check_mic_bounce.upcast_ref());
+    for w in [mic_open_box.upcast_ref::<gtk::Widget>(), spin_mic.upcast_ref(), check_mic_bounce.upcast_ref()] {
+        check_mic.bind_property("active", w, "sensitive").sync_create().build();
+    }
+
+    // Moving to the music
+    let check_dance = CheckButton::with_label("Dance to what's playing");
+    check_dance.set_active(preset.dance.is_some());
+    add_row("Music", check_dance.upcast_ref());
+    let move_labels: Vec<&str> = DanceMove::ALL.iter().map(|m| m.label()).collect();
+    let drop_dance = DropDown::from_strings(&move_labels);
+    drop_dance.set_selected(DanceMove::ALL.iter().position(|m| Some(*m) == preset.dance.map(|d| d.dance_move)).unwrap_or(0) as u32);
+    add_row("", drop_dance.upcast_ref());
+    let spin_dance = spin(2.0, 50.0, 1.0, preset.dance.map_or(default_strength(), |d| d.strength) as f64);
+    add_row("Dance strength (%)", spin_dance.upcast_ref());
+    for w in [drop_dance.upcast_ref::<gtk::Widget>(), spin_dance.upcast_ref()] {
+        check_dance.bind_property("active", w, "sensitive").sync_create().build();
+    }
+    // Media players over MPRIS
+    let media = preset.media.unwrap_or(MediaReaction { dance: false, album_art: false, ticker: false });
+    let check_media_dance = CheckButton::with_label("Dance while a player plays");
+    check_media_dance.set_active(media.dance);
+    add_row("Media players", check_media_dance.upcast_ref());
+    let check_album_art = CheckButton::with_label("Show the album art");
+    check_album_art.set_active(media.album_art);
+    add_row("", check_album_art.upcast_ref());
+    let check_ticker = CheckButton::with_label("Now-playing ticker (click to play/pause)");
+    check_ticker.set_active(media.ticker);
+    add_row("", check_ticker.upcast_ref());
+
+    // Text file of quotes for the bubble, see quotes.rs
+    let (quotes_box, quotes_path) = optional_file_row(&dialog, "Select Quotes File", preset.quotes.as_ref().map(|q| q.file.clone()), false);
+    add_row("Quotes file", quotes_box.upcast_ref());
+    let spin_quote_every = spin(0.0, 240.0, 1.0, preset.quotes.as_ref().map_or(quotes::default_every(), |q| q.every_minutes) as f64);
+    add_row("Quote every (min)", spin_quote_every.upcast_ref());
+    let spin_quote_secs = spin(1.0, 60.0, 1.0, preset.quotes.as_ref().map_or(quotes::default_seconds(), |q| q.seconds) as f64);
+    add_row("Show quotes for (s)", spin_quote_secs.upcast_ref());
+    let check_quote_click = CheckButton::with_label("Quote when clicked");
+    check_quote_click.set_active(preset.quotes.as_ref().is_some_and(|q| q.on_click));
+    add_row("", check_quote_click.upcast_ref());
+
+    // CPU/RAM/temperature, see sysinfo.rs
+    let monitor = preset.monitor.as_ref();
+    let check_monitor = CheckButton::with_label("Act as a system monitor");
+    check_monitor.set_active(monitor.is_some());
+    add_row("System monitor", check_monitor.upcast_ref());
+    let spin_report = spin(0.0, 240.0, 1.0, monitor.map_or(sysinfo::default_report_minutes(), |m| m.report_minutes) as f64);
+    add_row("Report every (min)", spin_report.upcast_ref());
+    let (busy_box, busy_path) = optional_file_row(&dialog, "Select Busy Image", monitor.and_then(|m| m.busy_image.clone()), false);
+    add_row("Busy image", busy_box.upcast_ref());
+    let spin_busy = spin(10.0, 100.0, 5.0, monitor.map_or(sysinfo::default_busy_above(), |m| m.busy_above) as f64);
+    add_row("Busy above (% CPU)", spin_busy.upcast_ref());
+    for w in [spin_report.upcast_ref::<gtk::Widget>(), busy_box.upcast_ref(), spin_busy.upcast_ref()] {
+        check_monitor.bind_property("active", w, "sensitive").sync_create().build();
+    }
+
+    // UPower, see sysinfo.rs
+    let battery = preset.battery.as_ref();
+    let check_battery = CheckButton::with_label("React to the battery");
+    check_battery.set_active(battery.is_some());
+    add_row("Battery", check_battery.upcast_ref());
+    let (worried_box, worried_path) = optional_file_row(&dialog, "Select Worried Image", battery.and_then(|b| b.worried_image.clone()), false);
+    add_row("Worried image", worried_box.upcast_ref());
+    let spin_low = spin(5.0, 95.0, 5.0, battery.map_or(sysinfo::default_low_percent(), |b| b.low_percent) as f64);
+    add_row("Worried below (%)", spin_low.upcast_ref());
+    let check_sleep_full = CheckButton::with_label("Sleep once charged (sleep image)");
+    check_sleep_full.set_active(battery.is_some_and(|b| b.sleep_when_full));
+    add_row("", check_sleep_full.upcast_ref());
+    let spin_warn = spin(0.0, 95.0, 5.0, battery.map_or(0, |b| b.warn_percent) as f64);
+    add_row("Warn at (%, 0 = never)", spin_warn.upcast_ref());
+    for w in [worried_box.upcast_ref::<gtk::Widget>(), spin_low.upcast_ref(), check_sleep_full.upcast_ref(), spin_warn.upcast_ref()] {
+        check_battery.bind_property("active", w, "sensitive").sync_create().build();
+    }
+
+    // Needs a location in Preferences, see weather.rs
+    let weather = preset.weather.clone().unwrap_or_default();
+    let check_weather = CheckButton::with_label("Say the weather");
+    check_weather.set_active(weather.report);
+    add_row("Weather", check_weather.upcast_ref());
+    let (rain_box, rain_path) = optional_file_row(&dialog, "Select Rain Image", weather.rain_image.clone(), false);
+    add_row("Rain image", rain_box.upcast_ref());
+    let (snow_box, snow_path) = optional_file_row(&dialog, "Select Snow Image", weather.snow_image.clone(), false);
+    add_row("Snow image", snow_box.upcast_ref());
+
+    // Focus/break timer, see pomodoro.rs
+    let timer = preset.pomodoro.clone();
+    let check_pomodoro = CheckButton::with_label("Run a pomodoro timer");
+    check_pomodoro.set_active(timer.is_some());
+    add_row("Pomodoro", check_pomodoro.upcast_ref());
+    let timer = timer.unwrap_or_default();
+    let spin_focus = spin(1.0, 180.0, 5.0, timer.focus_minutes as f64);
+    add_row("Focus (min)", spin_focus.upcast_ref());
+    let spin_break = spin(1.0, 60.0, 1.0, timer.break_minutes as f64);
+    add_row("Break (min)", spin_break.upcast_ref());
+    let spin_long_break = spin(1.0, 120.0, 5.0, timer.long_break_minutes as f64);
+    add_row("Long break (min)", spin_long_break.upcast_ref());
+    let spin_long_every = spin(0.0, 12.0, 1.0, timer.long_break_every as f64);
+    add_row("Long break every (rounds)", spin_long_every.upcast_ref());
+    let (focus_box, focus_path) = optional_file_row(&dialog, "Select Focus Image", timer.focus_image.clone(), false);
+    add_row("Focus image", focus_box.upcast_ref());
+    let (break_box, break_path) = optional_file_row(&dialog, "Select Break Image", timer.break_image.clone(), false);
+    add_row("Break image", break_box.upcast_ref());
+    let (pomodoro_sound_box, pomodoro_sound_path) = optional_file_row(&dialog, "Select Pomodoro Sound", timer.sound.clone(), false);
+    add_row("Phase sound", pomodoro_sound_box.upcast_ref());
+    let check_pomodoro_auto = CheckButton::with_label("Start when it appears");
+    check_pomodoro_auto.set_active(timer.autostart);
+    add_row("", check_pomodoro_auto.upcast_ref());
+    for w in [spin_focus.upcast_ref::<gtk::Widget>(), spin_break.upcast_ref(), spin_long_break.upcast_ref(), spin_long_every.upcast_ref(),
+              focus_box.upcast_ref(), break_box.upcast_ref(), pomodoro_sound_box.upcast_ref(), check_pomodoro_auto.upcast_ref()] {
+        check_pomodoro.bind_property("active", w, "sensitive").sync_create().build();
+    }
+
+    // Bongo cat: a frame per key press, see typing.rs
+    let typing = preset.typing.clone();
+    let check_typing = CheckButton::with_label("Tap along while I type");
+    check_typing.set_active(typing.is_some());
+    check_typing.set_tooltip_text(Some("Reads key presses from /dev/input, which needs you in the input group"));
+    add_row("Typing", check_typing.upcast_ref());
+    let typing = typing.unwrap_or_default();
+    let typing_frames = Rc::new(RefCell::new(typing.frames.clone()));
+    let typing_frames_btn = Button::with_label(&frames_label(typing.frames.len()));
+    typing_frames_btn.set_tooltip_text(Some("Images shown one per key press, in file name order"));
+    let tf = typing_frames.clone();
+    let dlg_typing = dialog.clone();
+    typing_frames_btn.connect_clicked(move |btn| {
+        let fd = FileDialog::builder().title("Select Typing Frames").modal(true).build();
+        let tf = tf.clone();
+        let b = btn.clone();
+        fd.open_multiple(Some(&dlg_typing), None::<&gtk::gio::Cancellable>, move |res| {
+            let mut paths = report::chosen_paths(res);
+            if paths.is_empty() { return; }
+            paths.sort();
+            b.set_label(&frames_label(paths.len()));
+            *tf.borrow_mut() = paths;
+        });
+    });
+    add_row("Typing frames", typing_frames_btn.upcast_ref());
+    let spin_typing_idle = spin(100.0, 5000.0, 50.0, typing.idle_ms as f64);
+    add_row("Rest after (ms)", spin_typing_idle.upcast_ref());
+    for w in [typing_frames_btn.upcast_ref::<gtk::Widget>(), spin_typing_idle.upcast_ref()] {
+        check_typing.bind_property("active", w, "sensitive").sync_create().build();
+    }
+
+    // State machine file; ✕ goes back to the plain image
+    let (behavior_box, behavior_path) = optional_file_row(&dialog, "Select Behavior File", preset.behavior.clone(), false);
+    add_row("Behavior file", behavior_box.upcast_ref());
+
+    let (click_sound_box, click_sound_path) = optional_file_row(&dialog, "Select Click Sound", preset.click_sound.clone(), false);
+    add_row("Click sound", click_sound_box.upcast_ref());
+    let (spawn_sound_box, spawn_sound_path) = optional_file_row(&dialog, "Select Spawn Sound", preset.spawn_sound.clone(), false);
+    add_row("Spawn sound", spawn_sound_box.upcast_ref());
+    // Directory with a pack.json, see audio.rs
+    let (pack_box, pack_path) = optional_file_row(&dialog, "Select Sound Pack Folder", preset.sound_pack.clone(), true);
+    add_row("Sound pack", pack_box.upcast_ref());
+    // Cron-like times, see schedule.rs
+    let chime = preset.chime.clone();
+    let entry_chime = Entry::new();
+    entry_chime.set_placeholder_text(Some("e.g. hourly | 30 9 * * mon-fri"));
+    entry_chime.set_text(&chime.iter().flat_map(|c| &c.at).map(|c| c.to_string()).collect::<Vec<_>>().join(" | "));
+    add_row("Chime at", entry_chime.upcast_ref());
+    let (chime_sound_box, chime_sound_path) = optional_file_row(&dialog, "Select Chime Sound", chime.as_ref().and_then(|c| c.sound.clone()), false);
+    add_row("Chime sound", chime_sound_box.upcast_ref());
+    let drop_chime = DropDown::from_strings(&click_labels);
+    let chime_reaction = chime.map_or(ClickReaction::Nothing, |c| c.reaction);
+    drop_chime.set_selected(ClickReaction::ALL.iter().position(|r| *r == chime_reaction).unwrap_or(0) as u32);
+    add_row("On chime", drop_chime.upcast_ref());
+
+    let entry_sched = Entry::new();
+    entry_sched.set_placeholder_text(Some("e.g. Mon-Fri 09:00-18:00"));
+    entry_sched.set_text(&preset.schedule.as_ref().map(|s| s.to_string()).unwrap_or_default());
+    add_row("Schedule", entry_sched.upcast_ref());
+
+    let entry_tags = Entry::new();
+    entry_tags.set_text(&preset.tags.join(", "));
+    add_row("Tags", entry_tags.upcast_ref());
+
+    let entry_link = Entry::new();
+    entry_link.set_placeholder_text(Some("https://… opened when the chibi is clicked"));
+    entry_link.set_text(preset.link.as_deref().unwrap_or_default());
+    add_row("Link", entry_link.upcast_ref());
+
+    // Double-click action: the entry holds the command, URL or file path
+    let drop_dbl = DropDown::from_strings(&launch::KINDS);
+    drop_dbl.set_selected(preset.on_double_click.kind() as u32);
+    add_row("On double-click", drop_dbl.upcast_ref());
+    let entry_dbl = Entry::new();
+    entry_dbl.set_text(&preset.on_double_click.target());
+    let set_placeholder = |entry: &Entry, kind: u32| entry.set_placeholder_text(Some(match kind {
+        1 => "shell command, e.g. mpc toggle",
+        2 => "https://…",
+        3 => "/path/to/file",
+        _ => "",
+    }));
+    set_placeholder(&entry_dbl, drop_dbl.selected());
+    entry_dbl.set_sensitive(drop_dbl.selected() != 0);
+    let e_dbl = entry_dbl.clone();
+    drop_dbl.connect_selected_notify(move |d| {
+        set_placeholder(&e_dbl, d.selected());
+        e_dbl.set_sensitive(d.selected() != 0);
+    });
+    add_row("", entry_dbl.upcast_ref());
+
+    let (speech_box, read_speech) = speech::editor(&preset.speech);
+    grid.attach(&speech_box, 0, row, 2, 1);
+    row += 1;
+
+    let (hooks_box, read_hooks) = hooks::editor(&preset.hooks);
+    grid.attach(&hooks_box, 0, row, 2, 1);
+    row += 1;
+
+    let hb = GtkBox::new(Orientation::Horizontal, 10);
+    hb.set_halign(gtk::Align::End);
+    let b_cancel = Button::with_label("Cancel");
+    let b_save = Button::with_label("Save");
+    b_save.add_css_class("suggested-action");
+    hb.append(&b_cancel);
+    hb.append(&b_save);
+    grid.attach(&hb, 0, row, 2, 1);
+    let scrolled = ScrolledWindow::builder()
+    .hscrollbar_policy(gtk::PolicyType::Never)
+    .propagate_natural_height(true)
+    .max_content_height(700)
+    .child(&grid)
+    .build();
+    dialog.set_child(Some(&scrolled));
+
+    let d_c = dialog.clone();
+    b_cancel.connect_clicked(move |_| d_c.close());
+
+    let d_ok = dialog.clone();
+    let ctx = ctx.clone();
+    let pid = preset.id.clone();
+    b_save.connect_clicked(move |_| {
+        let sched_txt = entry_sched.text();
+        let schedule = Schedule::parse(&sched_txt);
+        if schedule.is_none() && !sched_txt.trim().is_empty() {
+            entry_sched.add_css_class("error");
+            return;
+        }
+        let Some(chime_at) = parse_crons(&entry_chime.text()) else {
+            entry_chime.add_css_class("error");
+            return;
+        };
+        let mut vec = ctx.state.presets.borrow_mut();
+        if let Some(p) = vec.iter_mut().find(|p| p.id == pid) {
+            let name = entry_name.text().trim().to_string();
+            if !name.is_empty() { p.name = name; }
+            p.path = image_path.borrow().clone();
+            p.width = spin_size.value() as i32;
+            p.x = spin_x.value() as i32;
+            p.y = spin_y.value() as i32;
+            p.always_on_top = check_top.is_active();
+            p.click_through = check_ghost.is_active();
+            p.wander = check_wander.is_active().then(|| WanderArea {
+                left: spin_wander_left.value() as i32,
+                right: spin_wander_right.value() as i32,
+                speed: spin_wander_speed.value() as u32,
+            });
+            p.walk = check_walk.is_active().then(|| WalkCycle {
+                speed: spin_wander_speed.value() as u32,
+                frames: walk_frames.borrow().clone(),
+                faces_left: check_faces_left.is_active(),
+                ..p.walk.clone().unwrap_or_default()
+            });
+            p.smart_hide = check_hide.is_active();
+            p.hover_threshold_ms = spin_hover.value() as u32;
+            p.hide_delay = spin_delay.value() as u32;
+            p.workspaces = parse_list(&entry_ws.text());
+            p.idle_action = IdleAction::ALL[drop_idle.selected() as usize];
+            p.idle_minutes = spin_idle.value() as u32;
+            p.on_pet = PetReaction::ALL[drop_pet.selected() as usize];
+            p.happy_image = happy_path.borrow().clone();
+            p.on_click = ClickReaction::ALL[drop_click.selected() as usize];
+            p.click_image = click_path.borrow().clone();
+            p.click_ms = spin_click.value() as u32;
+            p.spawn_sparkles = check_sparkles.is_active();
+            p.follow_layer = layer_path.borrow().clone().map(|image| FollowLayer { image, max_offset: spin_gaze.value() as u32 });
+            p.behavior = behavior_path.borrow().clone();
+            p.dance = check_dance.is_active().then(|| Dance {
+                dance_move: DanceMove::ALL[drop_dance.selected() as usize],
+                strength: spin_dance.value() as u32,
+            });
+            let media = MediaReaction { dance: check_media_dance.is_active(), album_art: check_album_art.is_active(), ticker: check_ticker.is_active() };
+            p.media = (media.dance || media.album_art || media.ticker).then_some(media);
+            let weather = WeatherReaction {
+                report: check_weather.is_active(),
+                rain_image: rain_path.borrow().clone(),
+                snow_image: snow_path.borrow().clone(),
+            };
+            p.weather = (weather != WeatherReaction::default()).then_some(weather);
+            p.pomodoro = check_pomodoro.is_active().then(|| Pomodoro {
+                focus_minutes: spin_focus.value() as u32,
+                break_minutes: spin_break.value() as u32,
+                long_break_minutes: spin_long_break.value() as u32,
+                long_break_every: spin_long_every.value() as u32,
+                focus_image: focus_path.borrow().clone(),
+                break_image: break_path.borrow().clone(),
+                sound: pomodoro_sound_path.borrow().clone(),
+                autostart: check_pomodoro_auto.is_active(),
+            });
+            p.typing = check_typing.is_active().then(|| TypingReaction {
+                frames: typing_frames.borrow().clone(),
+                idle_ms: spin_typing_idle.value() as u32,
+            });
+            p.battery = check_battery.is_active().then(|| BatteryReaction {
+                worried_image: worried_path.borrow().clone(),
+                low_percent: spin_low.value() as u32,
+                sleep_when_full: check_sleep_full.is_active(),
+                warn_percent: spin_warn.value() as u32,
+            });
+            p.monitor = check_monitor.is_active().then(|| SystemMonitor {
+                report_minutes: spin_report.value() as u32,
+                busy_image: busy_path.borrow().clone(),
+                busy_above: spin_busy.value() as u32,
+            });
+            p.quotes = quotes_path.borrow().clone().map(|file| Quotes {
+                file,
+                every_minutes: spin_quote_every.value() as u32,
+                on_click: check_quote_click.is_active(),
+                seconds: spin_quote_secs.value() as u32,
+            });
+            p.mic = check_mic.is_active().then(|| MicReaction {
+                open_image: mic_open_path.borrow().clone(),
+                threshold: spin_mic.value() as u32,
+                bounce: check_mic_bounce.is_active(),
+            });
+            p.click_sound = click_sound_path.borrow().clone();
+            p.spawn_sound = spawn_sound_path.borrow().clone();
+            p.sound_pack = pack_path.borrow().clone();
+            p.chime = (!chime_at.is_empty()).then(|| Chime {
+                at: chime_at,
+                sound: chime_sound_path.borrow().clone(),
+                reaction: ClickReaction::ALL[drop_chime.selected() as usize],
+            });
+            p.schedule = schedule;
+            p.tags = parse_list(&entry_tags.text());
+            p.hooks = read_hooks();
+            p.speech = read_speech();
+            let link = entry_link.text().trim().to_string();
+            p.link = (!link.is_empty()).then_some(link);
+            p.on_double_click = ClickAction::from_kind(drop_dbl.selected() as usize, &entry_dbl.text());
+            save_presets(&vec);
+        }
+        let _ = ctx.sender.send(AppMsg::RefreshPresets);
+        d_ok.close();
+    });
+
+    dialog.present();
 }
 
-fn save_presets(presets: &Vec<ChibiPreset>) {
-    let path = get_config_path();
-    if let Ok(json) = serde_json::to_string_pretty(presets) {
-        let _ = fs::write(path, json);
+// Right-click menu on the chibi itself; it drives the same controls as the chibi's row
+fn setup_chibi_menu(win: &ChibiWindow, key: u64, move_btn: &ToggleButton, size_spin: &SpinButton,
+                    save_btn: &Button, close_btn: &Button, sender: &AppSender) {
+    let group = gtk::gio::SimpleActionGroup::new();
+
+    let hide = gtk::gio::SimpleAction::new("hide", None);
+    let s_hide = sender.clone();
+    hide.connect_activate(move |_, _| {
+        let _ = s_hide.send(AppMsg::ToggleChibi(key));
+    });
+    group.add_action(&hide);
+
+    let move_mode = gtk::gio::SimpleAction::new_stateful("move", None, &move_btn.is_active().to_variant());
+    let btn_move = move_btn.clone();
+    move_mode.connect_activate(move |_, _| btn_move.set_active(!btn_move.is_active()));
+    let a_move = move_mode.clone();
+    move_btn.connect_toggled(move |btn| a_move.set_state(&btn.is_active().to_variant()));
+    group.add_action(&move_mode);
+
+    for (name, btn) in [("save", save_btn), ("close", close_btn)] {
+        let action = gtk::gio::SimpleAction::new(name, None);
+        let btn = btn.clone();
+        action.connect_activate(move |_, _| btn.emit_clicked());
+        group.add_action(&action);
     }
+
+    let remind = gtk::gio::SimpleAction::new("remind", None);
+    let s_remind = sender.clone();
+    remind.connect_activate(move |_, _| {
+        let _ = s_remind.send(AppMsg::AskReminder(key));
+    });
+    group.add_action(&remind);
+
+    let manager = gtk::gio::SimpleAction::new("manager", None);
+    let s_manager = sender.clone();
+    manager.connect_activate(move |_, _| {
+        let _ = s_manager.send(AppMsg::ToggleManager);
+    });
+    group.add_action(&manager);
+    win.insert_action_group("chibi", Some(&group));
+
+    // Mirrors the row's size control, so either one resizes the chibi
+    let menu_size = SpinButton::with_range(50.0, 1000.0, 10.0);
+    size_spin.bind_property("value", &menu_size, "value").bidirectional().sync_create().build();
+    let size_row = GtkBox::new(Orientation::Horizontal, 10);
+    size_row.set_margin_start(10);
+    size_row.set_margin_end(10);
+    let size_label = Label::new(Some("Size"));
+    size_label.set_hexpand(true);
+    size_label.set_xalign(0.0);
+    size_row.append(&size_label);
+    size_row.append(&menu_size);
+
+    let menu = gtk::gio::Menu::new();
+    let top = gtk::gio::Menu::new();
+    top.append(Some("Hide"), Some("chibi.hide"));
+    top.append(Some("Move mode"), Some("chibi.move"));
+    top.append(Some("Remind me…"), Some("chibi.remind"));
+    let resize = gtk::gio::MenuItem::new(None, None);
+    resize.set_attribute_value("custom", Some(&"size".to_variant()));
+    top.append_item(&resize);
+    menu.append_section(None, &top);
+    // Only shows on chibis that started a timer, see pomodoro::start
+    menu.append_section(None, &pomodoro::menu());
+    let bottom = gtk::gio::Menu::new();
+    bottom.append(Some("Save position"), Some("chibi.save"));
+    bottom.append(Some("Close"), Some("chibi.close"));
+    bottom.append(Some("Open manager"), Some("chibi.manager"));
+    menu.append_section(None, &bottom);
+    win.set_context_menu(menu.upcast_ref(), &[(size_row.upcast_ref(), "size")]);
 }
 
-fn load_presets() -> Vec<ChibiPreset> {
-    let path = get_config_path();
-    if path.exists() {
-        if let Ok(content) = fs::read_to_string(path) {
-            if let Ok(data) = serde_json::from_str(&content) {
-                return data;
+// Names the current set of on-screen presets; reusing a name overwrites that scene
+fn open_scene_dialog(parent: &impl IsA<gtk::Window>, state: &Rc<AppState>, preset_ids: Vec<String>, sender: &AppSender) {
+    let dialog = gtk::Window::builder()
+    .title("Save Scene")
+    .transient_for(parent)
+    .modal(true)
+    .default_width(300)
+    .build();
+
+    let vb = GtkBox::new(Orientation::Vertical, 10);
+    vb.set_margin_top(10); vb.set_margin_bottom(10); vb.set_margin_start(10); vb.set_margin_end(10);
+    let count = preset_ids.len();
+    vb.append(&Label::new(Some(&format!("{} saved preset{} on screen", count, if count == 1 { "" } else { "s" }))));
+
+    let entry = Entry::new();
+    entry.set_placeholder_text(Some("Scene name, e.g. Work"));
+    if let Some(active) = &state.settings.borrow().active_scene { entry.set_text(active); }
+    vb.append(&entry);
+
+    let hb = GtkBox::new(Orientation::Horizontal, 10);
+    hb.set_halign(gtk::Align::End);
+    let b_delete = Button::with_label("Delete");
+    b_delete.add_css_class("destructive-action");
+    let b_cancel = Button::with_label("Cancel");
+    let b_save = Button::with_label("Save");
+    b_save.add_css_class("suggested-action");
+    b_save.set_sensitive(count > 0);
+    hb.append(&b_delete);
+    hb.append(&b_cancel);
+    hb.append(&b_save);
+    vb.append(&hb);
+    dialog.set_child(Some(&vb));
+
+    let d_c = dialog.clone();
+    b_cancel.connect_clicked(move |_| d_c.close());
+
+    let d_del = dialog.clone();
+    let e_del = entry.clone();
+    let st_del = state.clone();
+    let s_del = sender.clone();
+    b_delete.connect_clicked(move |_| {
+        let name = e_del.text().trim().to_string();
+        {
+            let mut st = st_del.settings.borrow_mut();
+            st.scenes.retain(|s| s.name != name);
+            if st.active_scene.as_deref() == Some(name.as_str()) { st.active_scene = None; }
+            save_settings(&st);
+        }
+        let _ = s_del.send(AppMsg::RefreshScenes);
+        d_del.close();
+    });
+
+    let d_ok = dialog.clone();
+    let st_ok = state.clone();
+    let s_ok = sender.clone();
+    b_save.connect_clicked(move |_| {
+        let name = entry.text().trim().to_string();
+        if name.is_empty() { return; }
+        {
+            let mut st = st_ok.settings.borrow_mut();
+            let scene = Scene { name: name.clone(), presets: preset_ids.clone() };
+            match st.scenes.iter_mut().find(|s| s.name == name) {
+                Some(existing) => *existing = scene,
+                None => st.scenes.push(scene),
             }
+            st.active_scene = Some(name);
+            save_settings(&st);
         }
+        let _ = s_ok.send(AppMsg::RefreshScenes);
+        d_ok.close();
+    });
+
+    dialog.present();
+}
+
+// Application-wide defaults; `on_saved` runs after settings.json has been written
+fn open_preferences<F: Fn(&AppSettings) + 'static>(parent: &impl IsA<gtk::Window>, state: &Rc<AppState>, on_saved: F) {
+    let current = state.settings.borrow().clone();
+    let presets = state.presets.borrow().clone();
+    let dialog = gtk::Window::builder()
+    .title("Preferences")
+    .transient_for(parent)
+    .modal(true)
+    .default_width(360)
+    .build();
+
+    let grid = gtk::Grid::builder()
+    .row_spacing(6)
+    .column_spacing(10)
+    .margin_top(10).margin_bottom(10).margin_start(10).margin_end(10)
+    .build();
+    let mut row = 0;
+    let mut add_row = |label: &str, widget: &gtk::Widget| {
+        let l = Label::new(Some(label));
+        l.set_xalign(0.0);
+        grid.attach(&l, 0, row, 1, 1);
+        widget.set_hexpand(true);
+        grid.attach(widget, 1, row, 1, 1);
+        row += 1;
+    };
+    let spin = |min: f64, max: f64, step: f64, val: f64| {
+        let sp = SpinButton::with_range(min, max, step);
+        sp.set_value(val);
+        sp
+    };
+
+    let spin_size = spin(50.0, 1000.0, 10.0, current.default_width as f64);
+    add_row("Default size (px)", spin_size.upcast_ref());
+    let spin_x = spin(0.0, 5000.0, 50.0, current.default_x as f64);
+    add_row("Default X", spin_x.upcast_ref());
+    let spin_y = spin(0.0, 3000.0, 50.0, current.default_y as f64);
+    add_row("Default Y", spin_y.upcast_ref());
+    let check_hide = CheckButton::with_label("Smart Hide");
+    check_hide.set_active(current.default_smart_hide);
+    add_row("Hiding", check_hide.upcast_ref());
+    let spin_hover = spin(0.0, 5000.0, 50.0, current.default_hover_ms as f64);
+    add_row("Hover before hide (ms)", spin_hover.upcast_ref());
+    let spin_delay = spin(1.0, 60.0, 1.0, current.default_hide_delay as f64);
+    add_row("Stay hidden (s)", spin_delay.upcast_ref());
+    let check_top = CheckButton::with_label("Always on Top");
+    check_top.set_active(current.default_on_top);
+    add_row("Default layer", check_top.upcast_ref());
+    let spin_max_chibis = spin(0.0, 500.0, 1.0, current.max_chibis as f64);
+    spin_max_chibis.set_tooltip_text(Some("0 for no limit"));
+    add_row("Most chibis out at once", spin_max_chibis.upcast_ref());
+    let full_labels: Vec<&str> = WhenFull::ALL.iter().map(|w| w.label()).collect();
+    let drop_when_full = DropDown::from_strings(&full_labels);
+    drop_when_full.set_selected(WhenFull::ALL.iter().position(|w| *w == current.when_full).unwrap_or(0) as u32);
+    add_row("Spawning past that", drop_when_full.upcast_ref());
+
+    let check_autostart = CheckButton::with_label("Spawn autostart presets");
+    check_autostart.set_active(current.spawn_autostart);
+    add_row("On launch", check_autostart.upcast_ref());
+    let check_minimized = CheckButton::with_label("Start minimized to the tray");
+    check_minimized.set_active(current.start_minimized);
+    add_row("", check_minimized.upcast_ref());
+    let restore_labels: Vec<&str> = RestoreMode::ALL.iter().map(|m| m.label()).collect();
+    let drop_restore = DropDown::from_strings(&restore_labels);
+    drop_restore.set_selected(RestoreMode::ALL.iter().position(|m| *m == current.restore_session).unwrap_or(0) as u32);
+    add_row("Restore last session", drop_restore.upcast_ref());
+
+    let drop_renderer = DropDown::from_strings(&RENDERERS);
+    drop_renderer.set_selected(RENDERERS.iter().position(|r| *r == current.renderer).unwrap_or(0) as u32);
+    drop_renderer.set_tooltip_text(Some("Takes effect after a restart"));
+    add_row("Renderer", drop_renderer.upcast_ref());
+    let quality_labels: Vec<&str> = ImageQuality::ALL.iter().map(|q| q.label()).collect();
+    let drop_quality = DropDown::from_strings(&quality_labels);
+    drop_quality.set_selected(ImageQuality::ALL.iter().position(|q| *q == current.image_quality).unwrap_or(0) as u32);
+    drop_quality.set_tooltip_text(Some("Large images are decoded at about the size they're shown, which saves memory; chibis spawned from now on use it"));
+    add_row("Image quality", drop_quality.upcast_ref());
+    let spin_fps = spin(0.0, 240.0, 5.0, current.max_fps as f64);
+    spin_fps.set_tooltip_text(Some("How often walking, dancing and other animations draw; 0 keeps up with the display. Hidden chibis don't animate at all"));
+    add_row("Animation FPS cap", spin_fps.upcast_ref());
+    let check_debug = CheckButton::with_label("Show FPS and memory on every chibi");
+    check_debug.set_active(current.debug_overlay);
+    add_row("Debug overlay", check_debug.upcast_ref());
+
+    let check_http = CheckButton::with_label("Enable (localhost only)");
+    check_http.set_active(current.http_api);
+    check_http.set_tooltip_text(Some("Takes effect after a restart"));
+    add_row("HTTP API", check_http.upcast_ref());
+    let spin_port = spin(1024.0, 65535.0, 1.0, current.http_port as f64);
+    add_row("HTTP port", spin_port.upcast_ref());
+
+    let icon_ids: Vec<Option<String>> = std::iter::once(None)
+        .chain(presets.iter().map(|p| Some(p.id.clone())))
+        .collect();
+    let icon_labels: Vec<&str> = std::iter::once("Default")
+        .chain(presets.iter().map(|p| p.name.as_str()))
+        .collect();
+    let drop_icon = DropDown::from_strings(&icon_labels);
+    drop_icon.set_selected(icon_ids.iter().position(|id| *id == current.tray_icon_preset).unwrap_or(0) as u32);
+    add_row("Tray icon", drop_icon.upcast_ref());
+
+    let action_labels: Vec<&str> = TrayAction::ALL.iter().map(|a| a.label()).collect();
+    let action_index = |a: TrayAction| TrayAction::ALL.iter().position(|x| *x == a).unwrap_or(0) as u32;
+    let drop_primary = DropDown::from_strings(&action_labels);
+    drop_primary.set_selected(action_index(current.tray_primary));
+    add_row("Tray left click", drop_primary.upcast_ref());
+    let drop_middle = DropDown::from_strings(&action_labels);
+    drop_middle.set_selected(action_index(current.tray_middle));
+    add_row("Tray middle click", drop_middle.upcast_ref());
+
+    let click_labels: Vec<&str> = ChibiClick::ALL.iter().map(|a| a.label()).collect();
+    let drop_chibi_middle = DropDown::from_strings(&click_labels);
+    drop_chibi_middle.set_selected(ChibiClick::ALL.iter().position(|a| *a == current.chibi_middle).unwrap_or(0) as u32);
+    add_row("Chibi middle click", drop_chibi_middle.upcast_ref());
+    let check_fling = CheckButton::with_label("Keep sliding when let go mid-drag");
+    check_fling.set_active(current.fling);
+    add_row("Throwing", check_fling.upcast_ref());
+    let scale_volume = gtk::Scale::with_range(Orientation::Horizontal, 0.0, 100.0, 5.0);
+    scale_volume.set_value(current.volume as f64);
+    scale_volume.set_draw_value(true);
+    add_row("Volume", scale_volume.upcast_ref());
+    let check_mute = CheckButton::with_label("Mute sounds and speech");
+    check_mute.set_active(current.mute_sounds);
+    add_row("", check_mute.upcast_ref());
+    let check_mute_speech = CheckButton::with_label("Mute chibi speech");
+    check_mute_speech.set_active(current.mute_speech);
+    add_row("Speech", check_mute_speech.upcast_ref());
+    // A file per app event; ✕ leaves the event silent
+    let sounds_grid = gtk::Grid::builder().row_spacing(6).column_spacing(10).margin_top(6).build();
+    let sound_paths: Vec<(AppSound, Rc<RefCell<Option<PathBuf>>>)> = AppSound::ALL.iter().enumerate().map(|(i, sound)| {
+        let (file_box, path) = optional_file_row(&dialog, "Select Sound", current.sounds.get(sound).cloned(), false);
+        let l = Label::new(Some(sound.label()));
+        l.set_xalign(0.0);
+        file_box.set_hexpand(true);
+        sounds_grid.attach(&l, 0, i as i32, 1, 1);
+        sounds_grid.attach(&file_box, 1, i as i32, 1, 1);
+        (*sound, path)
+    }).collect();
+    let sounds_expander = gtk::Expander::new(Some("Event sounds"));
+    sounds_expander.set_child(Some(&sounds_grid));
+    sounds_expander.set_expanded(!current.sounds.is_empty());
+    add_row("", sounds_expander.upcast_ref());
+    let crowd_labels: Vec<&str> = CrowdMode::ALL.iter().map(|m| m.label()).collect();
+    let drop_crowds = DropDown::from_strings(&crowd_labels);
+    drop_crowds.set_selected(CrowdMode::ALL.iter().position(|m| *m == current.crowds).unwrap_or(0) as u32);
+    add_row("Moving chibis", drop_crowds.upcast_ref());
+    // Open-Meteo; chibis pick it up under Weather in their edit dialog
+    let weather = current.weather.clone().unwrap_or_default();
+    let check_weather = CheckButton::with_label("Get the weather (Open-Meteo)");
+    check_weather.set_active(current.weather.is_some());
+    add_row("Weather", check_weather.upcast_ref());
+    let spin_lat = spin(-90.0, 90.0, 0.1, weather.latitude);
+    spin_lat.set_digits(4);
+    add_row("Latitude", spin_lat.upcast_ref());
+    let spin_lon = spin(-180.0, 180.0, 0.1, weather.longitude);
+    spin_lon.set_digits(4);
+    add_row("Longitude", spin_lon.upcast_ref());
+    let spin_refresh = spin(5.0, 240.0, 5.0, weather.refresh_minutes as f64);
+    add_row("Refresh every (min)", spin_refresh.upcast_ref());
+    let check_fahrenheit = CheckButton::with_label("Fahrenheit");
+    check_fahrenheit.set_active(weather.fahrenheit);
+    add_row("", check_fahrenheit.upcast_ref());
+    for w in [spin_lat.upcast_ref::<gtk::Widget>(), spin_lon.upcast_ref(), spin_refresh.upcast_ref(), check_fahrenheit.upcast_ref()] {
+        check_weather.bind_property("active", w, "sensitive").sync_create().build();
+    }
+
+    // Repeated in a bubble, see notifications.rs
+    let notes = current.notifications.clone().unwrap_or_default();
+    let check_notes = CheckButton::with_label("Show them in a chibi's bubble");
+    check_notes.set_active(current.notifications.is_some());
+    add_row("Notifications", check_notes.upcast_ref());
+    let note_labels: Vec<&str> = std::iter::once("First chibi")
+        .chain(presets.iter().map(|p| p.name.as_str()))
+        .collect();
+    let drop_note_chibi = DropDown::from_strings(&note_labels);
+    drop_note_chibi.set_selected(icon_ids.iter().position(|id| *id == notes.preset).unwrap_or(0) as u32);
+    add_row("Shown by", drop_note_chibi.upcast_ref());
+    let entry_only_apps = Entry::new();
+    entry_only_apps.set_placeholder_text(Some("All apps"));
+    entry_only_apps.set_text(&notes.only_apps.join(", "));
+    add_row("Only from", entry_only_apps.upcast_ref());
+    let entry_ignored_apps = Entry::new();
+    entry_ignored_apps.set_placeholder_text(Some("e.g. Spotify, Steam"));
+    entry_ignored_apps.set_text(&notes.ignored_apps.join(", "));
+    add_row("Ignore", entry_ignored_apps.upcast_ref());
+    let spin_note_secs = spin(1.0, 60.0, 1.0, notes.seconds as f64);
+    add_row("Show for (s)", spin_note_secs.upcast_ref());
+    for w in [drop_note_chibi.upcast_ref::<gtk::Widget>(), entry_only_apps.upcast_ref(), entry_ignored_apps.upcast_ref(), spin_note_secs.upcast_ref()] {
+        check_notes.bind_property("active", w, "sensitive").sync_create().build();
     }
-    Vec::new()
+
+    // Same choices as the tray icon; without a favorite a random preset is spawned
+    let fav_labels: Vec<&str> = std::iter::once("Random")
+        .chain(presets.iter().map(|p| p.name.as_str()))
+        .collect();
+    let drop_fav = DropDown::from_strings(&fav_labels);
+    drop_fav.set_selected(icon_ids.iter().position(|id| *id == current.favorite_preset).unwrap_or(0) as u32);
+    add_row("Favorite preset", drop_fav.upcast_ref());
+
+    // Global hooks, run for every chibi
+    let (hooks_box, read_hooks) = hooks::editor(&current.hooks);
+    grid.attach(&hooks_box, 0, row, 2, 1);
+    row += 1;
+
+    let hb = GtkBox::new(Orientation::Horizontal, 10);
+    hb.set_halign(gtk::Align::End);
+    let b_cancel = Button::with_label("Cancel");
+    let b_save = Button::with_label("Save");
+    b_save.add_css_class("suggested-action");
+    hb.append(&b_cancel);
+    hb.append(&b_save);
+    grid.attach(&hb, 0, row, 2, 1);
+    let scrolled = ScrolledWindow::builder()
+    .hscrollbar_policy(gtk::PolicyType::Never)
+    .propagate_natural_height(true)
+    .max_content_height(700)
+    .child(&grid)
+    .build();
+    dialog.set_child(Some(&scrolled));
+
+    let d_c = dialog.clone();
+    b_cancel.connect_clicked(move |_| d_c.close());
+
+    let d_ok = dialog.clone();
+    let state = state.clone();
+    b_save.connect_clicked(move |_| {
+        {
+            let mut st = state.settings.borrow_mut();
+            st.default_width = spin_size.value() as i32;
+            st.default_x = spin_x.value() as i32;
+            st.default_y = spin_y.value() as i32;
+            st.default_smart_hide = check_hide.is_active();
+            st.default_hover_ms = spin_hover.value() as u32;
+            st.default_hide_delay = spin_delay.value() as u32;
+            st.default_on_top = check_top.is_active();
+            st.spawn_autostart = check_autostart.is_active();
+            st.start_minimized = check_minimized.is_active();
+            st.http_api = check_http.is_active();
+            st.http_port = spin_port.value() as u16;
+            st.restore_session = RestoreMode::ALL[drop_restore.selected() as usize];
+            st.renderer = RENDERERS[drop_renderer.selected() as usize].to_string();
+            st.image_quality = ImageQuality::ALL[drop_quality.selected() as usize];
+            animation::set_quality(st.image_quality);
+            st.max_fps = spin_fps.value() as u32;
+            animation::set_max_fps(st.max_fps);
+            st.max_chibis = spin_max_chibis.value() as u32;
+            st.when_full = WhenFull::ALL[drop_when_full.selected() as usize];
+            st.debug_overlay = check_debug.is_active();
+            set_debug_overlay(&state, st.debug_overlay);
+            st.tray_icon_preset = icon_ids[drop_icon.selected() as usize].clone();
+            st.tray_primary = TrayAction::ALL[drop_primary.selected() as usize];
+            st.tray_middle = TrayAction::ALL[drop_middle.selected() as usize];
+            st.favorite_preset = icon_ids[drop_fav.selected() as usize].clone();
+            st.chibi_middle = ChibiClick::ALL[drop_chibi_middle.selected() as usize];
+            st.fling = check_fling.is_active();
+            movement::set_fling_enabled(st.fling);
+            st.crowds = CrowdMode::ALL[drop_crowds.selected() as usize];
+            st.volume = scale_volume.value() as u32;
+            audio::set_volume(st.volume);
+            st.mute_sounds = check_mute.is_active();
+            st.weather = check_weather.is_active().then(|| WeatherSettings {
+                latitude: spin_lat.value(),
+                longitude: spin_lon.value(),
+                refresh_minutes: spin_refresh.value() as u32,
+                fahrenheit: check_fahrenheit.is_active(),
+            });
+            weather::configure(st.weather.clone());
+            st.notifications = check_notes.is_active().then(|| NotificationSettings {
+                preset: icon_ids[drop_note_chibi.selected() as usize].clone(),
+                only_apps: parse_list(&entry_only_apps.text()),
+                ignored_apps: parse_list(&entry_ignored_apps.text()),
+                seconds: spin_note_secs.value() as u32,
+            });
+            st.sounds = sound_paths.iter().filter_map(|(sound, path)| Some((*sound, path.borrow().clone()?))).collect();
+            audio::set_app_sounds(&st.sounds);
+            st.mute_speech = check_mute_speech.is_active();
+            speech::set_muted(st.mute_speech);
+            st.hooks = read_hooks();
+            hooks::set_global(&st.hooks);
+            save_settings(&st);
+        }
+        on_saved(&state.settings.borrow());
+        d_ok.close();
+    });
+
+    dialog.present();
+}
+
+// Collapsible section header for a preset category
+fn build_category_header(category: &str, count: usize, collapsed: bool, ctx: &PresetListCtx) -> ListBoxRow {
+    let row = ListBoxRow::new();
+    row.set_activatable(false);
+    row.set_selectable(false);
+    let arrow = if collapsed { "▸" } else { "▾" };
+    let btn = Button::with_label(&format!("{} {} ({})", arrow, category, count));
+    btn.add_css_class("flat");
+    btn.set_halign(gtk::Align::Start);
+    let cat = category.to_string();
+    let state = ctx.state.clone();
+    let sender = ctx.sender.clone();
+    btn.connect_clicked(move |_| {
+        let mut st = state.settings.borrow_mut();
+        if let Some(pos) = st.collapsed_categories.iter().position(|c| c == &cat) {
+            st.collapsed_categories.remove(pos);
+        } else {
+            st.collapsed_categories.push(cat.clone());
+        }
+        save_settings(&st);
+        let _ = sender.send(AppMsg::RefreshPresets);
+    });
+    row.set_child(Some(&btn));
+    row
+}
+
+// Small modal asking for one line of text; `on_ok` gets the trimmed result
+fn prompt_text<F: Fn(String) + 'static>(parent: &impl IsA<gtk::Window>, title: &str, label: &str, initial: &str, on_ok: F) {
+    let dialog = gtk::Window::builder()
+    .title(title)
+    .transient_for(parent)
+    .modal(true)
+    .default_width(300)
+    .build();
+
+    let vb = GtkBox::new(Orientation::Vertical, 10);
+    vb.set_margin_top(10); vb.set_margin_bottom(10);
+    vb.set_margin_start(10); vb.set_margin_end(10);
+
+    let entry = Entry::new();
+    entry.set_text(initial);
+    let hb = GtkBox::new(Orientation::Horizontal, 10);
+    let b_cancel = Button::with_label("Cancel");
+    let b_ok = Button::with_label("OK");
+    hb.append(&b_cancel); hb.append(&b_ok);
+    vb.append(&Label::new(Some(label)));
+    vb.append(&entry);
+    vb.append(&hb);
+    dialog.set_child(Some(&vb));
+
+    let d_c = dialog.clone();
+    b_cancel.connect_clicked(move |_| d_c.close());
+
+    let d_ok = dialog.clone();
+    let submit = Rc::new(move |txt: String| {
+        on_ok(txt.trim().to_string());
+        d_ok.close();
+    });
+    let submit_btn = submit.clone();
+    let entry_btn = entry.clone();
+    b_ok.connect_clicked(move |_| submit_btn(entry_btn.text().to_string()));
+    entry.connect_activate(move |e| submit(e.text().to_string()));
+    dialog.present();
 }
@@ -0,0 +1,515 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::animation::{self, Frame, FrameClock};
+use crate::window::ChibiWindow;
+
+// --- WANDERING ---
+// A wandering chibi strolls left and right between two x positions at its own height,
+// stopping now and then. A tick callback on the window advances it every frame and
+// moves the window through its x property; it stands still in move mode or while
+// tucked away by smart hide.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WanderArea {
+    // Range of the chibi's x (left edge), in px from the left of the screen
+    pub left: i32,
+    pub right: i32,
+    // Walking speed in px per second
+    #[serde(default = "default_speed")]
+    pub speed: u32,
+}
+
+pub fn default_speed() -> u32 { 40 }
+
+// Seconds spent walking / standing between decisions
+const WALK_TIME: (f64, f64) = (2.0, 6.0);
+const PAUSE_TIME: (f64, f64) = (1.0, 4.0);
+// Longer frame gaps (suspend, a stalled compositor) don't teleport the chibi
+const MAX_STEP: f64 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stride {
+    Pause,
+    // -1.0 walks left, 1.0 right
+    Walk(f64),
+}
+
+pub struct Wanderer {
+    area: WanderArea,
+    stride: Stride,
+    // Seconds left in the current stride
+    left_for: f64,
+}
+
+impl Wanderer {
+    pub fn new(area: WanderArea) -> Self {
+        Wanderer { area, stride: Stride::Pause, left_for: 0.0 }
+    }
+
+    pub fn set_range(&mut self, left: i32, right: i32) {
+        self.area.left = left;
+        self.area.right = right;
+    }
+
+    // Walks back the way it came (another chibi is in the way)
+    pub fn turn_around(&mut self) {
+        if let Stride::Walk(dir) = self.stride { self.stride = Stride::Walk(-dir); }
+    }
+
+    // -1.0 or 1.0 while walking, None while standing
+    pub fn direction(&self) -> Option<f64> {
+        match self.stride {
+            Stride::Walk(dir) => Some(dir),
+            Stride::Pause => None,
+        }
+    }
+
+    // Advances by `dt` seconds from `x` and returns the new x. `pick(lo, hi)` supplies the
+    // randomness: a number in [lo, hi).
+    pub fn step(&mut self, x: f64, dt: f64, pick: &mut dyn FnMut(f64, f64) -> f64) -> f64 {
+        let dt = dt.clamp(0.0, MAX_STEP);
+        self.left_for -= dt;
+        if self.left_for <= 0.0 {
+            self.stride = match self.stride {
+                Stride::Walk(_) => {
+                    self.left_for = pick(PAUSE_TIME.0, PAUSE_TIME.1);
+                    Stride::Pause
+                }
+                Stride::Pause => {
+                    self.left_for = pick(WALK_TIME.0, WALK_TIME.1);
+                    Stride::Walk(if pick(0.0, 1.0) < 0.5 { -1.0 } else { 1.0 })
+                }
+            };
+        }
+        let Stride::Walk(dir) = self.stride else { return x };
+        // Turn around at the ends; from outside the area (after a drag) that walks back in
+        let (left, right) = (self.area.left.min(self.area.right) as f64, self.area.left.max(self.area.right) as f64);
+        let dir = if x <= left { 1.0 } else if x >= right { -1.0 } else { dir };
+        self.stride = Stride::Walk(dir);
+        x + dir * self.area.speed as f64 * dt
+    }
+}
+
+// Keeps the chibi wandering until the window goes away
+pub fn start_wandering(win: &ChibiWindow, area: WanderArea) -> gtk::TickCallbackId {
+    let wanderer = RefCell::new(Wanderer::new(area));
+    animation::add_paced_tick(win, move |w, dt| {
+        if w.move_mode() || w.hidden() { return glib::ControlFlow::Continue; }
+        if w.take_bump() { wanderer.borrow_mut().turn_around(); }
+        let x = wanderer.borrow_mut().step(w.x(), dt, &mut |lo, hi| glib::random_double_range(lo, hi));
+        if x != w.x() { w.set_x(x); }
+        glib::ControlFlow::Continue
+    })
+}
+
+// --- WALKING ---
+// Wandering along the bottom edge of the screen, across its whole width. The window is
+// anchored to the bottom, mirrored to face the way it walks and, given walk frames, cycles
+// through them while moving; standing still shows the regular image again.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WalkCycle {
+    #[serde(default = "default_speed")]
+    pub speed: u32,
+    // Images cycled while walking, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<PathBuf>,
+    #[serde(default = "default_frame_ms")]
+    pub frame_ms: u32,
+    // The images show the chibi facing left (they are mirrored when walking right)
+    #[serde(default)]
+    pub faces_left: bool,
+}
+
+pub fn default_frame_ms() -> u32 { 150 }
+
+impl Default for WalkCycle {
+    fn default() -> Self {
+        WalkCycle { speed: default_speed(), frames: Vec::new(), frame_ms: default_frame_ms(), faces_left: false }
+    }
+}
+
+// Keeps the chibi walking until the window goes away
+pub fn start_walking(win: &ChibiWindow, walk: &WalkCycle) -> gtk::TickCallbackId {
+    win.set_bottom_anchored(true);
+    let frames: Vec<Frame> = walk.frames.iter().filter_map(|path| win.load_frame(path)
+        .map_err(|e| tracing::warn!("Skipping walk frame {}: {}", path.display(), e))
+        .ok())
+        .collect();
+    let frame_clock = RefCell::new(FrameClock::new(frames.len(), walk.frame_ms as f64 / 1000.0));
+    let wanderer = RefCell::new(Wanderer::new(WanderArea { left: 0, right: 0, speed: walk.speed }));
+    // What to show again when the chibi stops
+    let standing: RefCell<Option<Frame>> = RefCell::new(None);
+    let faces_left = walk.faces_left;
+    animation::add_paced_tick(win, move |w, dt| {
+        if w.move_mode() || w.hidden() { return glib::ControlFlow::Continue; }
+        let Some(screen) = w.screen_area() else { return glib::ControlFlow::Continue };
+        let mut wanderer = wanderer.borrow_mut();
+        if w.take_bump() { wanderer.turn_around(); }
+        wanderer.set_range(screen.x(), screen.x() + screen.width() - w.size());
+        let was_walking = wanderer.direction().is_some();
+        let x = wanderer.step(w.x(), dt, &mut |lo, hi| glib::random_double_range(lo, hi));
+        if x != w.x() { w.set_x(x); }
+
+        match wanderer.direction() {
+            Some(dir) => {
+                w.set_flipped((dir < 0.0) != faces_left);
+                if frames.is_empty() { return glib::ControlFlow::Continue; }
+                let mut frame_clock = frame_clock.borrow_mut();
+                if !was_walking {
+                    standing.replace(w.frame());
+                    frame_clock.restart();
+                    w.show_frame(&frames[0]);
+                } else if let Some(i) = frame_clock.advance(dt) {
+                    w.show_frame(&frames[i]);
+                }
+            }
+            None if was_walking => {
+                if let Some(frame) = standing.take() { w.show_frame(&frame); }
+            }
+            None => {}
+        }
+        glib::ControlFlow::Continue
+    })
+}
+
+// --- CROWDS ---
+// Chibis notice each other. Every CROWD_TICK the registry's windows are checked for
+// overlaps (a bit inside their squares, which are mostly transparent margin): a moving
+// chibi headed into another one is bumped and turns around, and with Greet both play a
+// little reaction the moment they meet. Which way a chibi is headed comes from how its
+// x changed since the last check, so the crowd needs nothing from the movers themselves.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CrowdMode {
+    // Walk through each other
+    Ignore,
+    #[default]
+    Avoid,
+    // Avoid, and say hello when meeting
+    Greet,
+}
+
+impl CrowdMode {
+    pub const ALL: [CrowdMode; 3] = [CrowdMode::Ignore, CrowdMode::Avoid, CrowdMode::Greet];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CrowdMode::Ignore => "Walk through each other",
+            CrowdMode::Avoid => "Avoid each other",
+            CrowdMode::Greet => "Avoid and greet",
+        }
+    }
+}
+
+pub const CROWD_TICK: Duration = Duration::from_millis(50);
+// Share of the window trimmed off each side before checking overlaps
+const INSET: f64 = 0.2;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Body {
+    // Registry key of the chibi
+    pub key: u64,
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    // Wanders or walks, so bumping it does something
+    pub mover: bool,
+}
+
+impl Body {
+    fn inner(&self) -> (f64, f64, f64, f64) {
+        let m = self.size * INSET;
+        (self.x + m, self.y + m, self.x + self.size - m, self.y + self.size - m)
+    }
+
+    fn overlaps(&self, other: &Body) -> bool {
+        let (a, b) = (self.inner(), other.inner());
+        a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+    }
+
+    fn center_x(&self) -> f64 {
+        self.x + self.size / 2.0
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct CrowdStep {
+    pub bumped: Vec<u64>,
+    // Pairs that started overlapping this step
+    pub met: Vec<(u64, u64)>,
+}
+
+#[derive(Default)]
+pub struct Crowd {
+    last_x: HashMap<u64, f64>,
+    touching: HashSet<(u64, u64)>,
+}
+
+impl Crowd {
+    pub fn step(&mut self, bodies: &[Body]) -> CrowdStep {
+        let mut step = CrowdStep::default();
+        let mut touching = HashSet::new();
+        for (i, a) in bodies.iter().enumerate() {
+            for b in &bodies[i + 1..] {
+                if !a.overlaps(b) { continue; }
+                let pair = (a.key.min(b.key), a.key.max(b.key));
+                if !self.touching.contains(&pair) { step.met.push(pair); }
+                touching.insert(pair);
+                for (me, other) in [(a, b), (b, a)] {
+                    if me.mover && self.heading_towards(me, other) && !step.bumped.contains(&me.key) {
+                        step.bumped.push(me.key);
+                    }
+                }
+            }
+        }
+        self.touching = touching;
+        self.last_x = bodies.iter().map(|b| (b.key, b.x)).collect();
+        step
+    }
+
+    fn heading_towards(&self, me: &Body, other: &Body) -> bool {
+        let Some(last) = self.last_x.get(&me.key) else { return false };
+        let dx = me.x - last;
+        dx != 0.0 && dx.signum() == (other.center_x() - me.center_x()).signum()
+    }
+}
+
+// --- FLING ---
+// A chibi let go mid-drag keeps going: friction slows it down and it bounces softly off the
+// screen edges before settling. The window records where the drag took it (VelocityTracker)
+// and runs the Fling step from a tick callback after the release.
+
+thread_local! {
+    static FLING: Cell<bool> = const { Cell::new(true) };
+}
+
+pub fn set_fling_enabled(enabled: bool) {
+    FLING.with(|f| f.set(enabled));
+}
+
+pub fn fling_enabled() -> bool {
+    FLING.with(|f| f.get())
+}
+
+// Releases slower than this (px/s) just drop the chibi where it is
+pub const MIN_FLING_SPEED: f64 = 300.0;
+// Settled below this speed
+const STOP_SPEED: f64 = 20.0;
+// Exponential slow-down rate, per second
+const FRICTION: f64 = 3.0;
+// Share of the speed kept when bouncing off an edge
+const BOUNCE: f64 = 0.5;
+// Only the last bit of the drag counts towards the release velocity
+const VELOCITY_WINDOW_MS: u32 = 80;
+
+#[derive(Default)]
+pub struct VelocityTracker {
+    // (event time in ms, x, y)
+    samples: Vec<(u32, f64, f64)>,
+}
+
+impl VelocityTracker {
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn push(&mut self, time: u32, x: f64, y: f64) {
+        self.samples.retain(|(t, _, _)| time.saturating_sub(*t) <= VELOCITY_WINDOW_MS);
+        self.samples.push((time, x, y));
+    }
+
+    // Velocity in px/s at `time`; zero if the pointer had stopped before then
+    pub fn velocity(&self, time: u32) -> (f64, f64) {
+        let recent: Vec<_> = self.samples.iter()
+            .filter(|(t, _, _)| time.saturating_sub(*t) <= VELOCITY_WINDOW_MS)
+            .collect();
+        let (Some(first), Some(last)) = (recent.first(), recent.last()) else { return (0.0, 0.0) };
+        let dt = last.0.saturating_sub(first.0) as f64 / 1000.0;
+        if dt <= 0.0 { return (0.0, 0.0); }
+        ((last.1 - first.1) / dt, (last.2 - first.2) / dt)
+    }
+}
+
+pub struct Fling {
+    pub x: f64,
+    pub y: f64,
+    vx: f64,
+    vy: f64,
+    // Range of positions that keep the whole chibi on screen: (min_x, min_y, max_x, max_y)
+    bounds: (f64, f64, f64, f64),
+}
+
+// Moves `pos` by `v * dt` within [min, max], reflecting off the ends
+fn bounce_axis(pos: f64, v: f64, dt: f64, min: f64, max: f64) -> (f64, f64) {
+    let next = pos + v * dt;
+    if next < min {
+        ((2.0 * min - next).min(max), -v * BOUNCE)
+    } else if next > max {
+        ((2.0 * max - next).max(min), -v * BOUNCE)
+    } else {
+        (next, v)
+    }
+}
+
+impl Fling {
+    pub fn new(x: f64, y: f64, (vx, vy): (f64, f64), bounds: (f64, f64, f64, f64)) -> Self {
+        let (min_x, min_y, max_x, max_y) = bounds;
+        // A chibi larger than the screen is pinned to the top-left
+        let bounds = (min_x, min_y, max_x.max(min_x), max_y.max(min_y));
+        Fling { x, y, vx, vy, bounds }
+    }
+
+    // Advances by `dt` seconds; false once the chibi has settled
+    pub fn step(&mut self, dt: f64) -> bool {
+        let dt = dt.clamp(0.0, MAX_STEP);
+        let (min_x, min_y, max_x, max_y) = self.bounds;
+        (self.x, self.vx) = bounce_axis(self.x, self.vx, dt, min_x, max_x);
+        (self.y, self.vy) = bounce_axis(self.y, self.vy, dt, min_y, max_y);
+        let decay = (-FRICTION * dt).exp();
+        self.vx *= decay;
+        self.vy *= decay;
+        self.vx.hypot(self.vy) >= STOP_SPEED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: WanderArea = WanderArea { left: 100, right: 300, speed: 40 };
+
+    // Always the low end of the range: shortest strides, walking left
+    fn low(lo: f64, _hi: f64) -> f64 { lo }
+
+    #[test]
+    fn pauses_then_walks() {
+        let mut w = Wanderer::new(AREA);
+        // First step decides to walk left for 2s, 10px per quarter second
+        let mut x = w.step(200.0, 0.25, &mut low);
+        assert_eq!(x, 190.0);
+        for _ in 0..7 { x = w.step(x, 0.25, &mut low); }
+        assert_eq!(x, 120.0);
+        // Then stands still for a second
+        assert_eq!(w.step(x, 0.25, &mut low), 120.0);
+        for _ in 0..3 { x = w.step(x, 0.25, &mut low); }
+        assert_eq!(x, 120.0);
+        assert_eq!(w.step(x, 0.25, &mut low), 110.0);
+    }
+
+    #[test]
+    fn turns_around_at_the_edges() {
+        let mut w = Wanderer::new(AREA);
+        let x = w.step(100.0, 0.1, &mut low);
+        assert!(x > 100.0);
+        let mut w = Wanderer::new(AREA);
+        let x = w.step(400.0, 0.1, &mut |lo, hi| if hi <= 1.0 { 0.9 } else { lo });
+        assert!(x < 400.0);
+    }
+
+    #[test]
+    fn long_frame_gaps_are_capped() {
+        let mut w = Wanderer::new(AREA);
+        let x = w.step(200.0, 30.0, &mut low);
+        assert_eq!(x, 200.0 - AREA.speed as f64 * MAX_STEP);
+    }
+
+    #[test]
+    fn direction_follows_the_stride() {
+        let mut w = Wanderer::new(AREA);
+        assert_eq!(w.direction(), None);
+        w.step(200.0, 0.25, &mut low);
+        assert_eq!(w.direction(), Some(-1.0));
+        // Narrowing the range past the chibi turns it around
+        w.set_range(250, 900);
+        w.step(200.0, 0.25, &mut low);
+        assert_eq!(w.direction(), Some(1.0));
+    }
+
+    fn body(key: u64, x: f64, mover: bool) -> Body {
+        Body { key, x, y: 0.0, size: 100.0, mover }
+    }
+
+    #[test]
+    fn movers_are_bumped_walking_into_others() {
+        let mut crowd = Crowd::default();
+        crowd.step(&[body(1, 0.0, true), body(2, 200.0, false)]);
+        let step = crowd.step(&[body(1, 50.0, true), body(2, 200.0, false)]);
+        assert!(step.bumped.is_empty() && step.met.is_empty());
+        // Overlapping past the transparent margins, still walking right
+        let step = crowd.step(&[body(1, 150.0, true), body(2, 200.0, false)]);
+        assert_eq!(step.bumped, vec![1]);
+        assert_eq!(step.met, vec![(1, 2)]);
+        // Walking away again is left alone, and they have already met
+        let step = crowd.step(&[body(1, 140.0, true), body(2, 200.0, false)]);
+        assert!(step.bumped.is_empty() && step.met.is_empty());
+    }
+
+    #[test]
+    fn standing_chibis_are_not_bumped() {
+        let mut crowd = Crowd::default();
+        crowd.step(&[body(1, 150.0, true), body(2, 200.0, true)]);
+        let step = crowd.step(&[body(1, 150.0, true), body(2, 200.0, true)]);
+        assert!(step.bumped.is_empty());
+    }
+
+    #[test]
+    fn turning_around_reverses_the_stride() {
+        let mut w = Wanderer::new(AREA);
+        w.turn_around();
+        assert_eq!(w.direction(), None);
+        w.step(200.0, 0.25, &mut low);
+        w.turn_around();
+        assert_eq!(w.direction(), Some(1.0));
+    }
+
+    #[test]
+    fn walk_cycle_defaults() {
+        let walk: WalkCycle = serde_json::from_str("{}").unwrap();
+        assert_eq!(walk, WalkCycle::default());
+        assert!(!serde_json::to_string(&walk).unwrap().contains("frames"));
+    }
+
+    #[test]
+    fn speed_defaults_when_missing() {
+        let area: WanderArea = serde_json::from_str(r#"{"left": 0, "right": 500}"#).unwrap();
+        assert_eq!(area.speed, default_speed());
+    }
+
+    #[test]
+    fn velocity_from_recent_samples() {
+        let mut t = VelocityTracker::default();
+        t.push(0, 0.0, 0.0);
+        t.push(100, 500.0, 0.0);
+        t.push(150, 550.0, 10.0);
+        t.push(200, 600.0, 20.0);
+        let (vx, vy) = t.velocity(200);
+        assert!((vx - 1000.0).abs() < 1e-6 && (vy - 200.0).abs() < 1e-6);
+        // Held still before letting go
+        assert_eq!(t.velocity(400), (0.0, 0.0));
+    }
+
+    #[test]
+    fn fling_slows_down_and_settles() {
+        let mut f = Fling::new(100.0, 100.0, (800.0, 0.0), (0.0, 0.0, 5000.0, 5000.0));
+        let mut steps = 0;
+        while f.step(1.0 / 60.0) { steps += 1; assert!(steps < 1000); }
+        assert!(f.x > 300.0 && f.x < 5000.0);
+        assert_eq!(f.y, 100.0);
+    }
+
+    #[test]
+    fn fling_bounces_off_edges() {
+        let mut f = Fling::new(990.0, 10.0, (1000.0, -1000.0), (0.0, 0.0, 1000.0, 1000.0));
+        f.step(0.02);
+        assert!((f.x - 990.0).abs() < 1e-6 && (f.y - 10.0).abs() < 1e-6);
+        assert!(f.vx < 0.0 && f.vx > -1000.0 * BOUNCE);
+        assert!(f.vy > 0.0);
+    }
+}
@@ -0,0 +1,476 @@
+use gtk::{gdk, gio, glib};
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::animation;
+use crate::window::ChibiWindow;
+
+// --- MEDIA PLAYERS ---
+// Follows MPRIS players (org.mpris.MediaPlayer2.* on the session bus): whether one is playing,
+// and what. One watcher is shared by all chibis; the player that most recently started
+// playing is the one they react to, dancing along and showing its album art in a bubble.
+// A chibi can also carry a scrolling now-playing ticker, which stays up while the player is
+// paused and plays or pauses it when clicked.
+
+const PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_IFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct MediaReaction {
+    // Loops the dance animation while something plays
+    pub dance: bool,
+    // Bubble with the album art and title
+    pub album_art: bool,
+    // Scrolling title/artist under the chibi
+    pub ticker: bool,
+}
+
+impl Default for MediaReaction {
+    fn default() -> Self {
+        MediaReaction { dance: true, album_art: true, ticker: false }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    pub title: String,
+    pub artist: String,
+    pub art_url: Option<String>,
+}
+
+impl Track {
+    // "Title — Artist", or whichever of them there is
+    pub fn caption(&self) -> String {
+        match (self.title.is_empty(), self.artist.is_empty()) {
+            (false, false) => format!("{} — {}", self.title, self.artist),
+            (false, true) => self.title.clone(),
+            _ => self.artist.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Player {
+    playing: bool,
+    track: Track,
+    // When it last started playing, in updates seen
+    started: u64,
+}
+
+// Players by unique bus name
+#[derive(Default)]
+pub struct Players {
+    players: HashMap<String, Player>,
+    updates: u64,
+}
+
+impl Players {
+    // Applies what changed (None is unchanged) for the player at `name`
+    pub fn update(&mut self, name: &str, playing: Option<bool>, track: Option<Track>) {
+        self.updates += 1;
+        let player = self.players.entry(name.to_string()).or_default();
+        if let Some(playing) = playing {
+            if playing && !player.playing { player.started = self.updates; }
+            player.playing = playing;
+        }
+        if let Some(track) = track { player.track = track; }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.players.remove(name);
+    }
+
+    // The track of the player that started playing last, if any is playing
+    pub fn now_playing(&self) -> Option<&Track> {
+        self.players.values().filter(|p| p.playing).max_by_key(|p| p.started).map(|p| &p.track)
+    }
+
+    // The player that started playing last, even if it's paused now
+    pub fn latest(&self) -> Option<NowPlaying> {
+        let (name, p) = self.players.iter()
+            .filter(|(_, p)| p.track != Track::default())
+            .max_by_key(|(_, p)| (p.playing, p.started))?;
+        Some(NowPlaying { name: name.clone(), track: p.track.clone(), playing: p.playing })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NowPlaying {
+    // Unique bus name, for sending it commands
+    pub name: String,
+    pub track: Track,
+    pub playing: bool,
+}
+
+type Listener = Box<dyn Fn(Option<&Track>) -> bool>;
+type PlayerListener = Box<dyn Fn(Option<&NowPlaying>) -> bool>;
+
+#[derive(Default)]
+struct Watcher {
+    players: Players,
+    // Dropped once they return false
+    listeners: Vec<Listener>,
+    last: Option<Track>,
+    player_listeners: Vec<PlayerListener>,
+    last_player: Option<NowPlaying>,
+    conn: Option<gio::DBusConnection>,
+}
+
+thread_local! {
+    // Started with the first listener
+    static WATCHER: RefCell<Option<Watcher>> = const { RefCell::new(None) };
+}
+
+// Calls `on_change` with what's playing now and whenever that changes, until it returns false
+pub fn watch(on_change: impl Fn(Option<&Track>) -> bool + 'static) {
+    let start = WATCHER.with(|w| {
+        let mut w = w.borrow_mut();
+        let start = w.is_none();
+        let watcher = w.get_or_insert_with(Watcher::default);
+        if on_change(watcher.last.as_ref()) { watcher.listeners.push(Box::new(on_change)); }
+        start
+    });
+    if start { connect(); }
+}
+
+// Like `watch`, but follows the latest player through pauses too
+pub fn watch_player(on_change: impl Fn(Option<&NowPlaying>) -> bool + 'static) {
+    let start = WATCHER.with(|w| {
+        let mut w = w.borrow_mut();
+        let start = w.is_none();
+        let watcher = w.get_or_insert_with(Watcher::default);
+        if on_change(watcher.last_player.as_ref()) { watcher.player_listeners.push(Box::new(on_change)); }
+        start
+    });
+    if start { connect(); }
+}
+
+fn changed(apply: impl FnOnce(&mut Players)) {
+    let Some((now, listeners, player, player_listeners)) = WATCHER.with(|w| {
+        let mut w = w.borrow_mut();
+        let watcher = w.as_mut()?;
+        apply(&mut watcher.players);
+        let now = watcher.players.now_playing().cloned();
+        let player = watcher.players.latest();
+        let listeners = if now != watcher.last { std::mem::take(&mut watcher.listeners) } else { Vec::new() };
+        let player_listeners = if player != watcher.last_player { std::mem::take(&mut watcher.player_listeners) } else { Vec::new() };
+        watcher.last = now.clone();
+        watcher.last_player = player.clone();
+        Some((now, listeners, player, player_listeners))
+    }) else { return };
+    // Listeners run with the watcher released, so they may add more
+    let kept: Vec<Listener> = listeners.into_iter().filter(|l| l(now.as_ref())).collect();
+    let kept_player: Vec<PlayerListener> = player_listeners.into_iter().filter(|l| l(player.as_ref())).collect();
+    WATCHER.with(|w| {
+        if let Some(watcher) = w.borrow_mut().as_mut() {
+            watcher.listeners.splice(0..0, kept);
+            watcher.player_listeners.splice(0..0, kept_player);
+        }
+    });
+}
+
+// Toggles the latest player
+pub fn play_pause() {
+    let Some((conn, name)) = WATCHER.with(|w| {
+        let w = w.borrow();
+        let watcher = w.as_ref()?;
+        Some((watcher.conn.clone()?, watcher.last_player.as_ref()?.name.clone()))
+    }) else { return };
+    conn.call(
+        Some(&name),
+        PATH,
+        PLAYER_IFACE,
+        "PlayPause",
+        None,
+        None,
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        |reply| {
+            if let Err(e) = reply { tracing::warn!("Player didn't take PlayPause: {}", e); }
+        },
+    );
+}
+
+// (playing, track) from a Player interface property dict, None for what it doesn't mention
+fn read_properties(props: &glib::Variant) -> (Option<bool>, Option<Track>) {
+    let dict = glib::VariantDict::new(Some(props));
+    let playing = dict.lookup::<String>("PlaybackStatus").ok().flatten().map(|s| s == "Playing");
+    let track = dict.lookup_value("Metadata", None).map(|meta| {
+        let meta = glib::VariantDict::new(Some(&meta));
+        Track {
+            title: meta.lookup::<String>("xesam:title").ok().flatten().unwrap_or_default(),
+            artist: meta.lookup::<Vec<String>>("xesam:artist").ok().flatten().unwrap_or_default().join(", "),
+            art_url: meta.lookup::<String>("mpris:artUrl").ok().flatten().filter(|u| !u.is_empty()),
+        }
+    });
+    (playing, track)
+}
+
+// Reads everything a player currently has
+fn query(conn: &gio::DBusConnection, unique: String) {
+    conn.call(
+        Some(&unique.clone()),
+        PATH,
+        "org.freedesktop.DBus.Properties",
+        "GetAll",
+        Some(&(PLAYER_IFACE,).to_variant()),
+        Some(glib::VariantTy::new("(a{sv})").unwrap()),
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        move |reply| {
+            let Ok(reply) = reply else { return };
+            let (playing, track) = read_properties(&reply.child_value(0));
+            changed(|p| p.update(&unique, playing, track));
+        },
+    );
+}
+
+// Looks up who owns the well-known `name` and queries them
+fn query_name(conn: &gio::DBusConnection, name: &str) {
+    let conn_c = conn.clone();
+    conn.call(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "GetNameOwner",
+        Some(&(name,).to_variant()),
+        Some(glib::VariantTy::new("(s)").unwrap()),
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        move |reply| {
+            let Some(unique) = reply.ok().and_then(|r| r.child_value(0).str().map(str::to_string)) else { return };
+            query(&conn_c, unique);
+        },
+    );
+}
+
+fn connect() {
+    gio::bus_get(gio::BusType::Session, None::<&gio::Cancellable>, |res| {
+        let Ok(conn) = res else { return };
+        WATCHER.with(|w| {
+            if let Some(watcher) = w.borrow_mut().as_mut() { watcher.conn = Some(conn.clone()); }
+        });
+        // Signals come from the players' unique names
+        conn.signal_subscribe(
+            None,
+            Some("org.freedesktop.DBus.Properties"),
+            Some("PropertiesChanged"),
+            Some(PATH),
+            Some(PLAYER_IFACE),
+            gio::DBusSignalFlags::NONE,
+            |_, sender, _, _, _, params| {
+                // (s interface, a{sv} changed, as invalidated)
+                let (playing, track) = read_properties(&params.child_value(1));
+                let sender = sender.to_string();
+                changed(|p| p.update(&sender, playing, track));
+            },
+        );
+        // Players coming and going
+        let conn_c = conn.clone();
+        conn.signal_subscribe(
+            Some("org.freedesktop.DBus"),
+            Some("org.freedesktop.DBus"),
+            Some("NameOwnerChanged"),
+            Some("/org/freedesktop/DBus"),
+            Some("org.mpris.MediaPlayer2"),
+            gio::DBusSignalFlags::MATCH_ARG0_NAMESPACE,
+            move |_, _, _, _, _, params| {
+                // (s name, s old owner, s new owner)
+                let (Some(old), Some(new)) = (params.child_value(1).str().map(str::to_string), params.child_value(2).str().map(str::to_string)) else { return };
+                if !old.is_empty() { changed(|p| p.remove(&old)); }
+                if !new.is_empty() { query(&conn_c, new); }
+            },
+        );
+        // And the ones already running
+        let conn_c = conn.clone();
+        conn.call(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ListNames",
+            None,
+            Some(glib::VariantTy::new("(as)").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+            move |reply| {
+                let Some(names) = reply.ok().and_then(|r| r.child_value(0).get::<Vec<String>>()) else { return };
+                for name in names.iter().filter(|n| n.starts_with(PREFIX)) {
+                    query_name(&conn_c, name);
+                }
+            },
+        );
+    });
+}
+
+fn current_art() -> Option<String> {
+    WATCHER.with(|w| w.borrow().as_ref()?.last.as_ref()?.art_url.clone())
+}
+
+// Loads album art (file:// or, with gvfs, http) without blocking
+fn load_art(url: &str, done: impl FnOnce(gdk::Texture) + 'static) {
+    gio::File::for_uri(url).load_contents_async(None::<&gio::Cancellable>, move |res| {
+        let Ok((bytes, _)) = res else { return };
+        match gdk::Texture::from_bytes(&glib::Bytes::from_owned(bytes)) {
+            Ok(texture) => done(texture),
+            Err(e) => tracing::debug!("Can't load album art: {}", e),
+        }
+    });
+}
+
+// Reacts to the media players until the window goes away
+pub fn start(win: &ChibiWindow, reaction: MediaReaction) {
+    if reaction.ticker { start_ticker(win); }
+    if !reaction.dance && !reaction.album_art { return; }
+    let win_weak = win.downgrade();
+    watch(move |track| {
+        let Some(w) = win_weak.upgrade() else { return false };
+        if reaction.dance { w.set_picture_class("chibi-dancing", track.is_some()); }
+        if reaction.album_art {
+            match track {
+                Some(track) => {
+                    w.set_bubble(Some((&track.caption(), None)));
+                    if let Some(url) = &track.art_url {
+                        let (w_weak, caption, wanted) = (w.downgrade(), track.caption(), url.clone());
+                        load_art(url, move |texture| {
+                            // Still the same track by the time it loaded?
+                            if current_art() != Some(wanted) { return; }
+                            if let Some(w) = w_weak.upgrade() { w.set_bubble(Some((&caption, Some(&texture)))); }
+                        });
+                    }
+                }
+                None => w.set_bubble(None),
+            }
+        }
+        true
+    });
+}
+
+// --- NOW PLAYING TICKER ---
+
+// Characters of the title shown at once; longer ones scroll through
+const TICKER_WIDTH: usize = 28;
+const TICKER_STEP: std::time::Duration = std::time::Duration::from_millis(250);
+const TICKER_GAP: &str = "   ·   ";
+
+// `width` characters of `text` from `offset` on, wrapping around; short texts stand still
+pub fn ticker_frame(text: &str, width: usize, offset: usize) -> String {
+    let chars: Vec<char> = text.chars().chain(TICKER_GAP.chars()).collect();
+    if text.chars().count() <= width { return text.to_string(); }
+    (0..width).map(|i| chars[(offset + i) % chars.len()]).collect()
+}
+
+// Popover under the chibi with the latest player's track
+fn start_ticker(win: &ChibiWindow) {
+    let label = gtk::Label::builder().single_line_mode(true).width_chars(TICKER_WIDTH as i32 + 2).xalign(0.0).build();
+    let popover = gtk::Popover::builder()
+    .child(&label)
+    .autohide(false)
+    .can_focus(false)
+    .has_arrow(false)
+    .position(gtk::PositionType::Bottom)
+    .css_classes(["chibi-ticker"])
+    .build();
+    popover.set_parent(win);
+    let click = gtk::GestureClick::new();
+    click.connect_released(|_, _, _, _| play_pause());
+    label.add_controller(click);
+    let popover_c = popover.clone();
+    win.connect_destroy(move |_| popover_c.unparent());
+
+    // The scrolling timer only runs while there's a player to show
+    let current: Rc<RefCell<Option<NowPlaying>>> = Rc::new(RefCell::new(None));
+    let ticking = Rc::new(Cell::new(false));
+    let offset = Rc::new(Cell::new(0));
+    let win_weak = win.downgrade();
+    watch_player(move |player| {
+        let Some(win) = win_weak.upgrade() else { return false };
+        current.replace(player.cloned());
+        if player.is_none() || ticking.replace(true) { return true; }
+        let (current, ticking, offset) = (current.clone(), ticking.clone(), offset.clone());
+        let (popover, label) = (popover.clone(), label.clone());
+        animation::every_while_shown(&win, TICKER_STEP, move |_| {
+            let Some(player) = current.borrow().clone() else {
+                popover.popdown();
+                ticking.set(false);
+                return glib::ControlFlow::Break;
+            };
+            offset.set(offset.get() + 1);
+            let icon = if player.playing { "▶" } else { "⏸" };
+            label.set_text(&format!("{} {}", icon, ticker_frame(&player.track.caption(), TICKER_WIDTH, offset.get())));
+            // Comes back by itself after smart hide
+            if !popover.is_visible() { popover.popup(); }
+            glib::ControlFlow::Continue
+        });
+        true
+    });
+}
+
+pub const CSS: &str = "\
+@keyframes chibi-dancing { 0% { transform: rotate(0deg) translateY(0); } 25% { transform: rotate(-5deg) translateY(-4px); } \
+50% { transform: rotate(0deg) translateY(0); } 75% { transform: rotate(5deg) translateY(-4px); } 100% { transform: rotate(0deg) translateY(0); } }\n\
+.chibi-dancing { animation: chibi-dancing 900ms ease-in-out infinite; }\n\
+.chibi-ticker > contents { border-radius: 10px; padding: 2px 8px; } .chibi-ticker label { font-size: small; }";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str) -> Track {
+        Track { title: title.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn latest_player_to_start_wins() {
+        let mut p = Players::default();
+        assert_eq!(p.now_playing(), None);
+        p.update(":1.1", Some(true), Some(track("a")));
+        p.update(":1.2", Some(false), Some(track("b")));
+        assert_eq!(p.now_playing(), Some(&track("a")));
+        p.update(":1.2", Some(true), None);
+        assert_eq!(p.now_playing(), Some(&track("b")));
+        // A new track on the first player doesn't take over
+        p.update(":1.1", None, Some(track("c")));
+        assert_eq!(p.now_playing(), Some(&track("b")));
+        p.remove(":1.2");
+        assert_eq!(p.now_playing(), Some(&track("c")));
+        p.update(":1.1", Some(false), None);
+        assert_eq!(p.now_playing(), None);
+    }
+
+    #[test]
+    fn latest_player_survives_a_pause() {
+        let mut p = Players::default();
+        assert_eq!(p.latest(), None);
+        p.update(":1.1", Some(true), Some(track("a")));
+        p.update(":1.2", Some(true), Some(track("b")));
+        p.update(":1.2", Some(false), None);
+        assert_eq!(p.latest().map(|n| (n.name, n.playing)), Some((":1.1".into(), true)));
+        p.update(":1.1", Some(false), None);
+        assert_eq!(p.latest().map(|n| (n.name, n.playing)), Some((":1.2".into(), false)));
+    }
+
+    #[test]
+    fn ticker_scrolls_long_titles() {
+        assert_eq!(ticker_frame("Short", 10, 3), "Short");
+        assert_eq!(ticker_frame("abcdef", 4, 0), "abcd");
+        assert_eq!(ticker_frame("abcdef", 4, 4), "ef  ");
+        assert_eq!(ticker_frame("abcdef", 4, 13), "abcd");
+    }
+
+    #[test]
+    fn captions() {
+        let t = Track { title: "Song".into(), artist: "Band".into(), art_url: None };
+        assert_eq!(t.caption(), "Song — Band");
+        assert_eq!(track("Song").caption(), "Song");
+        assert_eq!(Track { artist: "Band".into(), ..Default::default() }.caption(), "Band");
+    }
+}
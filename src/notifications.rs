@@ -0,0 +1,167 @@
+use gtk::gio;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::{AppMsg, AppSender};
+
+// --- NOTIFICATIONS ---
+// Desktop notifications, repeated as speech bubbles by one chibi. A separate session bus
+// connection becomes a monitor (org.freedesktop.DBus.Monitoring) for the Notify calls apps
+// make to org.freedesktop.Notifications; the real notification daemon still gets them.
+// Monitored messages arrive on GDBus's worker thread, so they reach the main loop as AppMsgs.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    // Preset whose chibi says them; None is whichever chibi came first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    // Only these apps, if any are listed (names as they send them, any case)
+    pub only_apps: Vec<String>,
+    pub ignored_apps: Vec<String>,
+    // How long the bubble stays up
+    pub seconds: u32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings { preset: None, only_apps: Vec::new(), ignored_apps: Vec::new(), seconds: 8 }
+    }
+}
+
+impl NotificationSettings {
+    pub fn allows(&self, app: &str) -> bool {
+        let listed = |apps: &[String]| apps.iter().any(|a| a.eq_ignore_ascii_case(app.trim()));
+        (self.only_apps.is_empty() || listed(&self.only_apps)) && !listed(&self.ignored_apps)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub app: String,
+    pub summary: String,
+    pub body: String,
+}
+
+// Longest body line a bubble shows
+const MAX_BODY: usize = 100;
+
+// Bodies may carry the little markup the spec allows; the bubble shows plain text
+fn strip_markup(text: &str) -> String {
+    let mut plain = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    plain.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+impl Notification {
+    // The summary, with the first line of the body under it
+    pub fn text(&self) -> String {
+        let body = strip_markup(self.body.lines().find(|l| !l.trim().is_empty()).unwrap_or(""));
+        let mut body = body.trim().to_string();
+        if body.chars().count() > MAX_BODY {
+            body = body.chars().take(MAX_BODY - 1).collect::<String>() + "…";
+        }
+        match (self.summary.trim(), body.is_empty()) {
+            ("", _) => body,
+            (summary, true) => summary.to_string(),
+            (summary, false) => format!("{}\n{}", summary, body),
+        }
+    }
+}
+
+// (susssasa{sv}i) app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout
+fn read_notify(msg: &gio::DBusMessage) -> Option<Notification> {
+    if msg.message_type() != gio::DBusMessageType::MethodCall || msg.member().as_deref() != Some("Notify") { return None; }
+    let body = msg.body()?;
+    if body.n_children() < 5 { return None; }
+    let field = |i| body.child_value(i).str().unwrap_or_default().to_string();
+    Some(Notification { app: field(0), summary: field(3), body: field(4) })
+}
+
+thread_local! {
+    // The monitor connection, kept open once started
+    static MONITOR: RefCell<Option<gio::DBusConnection>> = const { RefCell::new(None) };
+}
+
+const RULE: &str = "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+
+// Starts listening, once; stays on for the rest of the run (the settings decide what's shown)
+pub fn listen(sender: &AppSender) {
+    if MONITOR.with(|m| m.borrow().is_some()) { return; }
+    let address = match gio::dbus_address_get_for_bus_sync(gio::BusType::Session, None::<&gio::Cancellable>) {
+        Ok(address) => address,
+        Err(e) => return tracing::warn!("No session bus for notifications: {}", e),
+    };
+    // A connection of its own: once it's a monitor it can't do anything else
+    let flags = gio::DBusConnectionFlags::AUTHENTICATION_CLIENT | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION;
+    let conn = match gio::DBusConnection::for_address_sync(&address, flags, None, None::<&gio::Cancellable>) {
+        Ok(conn) => conn,
+        Err(e) => return tracing::warn!("Can't connect to the session bus for notifications: {}", e),
+    };
+    let sender = sender.clone();
+    conn.add_filter(move |_, msg, incoming| {
+        // Monitored calls are swallowed here: GDBus would otherwise answer them, and a
+        // monitor that sends anything gets disconnected
+        match read_notify(msg).filter(|_| incoming) {
+            Some(n) => {
+                let _ = sender.send(AppMsg::Notification(n));
+                None
+            }
+            None => Some(msg.clone()),
+        }
+    });
+    conn.call(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.Monitoring",
+        "BecomeMonitor",
+        Some(&(vec![RULE], 0u32).to_variant()),
+        None,
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        |reply| {
+            if let Err(e) = reply { tracing::warn!("Can't watch notifications: {}", e); }
+        },
+    );
+    MONITOR.with(|m| m.replace(Some(conn)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(summary: &str, body: &str) -> Notification {
+        Notification { app: "Firefox".into(), summary: summary.into(), body: body.into() }
+    }
+
+    #[test]
+    fn bubble_text() {
+        assert_eq!(note("New mail", "").text(), "New mail");
+        assert_eq!(note("Alex", "\n<b>See you</b> at 5 &amp; bring snacks\nsecond line").text(), "Alex\nSee you at 5 & bring snacks");
+        assert_eq!(note("", "only a body").text(), "only a body");
+        let long = note("", &"a".repeat(300)).text();
+        assert_eq!(long.chars().count(), MAX_BODY);
+        assert!(long.ends_with('…'));
+    }
+
+    #[test]
+    fn app_filters() {
+        let mut s = NotificationSettings::default();
+        assert!(s.allows("Firefox"));
+        s.ignored_apps = vec!["spotify".into()];
+        assert!(!s.allows("Spotify"));
+        s.only_apps = vec!["Thunderbird".into(), "Signal".into()];
+        assert!(s.allows("signal"));
+        assert!(!s.allows("Firefox"));
+    }
+}
@@ -0,0 +1,257 @@
+use gtk::{cairo, glib, graphene};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+
+use crate::animation;
+
+// --- PARTICLES ---
+// Small glyphs drifting over a chibi: hearts when petted, z's while it sleeps, sparkles
+// when it arrives. ParticleSystem is the simulation; ParticleLayer draws it with cairo
+// and only runs a tick callback while something is on screen (or an ambient kind is set).
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticleKind {
+    Heart,
+    Sparkle,
+    Z,
+}
+
+impl ParticleKind {
+    fn glyph(self) -> &'static str {
+        match self {
+            ParticleKind::Heart => "❤",
+            ParticleKind::Sparkle => "✦",
+            ParticleKind::Z => "z",
+        }
+    }
+
+    fn color(self) -> (f64, f64, f64) {
+        match self {
+            ParticleKind::Heart => (1.0, 0.35, 0.55),
+            ParticleKind::Sparkle => (1.0, 0.9, 0.4),
+            ParticleKind::Z => (0.6, 0.7, 1.0),
+        }
+    }
+}
+
+// Older particles are dropped past this, so a burst storm can't pile up
+const MAX_PARTICLES: usize = 64;
+// Seconds between particles of the ambient kind
+const AMBIENT_INTERVAL: f64 = 0.9;
+
+#[derive(Clone, Debug)]
+pub struct Particle {
+    pub kind: ParticleKind,
+    pub x: f64,
+    pub y: f64,
+    vx: f64,
+    vy: f64,
+    age: f64,
+    life: f64,
+    // Glyph size in px
+    pub size: f64,
+}
+
+impl Particle {
+    // Fades out over its life; sparkles twinkle on the way
+    pub fn alpha(&self) -> f64 {
+        let left = (1.0 - self.age / self.life).clamp(0.0, 1.0);
+        match self.kind {
+            ParticleKind::Sparkle => left * (0.6 + 0.4 * (self.age * 20.0).sin()),
+            _ => left,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+}
+
+impl ParticleSystem {
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    // Adds `count` particles over a `width` x `height` area; `pick(a, b)` returns a value in [a, b)
+    pub fn burst(&mut self, kind: ParticleKind, count: usize, (width, height): (f64, f64), pick: &mut dyn FnMut(f64, f64) -> f64) {
+        for _ in 0..count {
+            let size = width * 0.12;
+            let p = match kind {
+                // Rise from the middle, spreading a little
+                ParticleKind::Heart => Particle {
+                    kind, size,
+                    x: pick(0.3, 0.7) * width, y: pick(0.4, 0.6) * height,
+                    vx: pick(-15.0, 15.0), vy: pick(-60.0, -40.0),
+                    age: 0.0, life: pick(1.5, 2.2),
+                },
+                // Blink in place anywhere on the chibi
+                ParticleKind::Sparkle => Particle {
+                    kind, size: size * pick(0.6, 1.0),
+                    x: pick(0.05, 0.95) * width, y: pick(0.1, 0.95) * height,
+                    vx: 0.0, vy: pick(-10.0, 0.0),
+                    age: 0.0, life: pick(0.6, 1.2),
+                },
+                // Drift up and to the right of the head
+                ParticleKind::Z => Particle {
+                    kind, size: size * 0.8,
+                    x: pick(0.55, 0.65) * width, y: pick(0.25, 0.35) * height,
+                    vx: pick(10.0, 20.0), vy: pick(-30.0, -20.0),
+                    age: 0.0, life: 2.5,
+                },
+            };
+            self.particles.push(p);
+        }
+        let excess = self.particles.len().saturating_sub(MAX_PARTICLES);
+        self.particles.drain(..excess);
+    }
+
+    // Moves everything on by `dt` seconds and drops what has faded out
+    pub fn step(&mut self, dt: f64) {
+        for p in &mut self.particles {
+            p.age += dt;
+            p.x += p.vx * dt;
+            p.y += p.vy * dt;
+            // Z's grow as they float away
+            if p.kind == ParticleKind::Z { p.size *= 1.0 + 0.3 * dt; }
+        }
+        self.particles.retain(|p| p.age < p.life);
+    }
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct ParticleLayer {
+        pub(super) system: RefCell<ParticleSystem>,
+        pub(super) ticking: Cell<bool>,
+        // Emitted on a timer until cleared, with the area to emit over
+        pub(super) ambient: Cell<Option<(ParticleKind, (f64, f64))>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ParticleLayer {
+        const NAME: &'static str = "ChibiParticleLayer";
+        type Type = super::ParticleLayer;
+        type ParentType = gtk::Widget;
+    }
+
+    impl ObjectImpl for ParticleLayer {}
+
+    impl WidgetImpl for ParticleLayer {
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let system = self.system.borrow();
+            if system.is_empty() { return; }
+            let obj = self.obj();
+            let bounds = graphene::Rect::new(0.0, 0.0, obj.width() as f32, obj.height() as f32);
+            let cr = snapshot.append_cairo(&bounds);
+            cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+            for p in system.particles() {
+                let (r, g, b) = p.kind.color();
+                cr.set_source_rgba(r, g, b, p.alpha());
+                cr.set_font_size(p.size);
+                cr.move_to(p.x - p.size / 2.0, p.y + p.size / 2.0);
+                let _ = cr.show_text(p.kind.glyph());
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct ParticleLayer(ObjectSubclass<imp::ParticleLayer>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for ParticleLayer {
+    fn default() -> Self {
+        let layer: Self = glib::Object::new();
+        layer.set_can_target(false);
+        layer
+    }
+}
+
+impl ParticleLayer {
+    // Adds `count` particles over an `area` (width, height) of the layer
+    pub fn emit(&self, kind: ParticleKind, count: usize, area: (f64, f64)) {
+        self.imp().system.borrow_mut().burst(kind, count, area, &mut |a, b| glib::random_double_range(a, b));
+        self.start();
+    }
+
+    // Keeps emitting `kind` every so often, or stops with None
+    pub fn set_ambient(&self, ambient: Option<(ParticleKind, (f64, f64))>) {
+        self.imp().ambient.set(ambient);
+        if ambient.is_some() { self.start(); }
+    }
+
+    fn start(&self) {
+        let imp = self.imp();
+        self.queue_draw();
+        if imp.ticking.replace(true) { return; }
+        let since_ambient = Cell::new(AMBIENT_INTERVAL);
+        animation::add_paced_tick(self, move |layer, dt| {
+            let imp = layer.imp();
+            if let Some((kind, area)) = imp.ambient.get() {
+                since_ambient.set(since_ambient.get() + dt);
+                if since_ambient.get() >= AMBIENT_INTERVAL {
+                    since_ambient.set(0.0);
+                    imp.system.borrow_mut().burst(kind, 1, area, &mut |a, b| glib::random_double_range(a, b));
+                }
+            }
+            imp.system.borrow_mut().step(dt);
+            layer.queue_draw();
+            if imp.system.borrow().is_empty() && imp.ambient.get().is_none() {
+                imp.ticking.set(false);
+                return glib::ControlFlow::Break;
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn middle(a: f64, b: f64) -> f64 { (a + b) / 2.0 }
+
+    #[test]
+    fn particles_fade_out_and_go_away() {
+        let mut s = ParticleSystem::default();
+        s.burst(ParticleKind::Heart, 3, (200.0, 200.0), &mut middle);
+        assert_eq!(s.particles().len(), 3);
+        let y = s.particles()[0].y;
+        s.step(1.0);
+        assert!(s.particles()[0].y < y, "hearts rise");
+        assert!(s.particles()[0].alpha() < 1.0);
+        s.step(2.0);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn bursts_are_capped() {
+        let mut s = ParticleSystem::default();
+        for _ in 0..10 {
+            s.burst(ParticleKind::Sparkle, 10, (100.0, 100.0), &mut middle);
+        }
+        assert_eq!(s.particles().len(), MAX_PARTICLES);
+    }
+
+    #[test]
+    fn particles_start_inside_the_area() {
+        let mut s = ParticleSystem::default();
+        for kind in [ParticleKind::Heart, ParticleKind::Sparkle, ParticleKind::Z] {
+            s.burst(kind, 1, (100.0, 50.0), &mut |a, _| a);
+            s.burst(kind, 1, (100.0, 50.0), &mut |_, b| b);
+        }
+        assert!(s.particles().iter().all(|p| (0.0..=100.0).contains(&p.x) && (0.0..=50.0).contains(&p.y)));
+    }
+}
@@ -0,0 +1,296 @@
+use gtk::{gio, glib};
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::audio::{self, AppSound};
+use crate::window::ChibiWindow;
+
+// --- POMODORO ---
+// Focus/break timer a chibi runs for you: the time left shows in its bubble, it switches
+// between a focus and a break image, and a sound plays at every change. The controls are
+// the "pomodoro" actions on the window (run, skip, stop), which its context menu and the
+// tray both go through.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Pomodoro {
+    pub focus_minutes: u32,
+    pub break_minutes: u32,
+    pub long_break_minutes: u32,
+    // Focus rounds before a long break; 0 never takes one
+    pub long_break_every: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_image: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub break_image: Option<PathBuf>,
+    // Played when a phase ends; None uses the app's Reminder sound
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<PathBuf>,
+    // Starts focusing as soon as the chibi appears
+    pub autostart: bool,
+}
+
+impl Default for Pomodoro {
+    fn default() -> Self {
+        Pomodoro {
+            focus_minutes: 25,
+            break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            focus_image: None,
+            break_image: None,
+            sound: None,
+            autostart: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Stopped,
+    Focus,
+    Break,
+    LongBreak,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timer {
+    pub phase: Phase,
+    // Seconds left in the phase
+    pub remaining: u32,
+    pub paused: bool,
+    // Focus rounds finished since it was started
+    pub rounds: u32,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Timer { phase: Phase::Stopped, remaining: 0, paused: false, rounds: 0 }
+    }
+}
+
+impl Timer {
+    fn enter(&mut self, phase: Phase, p: &Pomodoro) {
+        let minutes = match phase {
+            Phase::Stopped => 0,
+            Phase::Focus => p.focus_minutes,
+            Phase::Break => p.break_minutes,
+            Phase::LongBreak => p.long_break_minutes,
+        };
+        self.phase = phase;
+        self.remaining = minutes.max(1) * 60;
+    }
+
+    // Start when stopped, otherwise pause or resume
+    pub fn run(&mut self, p: &Pomodoro) {
+        if self.phase == Phase::Stopped {
+            *self = Timer::default();
+            self.enter(Phase::Focus, p);
+        } else {
+            self.paused = !self.paused;
+        }
+    }
+
+    // On to the next phase; a finished focus round counts towards the long break
+    pub fn skip(&mut self, p: &Pomodoro) {
+        let next = match self.phase {
+            Phase::Stopped => return,
+            Phase::Focus => {
+                self.rounds += 1;
+                if p.long_break_every > 0 && self.rounds.is_multiple_of(p.long_break_every) { Phase::LongBreak } else { Phase::Break }
+            }
+            Phase::Break | Phase::LongBreak => Phase::Focus,
+        };
+        self.enter(next, p);
+    }
+
+    pub fn stop(&mut self) {
+        *self = Timer::default();
+    }
+
+    // Counts a second off; true when that ended the phase
+    pub fn tick(&mut self, p: &Pomodoro) -> bool {
+        if self.phase == Phase::Stopped || self.paused { return false; }
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining > 0 { return false; }
+        self.skip(p);
+        true
+    }
+
+    // e.g. "Focus 24:59", "Break 04:00 (paused)"
+    pub fn text(&self) -> Option<String> {
+        let label = match self.phase {
+            Phase::Stopped => return None,
+            Phase::Focus => "Focus",
+            Phase::Break => "Break",
+            Phase::LongBreak => "Long break",
+        };
+        let mut text = format!("{} {:02}:{:02}", label, self.remaining / 60, self.remaining % 60);
+        if self.paused { text.push_str(" (paused)"); }
+        Some(text)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Run,
+    Skip,
+    Stop,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Run, Action::Skip, Action::Stop];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Run => "run",
+            Action::Skip => "skip",
+            Action::Stop => "stop",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Run => "Start / Pause",
+            Action::Skip => "Skip to Next",
+            Action::Stop => "Stop",
+        }
+    }
+}
+
+// Runs `action` on the chibi's timer; does nothing for chibis without one
+pub fn activate(win: &ChibiWindow, action: Action) {
+    let _ = win.activate_action(&format!("pomodoro.{}", action.name()), None);
+}
+
+// Section for the chibi's context menu; its items hide on windows without the actions
+pub fn menu() -> gio::Menu {
+    let section = gio::Menu::new();
+    for (label, action) in [("Pomodoro running", "pomodoro.run"), ("Skip to next", "pomodoro.skip"), ("Stop pomodoro", "pomodoro.stop")] {
+        let item = gio::MenuItem::new(Some(label), Some(action));
+        item.set_attribute_value("hidden-when", Some(&"action-missing".to_variant()));
+        section.append_item(&item);
+    }
+    section
+}
+
+fn show_time(win: &ChibiWindow, timer: &Timer) {
+    win.set_bubble(timer.text().as_deref().map(|text| (text, None)));
+}
+
+fn show_phase(win: &ChibiWindow, timer: &Timer, p: &Pomodoro, image: &Path) {
+    let own = match timer.phase {
+        Phase::Stopped => None,
+        Phase::Focus => p.focus_image.as_deref(),
+        Phase::Break | Phase::LongBreak => p.break_image.as_deref(),
+    };
+    win.set_image(own.unwrap_or(image));
+}
+
+fn chime(p: &Pomodoro) {
+    match &p.sound {
+        Some(path) => audio::play(path),
+        None => audio::play_app(AppSound::Reminder),
+    }
+}
+
+// The once-a-second countdown, while there is one
+type Ticker = Rc<RefCell<Option<glib::SourceId>>>;
+
+// Counts down while the timer runs; a stopped or paused one leaves the app idle
+fn tick_while_running(win: &ChibiWindow, timer: &Rc<RefCell<Timer>>, p: &Rc<Pomodoro>, image: &Path, ticker: &Ticker) {
+    if ticker.borrow().is_some() { return; }
+    let (win_weak, timer, p, image, this) = (win.downgrade(), timer.clone(), p.clone(), image.to_path_buf(), ticker.clone());
+    let src = glib::timeout_add_seconds_local(1, move || {
+        let mut t = timer.borrow_mut();
+        let Some(w) = win_weak.upgrade().filter(|_| t.phase != Phase::Stopped && !t.paused) else {
+            this.take();
+            return glib::ControlFlow::Break;
+        };
+        let before = t.phase;
+        if t.tick(&p) {
+            tracing::info!("Pomodoro: {:?} is over, now {:?}", before, t.phase);
+            chime(&p);
+            show_phase(&w, &t, &p, &image);
+        }
+        show_time(&w, &t);
+        glib::ControlFlow::Continue
+    });
+    ticker.replace(Some(src));
+}
+
+// Counts down until the window goes away
+pub fn start(win: &ChibiWindow, pomodoro: &Pomodoro, image: &Path) {
+    let (p, image) = (Rc::new(pomodoro.clone()), image.to_path_buf());
+    let timer = Rc::new(RefCell::new(Timer::default()));
+    let ticker: Ticker = Rc::new(RefCell::new(None));
+    let group = gio::SimpleActionGroup::new();
+
+    // Checked while counting down
+    let run = gio::SimpleAction::new_stateful("run", None, &false.to_variant());
+    for action in Action::ALL {
+        let handle = if action == Action::Run { run.clone() } else { gio::SimpleAction::new(action.name(), None) };
+        let (timer, p, image, run, w_weak, ticker) = (timer.clone(), p.clone(), image.clone(), run.downgrade(), win.downgrade(), ticker.clone());
+        handle.connect_activate(move |_, _| {
+            let Some(w) = w_weak.upgrade() else { return };
+            let mut t = timer.borrow_mut();
+            let before = t.phase;
+            match action {
+                Action::Run => t.run(&p),
+                Action::Skip => t.skip(&p),
+                Action::Stop => t.stop(),
+            }
+            if action == Action::Skip && before != Phase::Stopped { chime(&p); }
+            let running = t.phase != Phase::Stopped && !t.paused;
+            if let Some(run) = run.upgrade() { run.set_state(&running.to_variant()); }
+            if running { tick_while_running(&w, &timer, &p, &image, &ticker); }
+            show_time(&w, &t);
+            if t.phase != before { show_phase(&w, &t, &p, &image); }
+        });
+        group.add_action(&handle);
+    }
+    win.insert_action_group("pomodoro", Some(&group));
+    if p.autostart { activate(win, Action::Run); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_end_in_a_long_break() {
+        let p = Pomodoro { long_break_every: 2, ..Default::default() };
+        let mut t = Timer::default();
+        assert_eq!(t.text(), None);
+        t.run(&p);
+        assert_eq!((t.phase, t.remaining), (Phase::Focus, 25 * 60));
+        t.skip(&p);
+        assert_eq!((t.phase, t.remaining), (Phase::Break, 5 * 60));
+        t.skip(&p);
+        t.skip(&p);
+        assert_eq!((t.phase, t.remaining, t.rounds), (Phase::LongBreak, 15 * 60, 2));
+        t.skip(&p);
+        assert_eq!(t.phase, Phase::Focus);
+        t.stop();
+        assert_eq!(t, Timer::default());
+    }
+
+    #[test]
+    fn ticking_and_pausing() {
+        let p = Pomodoro { focus_minutes: 1, ..Default::default() };
+        let mut t = Timer::default();
+        assert!(!t.tick(&p));
+        t.run(&p);
+        for _ in 0..59 { assert!(!t.tick(&p)); }
+        assert_eq!(t.text().as_deref(), Some("Focus 00:01"));
+        t.run(&p);
+        assert!(!t.tick(&p));
+        assert_eq!(t.text().as_deref(), Some("Focus 00:01 (paused)"));
+        t.run(&p);
+        assert!(t.tick(&p));
+        assert_eq!(t.text().as_deref(), Some("Break 05:00"));
+    }
+}
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::capture::{Dance, MicReaction};
+use crate::gaze::FollowLayer;
+use crate::hooks;
+use crate::interaction::{default_click_ms, ClickReaction, PetReaction};
+use crate::launch::ClickAction;
+use crate::movement::{WalkCycle, WanderArea};
+use crate::mpris::MediaReaction;
+use crate::pomodoro::Pomodoro;
+use crate::quotes::Quotes;
+use crate::schedule::{Chime, Schedule};
+use crate::speech::Speech;
+use crate::sysinfo::{BatteryReaction, SystemMonitor};
+use crate::typing::TypingReaction;
+use crate::weather::WeatherReaction;
+
+// --- PRESETS ---
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChibiPreset {
+    pub id: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub width: i32,
+    pub x: i32,
+    pub y: i32,
+    pub smart_hide: bool,
+    pub always_on_top: bool,
+    // Takes no input; clicks go to the windows below
+    #[serde(default)]
+    pub click_through: bool,
+    // Strolls back and forth within this range of x positions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wander: Option<WanderArea>,
+    // Walks along the bottom edge of the screen instead (takes precedence over wander)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub walk: Option<WalkCycle>,
+    #[serde(default = "default_hover_threshold")]
+    pub hover_threshold_ms: u32,
+    // Seconds a smart-hidden chibi stays hidden
+    #[serde(default = "default_hide_delay")]
+    pub hide_delay: u32,
+    // Workspace names this chibi is shown on; empty means every workspace
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    #[serde(default)]
+    pub idle_action: IdleAction,
+    #[serde(default = "default_idle_minutes")]
+    pub idle_minutes: u32,
+    #[serde(default)]
+    pub sleep_image: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "PetReaction::is_nothing")]
+    pub on_pet: PetReaction,
+    // Shown for a moment when petted with the Happy reaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub happy_image: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "ClickReaction::is_nothing")]
+    pub on_click: ClickReaction,
+    // Shown for `click_ms` with the Swap click reaction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click_image: Option<PathBuf>,
+    #[serde(default = "default_click_ms")]
+    pub click_ms: u32,
+    // Played on a plain click / when the chibi appears
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click_sound: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn_sound: Option<PathBuf>,
+    // Directory of event sounds, see audio.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound_pack: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Speech::is_empty")]
+    pub speech: Speech,
+    // Shown in a bubble now and then, see quotes.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quotes: Option<Quotes>,
+    // Reports CPU/RAM/temperature in the bubble and looks busy under load, see sysinfo.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<SystemMonitor>,
+    // Looks worried on a low battery, sleeps once charged, see sysinfo.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery: Option<BatteryReaction>,
+    // Says the weather and dresses for it, see weather.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<WeatherReaction>,
+    // Focus/break timer shown in the bubble, see pomodoro.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pomodoro: Option<Pomodoro>,
+    // Taps along while you type, see typing.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typing: Option<TypingReaction>,
+    // Sound and/or reaction at cron-like times, see schedule.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chime: Option<Chime>,
+    // A burst of sparkles when it appears
+    #[serde(default)]
+    pub spawn_sparkles: bool,
+    // Drawn over the image and nudged towards the cursor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_layer: Option<FollowLayer>,
+    // PNGTuber mode: reacts to the microphone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mic: Option<MicReaction>,
+    // Bobs or pulses along with whatever is playing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dance: Option<Dance>,
+    // Reacts to media players (MPRIS): dancing while they play, album art in a bubble
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaReaction>,
+    // JSON state machine driving the chibi's images, see behavior.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behavior: Option<PathBuf>,
+    // Saved presets with a schedule are spawned/closed automatically at its boundaries
+    #[serde(default)]
+    pub schedule: Option<Schedule>,
+    // Spawned automatically when the app starts
+    #[serde(default)]
+    pub autostart: bool,
+    // Section in the Saved Presets list; None shows it ungrouped at the top
+    #[serde(default)]
+    pub category: Option<String>,
+    // Position in the Saved Presets list; rewritten from the list order on every save
+    #[serde(default)]
+    pub order: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "hooks::Hooks::is_empty")]
+    pub hooks: hooks::Hooks,
+    #[serde(default, skip_serializing_if = "ClickAction::is_nothing")]
+    pub on_double_click: ClickAction,
+    // Opened on a single click outside move mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+impl Default for ChibiPreset {
+    fn default() -> Self {
+        ChibiPreset {
+            id: Uuid::new_v4().to_string(),
+            name: "New Chibi".into(),
+            path: PathBuf::new(),
+            width: 200,
+            x: 100,
+            y: 100,
+            smart_hide: false,
+            always_on_top: false,
+            click_through: false,
+            wander: None,
+            walk: None,
+            hover_threshold_ms: default_hover_threshold(),
+            hide_delay: default_hide_delay(),
+            workspaces: Vec::new(),
+            idle_action: IdleAction::Off,
+            idle_minutes: default_idle_minutes(),
+            sleep_image: None,
+            on_pet: PetReaction::Nothing,
+            happy_image: None,
+            on_click: ClickReaction::Nothing,
+            click_image: None,
+            click_ms: default_click_ms(),
+            click_sound: None,
+            spawn_sound: None,
+            sound_pack: None,
+            speech: Speech::default(),
+            quotes: None,
+            monitor: None,
+            battery: None,
+            weather: None,
+            pomodoro: None,
+            typing: None,
+            chime: None,
+            spawn_sparkles: false,
+            follow_layer: None,
+            mic: None,
+            dance: None,
+            media: None,
+            behavior: None,
+            schedule: None,
+            autostart: false,
+            category: None,
+            order: 0,
+            tags: Vec::new(),
+            hooks: hooks::Hooks::default(),
+            on_double_click: ClickAction::Nothing,
+            link: None,
+        }
+    }
+}
+
+impl ChibiPreset {
+    // Case-insensitive match against name, category and tags; an empty query matches everything
+    pub fn matches_filter(&self, query: &str) -> bool {
+        let q = query.trim().to_lowercase();
+        if q.is_empty() { return true; }
+        self.name.to_lowercase().contains(&q)
+            || self.category.as_deref().is_some_and(|c| c.to_lowercase().contains(&q))
+            || self.tags.iter().any(|t| t.to_lowercase().contains(&q))
+    }
+
+    // The image was moved, deleted or is on a drive that isn't mounted; spawning would only
+    // make an empty window
+    pub fn image_missing(&self) -> bool {
+        !self.path.is_file()
+    }
+}
+
+// Drag-and-drop in the preset list: the dragged preset takes the target's slot (so dragging down lands
+// below it, dragging up above it) and joins its category. False when either id is unknown or they're the same.
+pub fn move_onto(presets: &mut Vec<ChibiPreset>, id: &str, onto: &str) -> bool {
+    if id == onto { return false; }
+    let (Some(from), Some(to)) = (presets.iter().position(|p| p.id == id), presets.iter().position(|p| p.id == onto)) else {
+        return false;
+    };
+    let mut moved = presets.remove(from);
+    moved.category = presets[if from < to { to - 1 } else { to }].category.clone();
+    presets.insert(to, moved);
+    true
+}
+
+pub fn default_hover_threshold() -> u32 { 300 }
+pub fn default_hide_delay() -> u32 { 3 }
+pub fn default_idle_minutes() -> u32 { 5 }
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleAction {
+    #[default]
+    Off,
+    // Only shown while the user is idle
+    Appear,
+    // Swaps to `sleep_image` while idle
+    Sleep,
+    Hide,
+}
+
+impl IdleAction {
+    pub const ALL: [IdleAction; 4] = [IdleAction::Off, IdleAction::Appear, IdleAction::Sleep, IdleAction::Hide];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            IdleAction::Off => "Do nothing",
+            IdleAction::Appear => "Appear",
+            IdleAction::Sleep => "Sleep",
+            IdleAction::Hide => "Hide",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // presets.json files from before hover/idle/hooks existed only carry the original fields
+    const OLD_PRESET: &str = r#"{
+        "id": "abc", "name": "Desk Cat", "path": "/tmp/cat.png",
+        "width": 150, "x": 10, "y": 20, "smart_hide": true, "always_on_top": false
+    }"#;
+
+    #[test]
+    fn old_presets_get_defaults() {
+        let p: ChibiPreset = serde_json::from_str(OLD_PRESET).unwrap();
+        assert_eq!(p.id, "abc");
+        assert_eq!(p.width, 150);
+        assert_eq!(p.hover_threshold_ms, default_hover_threshold());
+        assert_eq!(p.hide_delay, default_hide_delay());
+        assert_eq!(p.idle_minutes, default_idle_minutes());
+        assert_eq!(p.idle_action, IdleAction::Off);
+        assert!(p.workspaces.is_empty() && p.tags.is_empty());
+        assert!(p.schedule.is_none() && p.category.is_none());
+        assert!(p.hooks.is_empty());
+        assert!(p.on_double_click.is_nothing());
+        assert!(p.on_click.is_nothing());
+        assert!(p.speech.is_empty() && p.chime.is_none());
+        assert_eq!(p.click_ms, default_click_ms());
+        assert!(p.link.is_none());
+        assert!(!p.click_through);
+        assert!(p.wander.is_none() && p.walk.is_none());
+    }
+
+    #[test]
+    fn round_trip_skips_empty_hooks() {
+        let p = ChibiPreset { name: "Mage".into(), tags: vec!["fantasy".into()], idle_action: IdleAction::Sleep, ..Default::default() };
+        let json = serde_json::to_string(&p).unwrap();
+        assert!(!json.contains("hooks"));
+        assert!(!json.contains("on_double_click"));
+        assert!(json.contains("\"idle_action\":\"sleep\""));
+
+        let back: ChibiPreset = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.id, p.id);
+        assert_eq!(back.tags, p.tags);
+        assert_eq!(back.idle_action, IdleAction::Sleep);
+    }
+
+    #[test]
+    fn drop_hook_round_trips() {
+        let mut p = ChibiPreset::default();
+        p.hooks.on_drop = "mv \"$@\" ~/Sorted".into();
+        let back: ChibiPreset = serde_json::from_str(&serde_json::to_string(&p).unwrap()).unwrap();
+        assert_eq!(back.hooks.on_drop, p.hooks.on_drop);
+        assert!(back.hooks.on_click.is_empty());
+    }
+
+    #[test]
+    fn default_ids_are_unique() {
+        let (a, b) = (ChibiPreset::default(), ChibiPreset::default());
+        assert_ne!(a.id, b.id);
+        assert!(Uuid::parse_str(&a.id).is_ok());
+    }
+
+    #[test]
+    fn filter_matches_name_category_and_tags() {
+        let p = ChibiPreset { name: "Desk Cat".into(), category: Some("Animals".into()), tags: vec!["Cozy".into()], ..Default::default() };
+        assert!(p.matches_filter(""));
+        assert!(p.matches_filter("  desk "));
+        assert!(p.matches_filter("ANIMAL"));
+        assert!(p.matches_filter("cozy"));
+        assert!(!p.matches_filter("dog"));
+    }
+
+    #[test]
+    fn dragging_takes_the_target_slot() {
+        let mut presets: Vec<ChibiPreset> = ["a", "b", "c"].iter()
+            .map(|id| ChibiPreset { id: id.to_string(), ..Default::default() })
+            .collect();
+        presets[2].category = Some("Cats".into());
+        let ids = |v: &[ChibiPreset]| v.iter().map(|p| p.id.as_str()).collect::<Vec<_>>().join("");
+        assert!(move_onto(&mut presets, "a", "b"));
+        assert_eq!(ids(&presets), "bac");
+        assert!(move_onto(&mut presets, "a", "c"));
+        assert_eq!(ids(&presets), "bca");
+        assert_eq!(presets[2].category.as_deref(), Some("Cats"));
+        assert!(move_onto(&mut presets, "a", "b"));
+        assert_eq!(ids(&presets), "abc");
+        assert!(!move_onto(&mut presets, "a", "a"));
+        assert!(!move_onto(&mut presets, "a", "gone"));
+        assert_eq!(ids(&presets), "abc");
+    }
+
+    #[test]
+    fn missing_images() {
+        let image = std::env::temp_dir().join(format!("chibi-preset-test-{}.png", std::process::id()));
+        std::fs::write(&image, b"").unwrap();
+        let mut p = ChibiPreset { path: image.clone(), ..Default::default() };
+        assert!(!p.image_missing());
+        std::fs::remove_file(&image).unwrap();
+        assert!(p.image_missing());
+        p.path = std::env::temp_dir();
+        assert!(p.image_missing());
+        assert!(ChibiPreset::default().image_missing());
+    }
+}
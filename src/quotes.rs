@@ -0,0 +1,111 @@
+use gtk::glib;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::report;
+use crate::window::ChibiWindow;
+
+// --- QUOTES ---
+// A preset can point at a text file of quotes, one per line (blank lines and lines starting
+// with # are skipped), or fortune-style with entries between lines holding just `%`.
+// A random one shows in the chibi's bubble every so often and/or when it's clicked.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Quotes {
+    pub file: PathBuf,
+    // Roughly how often one comes up on its own; 0 only on clicks
+    #[serde(default = "default_every")]
+    pub every_minutes: u32,
+    #[serde(default)]
+    pub on_click: bool,
+    // How long it stays up
+    #[serde(default = "default_seconds")]
+    pub seconds: u32,
+}
+
+pub fn default_every() -> u32 { 10 }
+pub fn default_seconds() -> u32 { 6 }
+
+pub fn parse(text: &str) -> Vec<String> {
+    let fortune = text.lines().any(|l| l.trim() == "%");
+    if fortune {
+        text.split('\n')
+            .collect::<Vec<_>>()
+            .split(|l| l.trim() == "%")
+            .map(|entry| entry.join("\n").trim().to_string())
+            .filter(|q| !q.is_empty())
+            .collect()
+    } else {
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<String>, String> {
+    fs::read_to_string(path).map(|t| parse(&t)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Somewhere between half and one and a half times `every_minutes`, so chibis don't chime in together
+fn next_delay(every_minutes: u32, pick: f64) -> Duration {
+    Duration::from_secs_f64(every_minutes as f64 * 60.0 * (0.5 + pick.clamp(0.0, 1.0)))
+}
+
+fn show_one(win: &ChibiWindow, quotes: &[String], seconds: u32) {
+    if quotes.is_empty() || !win.is_visible() { return; }
+    let quote = &quotes[glib::random_int_range(0, quotes.len() as i32) as usize];
+    win.flash_bubble(quote, Duration::from_secs(seconds.max(1) as u64));
+}
+
+fn schedule(win: &ChibiWindow, quotes: Rc<Vec<String>>, every_minutes: u32, seconds: u32) {
+    let win_weak = win.downgrade();
+    glib::timeout_add_local_once(next_delay(every_minutes, glib::random_double()), move || {
+        let Some(w) = win_weak.upgrade() else { return };
+        show_one(&w, &quotes, seconds);
+        schedule(&w, quotes, every_minutes, seconds);
+    });
+}
+
+// Loads the file and starts quoting until the window goes away
+pub fn start(win: &ChibiWindow, config: &Quotes) {
+    let quotes = match load(&config.file) {
+        Ok(quotes) => Rc::new(quotes),
+        Err(e) => return report::error("Could not read the quotes file", e),
+    };
+    if config.every_minutes > 0 {
+        schedule(win, quotes.clone(), config.every_minutes, config.seconds);
+    }
+    if config.on_click {
+        let seconds = config.seconds;
+        win.connect_clicked(move |w| show_one(w, &quotes, seconds));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_one_per_line() {
+        assert_eq!(parse("# my quotes\nHello!\n\n  Stay hydrated  \n"), ["Hello!", "Stay hydrated"]);
+    }
+
+    #[test]
+    fn fortune_files_split_on_percent() {
+        let text = "A long\nquote\n%\nShort\n%\n";
+        assert_eq!(parse(text), ["A long\nquote", "Short"]);
+    }
+
+    #[test]
+    fn delays_spread_around_the_interval() {
+        assert_eq!(next_delay(10, 0.0), Duration::from_secs(300));
+        assert_eq!(next_delay(10, 1.0), Duration::from_secs(900));
+        assert_eq!(next_delay(10, 0.5), Duration::from_secs(600));
+    }
+}
@@ -0,0 +1,297 @@
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::{Label, ListBox, ListBoxRow, SpinButton};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::audio::{self, SoundEvent};
+use crate::control;
+use crate::hooks;
+use crate::interaction;
+use crate::movement::{Body, Crowd, CrowdMode};
+use crate::particles::ParticleKind;
+use crate::config::{Session, SessionChibi};
+use crate::preset::{ChibiPreset, IdleAction};
+use crate::window::ChibiWindow;
+
+// --- ACTIVE CHIBI REGISTRY ---
+
+pub struct ActiveWindowRef {
+    pub preset_id: Option<String>,
+    pub data: ChibiPreset,
+    pub is_new: Rc<Cell<bool>>,
+    pub window: glib::WeakRef<ChibiWindow>,
+    pub list_row: glib::WeakRef<ListBoxRow>,
+    pub name_label: glib::WeakRef<Label>,
+    pub size_spin: glib::WeakRef<SpinButton>,
+    pub idle_triggered: bool,
+    // Stable handle for the tray's per-chibi toggles
+    pub key: u64,
+    // Hidden individually from the tray
+    pub user_hidden: bool,
+}
+
+
+// Hide-All hides everything and the tray toggles hide single chibis; fullscreen hiding only applies to overlay-layer chibis,
+// and workspace-bound chibis only show on their workspaces
+fn held_hidden(r: &ActiveWindowRef, hide_all: bool, fullscreen_hide: bool, workspace: Option<&str>) -> bool {
+    let off_workspace = match workspace {
+        Some(ws) => !r.data.workspaces.is_empty() && !r.data.workspaces.iter().any(|w| w == ws),
+        None => false,
+    };
+    let idle_hidden = match r.data.idle_action {
+        IdleAction::Appear => !r.idle_triggered,
+        IdleAction::Hide => r.idle_triggered,
+        _ => false,
+    };
+    hide_all || r.user_hidden || off_workspace || idle_hidden || (fullscreen_hide && r.data.always_on_top)
+}
+
+// Hands every window the registry's verdict; a smart-hidden chibi stays tucked away either way, so
+// only real changes fire the Show/Hide hooks and sounds. Drops registry entries whose window is gone.
+pub fn apply_visibility(reg: &mut Vec<ActiveWindowRef>, hide_all: bool, fullscreen_hide: bool, workspace: Option<&str>) {
+    reg.retain(|r| {
+        if let Some(w) = r.window.upgrade() {
+            let held = held_hidden(r, hide_all, fullscreen_hide, workspace);
+            let visible = !held && !w.hidden();
+            if visible != w.is_visible() {
+                let event = if visible { hooks::Event::Show } else { hooks::Event::Hide };
+                hooks::fire(event, &r.data.hooks, &hook_info(r));
+                audio::play_for(&r.data, if visible { SoundEvent::Show } else { SoundEvent::Hide });
+            }
+            w.set_held(held);
+            true
+        } else {
+            false
+        }
+    });
+}
+
+impl ActiveWindowRef {
+    // Live (x, y, size), or the spawn values once the window is gone
+    pub fn geometry(&self) -> (i32, i32, i32) {
+        match self.window.upgrade() {
+            Some(w) => (w.x() as i32, w.y() as i32, w.size()),
+            None => (self.data.x, self.data.y, self.data.width),
+        }
+    }
+}
+
+pub fn hook_info(r: &ActiveWindowRef) -> hooks::ChibiInfo {
+    let (x, y, size) = r.geometry();
+    hooks::ChibiInfo {
+        id: r.key,
+        preset_id: r.preset_id.clone().unwrap_or_default(),
+        name: r.name_label.upgrade().map(|l| l.text().to_string()).unwrap_or_else(|| r.data.name.clone()),
+        image: r.data.path.clone(),
+        x,
+        y,
+        size,
+    }
+}
+
+pub fn active_chibis(reg: &[ActiveWindowRef]) -> Vec<control::ActiveChibi> {
+    reg.iter()
+        .filter_map(|r| {
+            let win = r.window.upgrade()?;
+            let name = r.name_label.upgrade().map(|l| l.text().to_string()).unwrap_or_default();
+            Some(control::ActiveChibi {
+                id: r.key,
+                name,
+                visible: win.is_visible(),
+                x: win.x() as i32,
+                y: win.y() as i32,
+                size: win.size(),
+            })
+        })
+        .collect()
+}
+
+// Closes the chibis behind the given Active Session rows
+pub fn close_active_rows(reg: &mut Vec<ActiveWindowRef>, active_list: &ListBox, rows: &[ListBoxRow]) {
+    reg.retain(|entry| {
+        let Some(row) = entry.list_row.upgrade() else { return true };
+        if !rows.contains(&row) { return true; }
+        hooks::fire(hooks::Event::Close, &entry.data.hooks, &hook_info(entry));
+        if let Some(w) = entry.window.upgrade() { w.close(); }
+        active_list.remove(&row);
+        false
+    });
+}
+
+pub fn close_preset_windows(reg: &mut Vec<ActiveWindowRef>, active_list: &ListBox, preset_id: &str) {
+    reg.retain(|entry| {
+        if entry.preset_id.as_deref() != Some(preset_id) { return true; }
+        hooks::fire(hooks::Event::Close, &entry.data.hooks, &hook_info(entry));
+        if let Some(w) = entry.window.upgrade() { w.close(); }
+        if let Some(r) = entry.list_row.upgrade() { active_list.remove(&r); }
+        false
+    });
+}
+
+// Marks chibis whose idle threshold has passed and swaps sleep images in/out
+pub fn update_idle_states(reg: &mut [ActiveWindowRef], idle_since: Option<i64>) {
+    let idle_secs = idle_since.map(|t| (glib::real_time() - t) / 1_000_000).unwrap_or(-1);
+    for r in reg.iter_mut() {
+        if r.data.idle_action == IdleAction::Off { continue; }
+        let triggered = idle_secs >= r.data.idle_minutes as i64 * 60;
+        if triggered == r.idle_triggered { continue; }
+        r.idle_triggered = triggered;
+
+        if r.data.idle_action == IdleAction::Sleep {
+            if let Some(w) = r.window.upgrade() {
+                match (&r.data.sleep_image, triggered) {
+                    (Some(sleep), true) => w.set_image(sleep),
+                    _ => w.set_image(&r.data.path),
+                }
+                w.set_ambient_particles(triggered.then_some(ParticleKind::Z));
+            }
+        }
+    }
+}
+
+// One crowd step over the visible chibis: movers that ran into another chibi are bumped,
+// and with Greet chibis that just met say hello
+pub fn step_crowd(reg: &[ActiveWindowRef], crowd: &mut Crowd, mode: CrowdMode) {
+    let windows: Vec<(u64, ChibiWindow, bool)> = reg.iter()
+        .filter_map(|r| Some((r.key, r.window.upgrade()?, r.data.wander.is_some() || r.data.walk.is_some())))
+        .filter(|(_, w, _)| w.is_visible() && !w.hidden())
+        .collect();
+    let bodies: Vec<Body> = windows.iter()
+        .map(|(key, w, mover)| Body { key: *key, x: w.x(), y: w.placed_y() as f64, size: w.size() as f64, mover: *mover })
+        .collect();
+    let step = crowd.step(&bodies);
+    let window = |key: u64| windows.iter().find(|(k, _, _)| *k == key).map(|(_, w, _)| w);
+    for w in step.bumped.into_iter().filter_map(window) {
+        w.bump();
+    }
+    if mode == CrowdMode::Greet {
+        for w in step.met.into_iter().flat_map(|(a, b)| [a, b]).filter_map(window) {
+            interaction::greet(w);
+        }
+    }
+}
+
+pub fn session_snapshot(reg: &[ActiveWindowRef], hidden: bool) -> Session {
+    let chibis = reg.iter()
+        .filter_map(|r| {
+            let win = r.window.upgrade()?;
+            let mut preset = r.data.clone();
+            if let Some(id) = &r.preset_id { preset.id = id.clone(); }
+            preset.x = win.x() as i32;
+            preset.y = win.y() as i32;
+            preset.width = win.size();
+            preset.smart_hide = win.smart_hide();
+            preset.click_through = win.click_through();
+            Some(SessionChibi { preset, saved: !r.is_new.get() })
+        })
+        .collect();
+    Session { hidden, chibis }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Entry whose window (and list row) have already been destroyed
+    fn closed_entry(key: u64, preset_id: Option<&str>) -> ActiveWindowRef {
+        ActiveWindowRef {
+            preset_id: preset_id.map(str::to_string),
+            data: ChibiPreset { name: format!("Chibi {}", key), x: 7, y: 8, width: 90, ..Default::default() },
+            is_new: Rc::new(Cell::new(preset_id.is_none())),
+            window: glib::WeakRef::new(),
+            list_row: glib::WeakRef::new(),
+            name_label: glib::WeakRef::new(),
+            size_spin: glib::WeakRef::new(),
+            idle_triggered: false,
+            key,
+            user_hidden: false,
+        }
+    }
+
+    #[test]
+    fn closed_windows_are_dropped() {
+        let mut reg = vec![closed_entry(1, Some("a")), closed_entry(2, None)];
+        apply_visibility(&mut reg, false, false, None);
+        assert!(reg.is_empty());
+    }
+
+    #[test]
+    fn hide_reasons() {
+        let mut entry = closed_entry(1, None);
+        assert!(!held_hidden(&entry, false, false, None));
+        assert!(held_hidden(&entry, true, false, None));
+        entry.user_hidden = true;
+        assert!(held_hidden(&entry, false, false, None));
+        entry.user_hidden = false;
+        // Fullscreen only moves chibis from the overlay layer
+        assert!(!held_hidden(&entry, false, true, None));
+        entry.data.always_on_top = true;
+        assert!(held_hidden(&entry, false, true, None));
+    }
+
+    #[test]
+    fn workspace_bound_chibis_hide_elsewhere() {
+        let mut entry = closed_entry(1, None);
+        entry.data.workspaces = vec!["2".into(), "3".into()];
+        assert!(held_hidden(&entry, false, false, Some("1")));
+        assert!(!held_hidden(&entry, false, false, Some("3")));
+        // Unknown workspace or an unbound chibi: nothing to hide for
+        assert!(!held_hidden(&entry, false, false, None));
+        entry.data.workspaces.clear();
+        assert!(!held_hidden(&entry, false, false, Some("1")));
+    }
+
+    #[test]
+    fn idle_actions_hold_chibis_hidden() {
+        let mut entry = closed_entry(1, None);
+        entry.data.idle_action = IdleAction::Appear;
+        assert!(held_hidden(&entry, false, false, None));
+        entry.idle_triggered = true;
+        assert!(!held_hidden(&entry, false, false, None));
+        entry.data.idle_action = IdleAction::Hide;
+        assert!(held_hidden(&entry, false, false, None));
+        entry.idle_triggered = false;
+        assert!(!held_hidden(&entry, false, false, None));
+        // Sleep swaps the image but leaves the chibi showing
+        entry.data.idle_action = IdleAction::Sleep;
+        entry.idle_triggered = true;
+        assert!(!held_hidden(&entry, false, false, None));
+    }
+
+    #[test]
+    fn closed_windows_are_left_out_of_snapshots() {
+        let reg = vec![closed_entry(1, Some("a"))];
+        assert!(active_chibis(&reg).is_empty());
+        let session = session_snapshot(&reg, true);
+        assert!(session.hidden);
+        assert!(session.chibis.is_empty());
+    }
+
+    #[test]
+    fn hook_info_falls_back_to_spawn_values() {
+        let entry = closed_entry(4, Some("preset-4"));
+        assert_eq!(entry.geometry(), (7, 8, 90));
+        let info = hook_info(&entry);
+        assert_eq!(info.id, 4);
+        assert_eq!(info.preset_id, "preset-4");
+        assert_eq!(info.name, "Chibi 4");
+        assert_eq!(hook_info(&closed_entry(5, None)).preset_id, "");
+    }
+
+    #[test]
+    fn idle_state_tracks_the_threshold() {
+        let mut reg = vec![closed_entry(1, None), closed_entry(2, None)];
+        reg[0].data.idle_action = IdleAction::Appear;
+        reg[0].data.idle_minutes = 1;
+        let two_minutes_ago = glib::real_time() - 120 * 1_000_000;
+
+        update_idle_states(&mut reg, Some(two_minutes_ago));
+        assert!(reg[0].idle_triggered);
+        // Off never triggers
+        assert!(!reg[1].idle_triggered);
+
+        update_idle_states(&mut reg, None);
+        assert!(!reg[0].idle_triggered);
+    }
+}
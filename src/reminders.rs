@@ -0,0 +1,226 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config;
+
+// --- REMINDERS ---
+// One-shot timers, set from a chibi's context menu or the command line ("remind me in 20m to
+// stretch"). When one comes due a chibi jumps and says it in its bubble. Pending reminders are
+// kept in reminders.json, so they survive a restart; any that came due while the app was
+// closed go off right after it starts. Between reminders nothing runs: a single timeout waits
+// for the next one due.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Reminder {
+    // Unix time, in seconds
+    pub due: i64,
+    pub text: String,
+    // Preset whose chibi should say it; any chibi on screen does when it isn't out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+}
+
+impl Reminder {
+    pub fn in_seconds(seconds: u64, text: &str, preset: Option<String>) -> Reminder {
+        let seconds = i64::try_from(seconds).unwrap_or(i64::MAX);
+        Reminder { due: now().saturating_add(seconds), text: text.to_string(), preset }
+    }
+}
+
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+fn unit_seconds(unit: &str) -> Option<u64> {
+    match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(3600),
+        _ => None,
+    }
+}
+
+// Anything further out is a typo (or someone poking at the socket), not a reminder
+pub const MAX_SECONDS: u64 = 365 * 24 * 3600;
+const TOO_FAR: &str = "that's more than a year away";
+
+// "20m", "1h30m", "90s"; a bare number is minutes unless `unit` gives one.
+// None when the word isn't a time, Err when it's one too big to count.
+fn word_seconds(word: &str, unit: Option<&str>) -> Result<Option<u64>, String> {
+    let mut total: u64 = 0;
+    let mut rest = word;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 { return Ok(None); }
+        let n: u64 = rest[..digits].parse().map_err(|_| TOO_FAR)?;
+        rest = &rest[digits..];
+        let letters = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let scale = match &rest[..letters] {
+            "" => unit.map_or(Some(60), unit_seconds),
+            u => unit_seconds(u),
+        };
+        let Some(scale) = scale else { return Ok(None) };
+        total = n.checked_mul(scale).and_then(|s| total.checked_add(s)).ok_or(TOO_FAR)?;
+        rest = &rest[letters..];
+    }
+    Ok(Some(total))
+}
+
+// "[remind] [me] [in] <time> [to] <what>" into (seconds, what).
+// Times are words like 20m, 1h30m, or "2 hours 10 minutes".
+pub fn parse(text: &str) -> Result<(u64, String), String> {
+    let words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+    let lower: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+    let mut i = 0;
+    for skip in ["remind", "me", "in"] {
+        if lower.get(i).is_some_and(|w| w == skip) { i += 1; }
+    }
+    let mut seconds = 0;
+    while let Some(word) = lower.get(i) {
+        let unit = lower.get(i + 1).filter(|u| unit_seconds(u).is_some());
+        match word_seconds(word, unit.map(String::as_str))? {
+            Some(s) if word.starts_with(|c: char| c.is_ascii_digit()) => {
+                seconds = s.checked_add(seconds).ok_or(TOO_FAR)?;
+                i += if unit.is_some() && word.chars().all(|c| c.is_ascii_digit()) { 2 } else { 1 };
+            }
+            _ if word == "and" && seconds > 0 => i += 1,
+            _ => break,
+        }
+    }
+    if seconds == 0 { return Err("when? e.g. \"in 20m to stretch\"".into()); }
+    if seconds > MAX_SECONDS { return Err(TOO_FAR.into()); }
+    if lower.get(i).is_some_and(|w| w == "to") { i += 1; }
+    let what = words[i..].join(" ");
+    Ok((seconds, if what.is_empty() { "Time's up!".into() } else { what }))
+}
+
+// e.g. "1h 30m", "45s"
+pub fn describe_in(seconds: u64) -> String {
+    let (h, m, s) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    let parts: Vec<String> = [(h, "h"), (m, "m"), (s, "s")].iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect();
+    if parts.is_empty() { "0s".into() } else { parts.join(" ") }
+}
+
+type Fire = Box<dyn Fn(&Reminder) -> bool>;
+
+thread_local! {
+    static PENDING: RefCell<Vec<Reminder>> = const { RefCell::new(Vec::new()) };
+    static FIRE: RefCell<Option<Fire>> = const { RefCell::new(None) };
+    // Waiting for the soonest reminder
+    static TIMER: RefCell<Option<glib::SourceId>> = const { RefCell::new(None) };
+}
+
+// Due ones that no chibi could say are tried again this often
+const RETRY_SECONDS: i64 = 1;
+// The timeout's clock stops while the machine sleeps, so long waits are checked against the
+// wall clock again every so often
+const MAX_WAIT: i64 = 60;
+
+// Seconds until the next check, if anything is pending
+fn next_check(pending: &[Reminder], now: i64) -> Option<i64> {
+    pending.iter().map(|r| (r.due - now).clamp(0, MAX_WAIT)).min()
+}
+
+// Sets the timeout for the soonest pending reminder, replacing the one before
+fn schedule() {
+    if let Some(src) = TIMER.with(|t| t.take()) { src.remove(); }
+    let Some(wait) = PENDING.with(|p| next_check(&p.borrow(), now())) else { return };
+    let src = glib::timeout_add_local_once(Duration::from_secs(wait as u64), || {
+        TIMER.with(|t| t.take());
+        check();
+    });
+    TIMER.with(|t| t.replace(Some(src)));
+}
+
+pub fn add(reminder: Reminder) {
+    tracing::info!("Reminder in {}: {}", describe_in((reminder.due - now()).max(0) as u64), reminder.text);
+    PENDING.with(|p| {
+        let mut pending = p.borrow_mut();
+        pending.push(reminder);
+        config::save_reminders(&pending);
+    });
+    if FIRE.with(|f| f.borrow().is_some()) { schedule(); }
+}
+
+// Goes off with the reminders that are due
+fn check() {
+    let now = now();
+    let due: Vec<Reminder> = PENDING.with(|p| {
+        let mut pending = p.borrow_mut();
+        let (due, later) = pending.drain(..).partition(|r| r.due <= now);
+        *pending = later;
+        due
+    });
+    let count = due.len();
+    let missed: Vec<Reminder> = FIRE.with(|f| {
+        let fire = f.borrow();
+        let Some(fire) = fire.as_ref() else { return due };
+        due.into_iter().filter(|r| !fire(r)).collect()
+    });
+    let fired = missed.len() < count;
+    PENDING.with(|p| {
+        let mut pending = p.borrow_mut();
+        // Not due again until the retry, so the timeout doesn't come straight back
+        pending.extend(missed.into_iter().map(|r| Reminder { due: now + RETRY_SECONDS, ..r }));
+        if fired { config::save_reminders(&pending); }
+    });
+    schedule();
+}
+
+// Loads the saved reminders and waits for them from now on; `fire` shows one and returns
+// false if no chibi could, which leaves it pending for another try
+pub fn start(fire: impl Fn(&Reminder) -> bool + 'static) {
+    PENDING.with(|p| *p.borrow_mut() = config::load_reminders());
+    FIRE.with(|f| f.replace(Some(Box::new(fire))));
+    schedule();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reminder_phrases() {
+        assert_eq!(parse("remind me in 20m to stretch"), Ok((1200, "stretch".into())));
+        assert_eq!(parse("in 1h30m to Call Mum"), Ok((5400, "Call Mum".into())));
+        assert_eq!(parse("2 hours and 10 minutes take the bread out"), Ok((7800, "take the bread out".into())));
+        assert_eq!(parse("5 drink water"), Ok((300, "drink water".into())));
+        assert_eq!(parse("90s"), Ok((90, "Time's up!".into())));
+        assert!(parse("remind me to stretch").is_err());
+        assert!(parse("in 20x to stretch").is_err());
+    }
+
+    #[test]
+    fn huge_times_are_refused() {
+        assert_eq!(parse("in 99999999999999999h to x"), Err(TOO_FAR.into()));
+        assert_eq!(parse("in 9223372036854775807s to x"), Err(TOO_FAR.into()));
+        assert_eq!(parse("in 99999999999999999999999s to x"), Err(TOO_FAR.into()));
+        assert_eq!(parse("in 8785h"), Err(TOO_FAR.into()));
+        assert_eq!(parse("in 8760h"), Ok((MAX_SECONDS, "Time's up!".into())));
+        let far = Reminder::in_seconds(u64::MAX, "x", None);
+        assert_eq!(far.due, i64::MAX);
+    }
+
+    #[test]
+    fn waits_for_the_soonest() {
+        let at = |due| Reminder { due, text: String::new(), preset: None };
+        assert_eq!(next_check(&[], 100), None);
+        assert_eq!(next_check(&[at(130), at(110)], 100), Some(10));
+        assert_eq!(next_check(&[at(50)], 100), Some(0));
+        assert_eq!(next_check(&[at(100_000)], 100), Some(MAX_WAIT));
+    }
+
+    #[test]
+    fn durations_read_back() {
+        assert_eq!(describe_in(5400), "1h 30m");
+        assert_eq!(describe_in(45), "45s");
+        assert_eq!(describe_in(3600), "1h");
+        let (seconds, _) = parse(&format!("in {}s to x", 1234)).unwrap();
+        assert_eq!(seconds, 1234);
+    }
+}
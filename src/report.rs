@@ -0,0 +1,134 @@
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::rc::Rc;
+use tracing_subscriber::EnvFilter;
+
+use crate::audio::{self, AppSound};
+
+// --- ERROR REPORTING ---
+// Problems the user should know about (saves failing, unreadable config, missing images) are
+// logged through `tracing` and also shown in a dismissable banner at the top of the manager.
+// Reports made before the banner exists, e.g. while loading the config, wait for it.
+
+const BANNER_SECONDS: u32 = 10;
+
+type Sink = Box<dyn Fn(&str, &str)>;
+
+thread_local! {
+    static SINK: RefCell<Option<Sink>> = const { RefCell::new(None) };
+    static PENDING: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Logs to stderr; CHIBI_LOG takes the usual filter syntax, e.g. CHIBI_LOG=debug
+pub fn init_logging() {
+    let filter = EnvFilter::try_from_env("CHIBI_LOG").unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr).init();
+}
+
+// Logs and, on the main thread, shows `summary` with `detail` underneath
+pub fn error(summary: &str, detail: impl Display) {
+    let detail = detail.to_string();
+    tracing::error!("{}: {}", summary, detail);
+    audio::play_app(AppSound::Error);
+    show(summary, detail);
+}
+
+// Same banner for things that went as asked but not as hoped (a limit was reached), without the error sound
+pub fn notice(summary: &str, detail: impl Display) {
+    let detail = detail.to_string();
+    tracing::warn!("{}: {}", summary, detail);
+    show(summary, detail);
+}
+
+fn show(summary: &str, detail: String) {
+    let shown = SINK.with(|s| match s.borrow().as_ref() {
+        Some(sink) => { sink(summary, &detail); true }
+        None => false,
+    });
+    if !shown {
+        PENDING.with(|p| p.borrow_mut().push((summary.to_string(), detail)));
+    }
+}
+
+// Banner for the top of the manager window; showing it again replaces the previous message
+pub fn banner() -> gtk::Revealer {
+    let summary = gtk::Label::new(None);
+    summary.add_css_class("heading");
+    summary.set_xalign(0.0);
+    let detail = gtk::Label::new(None);
+    detail.set_xalign(0.0);
+    detail.set_wrap(true);
+    detail.set_selectable(true);
+    let text = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    text.set_hexpand(true);
+    text.append(&summary);
+    text.append(&detail);
+
+    let dismiss = gtk::Button::from_icon_name("window-close-symbolic");
+    dismiss.add_css_class("flat");
+    dismiss.set_valign(gtk::Align::Start);
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+    row.add_css_class("error-banner");
+    row.append(&gtk::Label::new(Some("⚠️")));
+    row.append(&text);
+    row.append(&dismiss);
+
+    let revealer = gtk::Revealer::new();
+    revealer.set_child(Some(&row));
+    let r_dismiss = revealer.clone();
+    dismiss.connect_clicked(move |_| r_dismiss.set_reveal_child(false));
+
+    let r_sink = revealer.downgrade();
+    let timer: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+    let sink: Sink = Box::new(move |s: &str, d: &str| {
+        let Some(revealer) = r_sink.upgrade() else { return };
+        summary.set_text(s);
+        detail.set_text(d);
+        revealer.set_reveal_child(true);
+        // Restart the auto-dismiss countdown for the newest message
+        if let Some(src) = timer.borrow_mut().take() { src.remove(); }
+        let r_tmr = revealer.downgrade();
+        let timer_tmr = timer.clone();
+        *timer.borrow_mut() = Some(glib::timeout_add_seconds_local(BANNER_SECONDS, move || {
+            timer_tmr.borrow_mut().take();
+            if let Some(r) = r_tmr.upgrade() { r.set_reveal_child(false); }
+            glib::ControlFlow::Break
+        }));
+    });
+    let pending = PENDING.with(|p| std::mem::take(&mut *p.borrow_mut()));
+    for (s, d) in &pending {
+        sink(s, d);
+    }
+    SINK.with(|s| *s.borrow_mut() = Some(sink));
+    revealer
+}
+
+// Path picked in a FileDialog; dismissing the dialog is not an error worth reporting
+pub fn chosen_path(res: Result<gio::File, glib::Error>) -> Option<PathBuf> {
+    match res {
+        Ok(file) => file.path(),
+        Err(e) => {
+            if !e.matches(gtk::DialogError::Dismissed) && !e.matches(gtk::DialogError::Cancelled) {
+                error("File chooser failed", &e);
+            }
+            None
+        }
+    }
+}
+
+// Same for dialogs that pick several files at once
+pub fn chosen_paths(res: Result<gio::ListModel, glib::Error>) -> Vec<PathBuf> {
+    match res {
+        Ok(files) => files.iter::<gio::File>().filter_map(|f| f.ok()?.path()).collect(),
+        Err(e) => {
+            if !e.matches(gtk::DialogError::Dismissed) && !e.matches(gtk::DialogError::Cancelled) {
+                error("File chooser failed", &e);
+            }
+            Vec::new()
+        }
+    }
+}
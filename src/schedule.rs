@@ -0,0 +1,251 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::interaction::ClickReaction;
+
+// --- VISIBILITY SCHEDULE ---
+// A daily time window, optionally limited to some weekdays.
+// Text form: "Mon-Fri 09:00-18:00", "Sat,Sun 10:00-22:00" or just "22:00-06:00".
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Schedule {
+    // ISO weekdays (1 = Monday … 7 = Sunday); empty means every day
+    #[serde(default)]
+    pub days: Vec<u32>,
+    pub start: String,
+    pub end: String,
+}
+
+fn parse_time(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+fn parse_day(s: &str) -> Option<u32> {
+    let s = s.trim().to_lowercase();
+    DAY_NAMES.iter().position(|d| s.starts_with(d)).map(|i| i as u32 + 1)
+}
+
+impl Schedule {
+    pub fn parse(text: &str) -> Option<Schedule> {
+        let text = text.trim();
+        let (days_part, times_part) = match text.rsplit_once(' ') {
+            Some((d, t)) => (Some(d), t),
+            None => (None, text),
+        };
+
+        let (start, end) = times_part.split_once('-')?;
+        parse_time(start)?;
+        parse_time(end)?;
+
+        let mut days = Vec::new();
+        if let Some(days_part) = days_part {
+            for chunk in days_part.split(',') {
+                match chunk.split_once('-') {
+                    Some((a, b)) => {
+                        let (a, b) = (parse_day(a)?, parse_day(b)?);
+                        let mut d = a;
+                        loop {
+                            days.push(d);
+                            if d == b { break; }
+                            d = d % 7 + 1;
+                        }
+                    }
+                    None => days.push(parse_day(chunk)?),
+                }
+            }
+            days.sort_unstable();
+            days.dedup();
+        }
+
+        Some(Schedule { days, start: start.trim().to_string(), end: end.trim().to_string() })
+    }
+
+    pub fn contains(&self, now: &glib::DateTime) -> bool {
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        let minute = now.hour() as u32 * 60 + now.minute() as u32;
+        let day = now.day_of_week() as u32;
+        let day_ok = |d: u32| self.days.is_empty() || self.days.contains(&d);
+
+        if start <= end {
+            day_ok(day) && minute >= start && minute < end
+        } else {
+            // Overnight window: the part after midnight belongs to the previous day
+            let yesterday = if day == 1 { 7 } else { day - 1 };
+            (day_ok(day) && minute >= start) || (day_ok(yesterday) && minute < end)
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.days.is_empty() {
+            let names: Vec<String> = self.days.iter()
+                .filter_map(|d| DAY_NAMES.get(d.checked_sub(1)? as usize))
+                .map(|n| n[..1].to_uppercase() + &n[1..])
+                .collect();
+            write!(f, "{} ", names.join(","))?;
+        }
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// --- CHIMES ---
+// Cron-like times for a preset's chime (a cuckoo clock, basically): "minute hour day month
+// weekday", each field `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a comma
+// list of those. Weekdays run 1 (Mon) to 7 (Sun), 0 is Sunday too, and take names.
+// "hourly" is short for "0 * * * *".
+
+// (min, max) of each field
+const CRON_FIELDS: [(u32, u32); 5] = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 7)];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    text: String,
+    // One bit per allowed value, per field
+    fields: [u64; 5],
+}
+
+fn parse_cron_value(s: &str, field: usize) -> Option<u32> {
+    if field == 4 {
+        if let Some(day) = parse_day(s) { return Some(day); }
+    }
+    s.parse().ok()
+}
+
+fn parse_cron_field(s: &str, field: usize) -> Option<u64> {
+    let (min, max) = CRON_FIELDS[field];
+    let mut bits = 0;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, n)) => (r, n.parse::<u32>().ok().filter(|n| *n > 0)?),
+            None => (part, 1),
+        };
+        let (a, b) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (parse_cron_value(a, field)?, parse_cron_value(b, field)?),
+                None => {
+                    let a = parse_cron_value(range, field)?;
+                    // "5/15" runs from 5 to the end
+                    (a, if step > 1 { max } else { a })
+                }
+            },
+        };
+        if a < min || b > max || a > b { return None; }
+        for v in (a..=b).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    // Sunday is both 0 and 7
+    if field == 4 && bits & (1 | 1 << 7) != 0 { bits |= 1 | 1 << 7; }
+    Some(bits)
+}
+
+impl Cron {
+    pub fn parse(text: &str) -> Option<Cron> {
+        let text = text.trim();
+        let spec = if text.eq_ignore_ascii_case("hourly") { "0 * * * *" } else { text };
+        let parts: Vec<&str> = spec.split_whitespace().collect();
+        if parts.len() != 5 { return None; }
+        let mut fields = [0; 5];
+        for (i, part) in parts.iter().enumerate() {
+            fields[i] = parse_cron_field(&part.to_lowercase(), i)?;
+        }
+        Some(Cron { text: text.to_string(), fields })
+    }
+
+    // `weekday` is ISO (1 = Monday … 7 = Sunday); both day fields have to match
+    pub fn matches(&self, (minute, hour, day, month, weekday): (u32, u32, u32, u32, u32)) -> bool {
+        [minute, hour, day, month, weekday].iter().zip(self.fields).all(|(v, bits)| *v < 64 && bits & (1 << v) != 0)
+    }
+
+    pub fn matches_time(&self, now: &glib::DateTime) -> bool {
+        self.matches((now.minute() as u32, now.hour() as u32, now.day_of_month() as u32, now.month() as u32, now.day_of_week() as u32))
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Cron::parse(&text).ok_or_else(|| format!("invalid chime time \"{}\"", text))
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> String {
+        cron.text
+    }
+}
+
+impl std::fmt::Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+// Several chime times in one text, split on `|`
+pub fn parse_crons(text: &str) -> Option<Vec<Cron>> {
+    text.split('|').map(str::trim).filter(|t| !t.is_empty()).map(Cron::parse).collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Chime {
+    pub at: Vec<Cron>,
+    // None plays the sound pack's hourly sound, if there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "ClickReaction::is_nothing")]
+    pub reaction: ClickReaction,
+}
+
+impl Chime {
+    pub fn due(&self, now: &glib::DateTime) -> bool {
+        self.at.iter().any(|c| c.matches_time(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_fields() {
+        let hourly = Cron::parse("hourly").unwrap();
+        assert!(hourly.matches((0, 13, 5, 6, 3)));
+        assert!(!hourly.matches((1, 13, 5, 6, 3)));
+        let work = Cron::parse("*/15 9-17 * * mon-fri").unwrap();
+        assert!(work.matches((45, 17, 1, 1, 5)));
+        assert!(!work.matches((50, 12, 1, 1, 1)));
+        assert!(!work.matches((0, 12, 1, 1, 6)));
+        let sunday = Cron::parse("30 8 * * 0").unwrap();
+        assert!(sunday.matches((30, 8, 1, 1, 7)));
+        assert!(Cron::parse("0 0 1,15 12 *").unwrap().matches((0, 0, 15, 12, 2)));
+    }
+
+    #[test]
+    fn bad_crons_are_rejected() {
+        for text in ["", "0 * * *", "60 * * * *", "* 5-2 * * *", "*/0 * * * *", "x * * * *", "0 0 0 * *"] {
+            assert!(Cron::parse(text).is_none(), "{}", text);
+        }
+        assert_eq!(parse_crons("hourly | 30 12 * * *").map(|c| c.len()), Some(2));
+        assert!(parse_crons("hourly | nope").is_none());
+    }
+
+    #[test]
+    fn chimes_round_trip_as_text() {
+        let chime = Chime { at: parse_crons("hourly").unwrap(), sound: None, reaction: ClickReaction::Jump };
+        let json = serde_json::to_string(&chime).unwrap();
+        assert_eq!(json, r#"{"at":["hourly"],"reaction":"jump"}"#);
+        assert_eq!(serde_json::from_str::<Chime>(&json).unwrap(), chime);
+        assert!(serde_json::from_str::<Chime>(r#"{"at":["bogus"]}"#).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::io::BufReader;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+// --- SCREENCAST DETECTION ---
+// Portal screencasts (xdg-desktop-portal-wlr/-hyprland/-gnome/-kde) publish a PipeWire
+// "Video/Source" node for as long as the share runs. Cameras are Video/Source too, but
+// carry a device.api property, so those are ignored. `pw-dump --monitor` streams the graph's
+// changes while the setting is on; while it's off nothing runs at all.
+
+// Before starting pw-dump again after it quit by itself (PipeWire restarting, say)
+const RETRY_DELAY: Duration = Duration::from_secs(3);
+
+// Folds one batch of pw-dump objects into the ids of the screencast nodes seen so far
+fn track(sources: &mut HashSet<u64>, objects: &serde_json::Value) {
+    for obj in objects.as_array().into_iter().flatten() {
+        let Some(id) = obj["id"].as_u64() else { continue };
+        let props = &obj["info"]["props"];
+        if obj["info"].is_null() {
+            // Removed from the graph
+            sources.remove(&id);
+        } else if props["media.class"].as_str() == Some("Video/Source") && props["device.api"].is_null() {
+            sources.insert(id);
+        } else if props.is_object() {
+            sources.remove(&id);
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    enabled: bool,
+    // pw-dump exited on its own
+    ended: bool,
+}
+
+// The "Hide while screencasting" setting, as the watcher thread sees it
+#[derive(Clone, Default)]
+pub struct Switch(Arc<(Mutex<State>, Condvar)>);
+
+impl Switch {
+    pub fn new(enabled: bool) -> Switch {
+        let switch = Switch::default();
+        switch.set(enabled);
+        switch
+    }
+
+    pub fn set(&self, enabled: bool) {
+        let (state, changed) = &*self.0;
+        state.lock().unwrap().enabled = enabled;
+        changed.notify_all();
+    }
+
+    fn end_stream(&self) {
+        let (state, changed) = &*self.0;
+        state.lock().unwrap().ended = true;
+        changed.notify_all();
+    }
+
+    // Blocks until the setting is on
+    fn wait_enabled(&self) {
+        let (state, changed) = &*self.0;
+        let mut state = changed.wait_while(state.lock().unwrap(), |s| !s.enabled).unwrap();
+        state.ended = false;
+    }
+
+    // Blocks until the setting goes off (true) or the stream ends first (false)
+    fn wait_disabled(&self) -> bool {
+        let (state, changed) = &*self.0;
+        let state = changed.wait_while(state.lock().unwrap(), |s| s.enabled && !s.ended).unwrap();
+        !state.enabled
+    }
+}
+
+// Watches in background threads while `switch` is on, calling `on_change` on transitions
+pub fn spawn_watcher<F>(switch: Switch, on_change: F)
+where
+    F: Fn(bool) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut notify = on_change;
+        loop {
+            switch.wait_enabled();
+            let child = Command::new("pw-dump").args(["--monitor", "--no-colors"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    tracing::warn!("Can't run pw-dump, so screencasts won't hide chibis: {}", e);
+                    switch.wait_disabled();
+                    continue;
+                }
+            };
+            let stdout = child.stdout.take().expect("stdout is piped");
+            let switch_reader = switch.clone();
+            // Reports while the stream runs and hands the callback back when it ends
+            let reader = std::thread::spawn(move || {
+                let mut sources = HashSet::new();
+                let mut last = false;
+                let stream = serde_json::Deserializer::from_reader(BufReader::new(stdout)).into_iter::<serde_json::Value>();
+                for objects in stream {
+                    let Ok(objects) = objects else { break };
+                    track(&mut sources, &objects);
+                    if sources.is_empty() == last {
+                        last = !last;
+                        notify(last);
+                    }
+                }
+                if last { notify(false); }
+                switch_reader.end_stream();
+                notify
+            });
+            let disabled = switch.wait_disabled();
+            let _ = child.kill();
+            let _ = child.wait();
+            notify = match reader.join() {
+                Ok(notify) => notify,
+                Err(_) => return,
+            };
+            if !disabled { std::thread::sleep(RETRY_DELAY); }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn follows_screencast_nodes() {
+        let mut sources = HashSet::new();
+        let node = |id: u64, props| json!({ "id": id, "type": "PipeWire:Interface:Node", "info": { "props": props } });
+        track(&mut sources, &json!([
+            node(40, json!({ "media.class": "Video/Source", "device.api": "v4l2" })),
+            node(41, json!({ "media.class": "Audio/Sink" })),
+            json!({ "id": 0, "type": "PipeWire:Interface:Core", "info": { "name": "pipewire-0" } }),
+        ]));
+        assert!(sources.is_empty());
+        track(&mut sources, &json!([node(52, json!({ "media.class": "Video/Source" }))]));
+        assert_eq!(sources, HashSet::from([52]));
+        track(&mut sources, &json!([{ "id": 52, "info": null }]));
+        assert!(sources.is_empty());
+    }
+}
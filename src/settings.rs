@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::animation::ImageQuality;
+use crate::audio::AppSound;
+use crate::hooks;
+use crate::movement::CrowdMode;
+use crate::notifications::NotificationSettings;
+use crate::preset::{default_hide_delay, default_hover_threshold, ChibiPreset};
+use crate::weather::WeatherSettings;
+
+// --- SETTINGS ---
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    #[default]
+    Ask,
+    Always,
+    Never,
+}
+
+impl RestoreMode {
+    pub const ALL: [RestoreMode; 3] = [RestoreMode::Ask, RestoreMode::Always, RestoreMode::Never];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RestoreMode::Ask => "Ask",
+            RestoreMode::Always => "Always",
+            RestoreMode::Never => "Never",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAction {
+    Nothing,
+    OpenManager,
+    ToggleHide,
+    SpawnFavorite,
+    Quit,
+}
+
+impl TrayAction {
+    pub const ALL: [TrayAction; 5] = [TrayAction::Nothing, TrayAction::OpenManager, TrayAction::ToggleHide, TrayAction::SpawnFavorite, TrayAction::Quit];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TrayAction::Nothing => "Do nothing",
+            TrayAction::OpenManager => "Open manager",
+            TrayAction::ToggleHide => "Toggle Hide All",
+            TrayAction::SpawnFavorite => "Spawn favorite",
+            TrayAction::Quit => "Quit",
+        }
+    }
+}
+
+// Middle click on a chibi window
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChibiClick {
+    Nothing,
+    ToggleMove,
+    ToggleSmartHide,
+    Hide,
+}
+
+impl ChibiClick {
+    pub const ALL: [ChibiClick; 4] = [ChibiClick::Nothing, ChibiClick::ToggleMove, ChibiClick::ToggleSmartHide, ChibiClick::Hide];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChibiClick::Nothing => "Do nothing",
+            ChibiClick::ToggleMove => "Toggle move mode",
+            ChibiClick::ToggleSmartHide => "Toggle smart hide",
+            ChibiClick::Hide => "Hide it",
+        }
+    }
+}
+
+// A spawn with as many chibis out as the limit allows
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WhenFull {
+    // Nothing appears; the manager says why
+    #[default]
+    Refuse,
+    // The chibi that has been out longest makes room
+    ReplaceOldest,
+}
+
+impl WhenFull {
+    pub const ALL: [WhenFull; 2] = [WhenFull::Refuse, WhenFull::ReplaceOldest];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WhenFull::Refuse => "Don't spawn it",
+            WhenFull::ReplaceOldest => "Close the oldest",
+        }
+    }
+}
+
+pub const RENDERERS: [&str; 5] = ["auto", "gl", "ngl", "vulkan", "cairo"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AppSettings {
+    pub hide_all: bool,
+    pub hide_on_fullscreen: bool,
+    pub hide_while_screencasting: bool,
+    pub collapsed_categories: Vec<String>,
+
+    // Spawn panel defaults
+    pub default_width: i32,
+    pub default_x: i32,
+    pub default_y: i32,
+    pub default_smart_hide: bool,
+    pub default_hover_ms: u32,
+    pub default_hide_delay: u32,
+    pub default_on_top: bool,
+
+    // Startup behavior
+    pub spawn_autostart: bool,
+    pub start_minimized: bool,
+    pub restore_session: RestoreMode,
+    // GSK renderer name, or "auto" to leave GTK's choice alone
+    pub renderer: String,
+    // How images bigger than their chibi are decoded (see animation.rs)
+    pub image_quality: ImageQuality,
+    // Cap on how often animations draw; 0 follows the display
+    pub max_fps: u32,
+    // Most chibis out at once; 0 is no limit
+    pub max_chibis: u32,
+    pub when_full: WhenFull,
+    // Stats drawn on every chibi, see debug.rs
+    pub debug_overlay: bool,
+    // Preset whose image stands in for the tray icon
+    pub tray_icon_preset: Option<String>,
+
+    pub scenes: Vec<Scene>,
+    pub active_scene: Option<String>,
+
+    // Localhost REST API, see http_api.rs
+    pub http_api: bool,
+    pub http_port: u16,
+
+    // Run for every chibi, after the preset's own hooks
+    pub hooks: hooks::Hooks,
+
+    // Tray clicks; right click always opens the menu
+    pub tray_primary: TrayAction,
+    pub tray_middle: TrayAction,
+    pub favorite_preset: Option<String>,
+    pub chibi_middle: ChibiClick,
+    // Chibis let go mid-drag keep sliding and bounce off the screen edges
+    pub fling: bool,
+    // What moving chibis do when they run into each other
+    pub crowds: CrowdMode,
+    // Master volume (percent) and mute for sounds and speech
+    pub volume: u32,
+    pub mute_sounds: bool,
+    // Silences what chibis say (see speech.rs)
+    pub mute_speech: bool,
+    // Sound file for each app event
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub sounds: HashMap<AppSound, PathBuf>,
+    // Where to get the weather for; None leaves it off (see weather.rs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weather: Option<WeatherSettings>,
+    // Desktop notifications repeated by a chibi; None leaves them alone (see notifications.rs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notifications: Option<NotificationSettings>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            hide_all: false,
+            hide_on_fullscreen: false,
+            hide_while_screencasting: false,
+            collapsed_categories: Vec::new(),
+            default_width: 200,
+            default_x: 100,
+            default_y: 100,
+            default_smart_hide: false,
+            default_hover_ms: default_hover_threshold(),
+            default_hide_delay: default_hide_delay(),
+            default_on_top: false,
+            spawn_autostart: true,
+            start_minimized: false,
+            restore_session: RestoreMode::Ask,
+            renderer: "auto".into(),
+            image_quality: ImageQuality::Smooth,
+            max_fps: 0,
+            max_chibis: 0,
+            when_full: WhenFull::Refuse,
+            debug_overlay: false,
+            tray_icon_preset: None,
+            scenes: Vec::new(),
+            active_scene: None,
+            hooks: hooks::Hooks::default(),
+            http_api: false,
+            http_port: 8787,
+            tray_primary: TrayAction::ToggleHide,
+            tray_middle: TrayAction::OpenManager,
+            favorite_preset: None,
+            chibi_middle: ChibiClick::ToggleMove,
+            fling: true,
+            crowds: CrowdMode::Avoid,
+            volume: 100,
+            mute_sounds: false,
+            mute_speech: false,
+            sounds: HashMap::new(),
+            weather: None,
+            notifications: None,
+        }
+    }
+}
+
+impl AppSettings {
+    // Starting point for ad-hoc spawns (e.g. dropped files)
+    pub fn preset_defaults(&self) -> ChibiPreset {
+        ChibiPreset {
+            width: self.default_width,
+            x: self.default_x,
+            y: self.default_y,
+            smart_hide: self.default_smart_hide,
+            hover_threshold_ms: self.default_hover_ms,
+            hide_delay: self.default_hide_delay,
+            always_on_top: self.default_on_top,
+            ..Default::default()
+        }
+    }
+
+    // How many of the `active` chibis would have to go for one more to fit under the limit
+    pub fn over_limit(&self, active: usize) -> usize {
+        if self.max_chibis == 0 { return 0; }
+        (active + 1).saturating_sub(self.max_chibis as usize)
+    }
+}
+
+// A named set of saved presets that can replace whatever is on screen
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scene {
+    pub name: String,
+    pub presets: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let s: AppSettings = serde_json::from_str(r#"{ "hide_all": true, "removed_option": 1 }"#).unwrap();
+        let d = AppSettings::default();
+        assert!(s.hide_all);
+        assert_eq!(s.spawn_autostart, d.spawn_autostart);
+        assert_eq!(s.http_port, d.http_port);
+        assert_eq!(s.restore_session, RestoreMode::Ask);
+        assert_eq!(s.renderer, d.renderer);
+        assert!(s.fling);
+        assert_eq!(s.crowds, CrowdMode::Avoid);
+        assert!(!s.mute_sounds && !s.mute_speech);
+        assert_eq!(s.volume, 100);
+    }
+
+    #[test]
+    fn event_sounds_are_keyed_by_name() {
+        let s: AppSettings = serde_json::from_str(r#"{ "sounds": { "hide_all": "/s/whoosh.ogg" } }"#).unwrap();
+        assert_eq!(s.sounds.get(&AppSound::HideAll), Some(&PathBuf::from("/s/whoosh.ogg")));
+        assert!(!serde_json::to_string(&AppSettings::default()).unwrap().contains("sounds"));
+    }
+
+    #[test]
+    fn chibi_limit() {
+        let mut s = AppSettings::default();
+        assert_eq!(s.over_limit(500), 0);
+        s.max_chibis = 3;
+        assert_eq!(s.over_limit(2), 0);
+        assert_eq!(s.over_limit(3), 1);
+        // Lowered while more were out
+        assert_eq!(s.over_limit(5), 3);
+    }
+
+    #[test]
+    fn preset_defaults_follow_settings() {
+        let s = AppSettings { default_width: 320, default_on_top: true, default_hover_ms: 0, ..Default::default() };
+        let p = s.preset_defaults();
+        assert_eq!(p.width, 320);
+        assert!(p.always_on_top);
+        assert_eq!(p.hover_threshold_ms, 0);
+    }
+}
@@ -0,0 +1,123 @@
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// --- GLOBAL SHORTCUTS ---
+// Registers system-wide hotkeys through org.freedesktop.portal.GlobalShortcuts, so they work
+// on Wayland without compositor-specific binds. The user confirms or changes the keys in the
+// portal's own dialog the first time; later runs reuse the stored choice.
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SHORTCUTS_IFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+// (id, description, preferred trigger)
+pub const SHORTCUTS: [(&str, &str, &str); 3] = [
+    ("toggle-hide", "Toggle Hide All chibis", "CTRL+ALT+H"),
+    ("spawn-favorite", "Spawn the favorite preset", "CTRL+ALT+S"),
+    ("toggle-manager", "Open the Chibi Manager window", "CTRL+ALT+M"),
+];
+
+fn token() -> String {
+    format!("chibi_manager_{}", glib::random_int())
+}
+
+fn options(entries: &[(&str, glib::Variant)]) -> glib::Variant {
+    let dict = glib::VariantDict::new(None);
+    for (key, value) in entries {
+        dict.insert_value(key, value);
+    }
+    dict.end()
+}
+
+// Portal calls answer through a Request object's Response signal; `on_results` gets the
+// results of a successful (code 0) response.
+fn request<F: FnOnce(glib::VariantDict) + 'static>(conn: &gio::DBusConnection, method: &str, token: &str, args: glib::Variant, on_results: F) {
+    let Some(sender) = conn.unique_name() else { return };
+    let path = format!("{}/request/{}/{}", PORTAL_PATH, sender.trim_start_matches(':').replace('.', "_"), token);
+    let on_results = RefCell::new(Some(on_results));
+    // Subscribed before calling so the response can't be missed; each request only fires once
+    conn.signal_subscribe(
+        Some(PORTAL),
+        Some("org.freedesktop.portal.Request"),
+        Some("Response"),
+        Some(&path),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            // (u response, a{sv} results)
+            let Some(on_results) = on_results.borrow_mut().take() else { return };
+            if params.child_value(0).get::<u32>() == Some(0) {
+                on_results(glib::VariantDict::new(Some(&params.child_value(1))));
+            }
+        },
+    );
+    conn.call(
+        Some(PORTAL),
+        PORTAL_PATH,
+        SHORTCUTS_IFACE,
+        method,
+        Some(&args),
+        None,
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        |_| {},
+    );
+}
+
+// `on_activated` is called on the main thread with the id from SHORTCUTS
+pub fn register<F: Fn(&str) + 'static>(on_activated: F) {
+    let on_activated = Rc::new(on_activated);
+    gio::bus_get(gio::BusType::Session, None::<&gio::Cancellable>, move |res| {
+        let Ok(conn) = res else { return };
+        let create_token = token();
+        let args = glib::Variant::tuple_from_iter([options(&[
+            ("handle_token", create_token.to_variant()),
+            ("session_handle_token", token().to_variant()),
+        ])]);
+        let conn_c = conn.clone();
+        request(&conn, "CreateSession", &create_token, args, move |results| {
+            let Some(session) = results.lookup_value("session_handle", None)
+                .and_then(|v| v.str().map(str::to_string)) else { return };
+            bind(&conn_c, &session, on_activated);
+        });
+    });
+}
+
+fn bind<F: Fn(&str) + 'static>(conn: &gio::DBusConnection, session: &str, on_activated: Rc<F>) {
+    let Ok(session_path) = glib::variant::ObjectPath::try_from(session.to_string()) else { return };
+    let shortcuts = SHORTCUTS.iter().map(|(id, description, trigger)| {
+        glib::Variant::tuple_from_iter([
+            id.to_variant(),
+            options(&[("description", description.to_variant()), ("preferred_trigger", trigger.to_variant())]),
+        ])
+    });
+    let bind_token = token();
+    let args = glib::Variant::tuple_from_iter([
+        session_path.to_variant(),
+        glib::Variant::array_from_iter_with_type(glib::VariantTy::new("(sa{sv})").unwrap(), shortcuts),
+        "".to_variant(),
+        options(&[("handle_token", bind_token.to_variant())]),
+    ]);
+    request(conn, "BindShortcuts", &bind_token, args, |_| {});
+
+    let session = session.to_string();
+    conn.signal_subscribe(
+        Some(PORTAL),
+        Some(SHORTCUTS_IFACE),
+        Some("Activated"),
+        Some(PORTAL_PATH),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            // (o session_handle, s shortcut_id, t timestamp, a{sv} options)
+            if params.child_value(0).str() != Some(session.as_str()) { return; }
+            if let Some(id) = params.child_value(1).str() {
+                on_activated(id);
+            }
+        },
+    );
+}
@@ -0,0 +1,109 @@
+use serde_json::Value;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+use crate::control::{handle_rpc, Command, Reply};
+
+// --- CONTROL SOCKET ---
+// Lets `chibi-manager <command>` and scripts drive the running instance. Each line is
+// one request, answered by one reply:
+//  - plain text ("spawn Desk Cat"), answered in text; replies starting with "error: " are failures
+//  - JSON-RPC 2.0 objects, answered with a JSON object on a single line
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("chibi-manager.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_else(|_| "user".into());
+            std::env::temp_dir().join(format!("chibi-manager-{}.sock", user))
+        }
+    }
+}
+
+// Client side: sends one command to the running instance and returns its reply
+pub fn send(cmd: &Command) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT * 2))?;
+    writeln!(stream, "{}", cmd.to_line())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+// Like `send`, but over JSON-RPC; returns the result or the error message
+pub fn send_json(cmd: &Command) -> io::Result<Result<Value, String>> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT * 2))?;
+    writeln!(stream, "{}", cmd.to_rpc_request())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let reply: Value = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(match reply["error"]["message"].as_str() {
+        Some(e) => Err(e.to_string()),
+        None => Ok(reply["result"].clone()),
+    })
+}
+
+// Server side: `on_command` gets each request plus a sender for the reply
+pub fn spawn_server<F>(on_command: F) -> io::Result<()>
+where
+    F: Fn(Command, Sender<Reply>) + Send + Clone + 'static,
+{
+    let path = socket_path();
+    // A leftover socket from a crashed instance refuses connections; replace it
+    if path.exists() && UnixStream::connect(&path).is_err() {
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let on_command = on_command.clone();
+            std::thread::spawn(move || {
+                let _ = handle_client(stream, &on_command);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle_client<F: Fn(Command, Sender<Reply>)>(stream: UnixStream, on_command: &F) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let run = |cmd: Command| {
+        let (tx, rx) = channel();
+        on_command(cmd, tx);
+        rx.recv_timeout(REPLY_TIMEOUT).unwrap_or_else(|_| Reply::Error("no reply from the manager".into()))
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() { continue; }
+
+        let reply = if line.starts_with('{') {
+            handle_rpc(line, &run).to_string()
+        } else {
+            let args: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+            match Command::parse(&args) {
+                Ok(cmd) => run(cmd).to_text(),
+                Err(e) => format!("error: {}", e),
+            }
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+pub fn remove_socket() {
+    let _ = fs::remove_file(socket_path());
+}
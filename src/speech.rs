@@ -0,0 +1,199 @@
+use gtk::glib;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::audio::{self, AppSound};
+use crate::window::ChibiWindow;
+
+// --- SPEECH ---
+// Chibis can say things out loud through speech-dispatcher's `spd-say`: a phrase when they
+// appear, at the top of every hour and as a reminder every few minutes. Each list is
+// picked from at random; {name} and {time} are filled in. Speech has its own mute, and
+// follows the master volume and mute in audio.rs too.
+
+thread_local! {
+    static MUTED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_muted(muted: bool) {
+    MUTED.with(|m| m.set(muted));
+}
+
+pub fn muted() -> bool {
+    MUTED.with(|m| m.get())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Speech {
+    pub spawn: Vec<String>,
+    pub hourly: Vec<String>,
+    pub reminders: Vec<String>,
+    // 0 never reminds
+    pub remind_minutes: u32,
+    // speech-dispatcher voice type; None keeps its default
+    pub voice: Option<String>,
+    // -100 (slowest) to 100
+    pub rate: i32,
+}
+
+impl Speech {
+    pub fn is_empty(&self) -> bool {
+        self.spawn.is_empty() && self.hourly.is_empty() && self.reminders.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeechEvent {
+    Spawn,
+    Hourly,
+    Reminder,
+}
+
+// What `spd-say -t` accepts
+pub const VOICES: [&str; 8] = ["male1", "male2", "male3", "female1", "female2", "female3", "child_male", "child_female"];
+
+// Fills in {name} and {time} (HH:MM)
+pub fn fill(phrase: &str, name: &str, (hour, minute): (i32, i32)) -> String {
+    phrase.replace("{name}", name).replace("{time}", &format!("{:02}:{:02}", hour, minute))
+}
+
+// `volume` is the master volume, 0.0..1.0
+fn args(text: &str, voice: Option<&str>, rate: i32, volume: f64) -> Vec<String> {
+    let mut args = Vec::new();
+    if volume < 1.0 {
+        // spd-say's scale runs from -100 (silent) to 100
+        args.extend(["-i".to_string(), ((volume * 200.0).round() as i32 - 100).to_string()]);
+    }
+    if let Some(voice) = voice {
+        args.extend(["-t".to_string(), voice.to_string()]);
+    }
+    if rate != 0 {
+        args.extend(["-r".to_string(), rate.clamp(-100, 100).to_string()]);
+    }
+    // Text starting with a dash isn't an option
+    args.extend(["--".to_string(), text.to_string()]);
+    args
+}
+
+pub fn say(text: &str, voice: Option<&str>, rate: i32) {
+    if muted() || audio::muted() || text.trim().is_empty() { return; }
+    match Command::new("spd-say").args(args(text, voice, rate, audio::volume())).spawn() {
+        Ok(mut child) => { std::thread::spawn(move || child.wait()); }
+        Err(e) => tracing::warn!("Can't run spd-say (is speech-dispatcher installed?): {}", e),
+    }
+}
+
+// Says one of the phrases for `event`, if there are any
+pub fn say_for(speech: &Speech, name: &str, event: SpeechEvent) {
+    let phrases = match event {
+        SpeechEvent::Spawn => &speech.spawn,
+        SpeechEvent::Hourly => &speech.hourly,
+        SpeechEvent::Reminder => &speech.reminders,
+    };
+    if phrases.is_empty() { return; }
+    let Ok(now) = glib::DateTime::now_local() else { return };
+    let phrase = &phrases[glib::random_int_range(0, phrases.len() as i32) as usize];
+    say(&fill(phrase, name, (now.hour(), now.minute())), speech.voice.as_deref(), speech.rate);
+}
+
+// Repeats the reminders until the window goes away; hidden chibis keep quiet
+pub fn start_reminders(win: &ChibiWindow, speech: &Speech, name: &str) {
+    if speech.reminders.is_empty() || speech.remind_minutes == 0 { return; }
+    let (speech, name) = (speech.clone(), name.to_string());
+    let win_weak = win.downgrade();
+    glib::timeout_add_local(Duration::from_secs(speech.remind_minutes as u64 * 60), move || {
+        let Some(w) = win_weak.upgrade() else { return glib::ControlFlow::Break };
+        if w.is_visible() {
+            audio::play_app(AppSound::Reminder);
+            say_for(&speech, &name, SpeechEvent::Reminder);
+        }
+        glib::ControlFlow::Continue
+    });
+}
+
+fn split_phrases(text: &str) -> Vec<String> {
+    text.split('|').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect()
+}
+
+// Collapsible block for the preset editor; the returned closure reads the values back
+pub fn editor(speech: &Speech) -> (gtk::Expander, impl Fn() -> Speech) {
+    let grid = gtk::Grid::builder().row_spacing(6).column_spacing(10).margin_top(6).build();
+    let mut row = 0;
+    let mut add_row = |label: &str, widget: &gtk::Widget| {
+        let l = gtk::Label::new(Some(label));
+        l.set_xalign(0.0);
+        widget.set_hexpand(true);
+        grid.attach(&l, 0, row, 1, 1);
+        grid.attach(widget, 1, row, 1, 1);
+        row += 1;
+    };
+    let phrases = |list: &[String]| {
+        let entry = gtk::Entry::new();
+        entry.set_placeholder_text(Some("Hi, I'm {name}! | Hello!"));
+        entry.set_text(&list.join(" | "));
+        entry
+    };
+    let entry_spawn = phrases(&speech.spawn);
+    add_row("On spawn", entry_spawn.upcast_ref());
+    let entry_hourly = phrases(&speech.hourly);
+    entry_hourly.set_placeholder_text(Some("It's {time}!"));
+    add_row("Every hour", entry_hourly.upcast_ref());
+    let entry_remind = phrases(&speech.reminders);
+    entry_remind.set_placeholder_text(Some("Time to stretch! | Drink some water"));
+    add_row("Reminders", entry_remind.upcast_ref());
+    let spin_remind = gtk::SpinButton::with_range(0.0, 240.0, 5.0);
+    spin_remind.set_value(speech.remind_minutes as f64);
+    add_row("Remind every (min)", spin_remind.upcast_ref());
+
+    let mut voice_labels = vec!["Default"];
+    voice_labels.extend(VOICES);
+    let drop_voice = gtk::DropDown::from_strings(&voice_labels);
+    let voice_index = speech.voice.as_deref().and_then(|v| VOICES.iter().position(|x| *x == v));
+    drop_voice.set_selected(voice_index.map_or(0, |i| i + 1) as u32);
+    add_row("Voice", drop_voice.upcast_ref());
+    let spin_rate = gtk::SpinButton::with_range(-100.0, 100.0, 10.0);
+    spin_rate.set_value(speech.rate as f64);
+    add_row("Rate", spin_rate.upcast_ref());
+
+    let expander = gtk::Expander::new(Some("Speech"));
+    expander.set_child(Some(&grid));
+    expander.set_expanded(!speech.is_empty());
+
+    let read = move || Speech {
+        spawn: split_phrases(&entry_spawn.text()),
+        hourly: split_phrases(&entry_hourly.text()),
+        reminders: split_phrases(&entry_remind.text()),
+        remind_minutes: spin_remind.value() as u32,
+        voice: (drop_voice.selected() as usize).checked_sub(1).map(|i| VOICES[i].to_string()),
+        rate: spin_rate.value() as i32,
+    };
+    (expander, read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_filled() {
+        assert_eq!(fill("{name} says it's {time}", "Mochi", (9, 5)), "Mochi says it's 09:05");
+        assert_eq!(fill("no placeholders", "Mochi", (0, 0)), "no placeholders");
+    }
+
+    #[test]
+    fn spd_say_arguments() {
+        assert_eq!(args("hi", None, 0, 1.0), ["--", "hi"]);
+        assert_eq!(args("-5 degrees", Some("female1"), 250, 1.0), ["-t", "female1", "-r", "100", "--", "-5 degrees"]);
+        assert_eq!(args("hi", None, 0, 0.25), ["-i", "-50", "--", "hi"]);
+    }
+
+    #[test]
+    fn phrases_split_on_bars() {
+        assert_eq!(split_phrases(" Hello! | | Hi, {name} "), ["Hello!", "Hi, {name}"]);
+        assert!(split_phrases("").is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::control;
+use crate::config::{load_presets, load_settings, save_session, save_settings, Session};
+use crate::preset::ChibiPreset;
+use crate::registry::{self, ActiveWindowRef};
+use crate::settings::AppSettings;
+
+// --- APP STATE ---
+// Everything the manager tracks at runtime, shared as one Rc<AppState> between the controller
+// and the UI callbacks. Keep field borrows short: the methods below borrow the same cells.
+
+pub struct AppState {
+    pub presets: RefCell<Vec<ChibiPreset>>,
+    pub registry: RefCell<Vec<ActiveWindowRef>>,
+    pub settings: RefCell<AppSettings>,
+    pub hide_all: Cell<bool>,
+    // Reported by the compositor, screencast and idle watchers
+    pub fullscreen: Cell<bool>,
+    pub screencast: Cell<bool>,
+    pub workspace: RefCell<Option<String>>,
+    pub idle_since: Cell<Option<i64>>,
+    pub tray_available: Cell<bool>,
+}
+
+impl AppState {
+    pub fn load() -> Rc<Self> {
+        let settings = load_settings();
+        Rc::new(AppState {
+            presets: RefCell::new(load_presets()),
+            registry: RefCell::new(Vec::new()),
+            hide_all: Cell::new(settings.hide_all),
+            settings: RefCell::new(settings),
+            fullscreen: Cell::new(false),
+            screencast: Cell::new(false),
+            workspace: RefCell::new(None),
+            idle_since: Cell::new(None),
+            tray_available: Cell::new(true),
+        })
+    }
+
+    pub fn preset(&self, id: &str) -> Option<ChibiPreset> {
+        self.presets.borrow().iter().find(|p| p.id == id).cloned()
+    }
+
+    // By id or case-insensitive name, as typed on the command line
+    pub fn find_preset(&self, name: &str) -> Option<ChibiPreset> {
+        self.presets.borrow().iter()
+            .find(|p| p.id == name || p.name.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    pub fn is_running(&self, preset_id: &str) -> bool {
+        self.registry.borrow().iter().any(|r| r.preset_id.as_deref() == Some(preset_id))
+    }
+
+    pub fn save_settings(&self) {
+        save_settings(&self.settings.borrow());
+    }
+
+    pub fn set_hide_all(&self, hidden: bool) {
+        self.hide_all.set(hidden);
+        self.settings.borrow_mut().hide_all = hidden;
+        self.save_settings();
+    }
+
+    // Screen sharing hides everything like Hide All, without touching the saved toggle
+    pub fn apply_visibility(&self) {
+        let fs_hide = self.fullscreen.get() && self.settings.borrow().hide_on_fullscreen;
+        registry::apply_visibility(
+            &mut self.registry.borrow_mut(),
+            self.hide_all.get() || self.screencast.get(),
+            fs_hide,
+            self.workspace.borrow().as_deref(),
+        );
+    }
+
+    pub fn active_chibis(&self) -> Vec<control::ActiveChibi> {
+        registry::active_chibis(&self.registry.borrow())
+    }
+
+    pub fn session(&self) -> Session {
+        registry::session_snapshot(&self.registry.borrow(), self.hide_all.get())
+    }
+
+    pub fn save_session(&self) {
+        save_session(&self.session());
+    }
+}
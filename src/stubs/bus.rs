@@ -0,0 +1,12 @@
+use std::sync::mpsc::Sender;
+
+use crate::control::{Command, Reply};
+
+// --- D-BUS INTERFACE (disabled) ---
+// Built without the "ipc" feature: nothing is exported and the signals go nowhere.
+
+pub fn export<F: Fn(Command, Sender<Reply>) + 'static>(_on_command: F) {}
+
+pub fn emit_spawned(_id: u64, _name: &str) {}
+
+pub fn emit_closed(_id: u64) {}
@@ -0,0 +1,14 @@
+use std::io;
+use std::sync::mpsc::Sender;
+
+use crate::control::{Command, Reply};
+
+// --- REST API (disabled) ---
+// Built without the "ipc" feature; enabling the API in Preferences only reports this.
+
+pub fn spawn_server<F>(_port: u16, _on_command: F) -> io::Result<()>
+where
+    F: Fn(Command, Sender<Reply>) + Send + Clone + 'static,
+{
+    Err(io::Error::new(io::ErrorKind::Unsupported, "built without the ipc feature"))
+}
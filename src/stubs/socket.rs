@@ -0,0 +1,30 @@
+use serde_json::Value;
+use std::io;
+use std::sync::mpsc::Sender;
+
+use crate::control::{Command, Reply};
+
+// --- CONTROL SOCKET (disabled) ---
+// Built without the "ipc" feature: the manager doesn't listen, so clients can't reach it.
+
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "built without the ipc feature")
+}
+
+pub fn send(_cmd: &Command) -> io::Result<String> {
+    Err(unsupported())
+}
+
+pub fn send_json(_cmd: &Command) -> io::Result<Result<Value, String>> {
+    Err(unsupported())
+}
+
+pub fn spawn_server<F>(_on_command: F) -> io::Result<()>
+where
+    F: Fn(Command, Sender<Reply>) + Send + Clone + 'static,
+{
+    tracing::info!("Control socket left out of this build");
+    Ok(())
+}
+
+pub fn remove_socket() {}
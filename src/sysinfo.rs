@@ -0,0 +1,424 @@
+use gtk::{gio, glib};
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::particles::ParticleKind;
+use crate::window::ChibiWindow;
+
+// --- SYSTEM MONITOR ---
+// Info sources a chibi can report on. CPU load and memory come from /proc, the temperature
+// from the hottest /sys/class/thermal zone. One sampler runs for every chibi that asks.
+
+const SAMPLE_SECS: u32 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sample {
+    // Percent
+    pub cpu: f32,
+    pub memory: f32,
+    // °C, if the machine has a thermal zone
+    pub temperature: Option<f32>,
+}
+
+impl Sample {
+    pub fn summary(&self) -> String {
+        let mut text = format!("CPU {:.0}% · RAM {:.0}%", self.cpu, self.memory);
+        if let Some(t) = self.temperature { text += &format!(" · {:.0}°C", t); }
+        text
+    }
+}
+
+// Jiffies from the first line of /proc/stat
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    pub fn parse(stat: &str) -> Option<CpuTimes> {
+        let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+        let fields: Vec<u64> = line.split_whitespace().skip(1).filter_map(|f| f.parse().ok()).collect();
+        if fields.len() < 4 { return None; }
+        // user nice system idle iowait irq softirq steal …; idle and iowait are the idle ones
+        let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+        let total: u64 = fields.iter().take(8).sum();
+        Some(CpuTimes { busy: total - idle, total })
+    }
+
+    // Load between an earlier reading and this one, in percent
+    pub fn percent_since(&self, earlier: &CpuTimes) -> f32 {
+        let total = self.total.saturating_sub(earlier.total);
+        if total == 0 { return 0.0; }
+        self.busy.saturating_sub(earlier.busy) as f32 * 100.0 / total as f32
+    }
+}
+
+// Memory in use from /proc/meminfo, in percent
+pub fn memory_percent(meminfo: &str) -> Option<f32> {
+    let field = |name: &str| meminfo.lines()
+        .find_map(|l| l.strip_prefix(name)?.trim().trim_end_matches("kB").trim().parse::<f64>().ok());
+    let (total, available) = (field("MemTotal:")?, field("MemAvailable:")?);
+    (total > 0.0).then(|| ((total - available) * 100.0 / total) as f32)
+}
+
+fn temperature() -> Option<f32> {
+    fs::read_dir("/sys/class/thermal").ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|e| fs::read_to_string(e.path().join("temp")).ok()?.trim().parse::<f32>().ok())
+        // Millidegrees; some zones report nonsense when idle
+        .map(|t| t / 1000.0)
+        .filter(|t| (0.0..150.0).contains(t))
+        .reduce(f32::max)
+}
+
+#[derive(Default)]
+struct Sampler {
+    cpu: Option<CpuTimes>,
+    latest: Sample,
+    // Monitor chibis reading the samples; the timer stops with the last one
+    users: Vec<glib::WeakRef<ChibiWindow>>,
+}
+
+impl Sampler {
+    fn sample(&mut self) {
+        let cpu = fs::read_to_string("/proc/stat").ok().and_then(|s| CpuTimes::parse(&s));
+        if let (Some(now), Some(before)) = (cpu, self.cpu) { self.latest.cpu = now.percent_since(&before); }
+        self.cpu = cpu.or(self.cpu);
+        if let Some(memory) = fs::read_to_string("/proc/meminfo").ok().and_then(|s| memory_percent(&s)) {
+            self.latest.memory = memory;
+        }
+        self.latest.temperature = temperature();
+    }
+}
+
+thread_local! {
+    // Runs while any monitor chibi is out
+    static SAMPLER: RefCell<Option<Sampler>> = const { RefCell::new(None) };
+}
+
+// Samples for `win` until it goes away, starting the timer if it's the first
+fn subscribe(win: &ChibiWindow) {
+    let started = SAMPLER.with(|s| {
+        let mut s = s.borrow_mut();
+        let started = s.is_some();
+        let sampler = s.get_or_insert_with(|| {
+            let mut sampler = Sampler::default();
+            sampler.sample();
+            sampler
+        });
+        sampler.users.push(win.downgrade());
+        started
+    });
+    if started { return; }
+    glib::timeout_add_seconds_local(SAMPLE_SECS, || {
+        SAMPLER.with(|s| {
+            let mut slot = s.borrow_mut();
+            let Some(sampler) = slot.as_mut() else { return glib::ControlFlow::Break };
+            sampler.users.retain(|w| w.upgrade().is_some());
+            if sampler.users.is_empty() {
+                *slot = None;
+                return glib::ControlFlow::Break;
+            }
+            sampler.sample();
+            glib::ControlFlow::Continue
+        })
+    });
+}
+
+// The most recent sample; the CPU reads 0 until two samples have been taken
+pub fn latest() -> Sample {
+    SAMPLER.with(|s| s.borrow().as_ref().map(|s| s.latest).unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SystemMonitor {
+    // Shows the numbers in the bubble this often; 0 never does
+    #[serde(default = "default_report_minutes")]
+    pub report_minutes: u32,
+    // Shown while the CPU is above `busy_above` percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub busy_image: Option<PathBuf>,
+    #[serde(default = "default_busy_above")]
+    pub busy_above: u32,
+}
+
+pub fn default_report_minutes() -> u32 { 15 }
+pub fn default_busy_above() -> u32 { 80 }
+
+// Percent below `busy_above` the load has to drop before the chibi calms down again
+const BUSY_HYSTERESIS: f32 = 10.0;
+const REPORT_TIME: Duration = Duration::from_secs(6);
+
+// Busy when `cpu` is over `above`, and stays busy until well under it
+pub fn still_busy(busy: bool, cpu: f32, above: u32) -> bool {
+    let above = above as f32;
+    if busy { cpu >= above - BUSY_HYSTERESIS } else { cpu >= above }
+}
+
+// Keeps reporting until the window goes away
+pub fn start(win: &ChibiWindow, monitor: &SystemMonitor, image: &Path) {
+    let monitor = monitor.clone();
+    let image = image.to_path_buf();
+    let busy = Cell::new(false);
+    let since_report = Cell::new(0);
+    let win_weak = win.downgrade();
+    subscribe(win);
+    glib::timeout_add_seconds_local(SAMPLE_SECS, move || {
+        let Some(w) = win_weak.upgrade() else { return glib::ControlFlow::Break };
+        let sample = latest();
+        if let Some(busy_image) = &monitor.busy_image {
+            let now_busy = still_busy(busy.get(), sample.cpu, monitor.busy_above);
+            if busy.replace(now_busy) != now_busy {
+                w.set_image(if now_busy { busy_image } else { &image });
+            }
+        }
+        if monitor.report_minutes > 0 {
+            since_report.set(since_report.get() + SAMPLE_SECS);
+            if since_report.get() >= monitor.report_minutes * 60 {
+                since_report.set(0);
+                if w.is_visible() { w.flash_bubble(&sample.summary(), REPORT_TIME); }
+            }
+        }
+        glib::ControlFlow::Continue
+    });
+}
+
+// --- BATTERY ---
+// UPower's display device (the combined laptop battery) on the system bus. Chibis can look
+// worried when it runs low, sleep once it's charged and warn in their bubble at a threshold.
+
+const UPOWER: &str = "org.freedesktop.UPower";
+const DISPLAY_DEVICE: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+const DEVICE_IFACE: &str = "org.freedesktop.UPower.Device";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Charge {
+    #[default]
+    Unknown,
+    Charging,
+    Discharging,
+    Full,
+}
+
+impl Charge {
+    // UPower's State property
+    fn from_upower(state: u32) -> Charge {
+        match state {
+            1 | 5 => Charge::Charging,
+            2 | 3 | 6 => Charge::Discharging,
+            4 => Charge::Full,
+            _ => Charge::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Battery {
+    // Desktops have no battery to react to
+    pub present: bool,
+    pub percent: f64,
+    pub charge: Charge,
+}
+
+impl Battery {
+    // Fills in what a property dict mentions
+    fn update(&mut self, props: &glib::Variant) {
+        let dict = glib::VariantDict::new(Some(props));
+        if let Some(present) = dict.lookup::<bool>("IsPresent").ok().flatten() { self.present = present; }
+        if let Some(percent) = dict.lookup::<f64>("Percentage").ok().flatten() { self.percent = percent; }
+        if let Some(state) = dict.lookup::<u32>("State").ok().flatten() { self.charge = Charge::from_upower(state); }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BatteryReaction {
+    // Shown while discharging below `low_percent`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worried_image: Option<PathBuf>,
+    #[serde(default = "default_low_percent")]
+    pub low_percent: u32,
+    // Shows the sleep image once it's charged up
+    #[serde(default)]
+    pub sleep_when_full: bool,
+    // Warns in the bubble when it drops to this; 0 never does
+    #[serde(default)]
+    pub warn_percent: u32,
+}
+
+pub fn default_low_percent() -> u32 { 20 }
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mood {
+    Normal,
+    Worried,
+    Asleep,
+}
+
+impl BatteryReaction {
+    pub fn mood(&self, battery: &Battery) -> Mood {
+        if !battery.present { return Mood::Normal; }
+        match battery.charge {
+            Charge::Discharging if battery.percent < self.low_percent as f64 => Mood::Worried,
+            Charge::Full if self.sleep_when_full => Mood::Asleep,
+            _ => Mood::Normal,
+        }
+    }
+
+    // Whether to warn now; `warned` remembers it until the battery is back above the threshold
+    pub fn should_warn(&self, battery: &Battery, warned: &mut bool) -> bool {
+        let low = battery.present && battery.charge == Charge::Discharging && battery.percent <= self.warn_percent as f64;
+        if !low { *warned = false; return false; }
+        !std::mem::replace(warned, true)
+    }
+}
+
+type BatteryListener = Box<dyn Fn(&Battery) -> bool>;
+
+thread_local! {
+    static BATTERY: RefCell<Option<(Battery, Vec<BatteryListener>)>> = const { RefCell::new(None) };
+}
+
+fn battery_changed(props: &glib::Variant) {
+    let Some((battery, listeners)) = BATTERY.with(|b| {
+        let mut b = b.borrow_mut();
+        let (battery, listeners) = b.as_mut()?;
+        let before = *battery;
+        battery.update(props);
+        (*battery != before).then(|| (*battery, std::mem::take(listeners)))
+    }) else { return };
+    let kept: Vec<BatteryListener> = listeners.into_iter().filter(|l| l(&battery)).collect();
+    BATTERY.with(|b| {
+        if let Some((_, listeners)) = b.borrow_mut().as_mut() { listeners.splice(0..0, kept); }
+    });
+}
+
+fn connect_upower() {
+    gio::bus_get(gio::BusType::System, None::<&gio::Cancellable>, |res| {
+        let Ok(conn) = res else { return };
+        conn.signal_subscribe(
+            Some(UPOWER),
+            Some("org.freedesktop.DBus.Properties"),
+            Some("PropertiesChanged"),
+            Some(DISPLAY_DEVICE),
+            Some(DEVICE_IFACE),
+            gio::DBusSignalFlags::NONE,
+            // (s interface, a{sv} changed, as invalidated)
+            |_, _, _, _, _, params| battery_changed(&params.child_value(1)),
+        );
+        conn.call(
+            Some(UPOWER),
+            DISPLAY_DEVICE,
+            "org.freedesktop.DBus.Properties",
+            "GetAll",
+            Some(&(DEVICE_IFACE,).to_variant()),
+            Some(glib::VariantTy::new("(a{sv})").unwrap()),
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+            |reply| {
+                if let Ok(reply) = reply { battery_changed(&reply.child_value(0)); }
+            },
+        );
+    });
+}
+
+// Calls `on_change` whenever the battery changes (and once it's first read), until it returns false
+pub fn watch_battery(on_change: impl Fn(&Battery) -> bool + 'static) {
+    let start = BATTERY.with(|b| {
+        let mut b = b.borrow_mut();
+        let start = b.is_none();
+        let (battery, listeners) = b.get_or_insert_with(|| (Battery::default(), Vec::new()));
+        if start || on_change(battery) { listeners.push(Box::new(on_change)); }
+        start
+    });
+    if start { connect_upower(); }
+}
+
+// Keeps reacting to the battery until the window goes away
+pub fn start_battery(win: &ChibiWindow, reaction: &BatteryReaction, image: &Path, sleep_image: Option<&Path>) {
+    let reaction = reaction.clone();
+    let (image, sleep_image) = (image.to_path_buf(), sleep_image.map(Path::to_path_buf));
+    let mood = Cell::new(Mood::Normal);
+    let warned = Cell::new(false);
+    let win_weak = win.downgrade();
+    watch_battery(move |battery| {
+        let Some(w) = win_weak.upgrade() else { return false };
+        let now = reaction.mood(battery);
+        if mood.replace(now) != now {
+            let shown = match now {
+                Mood::Normal => None,
+                Mood::Worried => reaction.worried_image.as_deref(),
+                Mood::Asleep => sleep_image.as_deref(),
+            };
+            w.set_image(shown.unwrap_or(&image));
+            w.set_ambient_particles((now == Mood::Asleep).then_some(ParticleKind::Z));
+        }
+        let mut warn = warned.get();
+        if reaction.warn_percent > 0 && reaction.should_warn(battery, &mut warn) && w.is_visible() {
+            w.flash_bubble(&format!("Battery at {:.0}%! Time to plug in?", battery.percent), REPORT_TIME);
+        }
+        warned.set(warn);
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_load_between_readings() {
+        let a = CpuTimes::parse("cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 1 2 3 4\n").unwrap();
+        let b = CpuTimes::parse("cpu  200 0 200 1000 100 0 0 0 0 0\n").unwrap();
+        assert_eq!(b.percent_since(&a), 40.0);
+        assert_eq!(a.percent_since(&a), 0.0);
+        assert!(CpuTimes::parse("intr 1 2 3").is_none());
+    }
+
+    #[test]
+    fn memory_in_use() {
+        let meminfo = "MemTotal:       16000000 kB\nMemFree:         1000000 kB\nMemAvailable:    4000000 kB\n";
+        assert_eq!(memory_percent(meminfo), Some(75.0));
+        assert_eq!(memory_percent("MemTotal: 10 kB\n"), None);
+    }
+
+    #[test]
+    fn busy_has_some_slack() {
+        assert!(!still_busy(false, 75.0, 80));
+        assert!(still_busy(false, 85.0, 80));
+        assert!(still_busy(true, 75.0, 80));
+        assert!(!still_busy(true, 65.0, 80));
+        let s = Sample { cpu: 12.4, memory: 50.0, temperature: Some(48.6) };
+        assert_eq!(s.summary(), "CPU 12% · RAM 50% · 49°C");
+    }
+
+    #[test]
+    fn battery_moods() {
+        let r = BatteryReaction { worried_image: None, low_percent: 20, sleep_when_full: true, warn_percent: 10 };
+        let on_battery = |percent| Battery { present: true, percent, charge: Charge::Discharging };
+        assert_eq!(r.mood(&on_battery(50.0)), Mood::Normal);
+        assert_eq!(r.mood(&on_battery(15.0)), Mood::Worried);
+        assert_eq!(r.mood(&Battery { charge: Charge::Charging, ..on_battery(15.0) }), Mood::Normal);
+        assert_eq!(r.mood(&Battery { charge: Charge::Full, ..on_battery(100.0) }), Mood::Asleep);
+        assert_eq!(r.mood(&Battery { present: false, ..on_battery(5.0) }), Mood::Normal);
+        assert_eq!(Charge::from_upower(4), Charge::Full);
+    }
+
+    #[test]
+    fn battery_warns_once_per_drop() {
+        let r = BatteryReaction { worried_image: None, low_percent: 20, sleep_when_full: false, warn_percent: 10 };
+        let on_battery = |percent| Battery { present: true, percent, charge: Charge::Discharging };
+        let mut warned = false;
+        assert!(!r.should_warn(&on_battery(11.0), &mut warned));
+        assert!(r.should_warn(&on_battery(10.0), &mut warned));
+        assert!(!r.should_warn(&on_battery(9.0), &mut warned));
+        assert!(!r.should_warn(&Battery { charge: Charge::Charging, ..on_battery(12.0) }, &mut warned));
+        assert!(r.should_warn(&on_battery(8.0), &mut warned));
+    }
+}
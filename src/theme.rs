@@ -0,0 +1,98 @@
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::path::Path;
+
+// --- THEMING ---
+// Optional user stylesheet (reloaded on change) and the portal's dark/light preference.
+
+thread_local! {
+    // The monitor stops reporting once dropped, so keep it for the app's lifetime
+    static STYLE_MONITOR: RefCell<Option<gio::FileMonitor>> = const { RefCell::new(None) };
+}
+
+pub fn load_user_css(display: &gtk::gdk::Display, path: &Path) {
+    let provider = gtk::CssProvider::new();
+    gtk::style_context_add_provider_for_display(display, &provider, gtk::STYLE_PROVIDER_PRIORITY_USER);
+
+    let reload = {
+        let provider = provider.clone();
+        let path = path.to_path_buf();
+        move || {
+            if path.exists() {
+                provider.load_from_path(&path);
+            } else {
+                provider.load_from_data("");
+            }
+        }
+    };
+    reload();
+
+    let file = gio::File::for_path(path);
+    if let Ok(monitor) = file.monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>) {
+        monitor.connect_changed(move |_, _, _, event| {
+            if matches!(event, gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::Created) {
+                reload();
+            }
+        });
+        STYLE_MONITOR.with(|m| *m.borrow_mut() = Some(monitor));
+    }
+}
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_IFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE: &str = "org.freedesktop.appearance";
+
+// Settings.Read wraps the value in one or two variant layers depending on portal version
+fn unwrap_u32(v: &glib::Variant) -> Option<u32> {
+    match v.get::<u32>() {
+        Some(n) => Some(n),
+        None => unwrap_u32(&v.as_variant()?),
+    }
+}
+
+fn apply_color_scheme(scheme: u32) {
+    // 0 = no preference, 1 = prefer dark, 2 = prefer light
+    if let Some(settings) = gtk::Settings::default() {
+        settings.set_gtk_application_prefer_dark_theme(scheme == 1);
+    }
+}
+
+pub fn follow_color_scheme() {
+    gio::bus_get(gio::BusType::Session, None::<&gio::Cancellable>, |res| {
+        let Ok(conn) = res else { return };
+        conn.call(
+            Some(PORTAL),
+            PORTAL_PATH,
+            SETTINGS_IFACE,
+            "Read",
+            Some(&(APPEARANCE, "color-scheme").to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+            None::<&gio::Cancellable>,
+            |reply| {
+                if let Some(scheme) = reply.ok().and_then(|r| unwrap_u32(&r.child_value(0))) {
+                    apply_color_scheme(scheme);
+                }
+            },
+        );
+        conn.signal_subscribe(
+            Some(PORTAL),
+            Some(SETTINGS_IFACE),
+            Some("SettingChanged"),
+            Some(PORTAL_PATH),
+            Some(APPEARANCE),
+            gio::DBusSignalFlags::NONE,
+            |_, _, _, _, _, params| {
+                // (s namespace, s key, v value)
+                if params.child_value(1).str() != Some("color-scheme") { return; }
+                if let Some(scheme) = unwrap_u32(&params.child_value(2)) {
+                    apply_color_scheme(scheme);
+                }
+            },
+        );
+    });
+}
@@ -0,0 +1,60 @@
+use gtk::gdk;
+use gtk::gdk_pixbuf::Pixbuf;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::tray::Icon;
+
+// --- THUMBNAILS ---
+// Small row previews, decoded once per image and reused until the file changes.
+
+pub const THUMB_SIZE: i32 = 32;
+
+thread_local! {
+    static CACHE: RefCell<HashMap<PathBuf, (Option<SystemTime>, gdk::Texture)>> = RefCell::new(HashMap::new());
+}
+
+pub fn thumbnail(path: &Path) -> Option<gdk::Texture> {
+    let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some((cached_mtime, tex)) = cache.get(path) {
+            if *cached_mtime == mtime { return Some(tex.clone()); }
+        }
+        let pixbuf = Pixbuf::from_file_at_scale(path, THUMB_SIZE, THUMB_SIZE, true).ok()?;
+        let tex = gdk::Texture::for_pixbuf(&pixbuf);
+        cache.insert(path.to_path_buf(), (mtime, tex.clone()));
+        Some(tex)
+    })
+}
+
+// Fixed-size image widget for list rows, with a missing-image icon if the file can't be read
+pub fn thumbnail_widget(path: &Path) -> gtk::Image {
+    let img = match thumbnail(path) {
+        Some(tex) => gtk::Image::from_paintable(Some(&tex)),
+        None => gtk::Image::from_icon_name("image-missing"),
+    };
+    img.set_pixel_size(THUMB_SIZE);
+    img
+}
+
+// Tray hosts want ARGB32 in network byte order
+pub const TRAY_ICON_SIZE: i32 = 64;
+
+pub fn tray_icon(path: &Path) -> Option<Icon> {
+    let pixbuf = Pixbuf::from_file_at_scale(path, TRAY_ICON_SIZE, TRAY_ICON_SIZE, true).ok()?
+        .add_alpha(false, 0, 0, 0).ok()?;
+    let (width, height) = (pixbuf.width(), pixbuf.height());
+    let stride = pixbuf.rowstride() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height as usize {
+        let row = &bytes[y * stride..];
+        for px in row[..width as usize * 4].chunks_exact(4) {
+            data.extend_from_slice(&[px[3], px[0], px[1], px[2]]);
+        }
+    }
+    Some(Icon { width, height, data })
+}
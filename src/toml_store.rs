@@ -0,0 +1,94 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item, Table};
+
+// --- TOML PRESET STORAGE ---
+// presets.toml holds one [[preset]] table per preset. Rewrites keep the comments a user
+// placed on a preset (matched by id) and on its individual keys.
+
+#[derive(Deserialize)]
+struct PresetFile<T> {
+    #[serde(default = "Vec::new")]
+    preset: Vec<T>,
+}
+
+#[derive(Serialize)]
+struct PresetFileRef<'a, T> {
+    preset: &'a [T],
+}
+
+pub fn parse<T: DeserializeOwned>(content: &str) -> Result<Vec<T>, toml_edit::de::Error> {
+    toml_edit::de::from_str::<PresetFile<T>>(content).map(|f| f.preset)
+}
+
+pub fn render<T: Serialize>(presets: &[T], previous: Option<&str>) -> Option<String> {
+    let text = toml_edit::ser::to_string_pretty(&PresetFileRef { preset: presets }).ok()?;
+    let mut doc: DocumentMut = text.parse().ok()?;
+
+    if let Some(old) = previous.and_then(|p| p.parse::<DocumentMut>().ok()) {
+        carry_comments(&old, &mut doc);
+    }
+    Some(doc.to_string())
+}
+
+fn table_id(table: &Table) -> Option<&str> {
+    table.get("id").and_then(Item::as_str)
+}
+
+fn carry_comments(old: &DocumentMut, new: &mut DocumentMut) {
+    new.set_trailing(old.trailing().as_str().unwrap_or_default());
+
+    let Some(old_tables) = old.get("preset").and_then(Item::as_array_of_tables) else { return };
+    let Some(new_tables) = new.get_mut("preset").and_then(Item::as_array_of_tables_mut) else { return };
+
+    for table in new_tables.iter_mut() {
+        let Some(id) = table_id(table).map(str::to_string) else { continue };
+        let Some(old_table) = old_tables.iter().find(|t| table_id(t) == Some(&id)) else { continue };
+
+        *table.decor_mut() = old_table.decor().clone();
+        for (key, item) in old_table.iter() {
+            let Some(old_key) = old_table.key(key) else { continue };
+            if let Some(mut new_key) = table.key_mut(key) {
+                *new_key.leaf_decor_mut() = old_key.leaf_decor().clone();
+            }
+            if let (Some(old_val), Some(new_val)) = (item.as_value(), table.get_mut(key).and_then(Item::as_value_mut)) {
+                *new_val.decor_mut() = old_val.decor().clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::ChibiPreset;
+
+    #[test]
+    fn render_then_parse_round_trips() {
+        let presets = vec![
+            ChibiPreset { id: "a".into(), name: "One".into(), ..Default::default() },
+            ChibiPreset { id: "b".into(), name: "Two".into(), tags: vec!["x".into()], ..Default::default() },
+        ];
+        let text = render(&presets, None).unwrap();
+        let back: Vec<ChibiPreset> = parse(&text).unwrap();
+        assert_eq!(back.len(), 2);
+        assert_eq!(back[1].name, "Two");
+        assert_eq!(back[1].tags, vec!["x".to_string()]);
+    }
+
+    #[test]
+    fn empty_file_has_no_presets() {
+        assert!(parse::<ChibiPreset>("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn comments_follow_the_preset_id() {
+        let presets = vec![ChibiPreset { id: "a".into(), name: "One".into(), ..Default::default() }];
+        let first = render(&presets, None).unwrap();
+        let edited = first.replacen("[[preset]]", "# my favourite\n[[preset]]", 1);
+
+        let renamed = vec![ChibiPreset { name: "Renamed".into(), ..presets[0].clone() }];
+        let second = render(&renamed, Some(&edited)).unwrap();
+        assert!(second.contains("# my favourite"));
+        assert!(second.contains("Renamed"));
+    }
+}
@@ -0,0 +1,406 @@
+#[cfg(feature = "tray")]
+use ksni::{Tray, MenuItem, menu::{StandardItem, CheckmarkItem, SubMenu, RadioGroup, RadioItem}, ToolTip};
+
+#[cfg(feature = "tray")]
+use crate::pomodoro;
+use crate::settings::TrayAction;
+use crate::{AppMsg, AppSender};
+
+// --- TRAY HANDLER ---
+// Built without the "tray" feature, the tray state is still kept (so the rest of the app
+// doesn't care), but nothing is exported and the manager is told there is no tray.
+
+#[cfg(feature = "tray")]
+pub use ksni::Icon;
+
+// Same shape as ksni::Icon, for the thumbnails
+#[cfg(not(feature = "tray"))]
+#[derive(Clone, Debug)]
+pub struct Icon {
+    pub width: i32,
+    pub height: i32,
+    pub data: Vec<u8>,
+}
+
+#[cfg(feature = "tray")]
+pub type TrayHandle = ksni::Handle<ChibiTray>;
+
+#[cfg(not(feature = "tray"))]
+pub struct TrayHandle(std::cell::RefCell<ChibiTray>);
+
+#[cfg(not(feature = "tray"))]
+impl TrayHandle {
+    pub fn update<R, F: FnOnce(&mut ChibiTray) -> R>(&self, f: F) -> R {
+        f(&mut self.0.borrow_mut())
+    }
+}
+
+// Starts the StatusNotifier service on its own thread
+#[cfg(feature = "tray")]
+pub fn spawn(tray: ChibiTray) -> TrayHandle {
+    let service = ksni::TrayService::new(tray);
+    let handle = service.handle();
+    service.spawn();
+    handle
+}
+
+#[cfg(not(feature = "tray"))]
+pub fn spawn(tray: ChibiTray) -> TrayHandle {
+    let _ = tray.sender.send(AppMsg::TrayAvailable(false));
+    TrayHandle(std::cell::RefCell::new(tray))
+}
+
+pub struct ChibiTray {
+    pub sender: AppSender,
+    pub is_hidden: bool,
+    pub hide_on_fullscreen: bool,
+    pub hide_while_screencasting: bool,
+    pub is_muted: bool,
+    // (id, name) of every saved preset, in list order
+    pub presets: Vec<(String, String)>,
+    pub tags: Vec<String>,
+    pub scenes: Vec<String>,
+    pub active_scene: Option<String>,
+    pub chibis: Vec<TrayChibi>,
+    pub primary_action: TrayAction,
+    pub middle_action: TrayAction,
+    pub favorite: Option<String>,
+    // Custom icon and the preset it came from; empty means the theme icon
+    pub icon: Vec<Icon>,
+    pub icon_label: Option<String>,
+}
+
+// One active chibi, as the Active Chibis and Click-through submenus show it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrayChibi {
+    pub key: u64,
+    pub label: String,
+    pub visible: bool,
+    pub click_through: bool,
+    // Has a pomodoro timer to control
+    pub pomodoro: bool,
+}
+
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+impl ChibiTray {
+    // e.g. "3 active chibis (1 hidden) · Hide All on"
+    fn status_line(&self) -> String {
+        let count = self.chibis.len();
+        let mut line = format!("{} active chibi{}", count, if count == 1 { "" } else { "s" });
+        let hidden = self.chibis.iter().filter(|c| !c.visible).count();
+        if hidden > 0 {
+            line.push_str(&format!(" ({} hidden)", hidden));
+        }
+        if self.is_hidden {
+            line.push_str(" · Hide All on");
+        }
+        if let Some(scene) = &self.active_scene {
+            line.push_str(&format!(" · Scene: {}", scene));
+        }
+        line
+    }
+
+    fn run_action(&mut self, action: TrayAction) {
+        let msg = match action {
+            TrayAction::Nothing => return,
+            TrayAction::OpenManager => AppMsg::ToggleManager,
+            TrayAction::ToggleHide => {
+                self.is_hidden = !self.is_hidden;
+                AppMsg::SetHideAll(self.is_hidden)
+            }
+            TrayAction::SpawnFavorite => match &self.favorite {
+                Some(id) => AppMsg::SpawnPreset(id.clone()),
+                None => AppMsg::SpawnRandom(None),
+            },
+            TrayAction::Quit => AppMsg::Quit,
+        };
+        let _ = self.sender.send(msg);
+    }
+}
+
+#[cfg(feature = "tray")]
+impl Tray for ChibiTray {
+    fn id(&self) -> String { "chibi-manager".into() }
+    fn category(&self) -> ksni::Category { ksni::Category::ApplicationStatus }
+    fn title(&self) -> String { "Chibi Manager".into() }
+    fn status(&self) -> ksni::Status { ksni::Status::Active }
+    // Hosts prefer a theme icon when both are set, so only name one without a pixmap
+    fn icon_name(&self) -> String {
+        if !self.icon.is_empty() { return String::new(); }
+        if self.is_hidden { "face-plain".into() } else { "face-smile".into() }
+    }
+    fn icon_pixmap(&self) -> Vec<ksni::Icon> {
+        if !self.is_hidden { return self.icon.clone(); }
+        // Faded copy of the custom icon while everything is hidden
+        self.icon.iter().map(|icon| {
+            let mut data = icon.data.clone();
+            for px in data.chunks_exact_mut(4) { px[0] /= 3; }
+            ksni::Icon { width: icon.width, height: icon.height, data }
+        }).collect()
+    }
+
+    // No StatusNotifier host: keep the service alive in case one shows up later
+    fn watcher_online(&self) {
+        let _ = self.sender.send(AppMsg::TrayAvailable(true));
+    }
+    fn watcher_offine(&self) -> bool {
+        let _ = self.sender.send(AppMsg::TrayAvailable(false));
+        true
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.run_action(self.primary_action);
+    }
+    // SNI's "secondary" activation is the middle button
+    fn secondary_activate(&mut self, _x: i32, _y: i32) {
+        self.run_action(self.middle_action);
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.icon_label.clone().unwrap_or_else(|| "Chibi Manager".into()),
+            description: self.status_line(),
+            icon_name: self.icon_name(),
+            icon_pixmap: self.icon_pixmap(),
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut spawn_items: Vec<MenuItem<Self>> = self.presets.iter().map(|(id, name)| {
+            let id = id.clone();
+            StandardItem {
+                label: name.replace('_', "__"),
+                activate: Box::new(move |this: &mut Self| {
+                    let _ = this.sender.send(AppMsg::SpawnPreset(id.clone()));
+                }),
+                ..Default::default()
+            }.into()
+        }).collect();
+        if spawn_items.is_empty() {
+            spawn_items.push(StandardItem {
+                label: "No saved presets".into(),
+                enabled: false,
+                ..Default::default()
+            }.into());
+        }
+
+        let mut chibi_items: Vec<MenuItem<Self>> = self.chibis.iter().map(|c| {
+            let key = c.key;
+            CheckmarkItem {
+                label: c.label.replace('_', "__"),
+                checked: c.visible,
+                activate: Box::new(move |this: &mut Self| {
+                    let _ = this.sender.send(AppMsg::ToggleChibi(key));
+                }),
+                ..Default::default()
+            }.into()
+        }).collect();
+        let ghost_items: Vec<MenuItem<Self>> = self.chibis.iter().map(|c| {
+            let key = c.key;
+            CheckmarkItem {
+                label: c.label.replace('_', "__"),
+                checked: c.click_through,
+                activate: Box::new(move |this: &mut Self| {
+                    let _ = this.sender.send(AppMsg::ToggleClickThrough(key));
+                }),
+                ..Default::default()
+            }.into()
+        }).collect();
+        let pomodoro_items: Vec<MenuItem<Self>> = self.chibis.iter().filter(|c| c.pomodoro).map(|c| {
+            let key = c.key;
+            SubMenu {
+                label: c.label.replace('_', "__"),
+                submenu: pomodoro::Action::ALL.iter().map(|&action| StandardItem {
+                    label: action.label().into(),
+                    activate: Box::new(move |this: &mut Self| {
+                        let _ = this.sender.send(AppMsg::Pomodoro(key, action));
+                    }),
+                    ..Default::default()
+                }.into()).collect(),
+                ..Default::default()
+            }.into()
+        }).collect();
+        if chibi_items.is_empty() {
+            chibi_items.push(StandardItem {
+                label: "No active chibis".into(),
+                enabled: false,
+                ..Default::default()
+            }.into());
+        }
+
+        vec![
+            StandardItem {
+                label: "Open Manager".into(),
+                activate: Box::new(move |this: &mut Self| {
+                    let _ = this.sender.send(AppMsg::ToggleManager);
+                }),
+                ..Default::default()
+            }.into(),
+            SubMenu {
+                label: "Spawn Preset".into(),
+                submenu: spawn_items,
+                ..Default::default()
+            }.into(),
+            SubMenu {
+                label: "Scenes".into(),
+                visible: !self.scenes.is_empty(),
+                submenu: vec![RadioGroup {
+                    // Out of range leaves every item unchecked
+                    selected: self.active_scene.as_ref()
+                        .and_then(|a| self.scenes.iter().position(|s| s == a))
+                        .unwrap_or(self.scenes.len()),
+                    select: Box::new(|this: &mut Self, idx| {
+                        if let Some(name) = this.scenes.get(idx) {
+                            let _ = this.sender.send(AppMsg::ActivateScene(name.clone()));
+                        }
+                    }),
+                    options: self.scenes.iter().map(|name| RadioItem {
+                        label: name.replace('_', "__"),
+                        ..Default::default()
+                    }).collect(),
+                }.into()],
+                ..Default::default()
+            }.into(),
+            StandardItem {
+                label: "Spawn Random".into(),
+                enabled: !self.presets.is_empty(),
+                activate: Box::new(move |this: &mut Self| {
+                    let _ = this.sender.send(AppMsg::SpawnRandom(None));
+                }),
+                ..Default::default()
+            }.into(),
+            SubMenu {
+                label: "Spawn Random by Tag".into(),
+                visible: !self.tags.is_empty(),
+                submenu: self.tags.iter().map(|tag| {
+                    let tag = tag.clone();
+                    StandardItem {
+                        label: format!("#{}", tag.replace('_', "__")),
+                        activate: Box::new(move |this: &mut Self| {
+                            let _ = this.sender.send(AppMsg::SpawnRandom(Some(tag.clone())));
+                        }),
+                        ..Default::default()
+                    }.into()
+                }).collect(),
+                ..Default::default()
+            }.into(),
+            SubMenu {
+                label: "Active Chibis".into(),
+                submenu: chibi_items,
+                ..Default::default()
+            }.into(),
+            SubMenu {
+                label: "Click-through".into(),
+                visible: !ghost_items.is_empty(),
+                submenu: ghost_items,
+                ..Default::default()
+            }.into(),
+            SubMenu {
+                label: "Pomodoro".into(),
+                visible: !pomodoro_items.is_empty(),
+                submenu: pomodoro_items,
+                ..Default::default()
+            }.into(),
+            CheckmarkItem {
+                label: "Hide All Chibis".into(),
+                checked: self.is_hidden,
+                activate: Box::new(move |this: &mut Self| {
+                    this.is_hidden = !this.is_hidden;
+                    let _ = this.sender.send(AppMsg::SetHideAll(this.is_hidden));
+                }),
+                ..Default::default()
+            }.into(),
+            CheckmarkItem {
+                label: "Hide on Fullscreen".into(),
+                checked: self.hide_on_fullscreen,
+                activate: Box::new(move |this: &mut Self| {
+                    this.hide_on_fullscreen = !this.hide_on_fullscreen;
+                    let _ = this.sender.send(AppMsg::SetHideOnFullscreen(this.hide_on_fullscreen));
+                }),
+                ..Default::default()
+            }.into(),
+            CheckmarkItem {
+                label: "Hide While Screen Sharing".into(),
+                checked: self.hide_while_screencasting,
+                activate: Box::new(move |this: &mut Self| {
+                    this.hide_while_screencasting = !this.hide_while_screencasting;
+                    let _ = this.sender.send(AppMsg::SetHideWhileScreencasting(this.hide_while_screencasting));
+                }),
+                ..Default::default()
+            }.into(),
+            CheckmarkItem {
+                label: "Mute Sounds".into(),
+                checked: self.is_muted,
+                activate: Box::new(move |this: &mut Self| {
+                    this.is_muted = !this.is_muted;
+                    let _ = this.sender.send(AppMsg::SetMuted(this.is_muted));
+                }),
+                ..Default::default()
+            }.into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Quit".into(),
+                activate: Box::new(move |this: &mut Self| {
+                    let _ = this.sender.send(AppMsg::Quit);
+                }),
+                ..Default::default()
+            }.into(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app_channel, AppReceiver};
+
+    fn tray() -> (ChibiTray, AppReceiver) {
+        let (sender, rx) = app_channel();
+        let tray = ChibiTray {
+            sender,
+            is_hidden: false,
+            hide_on_fullscreen: false,
+            hide_while_screencasting: false,
+            is_muted: false,
+            presets: Vec::new(),
+            tags: Vec::new(),
+            scenes: Vec::new(),
+            active_scene: None,
+            chibis: Vec::new(),
+            primary_action: TrayAction::ToggleHide,
+            middle_action: TrayAction::OpenManager,
+            favorite: None,
+            icon: Vec::new(),
+            icon_label: None,
+        };
+        (tray, rx)
+    }
+
+    #[test]
+    fn actions_send_messages() {
+        let (mut tray, rx) = tray();
+        tray.run_action(TrayAction::ToggleHide);
+        assert!(tray.is_hidden);
+        assert!(matches!(rx.try_recv(), Ok(AppMsg::SetHideAll(true))));
+
+        tray.run_action(TrayAction::SpawnFavorite);
+        assert!(matches!(rx.try_recv(), Ok(AppMsg::SpawnRandom(None))));
+        tray.favorite = Some("abc".into());
+        tray.run_action(TrayAction::SpawnFavorite);
+        assert!(matches!(rx.try_recv(), Ok(AppMsg::SpawnPreset(id)) if id == "abc"));
+
+        tray.run_action(TrayAction::Nothing);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn status_line_counts_hidden_chibis() {
+        let (mut tray, _rx) = tray();
+        assert_eq!(tray.status_line(), "0 active chibis");
+        let chibi = |key, label: &str, visible| TrayChibi { key, label: label.into(), visible, click_through: false, pomodoro: false };
+        tray.chibis = vec![chibi(1, "Cat", true), chibi(2, "Dog", false)];
+        tray.is_hidden = true;
+        tray.active_scene = Some("Work".into());
+        assert_eq!(tray.status_line(), "2 active chibis (1 hidden) · Hide All on · Scene: Work");
+    }
+}
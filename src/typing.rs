@@ -0,0 +1,225 @@
+use gtk::{gio, glib};
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::animation::{self, Frame};
+use crate::window::ChibiWindow;
+
+// --- TYPING (BONGO CAT) ---
+// Chibis that tap along while you type: every key press shows the next typing frame, and a
+// moment without one brings the regular image back. Presses are read straight from the
+// keyboards' evdev nodes (/dev/input/event*), which works the same under any compositor but
+// needs read access to them - usually by being in the `input` group. Only that a key went
+// down is counted, never which one.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TypingReaction {
+    // Images stepped through, one per key press, in order
+    pub frames: Vec<PathBuf>,
+    // Back to the regular image after this long without a press
+    pub idle_ms: u32,
+}
+
+impl Default for TypingReaction {
+    fn default() -> Self {
+        TypingReaction { frames: Vec::new(), idle_ms: 400 }
+    }
+}
+
+// Key presses seen on any keyboard since the app started
+static PRESSES: AtomicU64 = AtomicU64::new(0);
+// Event nodes with a reader thread on them
+static OPEN: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+static DENIED: AtomicBool = AtomicBool::new(false);
+
+const DEVICES: &str = "/proc/bus/input/devices";
+// udev sets a new node's permissions just after it appears, so a plug-in settles a moment first
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+thread_local! {
+    // Watches /dev/input for keyboards plugged in later; kept for the app's lifetime
+    static HOTPLUG: RefCell<Option<gio::FileMonitor>> = const { RefCell::new(None) };
+}
+
+const EV_KEY: u16 = 0x01;
+// Keyboards repeat keys; mice and most buttons-only devices don't
+const EV_KEY_BIT: u64 = 1 << EV_KEY;
+const EV_REP_BIT: u64 = 1 << 0x14;
+
+// The event nodes of the keyboards in /proc/bus/input/devices
+fn keyboards(devices: &str) -> Vec<PathBuf> {
+    devices.split("\n\n").filter_map(|block| {
+        let mut event = None;
+        let mut ev_bits = 0;
+        for line in block.lines() {
+            if let Some(handlers) = line.strip_prefix("H: Handlers=") {
+                event = handlers.split_whitespace().find(|h| h.starts_with("event")).map(str::to_string);
+            } else if let Some(bits) = line.strip_prefix("B: EV=") {
+                ev_bits = u64::from_str_radix(bits.trim(), 16).unwrap_or(0);
+            }
+        }
+        let is_keyboard = ev_bits & (EV_KEY_BIT | EV_REP_BIT) == EV_KEY_BIT | EV_REP_BIT;
+        event.filter(|_| is_keyboard).map(|e| PathBuf::from("/dev/input").join(e))
+    }).collect()
+}
+
+// struct input_event: a timeval, then u16 type, u16 code and i32 value
+#[cfg(target_pointer_width = "64")]
+const EVENT_SIZE: usize = 24;
+#[cfg(not(target_pointer_width = "64"))]
+const EVENT_SIZE: usize = 16;
+
+// A key going down (1); releases are 0 and auto-repeats 2
+fn is_press(event: &[u8; EVENT_SIZE]) -> bool {
+    let at = EVENT_SIZE - 8;
+    let kind = u16::from_ne_bytes([event[at], event[at + 1]]);
+    let value = i32::from_ne_bytes([event[at + 4], event[at + 5], event[at + 6], event[at + 7]]);
+    kind == EV_KEY && value == 1
+}
+
+// Counts presses until the device goes away
+fn read_device(path: PathBuf, mut file: File) {
+    let mut event = [0u8; EVENT_SIZE];
+    while file.read_exact(&mut event).is_ok() {
+        if is_press(&event) { PRESSES.fetch_add(1, Ordering::Relaxed); }
+    }
+    tracing::debug!("Stopped reading {}", path.display());
+    if let Some(open) = OPEN.lock().unwrap().as_mut() { open.remove(&path); }
+}
+
+// Opens keyboards that aren't being read yet, including ones plugged in since the last scan
+fn scan() {
+    let devices = std::fs::read_to_string(DEVICES).unwrap_or_default();
+    for path in keyboards(&devices) {
+        let mut open = OPEN.lock().unwrap();
+        let open = open.get_or_insert_with(HashSet::new);
+        if open.contains(&path) { continue; }
+        match File::open(&path) {
+            Ok(file) => {
+                tracing::info!("Watching {} for typing", path.display());
+                open.insert(path.clone());
+                std::thread::spawn(move || read_device(path, file));
+            }
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                if !DENIED.swap(true, Ordering::Relaxed) {
+                    tracing::warn!("Can't read {} for typing chibis; add yourself to the `input` group (and log in again)", path.display());
+                }
+            }
+            Err(e) => tracing::warn!("Can't read {}: {}", path.display(), e),
+        }
+    }
+}
+
+// Starts counting key presses, once; later keyboards are picked up as /dev/input changes
+fn listen() {
+    if HOTPLUG.with(|h| h.borrow().is_some()) { return; }
+    scan();
+    let monitor = match gio::File::for_path("/dev/input").monitor_directory(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            tracing::warn!("Keyboards plugged in later won't be seen: {}", e);
+            return;
+        }
+    };
+    let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+    monitor.connect_changed(move |_, _, _, event| {
+        if !matches!(event, gio::FileMonitorEvent::Created | gio::FileMonitorEvent::AttributeChanged) { return; }
+        if let Some(src) = pending.borrow_mut().take() { src.remove(); }
+        let pending_tmr = pending.clone();
+        *pending.borrow_mut() = Some(glib::timeout_add_local_once(SETTLE_TIME, move || {
+            pending_tmr.borrow_mut().take();
+            scan();
+        }));
+    });
+    HOTPLUG.with(|h| *h.borrow_mut() = Some(monitor));
+}
+
+pub fn presses() -> u64 {
+    PRESSES.load(Ordering::Relaxed)
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+
+// Taps along until the window goes away
+pub fn start(win: &ChibiWindow, typing: &TypingReaction) {
+    let frames: Vec<Frame> = typing.frames.iter().filter_map(|path| win.load_frame(path)
+        .map_err(|e| tracing::warn!("Skipping typing frame {}: {}", path.display(), e))
+        .ok())
+        .collect();
+    if frames.is_empty() { return; }
+    listen();
+    let idle = Duration::from_millis(typing.idle_ms.max(50) as u64);
+    let seen = Cell::new(presses());
+    let last_press = Cell::new(Instant::now());
+    let next = Cell::new(0);
+    // What to show again once typing stops; Some while tapping
+    let resting: RefCell<Option<Frame>> = RefCell::new(None);
+    animation::every_while_shown(win, POLL_INTERVAL, move |w| {
+        let count = presses();
+        if count != seen.replace(count) {
+            last_press.set(Instant::now());
+            if w.move_mode() { return glib::ControlFlow::Continue; }
+            if resting.borrow().is_none() { resting.replace(w.frame()); }
+            w.show_frame(&frames[next.get() % frames.len()]);
+            next.set(next.get() + 1);
+        } else if last_press.get().elapsed() >= idle {
+            if let Some(frame) = resting.take() { w.show_frame(&frame); }
+            next.set(0);
+        }
+        glib::ControlFlow::Continue
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_keyboards() {
+        let devices = "\
+I: Bus=0011 Vendor=0001 Product=0001 Version=ab41
+N: Name=\"AT Translated Set 2 keyboard\"
+H: Handlers=sysrq kbd leds event3
+B: PROP=0
+B: EV=120013
+
+I: Bus=0003 Vendor=046d Product=c077 Version=0111
+N: Name=\"Logitech USB Optical Mouse\"
+H: Handlers=mouse0 event5
+B: EV=17
+
+I: Bus=0019 Vendor=0000 Product=0001 Version=0000
+N: Name=\"Power Button\"
+H: Handlers=kbd event1
+B: EV=3
+";
+        assert_eq!(keyboards(devices), vec![PathBuf::from("/dev/input/event3")]);
+        assert!(keyboards("").is_empty());
+    }
+
+    #[test]
+    fn counts_only_presses() {
+        let event = |kind: u16, value: i32| {
+            let mut e = [0u8; EVENT_SIZE];
+            let at = EVENT_SIZE - 8;
+            e[at..at + 2].copy_from_slice(&kind.to_ne_bytes());
+            e[at + 2..at + 4].copy_from_slice(&30u16.to_ne_bytes());
+            e[at + 4..].copy_from_slice(&value.to_ne_bytes());
+            e
+        };
+        assert!(is_press(&event(EV_KEY, 1)));
+        assert!(!is_press(&event(EV_KEY, 0)));
+        assert!(!is_press(&event(EV_KEY, 2)));
+        assert!(!is_press(&event(0x04, 1)));
+    }
+}
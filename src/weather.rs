@@ -0,0 +1,256 @@
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::window::ChibiWindow;
+
+// --- WEATHER ---
+// Current conditions from Open-Meteo (no API key), fetched with curl on a background thread
+// for the location in Preferences. Chibis can say the weather in their bubble and switch to
+// a rain or snow image while it lasts.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct WeatherSettings {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub refresh_minutes: u32,
+    pub fahrenheit: bool,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        WeatherSettings { latitude: 0.0, longitude: 0.0, refresh_minutes: 30, fahrenheit: false }
+    }
+}
+
+impl WeatherSettings {
+    fn url(&self) -> String {
+        format!(
+            "https://api.open-meteo.com/v1/forecast?latitude={:.4}&longitude={:.4}&current=temperature_2m,weather_code&temperature_unit={}",
+            self.latitude, self.longitude, if self.fahrenheit { "fahrenheit" } else { "celsius" },
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conditions {
+    Clear,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+}
+
+impl Conditions {
+    // WMO weather interpretation codes, as Open-Meteo reports them
+    pub fn from_code(code: u32) -> Conditions {
+        match code {
+            0 | 1 => Conditions::Clear,
+            45 | 48 => Conditions::Fog,
+            51..=67 | 80..=82 => Conditions::Rain,
+            71..=77 | 85 | 86 => Conditions::Snow,
+            95..=99 => Conditions::Storm,
+            _ => Conditions::Cloudy,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Conditions::Clear => "clear ☀",
+            Conditions::Cloudy => "cloudy ☁",
+            Conditions::Fog => "foggy 🌫",
+            Conditions::Rain => "raining ☔",
+            Conditions::Snow => "snowing ❄",
+            Conditions::Storm => "stormy ⛈",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weather {
+    pub temperature: f64,
+    pub conditions: Conditions,
+    pub fahrenheit: bool,
+}
+
+impl Weather {
+    pub fn parse(json: &str, fahrenheit: bool) -> Result<Weather, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let current = &value["current"];
+        let temperature = current["temperature_2m"].as_f64().ok_or("no temperature in the reply")?;
+        let code = current["weather_code"].as_u64().ok_or("no weather code in the reply")?;
+        Ok(Weather { temperature, conditions: Conditions::from_code(code as u32), fahrenheit })
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{:.0}°{}, {}", self.temperature, if self.fahrenheit { "F" } else { "C" }, self.conditions.label())
+    }
+}
+
+fn fetch(settings: &WeatherSettings) -> Result<Weather, String> {
+    let out = Command::new("curl")
+        .args(["-fsS", "--max-time", "20", &settings.url()])
+        .output()
+        .map_err(|e| format!("can't run curl: {}", e))?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    Weather::parse(&String::from_utf8_lossy(&out.stdout), settings.fahrenheit)
+}
+
+// None switches the provider off
+static CONFIG: Mutex<Option<WeatherSettings>> = Mutex::new(None);
+// Wakes the watcher when the settings change
+static CHANGED: Condvar = Condvar::new();
+
+pub fn configure(settings: Option<WeatherSettings>) {
+    *CONFIG.lock().unwrap() = settings;
+    CHANGED.notify_all();
+}
+
+// Fetches in a background thread whenever the refresh interval is up or the settings change;
+// in between (and the whole time it's off) the thread just waits
+pub fn spawn_watcher<F>(on_update: F)
+where
+    F: Fn(Weather) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut last: Option<(WeatherSettings, Instant)> = None;
+        let mut config = CONFIG.lock().unwrap();
+        loop {
+            let Some(current) = config.clone() else {
+                config = CHANGED.wait(config).unwrap();
+                continue;
+            };
+            let refresh = Duration::from_secs(current.refresh_minutes.max(5) as u64 * 60);
+            let wait = match &last {
+                Some((was, at)) if *was == current => refresh.saturating_sub(at.elapsed()),
+                _ => Duration::ZERO,
+            };
+            if !wait.is_zero() {
+                config = CHANGED.wait_timeout(config, wait).unwrap().0;
+                continue;
+            }
+            // Not holding the lock through curl, so Preferences never waits on the network
+            drop(config);
+            match fetch(&current) {
+                Ok(weather) => on_update(weather),
+                Err(e) => tracing::warn!("Couldn't get the weather: {}", e),
+            }
+            last = Some((current, Instant::now()));
+            config = CONFIG.lock().unwrap();
+        }
+    });
+}
+
+type Listener = Box<dyn Fn(&Weather) -> bool>;
+
+thread_local! {
+    static CURRENT: Cell<Option<Weather>> = const { Cell::new(None) };
+    static LISTENERS: RefCell<Vec<Listener>> = const { RefCell::new(Vec::new()) };
+}
+
+// New weather from the watcher, on the main thread
+pub fn set_current(weather: Weather) {
+    CURRENT.with(|c| c.set(Some(weather)));
+    let listeners = LISTENERS.with(|l| std::mem::take(&mut *l.borrow_mut()));
+    let kept: Vec<Listener> = listeners.into_iter().filter(|l| l(&weather)).collect();
+    LISTENERS.with(|l| { l.borrow_mut().splice(0..0, kept); });
+}
+
+pub fn current() -> Option<Weather> {
+    CURRENT.with(|c| c.get())
+}
+
+// Calls `on_change` with each new reading (and the current one, if any) until it returns false
+pub fn watch(on_change: impl Fn(&Weather) -> bool + 'static) {
+    if current().is_some_and(|w| !on_change(&w)) { return; }
+    LISTENERS.with(|l| l.borrow_mut().push(Box::new(on_change)));
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct WeatherReaction {
+    // Says the weather in the bubble when it changes, and when clicked
+    pub report: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rain_image: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snow_image: Option<PathBuf>,
+}
+
+impl WeatherReaction {
+    // Storms get the umbrella too
+    pub fn image_for(&self, conditions: Conditions) -> Option<&Path> {
+        match conditions {
+            Conditions::Rain | Conditions::Storm => self.rain_image.as_deref(),
+            Conditions::Snow => self.snow_image.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+const REPORT_TIME: Duration = Duration::from_secs(6);
+
+// Keeps following the weather until the window goes away
+pub fn start(win: &ChibiWindow, reaction: &WeatherReaction, image: &Path) {
+    let (reaction, image) = (reaction.clone(), image.to_path_buf());
+    let last: Cell<Option<Weather>> = Cell::new(None);
+    let win_weak = win.downgrade();
+    let report = reaction.report;
+    watch(move |weather| {
+        let Some(w) = win_weak.upgrade() else { return false };
+        let before = last.replace(Some(*weather));
+        if before.map(|b| b.conditions) != Some(weather.conditions) {
+            w.set_image(reaction.image_for(weather.conditions).unwrap_or(&image));
+        }
+        if reaction.report && before != Some(*weather) && w.is_visible() {
+            w.flash_bubble(&weather.describe(), REPORT_TIME);
+        }
+        true
+    });
+    if report {
+        win.connect_clicked(|w| {
+            if let Some(weather) = current() { w.flash_bubble(&weather.describe(), REPORT_TIME); }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_meteo_reply() {
+        let json = r#"{"latitude":52.5,"current":{"time":"2024-05-01T12:00","temperature_2m":14.6,"weather_code":61}}"#;
+        let w = Weather::parse(json, false).unwrap();
+        assert_eq!(w.conditions, Conditions::Rain);
+        assert_eq!(w.describe(), "15°C, raining ☔");
+        assert!(Weather::parse(r#"{"error":true,"reason":"bad latitude"}"#, false).is_err());
+    }
+
+    #[test]
+    fn weather_codes() {
+        assert_eq!(Conditions::from_code(0), Conditions::Clear);
+        assert_eq!(Conditions::from_code(3), Conditions::Cloudy);
+        assert_eq!(Conditions::from_code(73), Conditions::Snow);
+        assert_eq!(Conditions::from_code(81), Conditions::Rain);
+        assert_eq!(Conditions::from_code(96), Conditions::Storm);
+        let r = WeatherReaction { rain_image: Some("umbrella.png".into()), ..Default::default() };
+        assert_eq!(r.image_for(Conditions::Storm), Some(Path::new("umbrella.png")));
+        assert_eq!(r.image_for(Conditions::Snow), None);
+    }
+
+    #[test]
+    fn url_has_units() {
+        let s = WeatherSettings { latitude: 52.52, longitude: 13.405, fahrenheit: true, ..Default::default() };
+        assert!(s.url().contains("latitude=52.5200&longitude=13.4050"));
+        assert!(s.url().ends_with("temperature_unit=fahrenheit"));
+    }
+}
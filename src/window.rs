@@ -0,0 +1,1087 @@
+use gtk::{cairo, gdk, gio, glib};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{Application, EventControllerMotion, EventControllerScroll, EventControllerScrollFlags, GestureClick, GestureLongPress, GestureZoom, Overlay, Picture, PopoverMenu};
+#[cfg(feature = "layer-shell")]
+use gtk4_layer_shell::{Edge, Layer, LayerShell};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::animation::{self, Frame};
+use crate::movement::{self, Fling, VelocityTracker};
+use crate::particles::{ParticleKind, ParticleLayer};
+use crate::preset::ChibiPreset;
+#[cfg(feature = "x11")]
+use crate::x11;
+
+// --- CHIBI WINDOW ---
+// Layer-shell window showing one chibi. Position and size are properties backed by the
+// layer margins/size request, so whoever holds the window reads the live geometry from it.
+// Without layer-shell (X11, GNOME on Wayland) it is an undecorated toplevel instead; see Placement.
+//
+// Properties: x, y (margins from the top-left), size, move-mode (dragging enabled),
+// always-on-top (overlay instead of bottom layer), smart-hide (hide on hover),
+// click-through (takes no input at all, purely decorative), hidden (tucked away by smart hide).
+// Signals: moved(x, y) after a drag (once a fling has settled), hidden(bool) when smart hide hides/reveals it,
+// clicked() for plain clicks outside move mode (once per double click), double-clicked() on
+// the second click of a double click, middle-clicked() for the middle button, files-dropped(list).
+// A right click (or a long press on touch) opens the context menu, if set; the scroll
+// wheel and pinching resize the chibi.
+// Only the opaque pixels of the image take input (see AlphaMask), the rest clicks through.
+// Walking chibis are anchored to the bottom of the screen and mirrored to face where they go.
+// An optional top layer is drawn over the image at an adjustable offset (see gaze.rs),
+// and particles (hearts, z's, sparkles) over everything.
+
+// Same range as the manager's size controls
+pub const MIN_SIZE: i32 = 50;
+pub const MAX_SIZE: i32 = 1000;
+const SCROLL_STEP: i32 = 20;
+
+mod imp {
+    use super::*;
+    use glib::subclass::Signal;
+    use glib::Properties;
+    use std::sync::OnceLock;
+
+    #[derive(Properties, Default)]
+    #[properties(wrapper_type = super::ChibiWindow)]
+    pub struct ChibiWindow {
+        #[property(get, set = Self::set_x)]
+        x: Cell<f64>,
+        #[property(get, set = Self::set_y)]
+        y: Cell<f64>,
+        #[property(get, set = Self::set_size)]
+        size: Cell<i32>,
+        #[property(get, set)]
+        move_mode: Cell<bool>,
+        #[property(get, set = Self::set_always_on_top)]
+        always_on_top: Cell<bool>,
+        #[property(get, set)]
+        smart_hide: Cell<bool>,
+        #[property(get, set = Self::set_click_through)]
+        click_through: Cell<bool>,
+        #[property(get)]
+        pub(super) hidden: Cell<bool>,
+        // Kept out of sight by the registry (Hide All, fullscreen, workspace, idle); smart
+        // hide revealing the chibi doesn't bring it back while this is set
+        pub(super) held: Cell<bool>,
+
+        pub(super) placement: Cell<Placement>,
+
+        pub(super) picture: Picture,
+        // Holds the picture, with the top layer and the particles above it
+        pub(super) overlay: Overlay,
+        pub(super) layer: RefCell<Option<Picture>>,
+        // Stats in the corner, see debug.rs
+        pub(super) debug_label: RefCell<Option<gtk::Label>>,
+        pub(super) particles: ParticleLayer,
+        pub(super) layer_offset: Cell<(i32, i32)>,
+        // What the picture shows; None if GTK had to load the file itself
+        pub(super) frame: RefCell<Option<Frame>>,
+        pub(super) flipped: Cell<bool>,
+        // Sits on the bottom edge of the screen, ignoring y
+        pub(super) bottom: Cell<bool>,
+        pub(super) menu: RefCell<Option<PopoverMenu>>,
+        // Speech-bubble popover, made when first shown
+        pub(super) bubble: RefCell<Option<Bubble>>,
+        pub(super) dragging: Cell<bool>,
+        // Pointer position inside the window when the drag started
+        pub(super) anchor: Cell<(f64, f64)>,
+        // Where the pointer has dragged it since the last frame; motion events come faster
+        // than frames, and every move is a surface reconfigure, so it's applied once a frame
+        pub(super) drag_to: Cell<Option<(f64, f64)>>,
+        // Where the drag has taken the window lately, for flinging it on release
+        pub(super) track: RefCell<VelocityTracker>,
+        // Ran into another chibi since the mover last looked (see movement::Crowd)
+        pub(super) bumped: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ChibiWindow {
+        const NAME: &'static str = "ChibiWindow";
+        type Type = super::ChibiWindow;
+        type ParentType = gtk::Window;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for ChibiWindow {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![
+                Signal::builder("moved").param_types([i32::static_type(), i32::static_type()]).build(),
+                Signal::builder("hidden").param_types([bool::static_type()]).build(),
+                Signal::builder("clicked").build(),
+                Signal::builder("double-clicked").build(),
+                Signal::builder("middle-clicked").build(),
+                Signal::builder("files-dropped").param_types([gdk::FileList::static_type()]).build(),
+            ])
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            obj.add_css_class("ghost-window");
+            obj.add_css_class("chibi-window");
+            let placement = placement();
+            self.placement.set(placement);
+            let w_weak = obj.downgrade();
+            init_surface(obj.upcast_ref(), placement, move |win| {
+                let Some(w) = w_weak.upgrade() else { return };
+                w.imp().place();
+                raise_surface(win, placement, w.always_on_top());
+            });
+
+            self.picture.set_content_fit(gtk::ContentFit::Contain);
+            self.picture.set_vexpand(true);
+            self.picture.set_hexpand(true);
+            self.picture.set_can_target(true);
+            self.overlay.set_child(Some(&self.picture));
+            let w_weak = obj.downgrade();
+            self.overlay.connect_get_child_position(move |overlay, child| {
+                let w = w_weak.upgrade()?;
+                let layer = w.imp().layer.borrow().clone()?;
+                if child != layer.upcast_ref::<gtk::Widget>() { return None; }
+                let (dx, dy) = w.imp().layer_offset.get();
+                Some(gdk::Rectangle::new(dx, dy, overlay.width(), overlay.height()))
+            });
+            self.overlay.add_overlay(&self.particles);
+            obj.set_child(Some(&self.overlay));
+            obj.setup_drag();
+            obj.setup_menu();
+            obj.setup_scroll();
+            obj.setup_touch();
+            obj.setup_drop();
+            obj.connect_scale_factor_notify(|w| w.refit_frame());
+            obj.connect_realize(|w| {
+                let Some(surface) = w.surface() else { return };
+                let w_weak = w.downgrade();
+                surface.connect_layout(move |_, _, _| {
+                    if let Some(w) = w_weak.upgrade() { w.update_input_region(); }
+                });
+            });
+        }
+
+        fn dispose(&self) {
+            if let Some(menu) = self.menu.take() { menu.unparent(); }
+            if let Some(mut bubble) = self.bubble.take() {
+                if let Some(flash) = bubble.flash.take() { flash.remove(); }
+                bubble.popover.unparent();
+            }
+        }
+    }
+
+    impl WidgetImpl for ChibiWindow {}
+    impl WindowImpl for ChibiWindow {}
+
+    impl ChibiWindow {
+        fn set_x(&self, x: f64) {
+            self.x.set(x);
+            self.place();
+        }
+
+        fn set_y(&self, y: f64) {
+            self.y.set(y);
+            self.place();
+        }
+
+        pub(super) fn place(&self) {
+            let obj = self.obj();
+            move_surface(obj.upcast_ref(), self.placement.get(), self.x.get() as i32, obj.placed_y());
+        }
+
+        // x and y at once, with one placement instead of one each
+        pub(super) fn set_position(&self, x: f64, y: f64) {
+            self.x.set(x);
+            self.y.set(y);
+            self.place();
+            let obj = self.obj();
+            obj.notify_x();
+            obj.notify_y();
+        }
+
+        // Square windows: shrinking needs both the request and the default size reset
+        fn set_size(&self, size: i32) {
+            let before = self.size.replace(size);
+            let obj = self.obj();
+            let scale = obj.scale_factor();
+            if animation::decode_size(before * scale) != animation::decode_size(size * scale) { obj.refit_frame(); }
+            obj.set_size_request(size, size);
+            obj.set_default_size(size, size);
+            // Growing from the bottom edge has to lift the window
+            if self.bottom.get() { self.place(); }
+        }
+
+        fn set_always_on_top(&self, on_top: bool) {
+            self.always_on_top.set(on_top);
+            raise_surface(self.obj().upcast_ref(), self.placement.get(), on_top);
+        }
+
+        fn set_click_through(&self, ghost: bool) {
+            self.click_through.set(ghost);
+            self.obj().update_input_region();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct ChibiWindow(ObjectSubclass<imp::ChibiWindow>)
+        @extends gtk::Window, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::Native, gtk::Root, gtk::ShortcutManager;
+}
+
+impl ChibiWindow {
+    // Builds the window for a preset; it still needs to be presented
+    pub fn new(app: &Application, data: &ChibiPreset) -> Self {
+        let window: Self = glib::Object::builder()
+        .property("application", app)
+        .property("decorated", false)
+        .property("resizable", false)
+        .property("title", &data.name)
+        .build();
+        window.set_x(data.x as f64);
+        window.set_y(data.y as f64);
+        window.set_size(data.width);
+        window.set_always_on_top(data.always_on_top);
+        window.set_image(&data.path);
+        window.set_smart_hide(data.smart_hide);
+        window.set_click_through(data.click_through);
+        window.setup_smart_hide(app, data.hover_threshold_ms, data.hide_delay.max(1));
+        window
+    }
+
+    // Decoded for the chibi's current size (see animation::ImageQuality)
+    pub fn load_frame(&self, path: &Path) -> Result<Frame, glib::Error> {
+        Frame::load(path, self.size() * self.scale_factor())
+    }
+
+    // Re-decodes what's showing after a resize took it past its decode size
+    fn refit_frame(&self) {
+        let Some(frame) = self.frame() else { return };
+        match self.load_frame(&frame.path) {
+            Ok(refit) if refit.scaled_to != frame.scaled_to => self.show_frame(&refit),
+            Ok(_) => {}
+            Err(e) => tracing::debug!("Couldn't re-decode {}: {}", frame.path.display(), e),
+        }
+    }
+
+    pub fn set_image(&self, path: &Path) {
+        match self.load_frame(path) {
+            Ok(frame) => self.show_frame(&frame),
+            Err(_) => {
+                // Whatever the picture makes of it; the whole window stays clickable
+                self.imp().frame.replace(None);
+                self.imp().picture.set_filename(Some(path));
+                self.update_input_region();
+            }
+        }
+    }
+
+    pub fn show_frame(&self, frame: &Frame) {
+        self.imp().picture.set_paintable(Some(&frame.texture));
+        self.imp().frame.replace(Some(frame.clone()));
+        self.update_input_region();
+    }
+
+    pub fn frame(&self) -> Option<Frame> {
+        self.imp().frame.borrow().clone()
+    }
+
+    // Mirrors the image (and top layer) left to right (see the .chibi-flipped style)
+    pub fn set_flipped(&self, flipped: bool) {
+        let imp = self.imp();
+        if imp.flipped.replace(flipped) == flipped { return; }
+        let layer = imp.layer.borrow().clone();
+        for picture in std::iter::once(imp.picture.clone()).chain(layer) {
+            if flipped {
+                picture.add_css_class("chibi-flipped");
+            } else {
+                picture.remove_css_class("chibi-flipped");
+            }
+        }
+        self.update_input_region();
+    }
+
+    // Small print over everything else in the top-left corner; None takes it away
+    pub fn set_debug_text(&self, text: Option<&str>) {
+        let imp = self.imp();
+        let Some(text) = text else {
+            if let Some(label) = imp.debug_label.take() { imp.overlay.remove_overlay(&label); }
+            return;
+        };
+        if let Some(label) = imp.debug_label.borrow().as_ref() {
+            label.set_text(text);
+            return;
+        }
+        let label = gtk::Label::builder()
+        .label(text)
+        .halign(gtk::Align::Start)
+        .valign(gtk::Align::Start)
+        .xalign(0.0)
+        .can_target(false)
+        .css_classes(["chibi-debug"])
+        .build();
+        imp.overlay.add_overlay(&label);
+        imp.debug_label.replace(Some(label));
+    }
+
+    // Second image drawn over the first, same size; it never takes input
+    pub fn set_top_layer(&self, path: Option<&Path>) {
+        let imp = self.imp();
+        if let Some(old) = imp.layer.take() { imp.overlay.remove_overlay(&old); }
+        let Some(path) = path else { return };
+        let layer = match self.load_frame(path) {
+            Ok(frame) => Picture::for_paintable(&frame.texture),
+            Err(_) => Picture::for_filename(path),
+        };
+        layer.set_content_fit(gtk::ContentFit::Contain);
+        layer.set_can_target(false);
+        if imp.flipped.get() { layer.add_css_class("chibi-flipped"); }
+        // Below the particles
+        imp.overlay.add_overlay(&layer);
+        imp.overlay.remove_overlay(&imp.particles);
+        imp.overlay.add_overlay(&imp.particles);
+        imp.layer.replace(Some(layer));
+    }
+
+    // Shifts the top layer by (dx, dy) px from where it lines up with the image
+    pub fn set_layer_offset(&self, dx: i32, dy: i32) {
+        let imp = self.imp();
+        if imp.layer_offset.replace((dx, dy)) == (dx, dy) { return; }
+        imp.overlay.queue_allocate();
+    }
+
+    // Pins the chibi to the bottom edge of its screen, or goes back to x/y placement
+    pub fn set_bottom_anchored(&self, bottom: bool) {
+        let imp = self.imp();
+        imp.bottom.set(bottom);
+        #[cfg(feature = "layer-shell")]
+        if imp.placement.get() == Placement::LayerShell {
+            self.set_anchor(Edge::Top, !bottom);
+            self.set_anchor(Edge::Bottom, bottom);
+            self.set_margin(Edge::Bottom, 0);
+        }
+        imp.place();
+    }
+
+    // Top edge in screen coordinates: y, or wherever bottom anchoring puts it
+    pub fn placed_y(&self) -> i32 {
+        if !self.imp().bottom.get() { return self.y() as i32; }
+        self.screen_area().map_or(self.y() as i32, |r| r.y() + r.height() - self.size())
+    }
+
+    // Another chibi is in the way; a wandering or walking chibi turns around
+    pub fn bump(&self) {
+        self.imp().bumped.set(true);
+    }
+
+    pub fn take_bump(&self) -> bool {
+        self.imp().bumped.replace(false)
+    }
+
+    pub fn emit_particles(&self, kind: ParticleKind, count: usize) {
+        let size = self.size() as f64;
+        self.imp().particles.emit(kind, count, (size, size));
+    }
+
+    // Emits `kind` every so often until set back to None (z's while asleep)
+    pub fn set_ambient_particles(&self, kind: Option<ParticleKind>) {
+        let size = self.size() as f64;
+        self.imp().particles.set_ambient(kind.map(|k| (k, (size, size))));
+    }
+
+    // Shrinks the image to `scale` of the window and lifts it `lift` px (see capture::Dance);
+    // the input region keeps the resting shape
+    pub fn set_picture_pose(&self, scale: f64, lift: i32) {
+        let picture = &self.imp().picture;
+        let inset = ((1.0 - scale.clamp(0.0, 1.0)) * self.size() as f64 / 2.0).round() as i32;
+        let lift = lift.clamp(0, inset);
+        picture.set_margin_start(inset);
+        picture.set_margin_end(inset);
+        picture.set_margin_top(inset - lift);
+        picture.set_margin_bottom(inset + lift);
+    }
+
+    // Puts a CSS class (e.g. a looping animation) on the picture, or takes it off
+    pub fn set_picture_class(&self, class: &str, on: bool) {
+        let picture = &self.imp().picture;
+        if on { picture.add_css_class(class); } else { picture.remove_css_class(class); }
+    }
+
+    // Puts a CSS class (e.g. an animation) on the picture for `lifetime`
+    pub fn flash_picture_class(&self, class: &str, lifetime: Duration) {
+        let picture = self.imp().picture.clone();
+        picture.add_css_class(class);
+        let class = class.to_string();
+        glib::timeout_add_local_once(lifetime, move || picture.remove_css_class(&class));
+    }
+
+    // Limits input to the opaque part of the image as currently laid out, or drops it entirely
+    fn update_input_region(&self) {
+        let Some(surface) = self.surface() else { return };
+        if self.click_through() {
+            surface.set_input_region(&cairo::Region::create());
+            return;
+        }
+        let (width, height) = (surface.width(), surface.height());
+        let mut rects = self.imp().frame.borrow().as_ref().map(|f| f.mask.opaque_rects(width, height)).unwrap_or_default();
+        if self.imp().flipped.get() {
+            for r in &mut rects { r.0 = width - r.0 - r.2; }
+        }
+        let region = if rects.is_empty() {
+            // Nothing opaque (or no mask): keep the chibi reachable rather than losing it entirely
+            cairo::Region::create_rectangle(&cairo::RectangleInt::new(0, 0, width, height))
+        } else {
+            let region = cairo::Region::create();
+            for (x, y, w, h) in rects {
+                let _ = region.union_rectangle(&cairo::RectangleInt::new(x, y, w, h));
+            }
+            region
+        };
+        surface.set_input_region(&region);
+    }
+
+    pub fn connect_moved<F: Fn(&Self, i32, i32) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("moved", false, move |values| {
+            let win = values[0].get::<Self>().ok()?;
+            f(&win, values[1].get().ok()?, values[2].get().ok()?);
+            None
+        })
+    }
+
+    pub fn connect_hidden<F: Fn(&Self, bool) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("hidden", false, move |values| {
+            let win = values[0].get::<Self>().ok()?;
+            f(&win, values[1].get().ok()?);
+            None
+        })
+    }
+
+    pub fn connect_clicked<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("clicked", false, move |values| {
+            f(&values[0].get::<Self>().ok()?);
+            None
+        })
+    }
+
+    pub fn connect_double_clicked<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("double-clicked", false, move |values| {
+            f(&values[0].get::<Self>().ok()?);
+            None
+        })
+    }
+
+    pub fn connect_middle_clicked<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("middle-clicked", false, move |values| {
+            f(&values[0].get::<Self>().ok()?);
+            None
+        })
+    }
+
+    // Local paths of the dropped files; remote ones are skipped
+    pub fn connect_files_dropped<F: Fn(&Self, Vec<PathBuf>) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("files-dropped", false, move |values| {
+            let win = values[0].get::<Self>().ok()?;
+            let files = values[1].get::<gdk::FileList>().ok()?;
+            f(&win, files.files().iter().filter_map(|file| file.path()).collect());
+            None
+        })
+    }
+
+    // Bubble above the chibi with a line of text and maybe a picture (album art, see mpris.rs);
+    // None takes it down
+    pub fn set_bubble(&self, content: Option<(&str, Option<&gdk::Texture>)>) {
+        let mut bubble = self.imp().bubble.borrow_mut();
+        if content.is_none() && bubble.is_none() { return; }
+        let bubble = bubble.get_or_insert_with(|| Bubble::new(self));
+        bubble.content = content.map(|(text, picture)| (text.to_string(), picture.cloned()));
+        if bubble.flash.is_none() { bubble.show_content(self); }
+    }
+
+    // Shows `text` in the bubble for `lifetime` (quotes, see quotes.rs), then goes back to
+    // whatever set_bubble put there
+    pub fn flash_bubble(&self, text: &str, lifetime: Duration) {
+        let mut bubble = self.imp().bubble.borrow_mut();
+        let bubble = bubble.get_or_insert_with(|| Bubble::new(self));
+        if let Some(old) = bubble.flash.take() { old.remove(); }
+        bubble.display(self, Some((text, None)));
+        let w_weak = self.downgrade();
+        bubble.flash = Some(glib::timeout_add_local_once(lifetime, move || {
+            let Some(w) = w_weak.upgrade() else { return };
+            let mut bubble = w.imp().bubble.borrow_mut();
+            let Some(bubble) = bubble.as_mut() else { return };
+            bubble.flash = None;
+            bubble.show_content(&w);
+        }));
+    }
+
+    // Right-click menu; its actions resolve against the action groups inserted on the window,
+    // and each (widget, id) pair fills the menu item whose "custom" attribute is that id
+    pub fn set_context_menu(&self, model: &gio::MenuModel, custom: &[(&gtk::Widget, &str)]) {
+        let menu = PopoverMenu::from_model(Some(model));
+        menu.set_has_arrow(false);
+        for (widget, id) in custom {
+            menu.add_child(*widget, id);
+        }
+        menu.set_parent(self);
+        if let Some(old) = self.imp().menu.replace(Some(menu)) { old.unparent(); }
+    }
+
+    // The registry's say in whether the chibi shows; it does only when smart hide agrees
+    pub fn set_held(&self, held: bool) {
+        let imp = self.imp();
+        imp.held.set(held);
+        self.set_visible(!held && !imp.hidden.get());
+    }
+
+    // Undoes smart hide, e.g. for dragging; a chibi the registry holds hidden stays hidden
+    pub fn reveal(&self) {
+        self.set_smart_hidden(false);
+    }
+
+    fn set_smart_hidden(&self, hidden: bool) {
+        let imp = self.imp();
+        let visible = !hidden && !imp.held.get();
+        self.set_visible(visible);
+        // The bubble comes back with the chibi
+        if visible {
+            if let Some(bubble) = imp.bubble.borrow().as_ref().filter(|b| b.up) { bubble.popover.popup(); }
+        }
+        if imp.hidden.replace(hidden) != hidden {
+            self.notify_hidden();
+            self.emit_by_name::<()>("hidden", &[&hidden]);
+        }
+    }
+
+    fn setup_drag(&self) {
+        let click = GestureClick::new();
+        let motion = EventControllerMotion::new();
+
+        let win_weak = self.downgrade();
+        click.connect_pressed(move |gesture, _, x, y| {
+            let Some(w) = win_weak.upgrade() else { return };
+            if !w.move_mode() { return; }
+            if w.imp().placement.get() == Placement::Floating {
+                // The compositor won't tell us where the window ends up, so it does the dragging
+                let toplevel = w.surface().and_then(|s| s.downcast::<gdk::Toplevel>().ok());
+                if let (Some(toplevel), Some(device)) = (toplevel, gesture.current_event_device()) {
+                    toplevel.begin_move(&device, gesture.current_button() as i32, x, y, gesture.current_event_time());
+                }
+                return;
+            }
+            w.imp().dragging.set(true);
+            w.imp().anchor.set((x, y));
+            w.imp().track.borrow_mut().reset();
+        });
+
+        let win_weak = self.downgrade();
+        click.connect_released(move |gesture, n_press, _, _| {
+            let Some(w) = win_weak.upgrade() else { return };
+            if w.imp().dragging.replace(false) {
+                w.apply_drag();
+                let (vx, vy) = w.imp().track.borrow().velocity(gesture.current_event_time());
+                if movement::fling_enabled() && vx.hypot(vy) >= movement::MIN_FLING_SPEED {
+                    w.fling((vx, vy));
+                } else {
+                    w.emit_by_name::<()>("moved", &[&(w.x() as i32), &(w.y() as i32)]);
+                }
+            } else if !w.move_mode() {
+                match n_press {
+                    1 => w.emit_by_name::<()>("clicked", &[]),
+                    2 => w.emit_by_name::<()>("double-clicked", &[]),
+                    _ => {}
+                }
+            }
+        });
+
+        let win_weak = self.downgrade();
+        motion.connect_motion(move |ctrl, x, y| {
+            let Some(w) = win_weak.upgrade() else { return };
+            if !w.imp().dragging.get() { return; }
+            let (ax, ay) = w.imp().anchor.get();
+            // Relative to where the window is now, not where it's headed
+            let to = (w.x() + x - ax, w.y() + y - ay);
+            w.imp().track.borrow_mut().push(ctrl.current_event_time(), to.0, to.1);
+            if w.imp().drag_to.replace(Some(to)).is_none() {
+                w.add_tick_callback(|w, _| {
+                    w.apply_drag();
+                    glib::ControlFlow::Break
+                });
+            }
+        });
+
+        self.add_controller(click);
+        self.add_controller(motion);
+    }
+
+    // Puts the chibi at (x, y) in one go; setting x and y one after the other places it twice
+    pub fn move_to(&self, x: f64, y: f64) {
+        self.imp().set_position(x, y);
+    }
+
+    // Moves to where the drag has got to since the last frame
+    fn apply_drag(&self) {
+        if let Some((x, y)) = self.imp().drag_to.take() { self.move_to(x, y); }
+    }
+
+    // Lets the chibi coast on after a drag; grabbing it again stops it
+    fn fling(&self, velocity: (f64, f64)) {
+        let size = self.size() as f64;
+        let bounds = self.screen_area()
+            .map(|r| (r.x() as f64, r.y() as f64, (r.x() + r.width()) as f64 - size, (r.y() + r.height()) as f64 - size))
+            .unwrap_or((f64::MIN, f64::MIN, f64::MAX, f64::MAX));
+        let fling = RefCell::new(Fling::new(self.x(), self.y(), velocity, bounds));
+        animation::add_paced_tick(self, move |w, dt| {
+            if w.imp().dragging.get() { return glib::ControlFlow::Break; }
+            let mut fling = fling.borrow_mut();
+            let moving = fling.step(dt);
+            w.move_to(fling.x.round(), fling.y.round());
+            if moving { return glib::ControlFlow::Continue; }
+            w.emit_by_name::<()>("moved", &[&(w.x() as i32), &(w.y() as i32)]);
+            glib::ControlFlow::Break
+        });
+    }
+
+    // The chibi's monitor in the coordinates x/y are measured in: layer-shell margins start at
+    // the monitor's corner, X11 positions are relative to the whole screen
+    pub fn screen_area(&self) -> Option<gdk::Rectangle> {
+        let surface = self.surface()?;
+        let geometry = surface.display().monitor_at_surface(&surface)?.geometry();
+        match self.imp().placement.get() {
+            #[cfg(feature = "layer-shell")]
+            Placement::LayerShell => Some(gdk::Rectangle::new(0, 0, geometry.width(), geometry.height())),
+            _ => Some(geometry),
+        }
+    }
+
+    fn setup_menu(&self) {
+        let middle = GestureClick::new();
+        middle.set_button(gdk::BUTTON_MIDDLE);
+        let win_weak = self.downgrade();
+        middle.connect_released(move |_, _, _, _| {
+            if let Some(w) = win_weak.upgrade() { w.emit_by_name::<()>("middle-clicked", &[]); }
+        });
+        self.add_controller(middle);
+
+        let click = GestureClick::new();
+        click.set_button(gdk::BUTTON_SECONDARY);
+        let win_weak = self.downgrade();
+        click.connect_pressed(move |gesture, _, x, y| {
+            let Some(w) = win_weak.upgrade() else { return };
+            if w.popup_menu(x, y) { gesture.set_state(gtk::EventSequenceState::Claimed); }
+        });
+        self.add_controller(click);
+    }
+
+    // False when there is no menu to show
+    fn popup_menu(&self, x: f64, y: f64) -> bool {
+        let Some(menu) = self.imp().menu.borrow().clone() else { return false };
+        menu.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+        menu.popup();
+        true
+    }
+
+    // Touch stand-ins: long press for the right click, pinch for the scroll wheel
+    fn setup_touch(&self) {
+        let long_press = GestureLongPress::new();
+        long_press.set_touch_only(true);
+        let win_weak = self.downgrade();
+        long_press.connect_pressed(move |gesture, x, y| {
+            let Some(w) = win_weak.upgrade() else { return };
+            if w.move_mode() { return; }
+            // Claiming cancels the click gesture, so lifting the finger isn't also a click
+            if w.popup_menu(x, y) { gesture.set_state(gtk::EventSequenceState::Claimed); }
+        });
+        self.add_controller(long_press);
+
+        let zoom = GestureZoom::new();
+        let start_size = Rc::new(Cell::new(0));
+        let win_weak = self.downgrade();
+        let start_begin = start_size.clone();
+        zoom.connect_begin(move |gesture, _| {
+            let Some(w) = win_weak.upgrade() else { return };
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+            // Two fingers down: whatever the first one started is no drag
+            w.imp().dragging.set(false);
+            start_begin.set(w.size());
+        });
+        let win_weak = self.downgrade();
+        zoom.connect_scale_changed(move |_, scale| {
+            let Some(w) = win_weak.upgrade() else { return };
+            let size = (start_size.get() as f64 * scale).round() as i32;
+            w.set_size(size.clamp(MIN_SIZE, MAX_SIZE));
+        });
+        self.add_controller(zoom);
+    }
+
+    fn setup_drop(&self) {
+        let drop = gtk::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+        let win_weak = self.downgrade();
+        drop.connect_drop(move |_, value, _, _| {
+            let (Some(w), Ok(files)) = (win_weak.upgrade(), value.get::<gdk::FileList>()) else { return false };
+            w.emit_by_name::<()>("files-dropped", &[&files]);
+            true
+        });
+        self.add_controller(drop);
+    }
+
+    fn setup_scroll(&self) {
+        let scroll = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL | EventControllerScrollFlags::DISCRETE);
+        let win_weak = self.downgrade();
+        scroll.connect_scroll(move |_, _, dy| {
+            let Some(w) = win_weak.upgrade() else { return glib::Propagation::Proceed };
+            // Wheel up grows the chibi
+            let steps = -dy.round() as i32;
+            if steps != 0 {
+                w.set_size((w.size() + steps * SCROLL_STEP).clamp(MIN_SIZE, MAX_SIZE));
+            }
+            glib::Propagation::Stop
+        });
+        self.add_controller(scroll);
+    }
+
+    fn setup_smart_hide(&self, app: &Application, threshold: u32, hide_delay: u32) {
+        let hide_ctrl = EventControllerMotion::new();
+        let w_weak = self.downgrade();
+        let app_zone = app.clone();
+        // Set when revealed from the hot zone, so the pointer already over the chibi doesn't re-hide it
+        let revealed = Rc::new(Cell::new(false));
+        let revealed_hide = revealed.clone();
+        // While hidden: the timer bringing the chibi back and its hot zone, dropped together
+        let tucked: Tucked = Rc::new(RefCell::new(None));
+
+        let hide_now = Rc::new(move || {
+            let revealed = revealed_hide.clone();
+            if let Some(w) = w_weak.upgrade() {
+                untuck(&tucked);
+                let width = w.width();
+                let placement = w.imp().placement.get();
+                w.set_smart_hidden(true);
+
+                // Hot zone: hovering the strip left behind reveals the chibi right away.
+                // A floating one would land wherever the compositor puts it, so those just wait.
+                let zone = (placement != Placement::Floating)
+                    .then(|| spawn_hot_zone(&app_zone, placement, w.always_on_top(), w.x() as i32, w.placed_y(), width));
+                if let Some(zone) = &zone {
+                    let zone_ctrl = EventControllerMotion::new();
+                    let w_zone = w.downgrade();
+                    let tucked_zone = tucked.clone();
+                    let revealed_zone = revealed.clone();
+                    zone_ctrl.connect_enter(move |_, _, _| {
+                        revealed_zone.set(true);
+                        untuck(&tucked_zone);
+                        if let Some(ww) = w_zone.upgrade() { ww.set_smart_hidden(false); }
+                    });
+                    zone.add_controller(zone_ctrl);
+                }
+
+                let w_tmr = w.downgrade();
+                let tucked_tmr = tucked.clone();
+                let src = glib::timeout_add_seconds_local(hide_delay, move || {
+                    // This source ends by returning Break, so only the zone is left to drop
+                    let taken = tucked_tmr.borrow_mut().take();
+                    if let Some((_, Some(z))) = taken { z.close(); }
+                    if let Some(ww) = w_tmr.upgrade() { ww.set_smart_hidden(false); }
+                    glib::ControlFlow::Break
+                });
+                *tucked.borrow_mut() = Some((src, zone));
+            }
+        });
+
+        // Debounce: only hide once the pointer has lingered for the hover threshold
+        let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+        let pending_leave = pending.clone();
+        let revealed_leave = revealed.clone();
+        hide_ctrl.connect_leave(move |_| {
+            revealed_leave.set(false);
+            if let Some(src) = pending_leave.borrow_mut().take() { src.remove(); }
+        });
+        let w_enter = self.downgrade();
+        hide_ctrl.connect_enter(move |_, _, _| {
+            let Some(w) = w_enter.upgrade() else { return };
+            if !w.smart_hide() || w.move_mode() || w.imp().dragging.get() || revealed.get() { return; }
+            if threshold == 0 {
+                hide_now();
+                return;
+            }
+            if let Some(src) = pending.borrow_mut().take() { src.remove(); }
+            let hide_tmr = hide_now.clone();
+            let pending_tmr = pending.clone();
+            let w_tmr = w.downgrade();
+            let src = glib::timeout_add_local(Duration::from_millis(threshold as u64), move || {
+                pending_tmr.borrow_mut().take();
+                if let Some(w) = w_tmr.upgrade() {
+                    if w.smart_hide() && !w.move_mode() && !w.imp().dragging.get() { hide_tmr(); }
+                }
+                glib::ControlFlow::Break
+            });
+            *pending.borrow_mut() = Some(src);
+        });
+        self.add_controller(hide_ctrl);
+    }
+}
+
+pub(crate) struct Bubble {
+    popover: gtk::Popover,
+    label: gtk::Label,
+    picture: Picture,
+    // What set_bubble asked for, shown whenever no flash is
+    content: Option<(String, Option<gdk::Texture>)>,
+    flash: Option<glib::SourceId>,
+    // Showing something; smart hide takes it down for a while
+    up: bool,
+}
+
+impl Bubble {
+    fn new(win: &ChibiWindow) -> Self {
+        let label = gtk::Label::builder().wrap(true).max_width_chars(24).build();
+        let picture = Picture::builder().width_request(64).height_request(64).content_fit(gtk::ContentFit::Cover).build();
+        let content = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        content.append(&picture);
+        content.append(&label);
+        let popover = gtk::Popover::builder()
+        .child(&content)
+        .autohide(false)
+        .can_focus(false)
+        .position(gtk::PositionType::Top)
+        .css_classes(["chibi-bubble"])
+        .build();
+        popover.set_parent(win);
+        Bubble { popover, label, picture, content: None, flash: None, up: false }
+    }
+
+    fn display(&mut self, win: &ChibiWindow, content: Option<(&str, Option<&gdk::Texture>)>) {
+        self.up = content.is_some();
+        let Some((text, picture)) = content else { return self.popover.popdown() };
+        self.label.set_text(text);
+        self.picture.set_paintable(picture);
+        self.picture.set_visible(picture.is_some());
+        if win.is_visible() { self.popover.popup(); }
+    }
+
+    fn show_content(&mut self, win: &ChibiWindow) {
+        let content = self.content.clone();
+        self.display(win, content.as_ref().map(|(text, picture)| (text.as_str(), picture.as_ref())));
+    }
+}
+
+// --- INPUT SHAPE ---
+// Alpha channel of the chibi image, used to shape the window's input region so hovers and
+// clicks on the transparent surroundings go to whatever is below (and don't trip smart hide).
+
+// Pixels at least this opaque take input; soft antialiased edges don't
+const ALPHA_THRESHOLD: u8 = 32;
+
+pub struct AlphaMask {
+    width: usize,
+    height: usize,
+    alpha: Vec<u8>,
+}
+
+impl AlphaMask {
+    pub fn new(width: usize, height: usize, alpha: Vec<u8>) -> Self {
+        debug_assert_eq!(alpha.len(), width * height);
+        AlphaMask { width, height, alpha }
+    }
+
+    pub fn from_texture(texture: &gdk::Texture) -> Self {
+        let (width, height) = (texture.width() as usize, texture.height() as usize);
+        let stride = width * 4;
+        let mut data = vec![0; stride * height];
+        texture.download(&mut data, stride);
+        // Downloads are premultiplied BGRA on little-endian machines, ARGB on big-endian ones
+        let a = if cfg!(target_endian = "little") { 3 } else { 0 };
+        Self::new(width, height, data.chunks_exact(4).map(|px| px[a]).collect())
+    }
+
+    // Opaque areas as (x, y, width, height) in a window of the given size, with the image
+    // scaled to fit and centered like ContentFit::Contain. Rows with the same runs of opaque
+    // pixels share one rectangle, which keeps the region small for typical chibi art.
+    pub fn opaque_rects(&self, width: i32, height: i32) -> Vec<(i32, i32, i32, i32)> {
+        if self.width == 0 || self.height == 0 || width <= 0 || height <= 0 { return Vec::new(); }
+        let scale = (width as f64 / self.width as f64).min(height as f64 / self.height as f64);
+        let drawn_w = ((self.width as f64 * scale).round() as i32).min(width);
+        let drawn_h = ((self.height as f64 * scale).round() as i32).min(height);
+        let (ox, oy) = ((width - drawn_w) / 2, (height - drawn_h) / 2);
+        let source = |pos: i32, len: usize| (((pos as f64 + 0.5) / scale) as usize).min(len - 1);
+
+        let mut rects: Vec<(i32, i32, i32, i32)> = Vec::new();
+        let mut prev_runs: Vec<(i32, i32)> = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        for y in 0..drawn_h {
+            let row = &self.alpha[source(y, self.height) * self.width..][..self.width];
+            let mut runs = Vec::new();
+            let mut start = None;
+            for x in 0..=drawn_w {
+                let opaque = x < drawn_w && row[source(x, self.width)] >= ALPHA_THRESHOLD;
+                match (opaque, start) {
+                    (true, None) => start = Some(x),
+                    (false, Some(s)) => {
+                        runs.push((s, x - s));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            if y > 0 && runs == prev_runs {
+                for &i in &open { rects[i].3 += 1; }
+                continue;
+            }
+            open = runs.iter().map(|&(x, w)| {
+                rects.push((ox + x, oy + y, w, 1));
+                rects.len() - 1
+            }).collect();
+            prev_runs = runs;
+        }
+        rects
+    }
+}
+
+// --- PLACEMENT ---
+// Layer-shell where the compositor has it. Otherwise chibis are undecorated toplevels:
+// on X11 they are positioned and kept above through the window manager, elsewhere
+// (GNOME on Wayland) the compositor places them and move mode hands dragging to it.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Placement {
+    #[cfg(feature = "layer-shell")]
+    #[default]
+    LayerShell,
+    #[cfg(feature = "x11")]
+    X11,
+    #[cfg_attr(not(feature = "layer-shell"), default)]
+    Floating,
+}
+
+pub fn placement() -> Placement {
+    #[cfg(feature = "layer-shell")]
+    if gtk4_layer_shell::is_supported() {
+        return Placement::LayerShell;
+    }
+    #[cfg(feature = "x11")]
+    if x11::is_x11() {
+        return Placement::X11;
+    }
+    Placement::Floating
+}
+
+// Anchors layer surfaces to the top-left; `on_map` re-places X11 windows each time they are shown
+#[cfg_attr(not(feature = "x11"), allow(unused_variables))]
+fn init_surface(win: &gtk::Window, placement: Placement, on_map: impl Fn(&gtk::Window) + 'static) {
+    match placement {
+        #[cfg(feature = "layer-shell")]
+        Placement::LayerShell => {
+            win.init_layer_shell();
+            win.set_layer(Layer::Bottom);
+            win.set_anchor(Edge::Top, true);
+            win.set_anchor(Edge::Left, true);
+        }
+        #[cfg(feature = "x11")]
+        Placement::X11 => x11::keep_placed(win, on_map),
+        Placement::Floating => {}
+    }
+}
+
+#[cfg_attr(not(any(feature = "layer-shell", feature = "x11")), allow(unused_variables))]
+fn move_surface(win: &gtk::Window, placement: Placement, x: i32, y: i32) {
+    match placement {
+        #[cfg(feature = "layer-shell")]
+        Placement::LayerShell => {
+            win.set_margin(Edge::Left, x);
+            win.set_margin(Edge::Top, y);
+        }
+        #[cfg(feature = "x11")]
+        Placement::X11 => x11::move_to(win, x, y),
+        Placement::Floating => {}
+    }
+}
+
+#[cfg_attr(not(any(feature = "layer-shell", feature = "x11")), allow(unused_variables))]
+fn raise_surface(win: &gtk::Window, placement: Placement, on_top: bool) {
+    match placement {
+        #[cfg(feature = "layer-shell")]
+        Placement::LayerShell => win.set_layer(if on_top { Layer::Overlay } else { Layer::Bottom }),
+        #[cfg(feature = "x11")]
+        Placement::X11 => x11::set_above(win, on_top),
+        Placement::Floating => {}
+    }
+}
+
+type Tucked = Rc<RefCell<Option<(glib::SourceId, Option<gtk::Window>)>>>;
+
+// Cancels a pending smart-hide reveal and closes its hot zone
+fn untuck(tucked: &Tucked) {
+    let taken = tucked.borrow_mut().take();
+    if let Some((src, zone)) = taken {
+        src.remove();
+        if let Some(z) = zone { z.close(); }
+    }
+}
+
+// Invisible 1-px strip along the top edge of a hidden chibi, used as a reveal trigger
+fn spawn_hot_zone(app: &Application, placement: Placement, on_top: bool, x: i32, y: i32, width: i32) -> gtk::Window {
+    let zone = gtk::Window::builder()
+    .application(app)
+    .default_width(width)
+    .default_height(1)
+    .decorated(false)
+    .resizable(false)
+    .build();
+
+    zone.add_css_class("ghost-window");
+    init_surface(&zone, placement, move |z| {
+        move_surface(z, placement, x, y);
+        raise_surface(z, placement, on_top);
+    });
+    raise_surface(&zone, placement, on_top);
+    move_surface(&zone, placement, x, y);
+    zone.set_size_request(width, 1);
+    zone.present();
+    zone
+}
+
+// Built-in styling for the chibi windows; user style.css can override it
+pub const CSS: &str = "\
+.ghost-window { background-color: rgba(0,0,0,0.001); }\n\
+.chibi-flipped { transform: scale(-1, 1); }\n\
+.chibi-bubble > contents { border-radius: 14px; padding: 6px 10px; } .chibi-bubble label { font-size: small; }";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4x4 image with an opaque 2x2 block in the middle
+    fn centered_block() -> AlphaMask {
+        let mut alpha = vec![0; 16];
+        for i in [5, 6, 9, 10] { alpha[i] = 255; }
+        AlphaMask::new(4, 4, alpha)
+    }
+
+    #[test]
+    fn opaque_block_scales_with_the_window() {
+        assert_eq!(centered_block().opaque_rects(4, 4), vec![(1, 1, 2, 2)]);
+        assert_eq!(centered_block().opaque_rects(100, 100), vec![(25, 25, 50, 50)]);
+    }
+
+    #[test]
+    fn contain_fit_centers_the_image() {
+        // A 2x1 image in a square window is letterboxed vertically
+        let mask = AlphaMask::new(2, 1, vec![255, 255]);
+        assert_eq!(mask.opaque_rects(10, 10), vec![(0, 2, 10, 5)]);
+    }
+
+    #[test]
+    fn faint_and_transparent_pixels_are_ignored() {
+        let mask = AlphaMask::new(3, 1, vec![ALPHA_THRESHOLD - 1, 255, 0]);
+        assert_eq!(mask.opaque_rects(3, 1), vec![(1, 0, 1, 1)]);
+        assert!(AlphaMask::new(2, 2, vec![0; 4]).opaque_rects(50, 50).is_empty());
+    }
+
+    #[test]
+    fn rows_with_different_runs_get_their_own_rects() {
+        // Opaque top-left and a full bottom row
+        let mask = AlphaMask::new(2, 2, vec![255, 0, 255, 255]);
+        assert_eq!(mask.opaque_rects(2, 2), vec![(0, 0, 1, 1), (0, 1, 2, 1)]);
+    }
+}
@@ -0,0 +1,110 @@
+use gdk4_x11::{X11Display, X11Surface};
+use gtk::gdk;
+use gtk::prelude::*;
+use std::cell::OnceCell;
+use std::rc::Rc;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ClientMessageEvent, ConfigureWindowAux, ConnectionExt, EventMask, Window};
+use x11rb::rust_connection::RustConnection;
+
+// --- X11 FALLBACK ---
+// Without layer-shell, chibis are plain undecorated toplevels. GTK4 has no API to position
+// those or keep them above other windows, so on X11 both go to the window manager over a
+// second connection: a configure request for the position, EWMH messages for the state.
+
+struct Wm {
+    conn: RustConnection,
+    root: Window,
+    net_wm_state: u32,
+    above: u32,
+}
+
+thread_local! {
+    static WM: OnceCell<Option<Wm>> = const { OnceCell::new() };
+}
+
+// _NET_WM_STATE actions
+const STATE_REMOVE: u32 = 0;
+const STATE_ADD: u32 = 1;
+
+pub fn is_x11() -> bool {
+    gdk::Display::default().is_some_and(|d| d.is::<X11Display>())
+}
+
+fn connect() -> Option<Wm> {
+    let display = gdk::Display::default()?.downcast::<X11Display>().ok()?;
+    let (conn, screen) = match x11rb::connect(Some(&display.name())) {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!("X11 placement unavailable: {}", e);
+            return None;
+        }
+    };
+    let root = conn.setup().roots[screen].root;
+    let atom = |name: &str| conn.intern_atom(false, name.as_bytes()).ok()?.reply().ok().map(|r| r.atom);
+    let net_wm_state = atom("_NET_WM_STATE")?;
+    let above = atom("_NET_WM_STATE_ABOVE")?;
+    Some(Wm { conn, root, net_wm_state, above })
+}
+
+fn with_wm(f: impl FnOnce(&Wm)) {
+    WM.with(|wm| {
+        if let Some(wm) = wm.get_or_init(connect) {
+            f(wm);
+            let _ = wm.conn.flush();
+        }
+    });
+}
+
+fn xid(win: &impl IsA<gtk::Window>) -> Option<Window> {
+    let surface = win.as_ref().surface()?.downcast::<X11Surface>().ok()?;
+    Some(surface.xid() as Window)
+}
+
+// Top-left corner of the window in root coordinates; a no-op until it is realized
+pub fn move_to(win: &impl IsA<gtk::Window>, x: i32, y: i32) {
+    let Some(id) = xid(win) else { return };
+    with_wm(|wm| {
+        let _ = wm.conn.configure_window(id, &ConfigureWindowAux::new().x(x).y(y));
+    });
+}
+
+// Asks the window manager to keep the (mapped) window above normal windows
+pub fn set_above(win: &impl IsA<gtk::Window>, above: bool) {
+    let Some(id) = xid(win) else { return };
+    with_wm(|wm| {
+        let action = if above { STATE_ADD } else { STATE_REMOVE };
+        // Source indication 2: a pager-like tool acting for the user, which WMs honor more readily
+        let event = ClientMessageEvent::new(32, id, wm.net_wm_state, [action, wm.above, 0, 2, 0]);
+        let mask = EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY;
+        let _ = wm.conn.send_event(false, wm.root, mask, event);
+    });
+}
+
+// Pointer position in root coordinates
+pub fn pointer() -> Option<(i32, i32)> {
+    let mut pos = None;
+    with_wm(|wm| {
+        if let Ok(reply) = wm.conn.query_pointer(wm.root).map_err(|_| ()).and_then(|c| c.reply().map_err(|_| ())) {
+            pos = Some((reply.root_x as i32, reply.root_y as i32));
+        }
+    });
+    pos
+}
+
+// Keeps the window off the taskbar/pager and re-applies `place` every time it is mapped,
+// since window managers pick their own spot for newly mapped windows
+pub fn keep_placed<W: IsA<gtk::Window>>(win: &W, place: impl Fn(&gtk::Window) + 'static) {
+    let place = Rc::new(place);
+    win.as_ref().connect_realize(move |w| {
+        let Some(surface) = w.surface().and_then(|s| s.downcast::<X11Surface>().ok()) else { return };
+        surface.set_skip_taskbar_hint(true);
+        surface.set_skip_pager_hint(true);
+        let w_weak = w.downgrade();
+        let place = place.clone();
+        surface.connect_mapped_notify(move |s| {
+            if !s.is_mapped() { return; }
+            if let Some(w) = w_weak.upgrade() { place(&w); }
+        });
+    });
+}